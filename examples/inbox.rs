@@ -0,0 +1,38 @@
+//! Lists the inbox, logging in with BankID first if needed.
+//!
+//!     cargo run --example inbox [profile]
+
+use kivinge::api::{Kivra, Status};
+use qrcode2::{render::unicode::Dense1x2, QrCode};
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut kivra = match std::env::args().nth(1) {
+        Some(profile) => Kivra::with_profile(&profile)?,
+        None => Kivra::new()?,
+    };
+
+    if !kivra.is_logged_in() {
+        let user = kivra.login(|prompt| {
+            match QrCode::new(prompt.qr_code) {
+                Ok(code) => println!("{}", code.render::<Dense1x2>().build()),
+                Err(_) => println!("Open {}", prompt.bankid_url()),
+            }
+            println!("Scan the code with BankID to log in");
+            true
+        })?;
+        println!("Logged in as {}", user.name);
+    }
+
+    for entry in kivra.inbox()?.iter() {
+        let item = &entry.item;
+        let new = match item.status {
+            Status::Unread => "NEW",
+            _ => "   ",
+        };
+        println!(
+            "{new} {:>4} {}: {}",
+            entry.id, item.sender_name, item.subject
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,287 @@
+//! The part of kivinge that is meant to be built on by other programs. It
+//! never draws a terminal UI and never prompts: calls that need a session
+//! fail with `Error::NotLoggedIn` until `Kivra::login` has been called, and
+//! the login hands the BankID QR code to a callback to show however it
+//! likes. A login can also be started and then polled step by step, even
+//! from another process, with `Kivra::start_login` and `Kivra::poll_login`.
+//!
+//! See `examples/inbox.rs` for a small program built on it.
+//!
+//! The rest of the crate is what the `kivinge` binary is made of, and may
+//! change between any two versions.
+
+use std::{thread, time::Duration};
+use thiserror::Error;
+
+use crate::client::{
+    self,
+    login::{bankid_url, LoginFlow},
+    session, AuthClient, ContentClient, KivraClient, SessionStore,
+};
+use crate::config::NetworkConfig;
+use crate::model::auth::AuthTokenResponse;
+pub use crate::{
+    client::session::UserInfo,
+    model::{
+        content::{
+            Attachment, Folder, InboxEntry, InboxItem, InboxListing,
+            ItemDetails, Status,
+        },
+        receipt::{ReceiptDetails, ReceiptEntry, ReceiptListing},
+        user::UserProfile,
+    },
+};
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("not logged in")]
+    NotLoggedIn,
+
+    #[error("login aborted")]
+    LoginAborted,
+
+    #[error("attachment has neither a key nor an inline body")]
+    EmptyAttachment,
+
+    #[error(transparent)]
+    ClientError(client::Error),
+}
+
+impl From<client::Error> for Error {
+    fn from(error: client::Error) -> Error {
+        match error {
+            client::Error::NoSession
+            | client::Error::SessionExpired
+            | client::Error::LoginRequired => Error::NotLoggedIn,
+            client::Error::LoginAborted => Error::LoginAborted,
+            error => Error::ClientError(error),
+        }
+    }
+}
+
+impl From<session::Error> for Error {
+    fn from(error: session::Error) -> Error {
+        Error::ClientError(error.into())
+    }
+}
+
+/// What to show while a login waits for BankID. The QR code changes every
+/// few seconds, and the callback is called again with each new one.
+pub struct LoginPrompt<'a> {
+    /// Data to encode in the QR code that BankID on another device scans
+    pub qr_code: &'a str,
+    pub auto_start_token: &'a str,
+    /// What BankID asks the user to do, or why the previous attempt failed
+    /// and a new one was started
+    pub status: &'a str,
+}
+
+impl LoginPrompt<'_> {
+    /// Opens BankID on this device instead of scanning the QR code
+    pub fn bankid_url(&self) -> String {
+        bankid_url(self.auto_start_token)
+    }
+}
+
+/// A login started by `Kivra::start_login`
+pub struct StartedLogin {
+    /// What to give `Kivra::poll_login`, in this process or another
+    pub token: String,
+    pub qr_code: String,
+    pub auto_start_token: String,
+}
+
+impl StartedLogin {
+    /// Opens BankID on this device instead of scanning the QR code
+    pub fn bankid_url(&self) -> String {
+        bankid_url(&self.auto_start_token)
+    }
+}
+
+/// How a login polled by `Kivra::poll_login` is going
+pub enum LoginState {
+    /// Still waiting for BankID. The QR code changes every few seconds, and
+    /// the login is to be polled again after `retry_after`.
+    Pending {
+        qr_code: String,
+        status: String,
+        retry_after: Duration,
+    },
+    LoggedIn(UserInfo),
+}
+
+/// A Kivra account, with the session of a kivinge profile. Sessions are
+/// shared with the `kivinge` binary, so a login by either is used by both.
+pub struct Kivra {
+    client: KivraClient,
+}
+
+impl Kivra {
+    /// The account of the default profile
+    pub fn new() -> Result<Kivra, Error> {
+        Kivra::with_profile(session::DEFAULT_PROFILE)
+    }
+
+    pub fn with_profile(profile: &str) -> Result<Kivra, Error> {
+        session::check_profile(profile).map_err(client::Error::from)?;
+        let mut client = make_client(profile)?;
+        client.get_or_load_session()?;
+        Ok(Kivra { client })
+    }
+
+    pub fn profile(&self) -> &str {
+        self.client.profile()
+    }
+
+    /// The logged in user, unless the session is missing or has expired
+    pub fn user(&self) -> Option<UserInfo> {
+        let session = self.client.get_session()?;
+        (!session.is_expired()).then_some(session.user_info)
+    }
+
+    pub fn is_logged_in(&self) -> bool {
+        self.user().is_some()
+    }
+
+    /// Everything Kivra has on the logged in user, which is more than
+    /// `user` tells
+    pub fn user_profile(&mut self) -> Result<UserProfile, Error> {
+        Ok(self.client.get_user_profile()?)
+    }
+
+    /// Logs in with BankID and saves the session for the profile. `prompt`
+    /// is called with every QR code until the user has signed, and aborts
+    /// the login by returning false.
+    pub fn login(
+        &mut self,
+        mut prompt: impl FnMut(&LoginPrompt) -> bool,
+    ) -> Result<UserInfo, Error> {
+        let auth_token = {
+            let mut flow = LoginFlow::start(&self.client)?;
+            loop {
+                let status = flow.status();
+                let login_prompt = LoginPrompt {
+                    qr_code: &flow.qr_code,
+                    auto_start_token: &flow.auto_start_token,
+                    status: &status,
+                };
+                if !prompt(&login_prompt) {
+                    flow.abort()?;
+                    return Err(Error::LoginAborted);
+                }
+                thread::sleep(flow.retry_after());
+                if let Some(auth_token) = flow.poll()? {
+                    break auth_token;
+                }
+            }
+        };
+        self.save_session(auth_token)
+    }
+
+    /// Starts a BankID login to go on with by polling `poll_login` with the
+    /// token, until the user has signed. The state of the login is saved
+    /// with the profile in the meantime.
+    pub fn start_login(&mut self) -> Result<StartedLogin, Error> {
+        let profile = self.client.profile();
+        let (token, flow) = LoginFlow::start_saved(&self.client, profile)?;
+        Ok(StartedLogin {
+            token,
+            qr_code: flow.qr_code,
+            auto_start_token: flow.auto_start_token,
+        })
+    }
+
+    /// Checks on a login started by `start_login`, saving the session once
+    /// the user has signed
+    pub fn poll_login(&mut self, token: &str) -> Result<LoginState, Error> {
+        let profile = self.client.profile();
+        let (flow, auth_token) =
+            LoginFlow::poll_saved(&self.client, profile, token)?;
+        match auth_token {
+            Some(auth_token) => {
+                Ok(LoginState::LoggedIn(self.save_session(auth_token)?))
+            }
+            None => Ok(LoginState::Pending {
+                qr_code: flow.qr_code.clone(),
+                status: flow.status(),
+                retry_after: flow.retry_after(),
+            }),
+        }
+    }
+
+    fn save_session(
+        &mut self,
+        auth_token: AuthTokenResponse,
+    ) -> Result<UserInfo, Error> {
+        let session =
+            session::make(auth_token.access_token, auth_token.id_token)?;
+        session::save(&session, self.client.profile())?;
+        self.client.set_session(session.clone());
+        Ok(session.user_info)
+    }
+
+    /// Revokes the session and forgets the saved one
+    pub fn logout(&mut self) -> Result<(), Error> {
+        self.client.revoke_auth_token()?;
+        session::delete_saved(self.client.profile())?;
+        self.client = make_client(self.client.profile())?;
+        Ok(())
+    }
+
+    /// What is in the inbox, oldest first. Archived and trashed items are
+    /// left out.
+    pub fn inbox(&mut self) -> Result<InboxListing, Error> {
+        self.folder(Folder::Inbox)
+    }
+
+    pub fn folder(&mut self, folder: Folder) -> Result<InboxListing, Error> {
+        Ok(self.client.get_folder_listing(folder)?)
+    }
+
+    pub fn item(&mut self, item_key: &str) -> Result<ItemDetails, Error> {
+        Ok(self.client.get_item_details(item_key)?)
+    }
+
+    /// The contents of one of the attachments of an item
+    pub fn attachment(
+        &mut self,
+        item_key: &str,
+        attachment: &Attachment,
+    ) -> Result<Vec<u8>, Error> {
+        match (&attachment.key, &attachment.body) {
+            (Some(key), _) => {
+                Ok(self.client.download_attachment(item_key, key)?.to_vec())
+            }
+            (_, Some(body)) => Ok(body.as_bytes().to_vec()),
+            (None, None) => Err(Error::EmptyAttachment),
+        }
+    }
+
+    pub fn mark_as_read(&mut self, item_key: &str) -> Result<(), Error> {
+        Ok(self.client.mark_as_read(item_key)?)
+    }
+
+    pub fn move_to_folder(
+        &mut self,
+        item_key: &str,
+        folder: Folder,
+    ) -> Result<(), Error> {
+        Ok(self.client.move_to_folder(item_key, folder)?)
+    }
+
+    pub fn receipts(&mut self) -> Result<ReceiptListing, Error> {
+        Ok(self.client.get_receipts()?)
+    }
+
+    pub fn receipt(
+        &mut self,
+        receipt_key: &str,
+    ) -> Result<ReceiptDetails, Error> {
+        Ok(self.client.get_receipt_details(receipt_key)?)
+    }
+}
+
+fn make_client(profile: &str) -> Result<KivraClient, Error> {
+    let network = NetworkConfig::default();
+    Ok(KivraClient::new(&network)?.with_profile(profile).non_interactive())
+}
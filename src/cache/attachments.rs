@@ -0,0 +1,147 @@
+use bytes::Bytes;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use tracing::warn;
+
+use super::{cache_dir, clear_dir, dir_usage, Error};
+use crate::{crypto, index};
+
+const COMPRESSED_EXTENSION: &str = "zst";
+const COMPRESSION_LEVEL: i32 = 3;
+
+/// Attachment contents kept on disk between runs. Attachments never change
+/// once sent, so entries do not expire.
+pub struct AttachmentCache {
+    dir: PathBuf,
+    compress: bool,
+}
+
+impl AttachmentCache {
+    pub fn open(profile: &str, compress: bool) -> Result<Self, Error> {
+        let dir = cache_dir(profile, "attachments")?;
+        Ok(AttachmentCache { dir, compress })
+    }
+
+    fn entry_path(&self, item_key: &str, attachment_key: &str) -> PathBuf {
+        let name =
+            index::sha256(format!("{item_key}/{attachment_key}").as_bytes());
+        self.dir.join(name)
+    }
+
+    /// Entries stored in the other format than the configured one, or
+    /// encrypted when encryption is not enabled or the other way around,
+    /// are rewritten as they are read.
+    pub fn get(&self, item_key: &str, attachment_key: &str) -> Option<Bytes> {
+        let path = self.stored_path(&self.entry_path(item_key, attachment_key));
+        let other_path = match self.compress {
+            true => path.with_extension(""),
+            false => path.with_extension(COMPRESSED_EXTENSION),
+        };
+        let (contents, stale, read_path) = match read_entry(&path) {
+            Some((contents, stale)) => (contents, stale, path.clone()),
+            None => {
+                let (contents, _) = read_entry(&other_path)?;
+                (contents, true, other_path)
+            }
+        };
+
+        let rewritten =
+            stale && self.put(item_key, attachment_key, &contents).is_ok();
+        if rewritten && read_path != path {
+            _ = fs::remove_file(read_path);
+        }
+        Some(contents.into())
+    }
+
+    pub fn put(
+        &self,
+        item_key: &str,
+        attachment_key: &str,
+        contents: &[u8],
+    ) -> Result<(), Error> {
+        self.write_entry(&self.entry_path(item_key, attachment_key), contents)
+    }
+
+    /// Where an entry is stored in the configured format, given the entry
+    /// path without the compression extension
+    fn stored_path(&self, plain_path: &Path) -> PathBuf {
+        match self.compress {
+            true => plain_path.with_extension(COMPRESSED_EXTENSION),
+            false => plain_path.to_path_buf(),
+        }
+    }
+
+    /// `plain_path` is the entry path without the compression extension
+    fn write_entry(
+        &self,
+        plain_path: &Path,
+        contents: &[u8],
+    ) -> Result<(), Error> {
+        let data = match self.compress {
+            true => zstd::encode_all(contents, COMPRESSION_LEVEL)?,
+            false => contents.to_vec(),
+        };
+        fs::write(self.stored_path(plain_path), crypto::seal(data)?)?;
+        Ok(())
+    }
+
+    /// Rewrites every entry in the configured format, and encrypted or not
+    /// as configured. Returns the number of entries converted.
+    pub fn migrate(&self) -> Result<usize, Error> {
+        let mut migrated = 0;
+        for file in fs::read_dir(&self.dir)? {
+            let path = file?.path();
+            let data = fs::read(&path)?;
+            let compressed = is_compressed(&path);
+            if compressed == self.compress && !crypto::needs_migration(&data) {
+                continue;
+            }
+            let data = crypto::unseal(data)?;
+            let contents = match compressed {
+                true => zstd::decode_all(data.as_slice())?,
+                false => data,
+            };
+            let plain_path = path.with_extension("");
+            self.write_entry(&plain_path, &contents)?;
+            if path != self.stored_path(&plain_path) {
+                fs::remove_file(&path)?;
+            }
+            migrated += 1;
+        }
+        Ok(migrated)
+    }
+
+    pub fn clear(&self) -> Result<(), Error> {
+        clear_dir(&self.dir)
+    }
+
+    /// Number of entries and their total size on disk
+    pub fn usage(&self) -> Result<(usize, u64), Error> {
+        dir_usage(&self.dir)
+    }
+}
+
+fn is_compressed(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == COMPRESSED_EXTENSION)
+}
+
+/// The contents of an entry, and whether it is encrypted otherwise than
+/// configured
+fn read_entry(path: &Path) -> Option<(Vec<u8>, bool)> {
+    let data = fs::read(path).ok()?;
+    let stale = crypto::needs_migration(&data);
+    let data = match crypto::unseal(data) {
+        Ok(data) => data,
+        Err(error) => {
+            warn!("failed to read cached attachment: {error}");
+            return None;
+        }
+    };
+    match is_compressed(path) {
+        true => Some((zstd::decode_all(data.as_slice()).ok()?, stale)),
+        false => Some((data, stale)),
+    }
+}
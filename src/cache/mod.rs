@@ -0,0 +1,425 @@
+use chrono::{DateTime, Utc};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    fs::{self, File},
+    hash::Hash,
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+use thiserror::Error;
+use tracing::{debug, warn};
+
+use crate::{
+    crypto, index,
+    model::content::{AttachmentSummary, ContentKey},
+};
+
+mod attachments;
+
+pub use attachments::AttachmentCache;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("failed to determine cache dir")]
+    CannotFindCacheDir,
+
+    #[error("(de)serialization error")]
+    SerializationError(#[from] serde_json::Error),
+
+    #[error("IO error")]
+    IOError(#[from] std::io::Error),
+
+    #[error("encryption error: {0}")]
+    CryptoError(#[from] crypto::Error),
+}
+
+/// How long entries stay fresh and how many are kept, both in memory and on
+/// disk. The least recently used entry is evicted first.
+#[derive(Clone, Copy, Debug)]
+pub struct Policy {
+    pub ttl: Option<Duration>,
+    pub max_entries: Option<usize>,
+}
+
+impl Policy {
+    pub const fn unbounded() -> Policy {
+        Policy { ttl: None, max_entries: None }
+    }
+
+    pub const fn with_ttl(self, ttl: Duration) -> Policy {
+        Policy { ttl: Some(ttl), ..self }
+    }
+
+    pub const fn with_max_entries(self, max_entries: usize) -> Policy {
+        Policy { max_entries: Some(max_entries), ..self }
+    }
+}
+
+// Shared by every layer so the same data goes stale at the same time
+pub const INBOX_POLICY: Policy =
+    Policy::unbounded().with_ttl(Duration::from_secs(60)).with_max_entries(1);
+pub const DETAILS_POLICY: Policy =
+    Policy::unbounded().with_ttl(Duration::from_mins(60));
+pub const ATTACHMENT_POLICY: Policy = Policy::unbounded().with_max_entries(10);
+pub const RECEIPTS_POLICY: Policy = INBOX_POLICY;
+pub const RECEIPT_POLICY: Policy = DETAILS_POLICY;
+// What an item has attached never changes
+pub const SUMMARY_POLICY: Policy = Policy::unbounded();
+// Responses are revalidated with Kivra, so they never go stale by age
+pub const RESPONSE_POLICY: Policy = Policy::unbounded().with_max_entries(100);
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Stats {
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+}
+
+struct Entry<V> {
+    value: V,
+    stored_at: Instant,
+    last_used: u64,
+}
+
+/// Where a cache keeps its entries between runs
+struct Disk<K, V> {
+    dir: PathBuf,
+    file_name: fn(&K) -> String,
+    encode: fn(&V, DateTime<Utc>) -> Result<Vec<u8>, Error>,
+    decode: fn(&[u8]) -> Option<DiskEntry<V>>,
+}
+
+#[derive(Deserialize, Serialize)]
+struct DiskEntry<V> {
+    stored_at: DateTime<Utc>,
+    value: V,
+}
+
+/// An in-memory cache, optionally backed by files in the cache dir
+pub struct Cache<K, V> {
+    name: &'static str,
+    policy: Policy,
+    entries: HashMap<K, Entry<V>>,
+    disk: Option<Disk<K, V>>,
+    stats: Stats,
+    clock: u64,
+}
+
+impl<K: Hash + Eq + Clone, V> Cache<K, V> {
+    pub fn new(name: &'static str, policy: Policy) -> Cache<K, V> {
+        Cache {
+            name,
+            policy,
+            entries: HashMap::new(),
+            disk: None,
+            stats: Stats::default(),
+            clock: 0,
+        }
+    }
+
+    pub fn get(&mut self, key: &K) -> Option<&V> {
+        match self.lookup(key) {
+            true => self.entries.get(key).map(|entry| &entry.value),
+            false => None,
+        }
+    }
+
+    pub fn insert(&mut self, key: K, value: V) -> &V {
+        if let Some(disk) = &self.disk {
+            if let Err(error) = disk.save(&key, &value, Utc::now()) {
+                warn!("cache {}: failed to persist entry: {error}", self.name);
+            }
+            if let Some(max_entries) = self.policy.max_entries {
+                if let Err(error) = disk.prune(max_entries) {
+                    warn!(
+                        "cache {}: failed to evict entries: {error}",
+                        self.name
+                    );
+                }
+            }
+        }
+        self.insert_at(key, value, Instant::now())
+    }
+
+    pub fn try_get_or_insert_with<E>(
+        &mut self,
+        key: K,
+        fetch: impl FnOnce() -> Result<V, E>,
+    ) -> Result<&V, E> {
+        if !self.lookup(&key) {
+            return Ok(self.insert(key.clone(), fetch()?));
+        }
+        let entry = self.entries.get(&key).expect("lookup found the entry");
+        Ok(&entry.value)
+    }
+
+    /// Forgets an entry, e.g. because the data it was made from changed
+    pub fn remove(&mut self, key: &K) {
+        self.entries.remove(key);
+        if let Some(disk) = &self.disk {
+            _ = fs::remove_file(disk.path(key));
+        }
+    }
+
+    /// Forgets every entry, including those on disk
+    pub fn clear(&mut self) -> Result<(), Error> {
+        self.entries.clear();
+        match &self.disk {
+            Some(disk) => clear_dir(&disk.dir),
+            None => Ok(()),
+        }
+    }
+
+    pub fn stats(&self) -> Stats {
+        self.stats
+    }
+
+    /// Number of entries on disk and their total size
+    pub fn disk_usage(&self) -> Result<(usize, u64), Error> {
+        match &self.disk {
+            Some(disk) => dir_usage(&disk.dir),
+            None => Ok((0, 0)),
+        }
+    }
+
+    /// Rewrites the entries on disk that are encrypted otherwise than
+    /// configured, returning how many there were
+    pub fn migrate(&self) -> Result<usize, Error> {
+        let Some(disk) = &self.disk else {
+            return Ok(0);
+        };
+        let mut migrated = 0;
+        for file in fs::read_dir(&disk.dir)? {
+            let path = file?.path();
+            let data = fs::read(&path)?;
+            if crypto::needs_migration(&data) {
+                fs::write(&path, crypto::seal(crypto::unseal(data)?)?)?;
+                migrated += 1;
+            }
+        }
+        Ok(migrated)
+    }
+
+    /// When the newest entry on disk was stored
+    pub fn last_stored(&self) -> Result<Option<DateTime<Utc>>, Error> {
+        let Some(disk) = &self.disk else {
+            return Ok(None);
+        };
+        let mut newest = None;
+        for file in fs::read_dir(&disk.dir)? {
+            newest = newest.max(Some(file?.metadata()?.modified()?));
+        }
+        Ok(newest.map(DateTime::from))
+    }
+
+    /// Whether a fresh entry is in memory, after loading it from disk if
+    /// needed. Counts towards the stats and marks the entry as used.
+    fn lookup(&mut self, key: &K) -> bool {
+        if !self.is_fresh(key) && !self.load(key) {
+            self.stats.misses += 1;
+            debug!("cache {}: miss", self.name);
+            return false;
+        }
+        self.stats.hits += 1;
+        self.clock += 1;
+        if let Some(entry) = self.entries.get_mut(key) {
+            entry.last_used = self.clock;
+        }
+        true
+    }
+
+    fn is_fresh(&self, key: &K) -> bool {
+        self.entries.get(key).is_some_and(|entry| {
+            self.policy.ttl.is_none_or(|ttl| entry.stored_at.elapsed() < ttl)
+        })
+    }
+
+    /// Reads a fresh entry from disk into memory
+    fn load(&mut self, key: &K) -> bool {
+        let Some(DiskEntry { stored_at, value }) =
+            self.disk.as_ref().and_then(|disk| disk.load(key))
+        else {
+            return false;
+        };
+        let age = (Utc::now() - stored_at).to_std().unwrap_or_default();
+        if self.policy.ttl.is_some_and(|ttl| age >= ttl) {
+            return false;
+        }
+        let stored_at =
+            Instant::now().checked_sub(age).unwrap_or(Instant::now());
+        self.insert_at(key.clone(), value, stored_at);
+        true
+    }
+
+    fn insert_at(&mut self, key: K, value: V, stored_at: Instant) -> &V {
+        self.entries.remove(&key);
+        let max_entries = self.policy.max_entries.unwrap_or(usize::MAX);
+        while self.entries.len() >= max_entries.max(1) {
+            let oldest = self
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(key, _)| key.clone());
+            let Some(oldest) = oldest else { break };
+            self.entries.remove(&oldest);
+            self.stats.evictions += 1;
+            debug!("cache {}: evicted an entry", self.name);
+        }
+        self.clock += 1;
+        let entry = Entry { value, stored_at, last_used: self.clock };
+        &self.entries.entry(key).insert_entry(entry).into_mut().value
+    }
+}
+
+impl<K, V> Cache<K, V>
+where
+    K: Hash + Eq + Clone + Display,
+    V: Serialize + DeserializeOwned,
+{
+    /// Keeps entries as JSON files under the profile's cache dir too, so
+    /// they survive between runs
+    pub fn persistent(
+        name: &'static str,
+        policy: Policy,
+        profile: &str,
+    ) -> Result<Cache<K, V>, Error> {
+        let disk = Disk {
+            dir: cache_dir(profile, name)?,
+            file_name: |key: &K| index::sha256(key.to_string().as_bytes()),
+            encode: |value, stored_at| {
+                Ok(serde_json::to_vec(&DiskEntry { stored_at, value })?)
+            },
+            decode: |data| serde_json::from_slice(data).ok(),
+        };
+        let mut cache = Cache::new(name, policy);
+        cache.disk = Some(disk);
+        Ok(cache)
+    }
+}
+
+impl<K, V> Drop for Cache<K, V> {
+    fn drop(&mut self) {
+        let Stats { hits, misses, evictions } = self.stats;
+        if hits + misses == 0 {
+            return;
+        }
+        debug!(
+            "cache {}: {hits} hit(s), {misses} miss(es), {evictions} eviction(s)",
+            self.name
+        );
+    }
+}
+
+impl<K, V> Disk<K, V> {
+    fn path(&self, key: &K) -> PathBuf {
+        self.dir.join((self.file_name)(key))
+    }
+
+    /// Reads an entry, marking it as used for `prune`. One kept from before
+    /// encryption was turned on or off is written again as configured.
+    fn load(&self, key: &K) -> Option<DiskEntry<V>> {
+        let path = self.path(key);
+        let data = fs::read(&path).ok()?;
+        let stale = crypto::needs_migration(&data);
+        let data = match crypto::unseal(data) {
+            Ok(data) => data,
+            Err(error) => {
+                warn!("failed to read cache entry: {error}");
+                return None;
+            }
+        };
+        let rewritten = match stale {
+            true => crypto::seal(data.clone())
+                .map_err(Error::from)
+                .and_then(|sealed| Ok(fs::write(&path, sealed)?)),
+            false => File::options()
+                .write(true)
+                .open(&path)
+                .and_then(|file| file.set_modified(SystemTime::now()))
+                .map_err(Error::from),
+        };
+        if let Err(error) = rewritten {
+            warn!("failed to update cache entry: {error}");
+        }
+        (self.decode)(&data)
+    }
+
+    /// Removes the least recently used entries beyond `max_entries`
+    fn prune(&self, max_entries: usize) -> Result<(), Error> {
+        let mut files = Vec::new();
+        for file in fs::read_dir(&self.dir)? {
+            let file = file?;
+            files.push((file.metadata()?.modified()?, file.path()));
+        }
+        if files.len() <= max_entries {
+            return Ok(());
+        }
+        files.sort();
+        for (_, path) in &files[..files.len() - max_entries] {
+            fs::remove_file(path)?;
+        }
+        debug!("evicted {} entries from disk", files.len() - max_entries);
+        Ok(())
+    }
+
+    fn save(
+        &self,
+        key: &K,
+        value: &V,
+        stored_at: DateTime<Utc>,
+    ) -> Result<(), Error> {
+        let data = crypto::seal((self.encode)(value, stored_at)?)?;
+        Ok(fs::write(self.path(key), data)?)
+    }
+}
+
+/// API responses along with the validators needed to ask Kivra whether they
+/// are still current
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct CachedResponse {
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    pub body: String,
+}
+
+pub type ResponseCache = Cache<String, CachedResponse>;
+
+pub fn response_cache(profile: &str) -> Result<ResponseCache, Error> {
+    Cache::persistent("responses", RESPONSE_POLICY, profile)
+}
+
+pub type SummaryCache = Cache<ContentKey, AttachmentSummary>;
+
+/// The attachment counts and sizes of items, so that listing them only
+/// fetches the details of items not seen before
+pub fn summary_cache(profile: &str) -> Result<SummaryCache, Error> {
+    Cache::persistent("summaries", SUMMARY_POLICY, profile)
+}
+
+fn cache_dir(profile: &str, kind: &str) -> Result<PathBuf, Error> {
+    let mut dir = dirs::cache_dir().ok_or(Error::CannotFindCacheDir)?;
+    dir.push("kivinge");
+    dir.push(profile);
+    dir.push(kind);
+    fs::create_dir_all(&dir)?;
+    Ok(dir)
+}
+
+/// Number of files in a cache dir and their total size
+fn dir_usage(dir: &Path) -> Result<(usize, u64), Error> {
+    let mut entries = 0;
+    let mut bytes = 0;
+    for file in fs::read_dir(dir)? {
+        entries += 1;
+        bytes += file?.metadata()?.len();
+    }
+    Ok((entries, bytes))
+}
+
+fn clear_dir(dir: &Path) -> Result<(), Error> {
+    fs::remove_dir_all(dir)?;
+    fs::create_dir_all(dir)?;
+    Ok(())
+}
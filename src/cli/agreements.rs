@@ -0,0 +1,27 @@
+use tabled::builder::Builder;
+use tabled::settings::Style;
+
+use crate::model::content::AgreementSummary;
+
+pub const ITEM_COLUMNS: [&str; 5] =
+    ["id", "subject", "amount", "due", "created"];
+
+pub fn format(agreements: Vec<AgreementSummary>) -> String {
+    if agreements.is_empty() {
+        return "No items refer to an agreement".to_string();
+    }
+    let mut builder = Builder::default();
+    builder.push_record(["Sender", "Status", "Items", "Key"]);
+    for agreement in agreements {
+        builder.push_record([
+            &agreement.sender_name,
+            agreement.status.as_deref().unwrap_or("-"),
+            &agreement.items.to_string(),
+            &agreement.key,
+        ]);
+    }
+
+    let mut table = builder.build();
+    table.with(Style::modern());
+    table.to_string()
+}
@@ -0,0 +1,91 @@
+use chrono::{DateTime, Utc};
+use std::io::Write;
+
+use crate::{
+    client::ContentClient, error::Error, model::content::InboxEntry,
+    progress::Progress, storage::Upload, util::get_attachment_body,
+};
+
+/// Writes a tar archive with a directory per inbox item, holding its
+/// attachments and its metadata as `item.json`, zstd compressed if asked to.
+/// Returns the number of items written.
+pub fn run(
+    client: &mut impl ContentClient,
+    upload: Box<dyn Upload>,
+    compress: bool,
+    progress: &mut Progress,
+) -> Result<usize, Error> {
+    match compress {
+        true => {
+            let encoder =
+                zstd::Encoder::new(upload, zstd::DEFAULT_COMPRESSION_LEVEL)?;
+            let (encoder, items) = write_archive(client, encoder, progress)?;
+            encoder.finish()?.finish()?;
+            Ok(items)
+        }
+        false => {
+            let (upload, items) = write_archive(client, upload, progress)?;
+            upload.finish()?;
+            Ok(items)
+        }
+    }
+}
+
+fn write_archive<W: Write>(
+    client: &mut impl ContentClient,
+    writer: W,
+    progress: &mut Progress,
+) -> Result<(W, usize), Error> {
+    let inbox = client.get_inbox_listing()?;
+    let mut archive = tar::Builder::new(writer);
+    progress.start(inbox.len());
+    for entry in inbox.iter() {
+        append_item(client, &mut archive, entry, progress)?;
+        progress.advance();
+    }
+    Ok((archive.into_inner()?, inbox.len()))
+}
+
+/// Adds the directory of one item, named after the entry, showing each
+/// attachment on the progress bar. Returns the names of the attachments.
+pub fn append_item<W: Write>(
+    client: &mut impl ContentClient,
+    archive: &mut tar::Builder<W>,
+    entry: &InboxEntry,
+    progress: &mut Progress,
+) -> Result<Vec<String>, Error> {
+    let item = &entry.item;
+    let details = client.get_item_details(&item.key)?;
+    let metadata =
+        serde_json::to_vec_pretty(item).map_err(std::io::Error::from)?;
+    let path = format!("{entry}/item.json");
+    append(archive, &path, &metadata, item.created_at)?;
+    let mut names = Vec::new();
+    let count = details.parts.len();
+    progress.set_message(entry.to_string());
+    for num in 0..count as u32 {
+        if count > 1 {
+            progress.set_message(format!("{entry} ({}/{count})", num + 1));
+        }
+        let body = get_attachment_body(client, item, &details, num)?;
+        let name = details.attachment_name_for(num as usize, &body)?;
+        let path = format!("{entry}/{name}");
+        append(archive, &path, &body, item.created_at)?;
+        names.push(name);
+    }
+    Ok(names)
+}
+
+pub fn append<W: Write>(
+    archive: &mut tar::Builder<W>,
+    path: &str,
+    data: &[u8],
+    modified: DateTime<Utc>,
+) -> Result<(), Error> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(data.len() as u64);
+    header.set_mode(0o600);
+    header.set_mtime(modified.timestamp().max(0) as u64);
+    archive.append_data(&mut header, path, data)?;
+    Ok(())
+}
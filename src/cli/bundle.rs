@@ -0,0 +1,298 @@
+use chrono::{DateTime, Local, Utc};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, File},
+    io::{ErrorKind, Read, Write},
+    path::Path,
+    process::{Child, Command, Stdio},
+    str::FromStr,
+};
+use tabled::{builder::Builder, settings::Style};
+
+use crate::{
+    cli::{backup, prompt},
+    client::ContentClient,
+    error::Error,
+    model::content::InboxEntry,
+    progress::Progress,
+    util,
+};
+
+const MANIFEST: &str = "manifest.json";
+
+/// Who a bundle is encrypted to, which also decides the tool that does it
+#[derive(Clone, Debug)]
+pub enum Recipient {
+    /// An age or SSH public key
+    Age(String),
+    /// A GPG key id, fingerprint or email address
+    Gpg(String),
+}
+
+impl FromStr for Recipient {
+    type Err = String;
+
+    fn from_str(recipient: &str) -> Result<Self, Self::Err> {
+        let recipient = recipient.trim();
+        if recipient.is_empty() {
+            return Err("empty recipient".to_string());
+        }
+        let is_age = ["age1", "ssh-ed25519 ", "ssh-rsa "]
+            .iter()
+            .any(|prefix| recipient.starts_with(prefix));
+        Ok(match is_age {
+            true => Recipient::Age(recipient.to_string()),
+            false => Recipient::Gpg(recipient.to_string()),
+        })
+    }
+}
+
+/// What a bundle holds, kept in it as `manifest.json`
+#[derive(Deserialize, Serialize)]
+pub struct Manifest {
+    pub exported_at: DateTime<Utc>,
+    pub items: Vec<BundledItem>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct BundledItem {
+    pub id: u32,
+    /// The directory holding the item's `item.json` and attachments
+    pub dir: String,
+    pub sender: String,
+    pub subject: String,
+    pub created_at: DateTime<Utc>,
+    pub attachments: Vec<String>,
+}
+
+/// Writes the items to `output` as a tar archive laid out like a backup,
+/// with a manifest, encrypted to the recipients by `age` or `gpg`. The
+/// bundle only takes the place of `output` once it is complete.
+pub fn export(
+    client: &mut impl ContentClient,
+    entries: &[InboxEntry],
+    recipients: &[Recipient],
+    output: &Path,
+    progress: &mut Progress,
+) -> Result<(), Error> {
+    let partial = util::partial_path(output);
+    let mut child = spawn(encrypt_command(recipients, &partial)?)?;
+    let stdin = child.stdin.take().expect("stdin is piped");
+    let written = write_bundle(client, entries, stdin, progress);
+    let status = child.wait()?;
+    if written.is_err() || !status.success() {
+        // Rather than leave a truncated bundle behind
+        _ = fs::remove_file(&partial);
+    }
+    written?;
+    match status.success() {
+        true => Ok(fs::rename(&partial, output)?),
+        false => Err(Error::UserError("Encryption failed, see above")),
+    }
+}
+
+fn encrypt_command(
+    recipients: &[Recipient],
+    output: &Path,
+) -> Result<Command, Error> {
+    let mut age = Vec::new();
+    let mut gpg = Vec::new();
+    for recipient in recipients {
+        match recipient {
+            Recipient::Age(key) => age.push(key),
+            Recipient::Gpg(key) => gpg.push(key),
+        }
+    }
+    let command = match (age.is_empty(), gpg.is_empty()) {
+        (false, true) => {
+            let mut command = Command::new("age");
+            command.arg("--encrypt").arg("--output").arg(output);
+            for key in age {
+                command.arg("--recipient").arg(key);
+            }
+            command
+        }
+        (true, false) => {
+            let mut command = Command::new("gpg");
+            if prompt::non_interactive() {
+                command.arg("--batch");
+            }
+            command.args(["--encrypt", "--yes", "--output"]).arg(output);
+            for key in gpg {
+                command.arg("--recipient").arg(key);
+            }
+            command
+        }
+        (true, true) => {
+            return Err(Error::UserError("Give a recipient to encrypt to"))
+        }
+        (false, false) => {
+            return Err(Error::UserError(
+                "Give either age or GPG recipients, not both",
+            ))
+        }
+    };
+    Ok(command)
+}
+
+fn write_bundle<W: Write>(
+    client: &mut impl ContentClient,
+    entries: &[InboxEntry],
+    writer: W,
+    progress: &mut Progress,
+) -> Result<(), Error> {
+    let mut archive = tar::Builder::new(writer);
+    let mut items = Vec::new();
+    progress.start(entries.len());
+    for entry in entries {
+        let attachments =
+            backup::append_item(client, &mut archive, entry, progress)?;
+        progress.advance();
+        items.push(BundledItem {
+            id: entry.id,
+            dir: entry.to_string(),
+            sender: entry.item.sender_name.clone(),
+            subject: entry.item.subject.clone(),
+            created_at: entry.item.created_at,
+            attachments,
+        });
+    }
+    let now = Utc::now();
+    let manifest = Manifest { exported_at: now, items };
+    let data =
+        serde_json::to_vec_pretty(&manifest).map_err(std::io::Error::from)?;
+    backup::append(&mut archive, MANIFEST, &data, now)?;
+    // Closing the pipe lets the encryption finish
+    drop(archive.into_inner()?);
+    Ok(())
+}
+
+/// Decrypts a bundle, by `age` with the identity file given or by `gpg`,
+/// and reads its manifest, unpacking it into `extract_to` if given. Plain
+/// tar archives are read as they are.
+pub fn import(
+    path: &Path,
+    identity: Option<&Path>,
+    extract_to: Option<&Path>,
+) -> Result<Manifest, Error> {
+    let mut header = [0; 512];
+    let read = File::open(path)?.read(&mut header)?;
+    let header = &header[..read];
+
+    let (reader, child): (Box<dyn Read>, _) = match is_tar(header) {
+        true => (Box::new(File::open(path)?), None),
+        false => {
+            let command = decrypt_command(header, path, identity);
+            let mut child = spawn_reading(command)?;
+            let stdout = child.stdout.take().expect("stdout is piped");
+            (Box::new(stdout), Some(child))
+        }
+    };
+
+    let read = read_bundle(reader, extract_to);
+    if let Some(mut child) = child {
+        if !child.wait()?.success() {
+            return Err(Error::UserError("Decryption failed, see above"));
+        }
+    }
+    read?.ok_or(Error::UserError("Not a kivinge bundle, it has no manifest"))
+}
+
+fn decrypt_command(
+    header: &[u8],
+    path: &Path,
+    identity: Option<&Path>,
+) -> Command {
+    match is_age(header) {
+        true => {
+            let mut command = Command::new("age");
+            command.arg("--decrypt");
+            if let Some(identity) = identity {
+                command.arg("--identity").arg(identity);
+            }
+            command.arg(path);
+            command
+        }
+        false => {
+            let mut command = Command::new("gpg");
+            command.args(["--decrypt", "--quiet"]);
+            if prompt::non_interactive() {
+                command.args(["--batch", "--pinentry-mode", "error"]);
+            }
+            command.arg(path);
+            command
+        }
+    }
+}
+
+fn read_bundle(
+    reader: impl Read,
+    extract_to: Option<&Path>,
+) -> Result<Option<Manifest>, Error> {
+    if let Some(dir) = extract_to {
+        fs::create_dir_all(dir)?;
+    }
+    let mut archive = tar::Archive::new(reader);
+    let mut manifest = None;
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if entry.path()?.as_os_str() == MANIFEST {
+            let mut data = Vec::new();
+            entry.read_to_end(&mut data)?;
+            manifest = Some(serde_json::from_slice(&data)?);
+            if let Some(dir) = extract_to {
+                fs::write(dir.join(MANIFEST), data)?;
+            }
+        } else if let Some(dir) = extract_to {
+            // Leaves out paths that would end up outside of the dir
+            entry.unpack_in(dir)?;
+        }
+    }
+    Ok(manifest)
+}
+
+fn is_age(header: &[u8]) -> bool {
+    header.starts_with(b"age-encryption.org/")
+        || header.starts_with(b"-----BEGIN AGE ENCRYPTED FILE-----")
+}
+
+fn is_tar(header: &[u8]) -> bool {
+    header.get(257..262) == Some(b"ustar")
+}
+
+fn spawn(mut command: Command) -> Result<Child, Error> {
+    command.stdin(Stdio::piped());
+    command.spawn().map_err(|error| match error.kind() {
+        ErrorKind::NotFound => {
+            Error::UserError("age or gpg is needed, and was not found")
+        }
+        _ => error.into(),
+    })
+}
+
+fn spawn_reading(mut command: Command) -> Result<Child, Error> {
+    command.stdout(Stdio::piped());
+    let mut child = spawn(command)?;
+    // The tools ask for passphrases on the terminal, not on stdin
+    drop(child.stdin.take());
+    Ok(child)
+}
+
+pub fn format(manifest: &Manifest) -> String {
+    let mut builder = Builder::default();
+    builder.push_record(["Id", "Sender", "Subject", "Created", "Attachments"]);
+    for item in &manifest.items {
+        let created = item.created_at.with_timezone(&Local);
+        builder.push_record([
+            item.id.to_string(),
+            item.sender.clone(),
+            item.subject.clone(),
+            created.format("%Y-%m-%d").to_string(),
+            item.attachments.join("\n"),
+        ]);
+    }
+    let mut table = builder.build();
+    table.with(Style::modern());
+    let exported_at = manifest.exported_at.with_timezone(&Local);
+    format!("Exported {}\n{table}", exported_at.format("%Y-%m-%d %H:%M"))
+}
@@ -0,0 +1,36 @@
+use tabled::builder::Builder;
+use tabled::settings::Style;
+
+use crate::{
+    error::Error,
+    model::user::{Actor, Company},
+};
+
+/// The company that `reference`, an organization number or key, is
+pub fn find(
+    companies: Vec<Company>,
+    reference: &str,
+) -> Result<Company, Error> {
+    companies
+        .into_iter()
+        .find(|company| company.is(reference))
+        .ok_or(Error::UserError("No company inbox with that number"))
+}
+
+pub fn format(companies: &[Company], current: &Actor) -> String {
+    let mut builder = Builder::default();
+    builder.push_record(["", "Company", "Org nr", "Key"]);
+    for company in companies {
+        let active = *current == Actor::Company(company.clone());
+        builder.push_record([
+            if active { "*" } else { "" },
+            &company.name,
+            &company.org_number,
+            &company.key,
+        ]);
+    }
+
+    let mut table = builder.build();
+    table.with(Style::modern());
+    table.to_string()
+}
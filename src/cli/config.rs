@@ -0,0 +1,289 @@
+use lettre::message::Mailbox;
+use ratatui::style::Color;
+use reqwest::{
+    header::{HeaderName, HeaderValue},
+    Certificate, Url,
+};
+use std::{fs, ops::Range, path::Path, time::Duration};
+use toml_edit::{ImDocument, TableLike};
+
+use crate::{
+    cli::copy,
+    config::{self, Config},
+    filename, notify,
+    tui::inbox::MIN_SYNC_INTERVAL,
+};
+
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+pub struct Problem {
+    pub line: Option<usize>,
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl Problem {
+    fn error(line: Option<usize>, message: String) -> Problem {
+        Problem { line, severity: Severity::Error, message }
+    }
+
+    fn warning(line: Option<usize>, message: String) -> Problem {
+        Problem { line, severity: Severity::Warning, message }
+    }
+
+    pub fn is_error(&self) -> bool {
+        matches!(self.severity, Severity::Error)
+    }
+}
+
+/// Checks the config file for anything `config::load` would reject, ignore
+/// or silently work around. Stops early if the file cannot be parsed.
+pub fn validate(path: &Path) -> Result<Vec<Problem>, config::Error> {
+    Ok(validate_text(&fs::read_to_string(path)?))
+}
+
+/// Like `validate`, for the text of a config file
+pub fn validate_text(text: &str) -> Vec<Problem> {
+    let document = match ImDocument::parse(text) {
+        Ok(document) => document,
+        Err(error) => {
+            let line = line(text, error.span());
+            return vec![Problem::error(line, error.message().to_string())];
+        }
+    };
+    let locate = |key: &str| line(text, key_span(&document, key));
+    let is_set = |key: &str| key_span(&document, key).is_some();
+
+    let mut unknown = Vec::new();
+    let deserializer = toml::Deserializer::new(text);
+    let result: Result<Config, _> =
+        serde_ignored::deserialize(deserializer, |path| {
+            unknown.push(path.to_string())
+        });
+    let mut problems: Vec<Problem> = unknown
+        .iter()
+        .map(|key| Problem::warning(locate(key), format!("unknown key {key}")))
+        .collect();
+    let config = match result {
+        Ok(config) => config,
+        Err(error) => {
+            let line = line(text, error.span());
+            problems.push(Problem::error(line, error.message().to_string()));
+            return problems;
+        }
+    };
+
+    for placeholder in
+        copy::unknown_placeholders(&config.copy.template, &copy::PLACEHOLDERS)
+    {
+        problems.push(Problem::error(
+            locate("copy.template"),
+            format!("unknown placeholder {placeholder} in copy.template"),
+        ));
+    }
+
+    if let Err(problem) = filename::check_template(&config.download.template) {
+        problems.push(Problem::error(
+            locate("download.template"),
+            format!("invalid download.template: {problem}"),
+        ));
+    }
+    if is_set("tui.download_dir") {
+        problems.push(Problem::warning(
+            locate("tui.download_dir"),
+            "tui.download_dir is replaced by download.dir".to_string(),
+        ));
+    }
+
+    if let Some(template) = &config.notify.webhook.template {
+        for placeholder in
+            copy::unknown_placeholders(template, &notify::PLACEHOLDERS)
+        {
+            problems.push(Problem::error(
+                locate("notify.webhook.template"),
+                format!("unknown placeholder {placeholder} in the template"),
+            ));
+        }
+    }
+    for (name, value) in &config.notify.webhook.headers {
+        if HeaderName::try_from(name).is_err()
+            || HeaderValue::try_from(value).is_err()
+        {
+            problems.push(Problem::error(
+                locate(&format!("notify.webhook.headers.{name}")),
+                format!("invalid webhook header '{name}'"),
+            ));
+        }
+    }
+
+    if !(1..=5).contains(&config.notify.ntfy.priority) {
+        problems.push(Problem::error(
+            locate("notify.ntfy.priority"),
+            "notify.ntfy.priority must be from 1 to 5".to_string(),
+        ));
+    }
+
+    let smtp = &config.notify.smtp;
+    if let Some(from) = &smtp.from {
+        if from.parse::<Mailbox>().is_err() {
+            problems.push(Problem::error(
+                locate("notify.smtp.from"),
+                format!("invalid email address '{from}'"),
+            ));
+        }
+    }
+    if smtp.username.is_some() != smtp.password.is_some() {
+        problems.push(Problem::warning(
+            locate("notify.smtp"),
+            "notify.smtp needs both username and password to log in"
+                .to_string(),
+        ));
+    }
+
+    for (name, profile) in &config.profiles {
+        let Some(color) = &profile.color else {
+            continue;
+        };
+        if color.parse::<Color>().is_err() {
+            problems.push(Problem::error(
+                locate(&format!("profiles.{name}.color")),
+                format!("invalid color '{color}' for profile {name}"),
+            ));
+        }
+    }
+    if let Some(color) = &config.tui.color {
+        if color.parse::<Color>().is_err() {
+            problems.push(Problem::error(
+                locate("tui.color"),
+                format!("invalid color '{color}'"),
+            ));
+        }
+    }
+
+    let sync_interval = Duration::from_secs(config.tui.sync_interval);
+    if !sync_interval.is_zero() && sync_interval < MIN_SYNC_INTERVAL {
+        problems.push(Problem::warning(
+            locate("tui.sync_interval"),
+            format!(
+                "tui.sync_interval is raised to the minimum of {} seconds",
+                MIN_SYNC_INTERVAL.as_secs()
+            ),
+        ));
+    }
+
+    if is_set("cache.compress") && !config.cache.attachments {
+        problems.push(Problem::warning(
+            locate("cache.compress"),
+            "cache.compress has no effect without cache.attachments"
+                .to_string(),
+        ));
+    }
+
+    let webdav = &config.storage.webdav;
+    let s3 = &config.storage.s3;
+    let pairs = [
+        (
+            ("storage.webdav.username", webdav.username.is_some()),
+            ("storage.webdav.password", webdav.password.is_some()),
+        ),
+        (
+            ("storage.s3.access_key_id", s3.access_key_id.is_some()),
+            ("storage.s3.secret_access_key", s3.secret_access_key.is_some()),
+        ),
+    ];
+    for ((first, first_set), (second, second_set)) in pairs {
+        let (set, missing) = match (first_set, second_set) {
+            (true, false) => (first, second),
+            (false, true) => (second, first),
+            _ => continue,
+        };
+        problems.push(Problem::error(
+            locate(set),
+            format!("{set} is set but {missing} is not"),
+        ));
+    }
+    let endpoint = Url::parse(&s3.endpoint);
+    if !endpoint.is_ok_and(|url| ["http", "https"].contains(&url.scheme())) {
+        problems.push(Problem::error(
+            locate("storage.s3.endpoint"),
+            format!(
+                "storage.s3.endpoint '{}' is not an http(s) URL",
+                s3.endpoint
+            ),
+        ));
+    }
+
+    let network = &config.network;
+    if let Some(proxy) = &network.proxy {
+        let schemes = ["http", "https", "socks5", "socks5h"];
+        let url = Url::parse(proxy);
+        if !url.is_ok_and(|url| schemes.contains(&url.scheme())) {
+            problems.push(Problem::error(
+                locate("network.proxy"),
+                format!(
+                    "network.proxy '{proxy}' is not an http(s) or socks5 URL"
+                ),
+            ));
+        }
+    } else if network.no_proxy.is_some() {
+        problems.push(Problem::warning(
+            locate("network.no_proxy"),
+            "network.no_proxy has no effect without network.proxy, set \
+             NO_PROXY for proxies from the environment"
+                .to_string(),
+        ));
+    }
+    for path in &network.ca_certificates {
+        let message = match fs::read(path) {
+            Ok(pem) => match Certificate::from_pem_bundle(&pem) {
+                Ok(certificates) if !certificates.is_empty() => continue,
+                _ => format!("{} holds no PEM certificates", path.display()),
+            },
+            Err(error) => format!("cannot read {}: {error}", path.display()),
+        };
+        problems
+            .push(Problem::error(locate("network.ca_certificates"), message));
+    }
+
+    problems.sort_by_key(|problem| problem.line);
+    problems
+}
+
+pub fn format(path: &Path, problems: &[Problem]) -> String {
+    problems
+        .iter()
+        .map(|problem| {
+            let location = match problem.line {
+                Some(line) => format!("{}:{line}", path.display()),
+                None => path.display().to_string(),
+            };
+            let severity = match problem.severity {
+                Severity::Error => "error",
+                Severity::Warning => "warning",
+            };
+            format!("{location}: {severity}: {}\n", problem.message)
+        })
+        .collect()
+}
+
+/// Where a dotted key such as `tui.sync_interval` is set in the document
+fn key_span(document: &ImDocument<&str>, key: &str) -> Option<Range<usize>> {
+    let mut table: &dyn TableLike = document.as_table();
+    let mut segments = key.split('.').peekable();
+    while let Some(segment) = segments.next() {
+        let (key, item) = table.get_key_value(segment)?;
+        if segments.peek().is_none() {
+            return key.span().or(item.span());
+        }
+        table = item.as_table_like()?;
+    }
+    None
+}
+
+fn line(text: &str, span: Option<Range<usize>>) -> Option<usize> {
+    let start = span?.start.min(text.len());
+    Some(text[..start].matches('\n').count() + 1)
+}
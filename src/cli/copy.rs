@@ -0,0 +1,82 @@
+use crate::model::content::InboxEntry;
+
+pub const DEFAULT_TEMPLATE: &str = "\
+{sender}: {subject}
+Amount: {amount} {currency}
+Due date: {due_date}";
+
+// Keep in sync with the fields filled in by `format`
+pub const PLACEHOLDERS: [&str; 7] = [
+    "{id}",
+    "{sender}",
+    "{subject}",
+    "{created}",
+    "{amount}",
+    "{currency}",
+    "{due_date}",
+];
+
+/// Placeholders in the template that are not among `known`, and so would
+/// be left as they are. Only a name in braces counts as a placeholder, so
+/// that other braces, such as those of JSON, are not taken for one.
+pub fn unknown_placeholders(template: &str, known: &[&str]) -> Vec<String> {
+    let is_name = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    let mut unknown = Vec::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        rest = &rest[start + 1..];
+        let name_len = rest.find(|c| !is_name(c)).unwrap_or(rest.len());
+        if name_len == 0 || !rest[name_len..].starts_with('}') {
+            continue;
+        }
+        let placeholder = format!("{{{}}}", &rest[..name_len]);
+        if !known.contains(&placeholder.as_str()) {
+            unknown.push(placeholder);
+        }
+        rest = &rest[name_len + 1..];
+    }
+    unknown
+}
+
+/// Fills in the placeholders of a copy template. Lines where every
+/// placeholder is empty are left out, so the same template works for both
+/// bills and plain letters.
+pub fn format(entry: &InboxEntry, template: &str) -> String {
+    let fields = fields(entry);
+    template
+        .lines()
+        .filter_map(|line| {
+            let used: Vec<&(&str, String)> =
+                fields.iter().filter(|(key, _)| line.contains(key)).collect();
+            if !used.is_empty()
+                && used.iter().all(|(_, value)| value.is_empty())
+            {
+                return None;
+            }
+            let filled =
+                used.iter().fold(line.to_string(), |line, (key, value)| {
+                    line.replace(key, value)
+                });
+            Some(filled)
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+/// The value of each placeholder for the entry, empty where it has none
+pub fn fields(entry: &InboxEntry) -> [(&'static str, String); 7] {
+    let item = &entry.item;
+    let optional = |value: Option<String>| value.unwrap_or_default();
+    [
+        ("{id}", entry.id.to_string()),
+        ("{sender}", item.sender_name.clone()),
+        ("{subject}", item.subject.clone()),
+        ("{created}", item.created_at.date_naive().to_string()),
+        ("{amount}", optional(item.amount.map(|a| a.to_string()))),
+        ("{currency}", optional(item.currency.clone())),
+        (
+            "{due_date}",
+            optional(item.due_date.as_ref().map(|d| d.0.to_string())),
+        ),
+    ]
+}
@@ -0,0 +1,215 @@
+use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+use tabled::builder::Builder;
+use tabled::settings::Style;
+use walkdir::WalkDir;
+
+use crate::{
+    client::{Client, ContentClient},
+    crypto,
+    error::Error,
+    filename, fulltext, index,
+    model::content::{ContentKey, InboxEntry},
+    progress::Progress,
+};
+
+/// Optional `<file>.json` next to a downloaded file telling which item and
+/// attachment it came from
+#[derive(Deserialize)]
+struct Sidecar {
+    key: ContentKey,
+    #[serde(default)]
+    attachment: u32,
+}
+
+pub enum MatchedBy {
+    Sidecar,
+    Name,
+    Hash,
+}
+
+pub struct Imported {
+    pub path: PathBuf,
+    pub entry: Option<(InboxEntry, u32, MatchedBy)>,
+}
+
+struct Candidate {
+    entry: InboxEntry,
+    attachment: u32,
+    content_type: String,
+    size: usize,
+}
+
+/// Registers files in `dir` that are copies of inbox attachments in the
+/// local index. Files are matched by sidecar, by the name kivinge would give
+/// them, and last by downloading attachments of the same size and comparing
+/// hashes. The progress bar counts the items looked up, then the files.
+pub fn run(
+    client: &mut impl Client,
+    dir: &Path,
+    progress: &mut Progress,
+) -> Result<Vec<Imported>, Error> {
+    let profile = client.profile().to_string();
+    let index = index::load(&profile)?;
+    let inbox = client.get_inbox_listing()?;
+
+    let mut by_name: HashMap<String, Candidate> = HashMap::new();
+    progress.start(inbox.len());
+    for entry in inbox.iter() {
+        progress.set_message(entry.to_string());
+        let details = client.get_item_details(&entry.item.key)?;
+        for (num, attachment) in details.parts.iter().enumerate() {
+            let candidate = Candidate {
+                entry: entry.clone(),
+                attachment: num as u32,
+                content_type: attachment.content_type.clone(),
+                size: attachment.size,
+            };
+            by_name.insert(details.attachment_name(num)?, candidate);
+        }
+        progress.advance();
+    }
+
+    let files: Vec<_> = (WalkDir::new(dir).into_iter())
+        .filter_map(Result::ok)
+        .filter(|file| file.file_type().is_file())
+        .filter(|file| !is_sidecar(file.path()))
+        .filter(|file| !index.is_recorded(file.path()))
+        .collect();
+    let mut imported = Vec::new();
+    // The hash and text of each file imported
+    let mut records = Vec::new();
+    progress.start(files.len());
+    for file in &files {
+        let path = file.path();
+        progress.set_message(path.display().to_string());
+        let contents = crypto::unseal(fs::read(path)?)?;
+        let sha256 = index::sha256(&contents);
+
+        let found = match read_sidecar(path) {
+            Some(sidecar) => inbox
+                .iter()
+                .find(|entry| entry.item.key == sidecar.key)
+                .map(|entry| {
+                    (entry.clone(), sidecar.attachment, MatchedBy::Sidecar)
+                }),
+            None => None,
+        };
+        let found = found.or_else(|| {
+            // Files saved before names were sanitized match once they are
+            let name = filename::sanitize(&path.file_name()?.to_string_lossy());
+            let candidate = by_name.get(&name)?;
+            Some((
+                candidate.entry.clone(),
+                candidate.attachment,
+                MatchedBy::Name,
+            ))
+        });
+        let found = match found {
+            Some(found) => Some(found),
+            None => match_by_hash(client, &by_name, contents.len(), &sha256)?
+                .map(|(entry, num)| (entry, num, MatchedBy::Hash)),
+        };
+
+        let text = found.as_ref().and_then(|(entry, num, _)| {
+            let content_type = by_name
+                .values()
+                .find(|c| {
+                    c.entry.item.key == entry.item.key && c.attachment == *num
+                })
+                .map_or("", |candidate| candidate.content_type.as_str());
+            fulltext::extract(content_type, &contents)
+        });
+        imported.push(Imported { path: path.to_path_buf(), entry: found });
+        records.push((sha256, text));
+        progress.advance();
+    }
+
+    // Recorded all at once, so that the index is not held up while the
+    // attachments are matched
+    index::update(&profile, |index| {
+        for (imported, (sha256, text)) in imported.iter().zip(records) {
+            let Some((entry, num, _)) = &imported.entry else {
+                continue;
+            };
+            let key = &entry.item.key;
+            index.record_download(key, *num, &imported.path, sha256);
+            if let Some(text) = text {
+                index.record_text(key, *num, text);
+            }
+        }
+        ((), true)
+    })?;
+    Ok(imported)
+}
+
+fn is_sidecar(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "json")
+        && path.with_extension("").is_file()
+}
+
+fn read_sidecar(path: &Path) -> Option<Sidecar> {
+    let mut sidecar_path = path.as_os_str().to_owned();
+    sidecar_path.push(".json");
+    let contents = fs::read_to_string(sidecar_path).ok()?;
+    serde_json::from_str(&contents).ok()
+}
+
+fn match_by_hash(
+    client: &mut impl ContentClient,
+    candidates: &HashMap<String, Candidate>,
+    size: usize,
+    sha256: &str,
+) -> Result<Option<(InboxEntry, u32)>, Error> {
+    for candidate in candidates.values().filter(|c| c.size == size) {
+        let details = client.get_item_details(&candidate.entry.item.key)?;
+        // The item may have changed since the candidates were listed
+        let Some(attachment) = details.parts.get(candidate.attachment as usize)
+        else {
+            continue;
+        };
+        let contents = match (&attachment.body, &attachment.key) {
+            (Some(body), _) => body.clone().into_bytes().into(),
+            (None, Some(key)) => {
+                client.download_attachment(&candidate.entry.item.key, key)?
+            }
+            (None, None) => continue,
+        };
+        if index::sha256(&contents) == sha256 {
+            return Ok(Some((candidate.entry.clone(), candidate.attachment)));
+        }
+    }
+    Ok(None)
+}
+
+pub fn format(imported: &[Imported]) -> String {
+    let mut builder = Builder::default();
+    builder.push_record(["File", "Item", "Attachment", "Matched By"]);
+    for file in imported {
+        let path = file.path.to_string_lossy().to_string();
+        let record = match &file.entry {
+            None => [path, "-".into(), "-".into(), "no match".into()],
+            Some((entry, num, matched_by)) => {
+                let matched_by = match matched_by {
+                    MatchedBy::Sidecar => "sidecar",
+                    MatchedBy::Name => "name",
+                    MatchedBy::Hash => "content hash",
+                };
+                [path, entry.id.to_string(), num.to_string(), matched_by.into()]
+            }
+        };
+        builder.push_record(record);
+    }
+
+    let matched = imported.iter().filter(|file| file.entry.is_some()).count();
+    let mut table = builder.build();
+    table.with(Style::modern());
+    format!(
+        "{table}\n{matched} of {} file(s) imported into the index",
+        imported.len()
+    )
+}
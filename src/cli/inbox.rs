@@ -1,55 +1,73 @@
-use chrono::{Local, TimeZone};
 use crossterm::terminal;
-use tabled::builder::Builder;
-use tabled::settings::{object::Columns, width::Width, Modify, Style};
+use tracing::warn;
 
-use crate::model::content::InboxListing;
+use crate::{
+    cache::SummaryCache,
+    client::{self, ContentClient},
+    model::content::{AttachmentSummary, ContentKey, InboxEntry, InboxListing},
+    table::{self, Format, Table, TableRow},
+};
 
-pub fn format(inbox: InboxListing) -> String {
-    let mut builder = Builder::default();
-    builder.push_record(["Id", "Sender", "Subject", "Created At"]);
-    for entry in inbox {
-        let local_datetime = Local
-            .from_utc_datetime(&entry.item.created_at.naive_utc())
-            .format("%Y-%m-%d %H:%M")
-            .to_string();
-        builder.push_record([
-            &entry.id.to_string(),
-            &entry.item.sender_name,
-            &entry.item.subject,
-            &local_datetime,
-        ]);
-    }
-
-    let mut table = builder.build();
-    table.with(Style::modern());
+pub const DEFAULT_COLUMNS: &str = "id,sender,subject,created";
+/// What `--details` adds to the columns
+pub const DETAILS_COLUMNS: [&str; 2] = ["attachments", "size"];
 
-    // Table border overhead for modern style with 4 columns:
-    // - 5 separators (one before each column + one at end)
-    // - 8 padding spaces (1 space on each side of 4 columns)
-    const COLUMNS: usize = 4;
-    const BORDER_OVERHEAD: usize = (COLUMNS + 1) + (COLUMNS * 2); // 13
-
-    const ID_WIDTH: usize = 4;
-    const CREATED_AT_WIDTH: usize = 18;
-    const FIXED_WIDTH: usize = ID_WIDTH + CREATED_AT_WIDTH + BORDER_OVERHEAD;
-    const MIN_FLEX_WIDTH: usize = 20;
+pub fn format(
+    inbox: InboxListing,
+    columns: &[String],
+    format: Format,
+) -> Result<String, table::Error> {
+    let entries: Vec<InboxEntry> = inbox.into_iter().collect();
+    format_rows(&entries, columns, format)
+}
 
+/// Inbox entries, or rows listing them along with something more, in the
+/// named columns
+pub fn format_rows<R: TableRow + AsRef<InboxEntry>>(
+    rows: &[R],
+    columns: &[String],
+    format: Format,
+) -> Result<String, table::Error> {
     let term_width = terminal::size().map(|(w, _)| w as usize).unwrap_or(150);
+    let mut columns = columns.to_vec();
+    // Programs get the amount as a number, and its currency next to it
+    let amount = columns.iter().position(|name| name == "amount");
+    if let Some(amount) = amount {
+        let machine = !matches!(format, Format::Table);
+        if machine && !columns.iter().any(|name| name == "currency") {
+            columns.insert(amount + 1, "currency".to_string());
+        }
+    }
+    let table = Table::new(rows).with_columns(&columns)?;
+    Ok(table.format(format, term_width))
+}
 
-    // Split remaining space equally between Sender and Subject
-    let flex_width =
-        term_width.saturating_sub(FIXED_WIDTH).max(MIN_FLEX_WIDTH * 2) / 2;
-
-    table
-        .with(
-            Modify::new(Columns::single(1))
-                .with(Width::truncate(flex_width).suffix("…")),
-        )
-        .with(
-            Modify::new(Columns::single(2))
-                .with(Width::truncate(flex_width).suffix("…")),
-        );
+/// The attachments of an item, from its details the first time and from the
+/// cache after that
+pub fn attachments(
+    client: &mut impl ContentClient,
+    cache: &mut SummaryCache,
+    key: &ContentKey,
+) -> Result<AttachmentSummary, client::Error> {
+    let summary = cache.try_get_or_insert_with(key.clone(), || {
+        let details = client.get_item_details(key);
+        details.map(|details| AttachmentSummary::from(&details))
+    })?;
+    Ok(*summary)
+}
 
-    table.to_string()
+/// Fills in the attachments of every entry, except those whose details could
+/// not be fetched
+pub fn with_attachments(
+    client: &mut impl ContentClient,
+    cache: &mut SummaryCache,
+    inbox: InboxListing,
+) -> InboxListing {
+    inbox.with_attachments(|item| match attachments(client, cache, &item.key) {
+        Ok(summary) => Some(summary),
+        Err(error) => {
+            warn!("failed to get the details of {}: {error}", item.key);
+            None
+        }
+    })
 }
@@ -1,19 +1,43 @@
 use chrono::{Local, TimeZone};
+use std::io::{stderr, stdin, Write};
 
-use crate::{error::Error, model::content::ItemDetails};
+use crate::{
+    cli::labelled,
+    error::Error,
+    model::content::{InboxEntry, InboxItem, ItemDetails},
+    tr,
+};
 
-pub fn format(details: ItemDetails) -> Result<String, Error> {
+pub fn format(item: &InboxItem, details: ItemDetails) -> Result<String, Error> {
     let local_datetime = Local
         .from_utc_datetime(&details.created_at.naive_utc())
         .format("%Y-%m-%d %H:%M")
         .to_string();
 
-    let mut output = vec![
-        format!("Sender:   {}\n", details.sender_name),
-        format!("Subject:  {}\n", details.subject),
-        format!("Created:  {}\n\n", local_datetime),
-        format!("Attachments:\n"),
+    let fields = [
+        ("Sender", details.sender_name.clone()),
+        ("Subject", details.subject.clone()),
+        ("Created", local_datetime),
+        ("Labels", item.active_labels().join(", ")),
     ];
+    let mut output: Vec<String> = (labelled(fields).into_iter())
+        .map(|line| format!("{line}\n"))
+        .collect();
+    output.push("\n".to_string());
+
+    if let Some(form) = &item.form {
+        output.push(format!(
+            "{} ({}):\n",
+            tr!("Form"),
+            tr!("answer it in the Kivra app")
+        ));
+        for line in form.lines() {
+            output.push(format!("  {line}\n"));
+        }
+        output.push("\n".to_string());
+    }
+
+    output.push(format!("{}\n", tr!("Attachments:")));
 
     for i in 0..(details.parts.len()) {
         output.push(format!("  {}: {}\n", i, details.attachment_name(i)?));
@@ -21,3 +45,38 @@ pub fn format(details: ItemDetails) -> Result<String, Error> {
 
     Ok(output.concat())
 }
+
+/// One line per entry with its id, for picking one of several
+pub fn format_candidates(entries: &[InboxEntry]) -> String {
+    let lines = entries.iter().map(|entry| {
+        let created = Local
+            .from_utc_datetime(&entry.item.created_at.naive_utc())
+            .format("%Y-%m-%d");
+        format!(
+            "{:>6}  {created}  {}  {}\n",
+            entry.id, entry.item.sender_name, entry.item.subject
+        )
+    });
+    lines.collect()
+}
+
+/// Asks which of the entries was meant. Asked on stderr, like the login
+/// prompt, so that it is seen when the output is redirected.
+pub fn choose(mut entries: Vec<InboxEntry>) -> Result<InboxEntry, Error> {
+    eprint!("Several items match:\n{}", format_candidates(&entries));
+    loop {
+        eprint!("Which one? [id] ");
+        stderr().flush()?;
+        let mut answer = String::new();
+        if stdin().read_line(&mut answer)? == 0 || answer.trim().is_empty() {
+            return Err(Error::UserError("No inbox item chosen"));
+        }
+        let chosen = answer.trim().parse().ok().and_then(|id: u32| {
+            entries.iter().position(|entry| entry.id == id)
+        });
+        match chosen {
+            Some(index) => return Ok(entries.swap_remove(index)),
+            None => eprintln!("Not one of the ids above"),
+        }
+    }
+}
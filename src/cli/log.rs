@@ -0,0 +1,26 @@
+use chrono::{Local, TimeZone};
+use tabled::builder::Builder;
+use tabled::settings::Style;
+
+use crate::journal::Event;
+
+pub fn format(events: &[Event]) -> String {
+    let mut builder = Builder::default();
+    builder.push_record(["Time", "Event", "Sender", "Subject"]);
+    for event in events {
+        let local_datetime = Local
+            .from_utc_datetime(&event.at.naive_utc())
+            .format("%Y-%m-%d %H:%M")
+            .to_string();
+        builder.push_record([
+            &local_datetime,
+            &event.kind.to_string(),
+            &event.sender_name,
+            &event.subject,
+        ]);
+    }
+
+    let mut table = builder.build();
+    table.with(Style::modern());
+    table.to_string()
+}
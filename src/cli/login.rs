@@ -0,0 +1,155 @@
+use serde_json::json;
+use std::{
+    io::{stdout, IsTerminal, Write},
+    thread::sleep,
+};
+
+use crate::{
+    cli::{labelled, prompt, whoami::Format},
+    client::{
+        login::{bankid_url, LoginFlow},
+        session::UserInfo,
+        AuthClient, LoginOptions,
+    },
+    error::Error,
+    model::auth::AuthTokenResponse,
+    tr,
+    tui::qr::{self, QrRenderer},
+};
+
+/// Asks whether to log in now
+pub fn confirm() -> Result<bool, Error> {
+    prompt::confirm("No session - start BankID login now?")
+}
+
+/// Logs in without the terminal UI by printing the QR code to stdout and
+/// polling in plain text. Suitable for SSH sessions and headless servers.
+pub fn login(
+    client: &impl AuthClient,
+    options: LoginOptions,
+) -> Result<AuthTokenResponse, Error> {
+    let mut flow = LoginFlow::start(client)?;
+    let redraw = stdout().is_terminal();
+    let mut printed_lines = 0;
+    let mut last_status = String::new();
+
+    if options.same_device {
+        opener::open(bankid_url(&flow.auto_start_token))?;
+        println!("Waiting for BankID on this device...");
+    }
+
+    loop {
+        let status = flow.status();
+        if (options.same_device || !redraw) && status != last_status {
+            println!("{status}");
+        }
+        if !options.same_device {
+            printed_lines = print_qr(
+                &flow.qr_code,
+                &status,
+                options.qr_renderer,
+                redraw,
+                printed_lines,
+            )?;
+        }
+        last_status = status;
+
+        sleep(flow.retry_after());
+        if let Some(auth_token) = flow.poll()? {
+            return Ok(auth_token);
+        }
+    }
+}
+
+/// Prints the QR code, redrawing over the previous one on terminals. When
+/// piped, only the data of subsequent codes is printed to keep logs short.
+fn print_qr(
+    qr_data: &str,
+    status: &str,
+    renderer: QrRenderer,
+    redraw: bool,
+    printed_lines: usize,
+) -> Result<usize, Error> {
+    if printed_lines > 0 && !redraw {
+        println!("QR data: {qr_data}");
+        return Ok(printed_lines);
+    }
+    if printed_lines > 0 {
+        print!("\x1b[{printed_lines}A");
+    }
+    let output = qr_output(qr_data, renderer)?;
+    let output = match redraw {
+        // Clears what is left of a longer status drawn before
+        true => format!("{output}{status}\x1b[K\n"),
+        false => output,
+    };
+    print!("{output}");
+    stdout().flush()?;
+    Ok(output.lines().count())
+}
+
+fn qr_output(qr_data: &str, renderer: QrRenderer) -> Result<String, Error> {
+    let image = qr::encode(qr_data, renderer)?;
+    Ok(format!(
+        "Scan the QR code with the BankID app:\n\n{image}\n\n\
+         QR data: {qr_data}\n"
+    ))
+}
+
+/// A login started by `kivinge login --start`, with the token to poll it
+pub fn format_started<C: AuthClient>(
+    token: &str,
+    flow: &LoginFlow<C>,
+    format: Format,
+) -> Result<String, Error> {
+    let bankid_url = bankid_url(&flow.auto_start_token);
+    match format {
+        Format::Json => Ok(serde_json::to_string_pretty(&json!({
+            "token": token,
+            "qr_code": flow.qr_code,
+            "auto_start_token": flow.auto_start_token,
+            "bankid_url": bankid_url,
+        }))?),
+        Format::Text => Ok(labelled([
+            ("Token", token.to_string()),
+            ("QR data", flow.qr_code.clone()),
+            ("BankID", bankid_url),
+        ])
+        .join("\n")),
+    }
+}
+
+/// A login polled by `kivinge login --poll` that still waits for BankID,
+/// with the QR code to show now
+pub fn format_pending<C: AuthClient>(
+    flow: &LoginFlow<C>,
+    format: Format,
+) -> Result<String, Error> {
+    match format {
+        Format::Json => Ok(serde_json::to_string_pretty(&json!({
+            "status": "pending",
+            "message": flow.status(),
+            "qr_code": flow.qr_code,
+            "retry_after": flow.retry_after().as_secs(),
+        }))?),
+        Format::Text => Ok(labelled([
+            ("Status", flow.status()),
+            ("QR data", flow.qr_code.clone()),
+        ])
+        .join("\n")),
+    }
+}
+
+pub fn format_logged_in(
+    user: &UserInfo,
+    format: Format,
+) -> Result<String, Error> {
+    match format {
+        Format::Json => Ok(serde_json::to_string_pretty(&json!({
+            "status": "logged_in",
+            "kivra_user_id": user.kivra_user_id,
+            "name": user.name,
+        }))?),
+        Format::Text => Ok(tr!("Login Successful").to_string()),
+    }
+}
@@ -1,2 +1,39 @@
+pub mod agreements;
+pub mod backup;
+pub mod bundle;
+pub mod companies;
+pub mod config;
+pub mod copy;
+pub mod import;
 pub mod inbox;
 pub mod inbox_item;
+pub mod log;
+pub mod login;
+#[cfg(all(feature = "fuse", any(target_os = "linux", target_os = "macos")))]
+pub mod mounts;
+pub mod prompt;
+pub mod receipts;
+pub mod search;
+pub mod selftest;
+pub mod senders;
+pub mod service;
+pub mod stats;
+pub mod status;
+pub mod whoami;
+
+use crate::tr;
+
+/// Labels translated and lined up after their colons, one line each, for
+/// the text that describes one thing
+pub fn labelled<'a>(
+    fields: impl IntoIterator<Item = (&'a str, String)>,
+) -> Vec<String> {
+    let fields: Vec<_> = (fields.into_iter())
+        .map(|(label, value)| (format!("{}:", tr!(label)), value))
+        .collect();
+    let width = fields.iter().map(|(label, _)| label.chars().count()).max();
+    let width = width.unwrap_or_default();
+    (fields.into_iter())
+        .map(|(label, value)| format!("{label:width$} {value}"))
+        .collect()
+}
@@ -0,0 +1,26 @@
+use chrono::Local;
+use tabled::builder::Builder;
+use tabled::settings::Style;
+
+use crate::mounts::Mount;
+
+pub fn format(mounts: Vec<Mount>) -> String {
+    if mounts.is_empty() {
+        return "Nothing is mounted by kivinge".to_string();
+    }
+    let mut builder = Builder::default();
+    builder.push_record(["Mountpoint", "Profile", "Pid", "Mounted At"]);
+    for mount in mounts {
+        let mounted_at = mount.mounted_at.with_timezone(&Local);
+        builder.push_record([
+            mount.mountpoint.display().to_string(),
+            mount.profile,
+            mount.pid.to_string(),
+            mounted_at.format("%Y-%m-%d %H:%M").to_string(),
+        ]);
+    }
+
+    let mut table = builder.build();
+    table.with(Style::modern());
+    table.to_string()
+}
@@ -0,0 +1,49 @@
+//! Questions asked on the terminal, and whether they may be asked at all.
+//! With `--non-interactive` nothing is asked, so that commands run from
+//! cron fail instead of waiting for an answer that never comes.
+
+use std::{
+    io::{stderr, stdin, IsTerminal, Write},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use crate::error::Error;
+
+static NON_INTERACTIVE: AtomicBool = AtomicBool::new(false);
+static ASSUME_YES: AtomicBool = AtomicBool::new(false);
+
+/// Sets whether questions may be asked, and whether confirmations are
+/// answered with yes without asking, as given on the command line
+pub fn init(non_interactive: bool, assume_yes: bool) {
+    NON_INTERACTIVE.store(non_interactive, Ordering::Relaxed);
+    ASSUME_YES.store(assume_yes, Ordering::Relaxed);
+}
+
+/// Whether anything may be asked: not with `--non-interactive`, nor
+/// without a terminal to answer on
+pub fn interactive() -> bool {
+    !NON_INTERACTIVE.load(Ordering::Relaxed) && stdin().is_terminal()
+}
+
+/// Whether `--non-interactive` was given, even with a terminal at hand
+pub fn non_interactive() -> bool {
+    NON_INTERACTIVE.load(Ordering::Relaxed)
+}
+
+/// Asks a yes or no question, yes being the answer to enter. Asked on
+/// stderr so that it is seen even when the output of the command is
+/// redirected. Answered with yes by `--yes`, and an error when it can not
+/// be asked.
+pub fn confirm(question: &str) -> Result<bool, Error> {
+    if ASSUME_YES.load(Ordering::Relaxed) {
+        return Ok(true);
+    }
+    if !interactive() {
+        return Err(Error::UserError("Confirmation needed, give --yes"));
+    }
+    eprint!("{question} [Y/n] ");
+    stderr().flush()?;
+    let mut answer = String::new();
+    stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "" | "y" | "yes"))
+}
@@ -0,0 +1,67 @@
+use chrono::{Local, TimeZone};
+use tabled::builder::Builder;
+use tabled::settings::Style;
+
+use crate::{
+    cli::labelled,
+    model::receipt::{ReceiptDetails, ReceiptListing},
+    tr,
+};
+
+pub fn format(receipts: ReceiptListing) -> String {
+    let mut builder = Builder::default();
+    builder.push_record(
+        ["Id", "Store", "Amount", "Purchased At"].map(|header| tr!(header)),
+    );
+    for entry in receipts {
+        let local_datetime = Local
+            .from_utc_datetime(&entry.receipt.purchase_date.naive_utc())
+            .format("%Y-%m-%d %H:%M")
+            .to_string();
+        builder.push_record([
+            &entry.id.to_string(),
+            &entry.receipt.store_name,
+            &format!(
+                "{} {}",
+                entry.receipt.total_amount, entry.receipt.currency
+            ),
+            &local_datetime,
+        ]);
+    }
+
+    let mut table = builder.build();
+    table.with(Style::modern());
+    table.to_string()
+}
+
+pub fn format_details(details: &ReceiptDetails) -> String {
+    let local_datetime = Local
+        .from_utc_datetime(&details.purchase_date.naive_utc())
+        .format("%Y-%m-%d %H:%M")
+        .to_string();
+
+    let mut fields = vec![
+        ("Store", details.store_name.clone()),
+        ("Purchased", local_datetime),
+        ("Total", format!("{} {}", details.total_amount, details.currency)),
+    ];
+    if let Some(payment_method) = &details.payment_method {
+        fields.push(("Paid with", payment_method.clone()));
+    }
+    let mut output: Vec<String> = (labelled(fields).into_iter())
+        .map(|line| format!("{line}\n"))
+        .collect();
+
+    output.push(format!("\n{}\n", tr!("Items:")));
+    for line in &details.items {
+        output.push(format!(
+            "  {} x {}: {} {}\n",
+            line.quantity.normalize(),
+            line.description,
+            line.amount,
+            details.currency
+        ));
+    }
+
+    output.concat()
+}
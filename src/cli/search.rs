@@ -0,0 +1,77 @@
+use crate::{
+    cli,
+    filter::{self, Filter},
+    fulltext,
+    index::Index,
+    model::content::{InboxEntry, InboxListing},
+    table::{self, Column, Format, TableRow, Width},
+};
+
+/// Characters of attachment text shown around a match
+const SNIPPET_WIDTH: usize = 60;
+
+/// Items whose sender or subject contain `words`, and with `content` those
+/// with an attachment whose text does
+pub fn filter(words: &str, content: bool) -> Filter {
+    let value = filter::quote(words);
+    let mut expression = format!("sender={value} || subject={value}");
+    if content {
+        expression.push_str(&format!(" || content={value}"));
+    }
+    Filter::parse(&expression).expect("quoted values parse")
+}
+
+/// An item found, with where in the text of its attachments the words are
+/// when searching contents
+pub struct SearchHit {
+    pub entry: InboxEntry,
+    pub snippet: Option<String>,
+}
+
+impl AsRef<InboxEntry> for SearchHit {
+    fn as_ref(&self) -> &InboxEntry {
+        &self.entry
+    }
+}
+
+impl TableRow for SearchHit {
+    fn columns() -> Vec<Column<Self>> {
+        let mut columns: Vec<Column<Self>> = table::inbox_columns();
+        columns.push(Column {
+            name: "match",
+            header: "Match",
+            width: Width::Fill(20),
+            cell: |hit| hit.snippet.as_deref().unwrap_or_default().into(),
+            value: Some(|hit| hit.snippet.clone().into()),
+        });
+        columns
+    }
+}
+
+/// The items found in the named columns, with a `match` column when
+/// searching contents
+pub fn format(
+    inbox: InboxListing,
+    index: &Index,
+    words: &str,
+    content: bool,
+    columns: &[String],
+    format: Format,
+) -> Result<String, table::Error> {
+    let hits: Vec<SearchHit> = (inbox.into_iter())
+        .map(|entry| {
+            let texts = index.texts.get(&entry.item.key).into_iter().flatten();
+            let snippet = texts
+                .filter_map(|(_, text)| {
+                    fulltext::snippet(text, words, SNIPPET_WIDTH)
+                })
+                .next();
+            SearchHit { entry, snippet: snippet.filter(|_| content) }
+        })
+        .collect();
+    let mut columns = columns.to_vec();
+    if content && !columns.iter().any(|name| name == "match") {
+        columns.push("match".to_string());
+    }
+    cli::inbox::format_rows(&hits, &columns, format)
+}
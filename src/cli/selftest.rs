@@ -0,0 +1,98 @@
+use std::time::{Duration, Instant};
+
+use crate::client::Client;
+
+pub enum Outcome {
+    Pass(String),
+    Fail(String),
+    Skip,
+}
+
+pub struct Step {
+    pub name: &'static str,
+    pub outcome: Outcome,
+    pub elapsed: Duration,
+}
+
+impl Step {
+    pub fn passed(&self) -> bool {
+        matches!(self.outcome, Outcome::Pass(_))
+    }
+
+    pub fn failed(&self) -> bool {
+        matches!(self.outcome, Outcome::Fail(_))
+    }
+}
+
+fn step(
+    name: &'static str,
+    run: impl FnOnce() -> Result<String, String>,
+) -> Step {
+    let start = Instant::now();
+    let outcome = match run() {
+        Ok(message) => Outcome::Pass(message),
+        Err(message) => Outcome::Fail(message),
+    };
+    Step { name, outcome, elapsed: start.elapsed() }
+}
+
+fn skip(name: &'static str) -> Step {
+    Step { name, outcome: Outcome::Skip, elapsed: Duration::ZERO }
+}
+
+/// Runs a read-only sequence of API calls, stopping at the first failure
+pub fn run(client: &mut impl Client) -> Vec<Step> {
+    let mut steps = vec![step("Fetch config", || {
+        let config = client.get_config().map_err(|err| err.to_string())?;
+        Ok(format!("client id {}", config.oauth_default_client_id))
+    })];
+
+    steps.push(match steps.iter().all(Step::passed) {
+        false => skip("Load session"),
+        true => step("Load session", || {
+            let session =
+                client.get_session_or_login().map_err(|e| e.to_string())?;
+            Ok(format!("logged in as {}", session.user_info.name))
+        }),
+    });
+
+    let mut newest_key = None;
+    steps.push(match steps.iter().all(Step::passed) {
+        false => skip("List inbox"),
+        true => step("List inbox", || {
+            let inbox =
+                client.get_inbox_listing().map_err(|e| e.to_string())?;
+            newest_key = inbox.last().map(|entry| entry.item.key.clone());
+            Ok(format!("{} items", inbox.len()))
+        }),
+    });
+
+    steps.push(match (steps.iter().all(Step::passed), newest_key) {
+        (true, Some(key)) => step("Fetch item details", || {
+            let details =
+                client.get_item_details(&key).map_err(|e| e.to_string())?;
+            Ok(format!("{} attachments", details.parts.len()))
+        }),
+        _ => skip("Fetch item details"),
+    });
+
+    steps
+}
+
+pub fn format(steps: &[Step]) -> String {
+    steps
+        .iter()
+        .map(|step| {
+            let elapsed = step.elapsed.as_millis();
+            match &step.outcome {
+                Outcome::Pass(message) => {
+                    format!("[PASS] {} ({elapsed} ms): {message}\n", step.name)
+                }
+                Outcome::Fail(message) => {
+                    format!("[FAIL] {} ({elapsed} ms): {message}\n", step.name)
+                }
+                Outcome::Skip => format!("[SKIP] {}\n", step.name),
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,21 @@
+use tabled::builder::Builder;
+use tabled::settings::Style;
+
+use crate::model::content::SenderSummary;
+
+pub fn format(senders: Vec<SenderSummary>) -> String {
+    let mut builder = Builder::default();
+    builder.push_record(["Sender", "Items", "Unread", "Key"]);
+    for sender in senders {
+        builder.push_record([
+            &sender.name,
+            &sender.items.to_string(),
+            &sender.unread.to_string(),
+            &sender.key,
+        ]);
+    }
+
+    let mut table = builder.build();
+    table.with(Style::modern());
+    table.to_string()
+}
@@ -0,0 +1,228 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{client::session::DEFAULT_PROFILE, error::Error};
+
+/// A systemd unit file
+pub struct Unit {
+    pub name: String,
+    pub contents: String,
+}
+
+pub struct Options<'a> {
+    pub profile: &'a str,
+    /// A unit of the user's service manager rather than a system unit
+    pub user: bool,
+    pub mount: Option<&'a Path>,
+    /// Passed on to `watch`
+    pub watch_args: &'a [String],
+}
+
+/// Units that run `watch`, and the mount if asked for, as this program with
+/// the profile and the config, data and state dirs in use now
+pub fn units(options: &Options) -> Result<Vec<Unit>, Error> {
+    let program = env::current_exe()?;
+    let program = vec![
+        program.to_string_lossy().to_string(),
+        "--profile".to_string(),
+        options.profile.to_string(),
+    ];
+    let service = ServiceSection::new(options.user)?;
+
+    let mut watch = program.clone();
+    watch.push("watch".to_string());
+    watch.extend(options.watch_args.iter().cloned());
+    let mut units = vec![Unit {
+        name: unit_name("watch", options.profile),
+        contents: service.unit(
+            &format!(
+                "Watch the Kivra inbox of kivinge profile {}",
+                options.profile
+            ),
+            &[("ExecStart", command(&watch))],
+        ),
+    }];
+
+    if let Some(mountpoint) = options.mount {
+        if !cfg!(all(
+            feature = "fuse",
+            any(target_os = "linux", target_os = "macos")
+        )) {
+            return Err(Error::UserError("kivinge was built without FUSE"));
+        }
+        let mountpoint = env::current_dir()?.join(mountpoint);
+        let mountpoint = mountpoint.to_string_lossy().to_string();
+        let mut mount = program.clone();
+        mount.extend(["mount".to_string(), mountpoint.clone()]);
+        let mut unmount = program.clone();
+        unmount.extend(["unmount".to_string(), mountpoint.clone()]);
+        units.push(Unit {
+            name: unit_name("mount", options.profile),
+            contents: service.unit(
+                &format!("Mount the Kivra inbox at {mountpoint}"),
+                // The mount command goes to the background by itself
+                &[
+                    ("Type", "forking".to_string()),
+                    ("ExecStart", command(&mount)),
+                    ("ExecStop", command(&unmount)),
+                ],
+            ),
+        });
+    }
+    Ok(units)
+}
+
+/// Where units are installed: the user's units in the config dir, or the
+/// system's in /etc
+pub fn unit_dir(user: bool) -> Result<PathBuf, Error> {
+    match user {
+        true => {
+            let dir = dirs::config_dir().ok_or(Error::AppError(
+                "Cannot find the config dir for user units",
+            ))?;
+            Ok(dir.join("systemd").join("user"))
+        }
+        false => Ok(PathBuf::from("/etc/systemd/system")),
+    }
+}
+
+pub fn install(units: &[Unit], dir: &Path) -> Result<Vec<PathBuf>, Error> {
+    fs::create_dir_all(dir)?;
+    units
+        .iter()
+        .map(|unit| {
+            let path = dir.join(&unit.name);
+            fs::write(&path, &unit.contents)?;
+            Ok(path)
+        })
+        .collect()
+}
+
+/// Every unit after a comment with where it would be written
+pub fn format_dry_run(units: &[Unit], dir: &Path) -> String {
+    let units: Vec<String> = units
+        .iter()
+        .map(|unit| {
+            let path = dir.join(&unit.name);
+            format!("# {}\n{}", path.to_string_lossy(), unit.contents)
+        })
+        .collect();
+    units.join("\n")
+}
+
+/// What to do once the units are written
+pub fn format_installed(paths: &[PathBuf], user: bool) -> String {
+    let systemctl = match user {
+        true => "systemctl --user",
+        false => "systemctl",
+    };
+    let names: Vec<String> = paths
+        .iter()
+        .filter_map(|path| path.file_name())
+        .map(|name| name.to_string_lossy().to_string())
+        .collect();
+    let mut text: String = paths
+        .iter()
+        .map(|path| format!("Wrote {}\n", path.to_string_lossy()))
+        .collect();
+    text.push_str(&format!(
+        "Log in with `kivinge login` if you have not, then run\n  \
+         {systemctl} daemon-reload\n  {systemctl} enable --now {}",
+        names.join(" ")
+    ));
+    text
+}
+
+/// The parts that the units share
+struct ServiceSection {
+    user: bool,
+    /// For system units, the user to run as
+    run_as: Option<String>,
+    environment: Vec<String>,
+}
+
+impl ServiceSection {
+    fn new(user: bool) -> Result<ServiceSection, Error> {
+        let run_as = match user {
+            true => None,
+            false => Some(env::var("USER").map_err(|_| {
+                Error::UserError("Cannot tell which user to run as, set USER")
+            })?),
+        };
+        // So that the service finds the files this run would use
+        let dirs = [
+            ("XDG_CONFIG_HOME", dirs::config_dir()),
+            ("XDG_DATA_HOME", dirs::data_dir()),
+            ("XDG_STATE_HOME", dirs::state_dir()),
+        ];
+        let environment = dirs
+            .into_iter()
+            .filter_map(|(name, dir)| {
+                let dir = dir?;
+                Some(quote(&format!("{name}={}", dir.to_string_lossy())))
+            })
+            .collect();
+        Ok(ServiceSection { user, run_as, environment })
+    }
+
+    fn unit(&self, description: &str, service: &[(&str, String)]) -> String {
+        let mut text = format!("[Unit]\nDescription={description}\n");
+        // The user's service manager has no network target to wait for
+        if !self.user {
+            text.push_str(
+                "Wants=network-online.target\nAfter=network-online.target\n",
+            );
+        }
+        text.push_str("\n[Service]\n");
+        for (key, value) in service {
+            text.push_str(&format!("{key}={value}\n"));
+        }
+        if let Some(user) = &self.run_as {
+            text.push_str(&format!("User={user}\n"));
+        }
+        for variable in &self.environment {
+            text.push_str(&format!("Environment={variable}\n"));
+        }
+        text.push_str("Restart=on-failure\nRestartSec=60\n");
+        let target = match self.user {
+            true => "default.target",
+            false => "multi-user.target",
+        };
+        text.push_str(&format!("\n[Install]\nWantedBy={target}\n"));
+        text
+    }
+}
+
+/// Units of the default profile keep the plain names
+fn unit_name(kind: &str, profile: &str) -> String {
+    if profile == DEFAULT_PROFILE {
+        return format!("kivinge-{kind}.service");
+    }
+    let profile: String = profile
+        .chars()
+        .map(|c| match c.is_ascii_alphanumeric() || "-_.".contains(c) {
+            true => c,
+            false => '_',
+        })
+        .collect();
+    format!("kivinge-{kind}-{profile}.service")
+}
+
+fn command(args: &[String]) -> String {
+    let args: Vec<String> = args.iter().map(|arg| quote(arg)).collect();
+    args.join(" ")
+}
+
+/// An argument as systemd reads it, where `%` starts a specifier and `$` a
+/// variable
+fn quote(arg: &str) -> String {
+    let arg = arg.replace('%', "%%").replace('$', "$$");
+    if !arg.is_empty()
+        && !arg.contains(|c: char| c.is_whitespace() || "\"'\\;".contains(c))
+    {
+        return arg;
+    }
+    format!("\"{}\"", arg.replace('\\', "\\\\").replace('"', "\\\""))
+}
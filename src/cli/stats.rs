@@ -0,0 +1,223 @@
+use chrono::{Datelike, Local};
+use clap::ValueEnum;
+use rust_decimal::Decimal;
+use serde::Serialize;
+use std::{cmp::Reverse, collections::BTreeMap};
+use tabled::{builder::Builder, settings::Style};
+
+use crate::{
+    error::Error,
+    index::{Index, IndexedItem},
+    model::content::InboxListing,
+};
+
+/// Attachments listed as the largest
+const LARGEST: usize = 10;
+
+/// How `kivinge stats` prints the statistics
+#[derive(Clone, Copy, Debug, Default, ValueEnum)]
+pub enum Format {
+    #[default]
+    Table,
+    Json,
+}
+
+#[derive(Serialize)]
+pub struct Stats {
+    pub items: usize,
+    pub unread: usize,
+    /// Those with the most items first
+    pub senders: Vec<SenderStats>,
+    /// Oldest month first
+    pub billed: Vec<MonthlyBill>,
+    /// Largest first, among the attachments of items whose details have
+    /// been fetched
+    pub largest: Vec<LargeAttachment>,
+}
+
+#[derive(Serialize)]
+pub struct SenderStats {
+    pub sender: String,
+    pub items: usize,
+    pub years: BTreeMap<i32, usize>,
+}
+
+#[derive(Serialize)]
+pub struct MonthlyBill {
+    /// As in 2024-08
+    pub month: String,
+    pub currency: String,
+    pub amount: Decimal,
+}
+
+#[derive(Serialize)]
+pub struct LargeAttachment {
+    pub id: u32,
+    pub sender: String,
+    pub subject: String,
+    pub attachment: usize,
+    pub size: usize,
+}
+
+/// The items the index knows of, with their ids, leaving out the trash
+pub fn items_from_index(index: Index) -> Vec<(u32, IndexedItem)> {
+    let Index { ids, items, .. } = index;
+    items
+        .into_iter()
+        .filter(|(_, item)| !item.trashed)
+        .map(|(key, item)| (ids.get(&key).copied().unwrap_or_default(), item))
+        .collect()
+}
+
+/// For a client that keeps no index, the items of a listing. Attachment
+/// sizes are taken from the index where it has them.
+pub fn items_from_listing(
+    listing: &InboxListing,
+    index: &Index,
+) -> Vec<(u32, IndexedItem)> {
+    listing
+        .iter()
+        .map(|entry| {
+            let mut item = IndexedItem::new(&entry.item);
+            item.attachment_sizes = (index.items.get(&entry.item.key))
+                .and_then(|indexed| indexed.attachment_sizes.clone());
+            (entry.id, item)
+        })
+        .filter(|(_, item)| !item.trashed)
+        .collect()
+}
+
+pub fn compute(items: &[(u32, IndexedItem)]) -> Stats {
+    let mut senders: BTreeMap<&str, SenderStats> = BTreeMap::new();
+    let mut billed: BTreeMap<(String, String), Decimal> = BTreeMap::new();
+    let mut largest = Vec::new();
+    for (id, item) in items {
+        let created = item.created_at.with_timezone(&Local);
+        let sender =
+            senders.entry(&item.sender).or_insert_with(|| SenderStats {
+                sender: item.sender.clone(),
+                items: 0,
+                years: BTreeMap::new(),
+            });
+        sender.items += 1;
+        *sender.years.entry(created.year()).or_default() += 1;
+
+        if let (Some(amount), Some(currency)) = (item.amount, &item.currency) {
+            let month = created.format("%Y-%m").to_string();
+            *billed.entry((month, currency.clone())).or_default() += amount;
+        }
+
+        let sizes = item.attachment_sizes.iter().flatten();
+        for (attachment, &size) in sizes.enumerate() {
+            largest.push(LargeAttachment {
+                id: *id,
+                sender: item.sender.clone(),
+                subject: item.subject.clone(),
+                attachment,
+                size,
+            });
+        }
+    }
+
+    let mut senders: Vec<SenderStats> = senders.into_values().collect();
+    senders.sort_by_key(|sender| Reverse(sender.items));
+    largest.sort_by_key(|attachment| Reverse(attachment.size));
+    largest.truncate(LARGEST);
+    Stats {
+        items: items.len(),
+        unread: items.iter().filter(|(_, item)| item.unread).count(),
+        senders,
+        billed: billed
+            .into_iter()
+            .map(|((month, currency), amount)| MonthlyBill {
+                month,
+                currency,
+                amount,
+            })
+            .collect(),
+        largest,
+    }
+}
+
+pub fn format(stats: &Stats, format: Format) -> Result<String, Error> {
+    match format {
+        Format::Json => Ok(serde_json::to_string_pretty(stats)?),
+        Format::Table => Ok(format_tables(stats)),
+    }
+}
+
+fn format_tables(stats: &Stats) -> String {
+    let mut sections =
+        vec![format!("{} item(s), {} unread", stats.items, stats.unread)];
+
+    if !stats.senders.is_empty() {
+        let years: Vec<i32> = {
+            let mut years: Vec<i32> = (stats.senders.iter())
+                .flat_map(|sender| sender.years.keys().copied())
+                .collect();
+            years.sort();
+            years.dedup();
+            years
+        };
+        let mut builder = Builder::default();
+        builder.push_record(
+            ["Sender".to_string()]
+                .into_iter()
+                .chain(years.iter().map(|year| year.to_string()))
+                .chain(["Total".to_string()]),
+        );
+        for sender in &stats.senders {
+            let counts = years.iter().map(|year| {
+                let count = sender.years.get(year).copied().unwrap_or_default();
+                count.to_string()
+            });
+            builder.push_record(
+                [sender.sender.clone()]
+                    .into_iter()
+                    .chain(counts)
+                    .chain([sender.items.to_string()]),
+            );
+        }
+        sections.push(table(builder));
+    }
+
+    if !stats.billed.is_empty() {
+        let mut builder = Builder::default();
+        builder.push_record(["Month", "Billed"]);
+        for bill in &stats.billed {
+            let amount = format!("{} {}", bill.amount, bill.currency);
+            builder.push_record([bill.month.clone(), amount]);
+        }
+        sections.push(table(builder));
+    }
+
+    match stats.largest.is_empty() {
+        // Sizes come with the details, so none are known at first
+        true => sections.push(
+            "No attachment sizes are known yet, they are recorded as items \
+             are opened"
+                .to_string(),
+        ),
+        false => {
+            let mut builder = Builder::default();
+            builder.push_record(["Id", "Sender", "Subject", "#", "Bytes"]);
+            for attachment in &stats.largest {
+                builder.push_record([
+                    attachment.id.to_string(),
+                    attachment.sender.clone(),
+                    attachment.subject.clone(),
+                    attachment.attachment.to_string(),
+                    attachment.size.to_string(),
+                ]);
+            }
+            sections.push(table(builder));
+        }
+    }
+    sections.join("\n\n")
+}
+
+fn table(builder: Builder) -> String {
+    let mut table = builder.build();
+    table.with(Style::modern());
+    table.to_string()
+}
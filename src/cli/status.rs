@@ -0,0 +1,93 @@
+use chrono::{DateTime, Local, TimeDelta, Utc};
+use std::path::PathBuf;
+
+use crate::client::session::Session;
+
+/// What `kivinge status` reports about a profile
+pub struct Status {
+    pub profile: String,
+    pub session: Option<Session>,
+    /// Number of cached responses and when the newest was stored, unless
+    /// the response cache is off
+    pub responses: Option<(usize, Option<DateTime<Utc>>)>,
+    /// Filesystems mounted by kivinge, with the profile of each
+    pub mounts: Vec<(PathBuf, String)>,
+}
+
+impl Status {
+    pub fn is_logged_in(&self) -> bool {
+        self.session.as_ref().is_some_and(|session| !session.is_expired())
+    }
+}
+
+pub fn format(status: &Status) -> String {
+    let mut lines = vec![("Profile", status.profile.clone())];
+    match &status.session {
+        Some(session) => {
+            let user = &session.user_info;
+            lines.push((
+                "User",
+                format!("{} ({})", user.name, mask_ssn(&user.ssn)),
+            ));
+            lines.push(("Session", expiry(session.expires_at)));
+        }
+        None => lines.push(("Session", "not logged in".to_string())),
+    }
+    let cache = match status.responses {
+        None => "off".to_string(),
+        Some((0, _)) => "empty".to_string(),
+        Some((count, last_stored)) => {
+            let updated = last_stored
+                .map(|at| format!(", last updated {}", local_time(at)))
+                .unwrap_or_default();
+            format!("{count} response(s){updated}")
+        }
+    };
+    lines.push(("Cache", cache));
+    let mounts = status
+        .mounts
+        .iter()
+        .map(|(mountpoint, profile)| {
+            format!("{} ({profile})", mountpoint.display())
+        })
+        .collect::<Vec<_>>();
+    lines.push((
+        "Mounts",
+        match mounts.is_empty() {
+            true => "none".to_string(),
+            false => mounts.join(", "),
+        },
+    ));
+
+    lines
+        .into_iter()
+        .map(|(label, value)| format!("{:<9}{value}", format!("{label}:")))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Only the date of birth is shown
+fn mask_ssn(ssn: &str) -> String {
+    let birth_date = ssn.get(..ssn.len().saturating_sub(4)).unwrap_or(ssn);
+    format!("{birth_date}-****")
+}
+
+fn expiry(expires_at: Option<DateTime<Utc>>) -> String {
+    let Some(expires_at) = expires_at else {
+        return "valid, expiry unknown".to_string();
+    };
+    let left = expires_at - Utc::now();
+    match left <= TimeDelta::zero() {
+        true => format!("expired {}", local_time(expires_at)),
+        false => format!(
+            "valid until {} ({}h {}m left)",
+            local_time(expires_at),
+            left.num_hours(),
+            left.num_minutes() % 60
+        ),
+    }
+}
+
+fn local_time(at: DateTime<Utc>) -> String {
+    at.with_timezone(&Local).format("%Y-%m-%d %H:%M").to_string()
+}
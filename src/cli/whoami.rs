@@ -0,0 +1,49 @@
+use clap::ValueEnum;
+use serde_json::json;
+
+use crate::{
+    cli::labelled, client::session::UserInfo, error::Error,
+    model::user::UserProfile,
+};
+
+/// How `kivinge whoami` prints the user, and `kivinge login --start` and
+/// `--poll` the login
+#[derive(Clone, Copy, Debug, Default, ValueEnum)]
+pub enum Format {
+    #[default]
+    Text,
+    Json,
+}
+
+/// The user as the session tells, or with everything Kivra has on them
+/// when `profile` was fetched
+pub fn format(
+    user: &UserInfo,
+    profile: Option<&UserProfile>,
+    format: Format,
+) -> Result<String, Error> {
+    match (format, profile) {
+        (Format::Json, Some(profile)) => {
+            Ok(serde_json::to_string_pretty(profile)?)
+        }
+        (Format::Json, None) => Ok(serde_json::to_string_pretty(&json!({
+            "kivra_user_id": user.kivra_user_id,
+            "name": user.name,
+            "email": user.email,
+        }))?),
+        (Format::Text, profile) => {
+            let email = profile
+                .and_then(|profile| profile.email.as_ref())
+                .unwrap_or(&user.email);
+            let mut fields =
+                vec![("Name", user.name.clone()), ("Email", email.clone())];
+            let phone =
+                profile.and_then(|profile| profile.phone_number.as_ref());
+            if let Some(phone) = phone {
+                fields.push(("Phone", phone.clone()));
+            }
+            fields.push(("User id", user.kivra_user_id.to_string()));
+            Ok(labelled(fields).join("\n"))
+        }
+    }
+}
@@ -0,0 +1,228 @@
+use bytes::Bytes;
+use std::time::Duration;
+
+use super::{
+    AuthClient, Client, ContentClient, Error, Page, Session, SessionStore,
+};
+use crate::{
+    cache::{Cache, ATTACHMENT_POLICY, DETAILS_POLICY, INBOX_POLICY},
+    model::{auth::*, content::*, receipt::*, user::*, Config},
+};
+
+/// Wraps a client and remembers listings, item details and attachments, so
+/// that views asking for the same data again do not go to Kivra. Changes
+/// made through the client drop the cached listing.
+pub struct CachingClient<C: Client> {
+    client: C,
+    inbox: Cache<(), InboxListing>,
+    details: Cache<ContentKey, ItemDetails>,
+    attachments: Cache<(ContentKey, String), Bytes>,
+}
+
+impl<C: Client> CachingClient<C> {
+    pub fn new(client: C) -> Self {
+        CachingClient {
+            client,
+            inbox: Cache::new("inbox", INBOX_POLICY),
+            details: Cache::new("details", DETAILS_POLICY),
+            attachments: Cache::new("attachments", ATTACHMENT_POLICY),
+        }
+    }
+
+    pub fn with_inbox_ttl(self, ttl: Duration) -> Self {
+        let inbox = Cache::new("inbox", INBOX_POLICY.with_ttl(ttl));
+        CachingClient { inbox, ..self }
+    }
+
+    pub fn with_details_ttl(self, ttl: Duration) -> Self {
+        let details = Cache::new("details", DETAILS_POLICY.with_ttl(ttl));
+        CachingClient { details, ..self }
+    }
+
+    fn clear(&mut self) {
+        // Only disk backed caches can fail to clear
+        _ = self.inbox.clear();
+        _ = self.details.clear();
+        _ = self.attachments.clear();
+    }
+}
+
+impl<C: Client> AuthClient for CachingClient<C> {
+    fn get_config(&self) -> Result<Config, Error> {
+        self.client.get_config()
+    }
+
+    fn start_auth(
+        &self,
+        config: &Config,
+    ) -> Result<(CodeVerifier, AuthResponse), Error> {
+        self.client.start_auth(config)
+    }
+
+    fn check_auth(&self, poll_url: &str) -> Result<AuthStatus, Error> {
+        self.client.check_auth(poll_url)
+    }
+
+    fn abort_auth(&self, poll_url: &str) -> Result<(), Error> {
+        self.client.abort_auth(poll_url)
+    }
+
+    fn get_auth_token(
+        &self,
+        config: &Config,
+        auth_code: AuthCode,
+        verifier: CodeVerifier,
+    ) -> Result<AuthTokenResponse, Error> {
+        self.client.get_auth_token(config, auth_code, verifier)
+    }
+
+    fn revoke_auth_token(&mut self) -> Result<(), Error> {
+        self.clear();
+        self.client.revoke_auth_token()
+    }
+}
+
+impl<C: Client> ContentClient for CachingClient<C> {
+    fn get_inbox_listing(&mut self) -> Result<InboxListing, Error> {
+        let client = &mut self.client;
+        let listing = self
+            .inbox
+            .try_get_or_insert_with((), || client.get_inbox_listing())?;
+        Ok(listing.clone())
+    }
+
+    fn invalidate(&mut self) {
+        self.inbox.remove(&());
+        self.client.invalidate()
+    }
+
+    // Pages are fetched once each as the user scrolls, so they are not kept
+    #[cfg(feature = "unverified")]
+    fn get_inbox_page(&mut self, page: Page) -> Result<Vec<InboxItem>, Error> {
+        self.client.get_inbox_page(page)
+    }
+
+    // Kivra is asked for the whole listing for every page, so pages are cut
+    // from the kept one instead
+    #[cfg(not(feature = "unverified"))]
+    fn get_inbox_page(&mut self, page: Page) -> Result<Vec<InboxItem>, Error> {
+        let listing = self.get_inbox_listing()?;
+        let newest_first = listing.iter().rev().skip(page.offset);
+        Ok(newest_first.take(page.limit).map(|e| e.item.clone()).collect())
+    }
+
+    fn number_items(&mut self, items: Vec<InboxItem>) -> InboxListing {
+        self.client.number_items(items)
+    }
+
+    fn get_item_details(
+        &mut self,
+        item_key: &str,
+    ) -> Result<ItemDetails, Error> {
+        let client = &mut self.client;
+        let details = self
+            .details
+            .try_get_or_insert_with(item_key.to_string(), || {
+                client.get_item_details(item_key)
+            })?;
+        Ok(details.clone())
+    }
+
+    fn mark_as_read(&mut self, item_key: &str) -> Result<(), Error> {
+        self.inbox.remove(&());
+        self.client.mark_as_read(item_key)
+    }
+
+    fn set_label(&mut self, item_key: &str, label: &str) -> Result<(), Error> {
+        self.inbox.remove(&());
+        self.client.set_label(item_key, label)
+    }
+
+    fn remove_label(
+        &mut self,
+        item_key: &str,
+        label: &str,
+    ) -> Result<(), Error> {
+        self.inbox.remove(&());
+        self.client.remove_label(item_key, label)
+    }
+
+    fn download_attachment(
+        &mut self,
+        item_key: &str,
+        attachment_key: &str,
+    ) -> Result<Bytes, Error> {
+        let client = &mut self.client;
+        let key = (item_key.to_string(), attachment_key.to_string());
+        let contents = self.attachments.try_get_or_insert_with(key, || {
+            client.download_attachment(item_key, attachment_key)
+        })?;
+        Ok(contents.clone())
+    }
+
+    fn get_sender_icon(&mut self, url: &str) -> Result<Bytes, Error> {
+        self.client.get_sender_icon(url)
+    }
+
+    fn get_receipts(&mut self) -> Result<ReceiptListing, Error> {
+        self.client.get_receipts()
+    }
+
+    fn get_receipt_details(
+        &mut self,
+        receipt_key: &str,
+    ) -> Result<ReceiptDetails, Error> {
+        self.client.get_receipt_details(receipt_key)
+    }
+
+    fn get_user_profile(&mut self) -> Result<UserProfile, Error> {
+        self.client.get_user_profile()
+    }
+
+    fn get_companies(&mut self) -> Result<Vec<Company>, Error> {
+        self.client.get_companies()
+    }
+
+    fn actor(&self) -> Actor {
+        self.client.actor()
+    }
+
+    // The cached listing and details belong to the previous inbox
+    fn set_actor(&mut self, actor: Actor) {
+        self.clear();
+        self.client.set_actor(actor)
+    }
+
+    fn is_offline(&self) -> bool {
+        self.client.is_offline()
+    }
+
+    // What was fetched before may be newer than what is kept locally, and
+    // the other way around
+    fn set_offline(&mut self, offline: bool) {
+        if offline != self.client.is_offline() {
+            self.clear();
+        }
+        self.client.set_offline(offline)
+    }
+}
+
+impl<C: Client> SessionStore for CachingClient<C> {
+    fn set_session(&mut self, session: Session) {
+        self.client.set_session(session)
+    }
+
+    fn get_session(&self) -> Option<Session> {
+        self.client.get_session()
+    }
+
+    fn profile(&self) -> &str {
+        self.client.profile()
+    }
+
+    // Whoever logs in may not be the user the cached data belongs to
+    fn login(&mut self) -> Result<Session, Error> {
+        self.clear();
+        self.client.login()
+    }
+}
@@ -0,0 +1,65 @@
+use serde_json::Value;
+use tracing::warn;
+
+use super::Error;
+use crate::model::content::{ContentKey, InboxItem};
+
+/// The items of a listing that decoded, and the keys of those that did not
+#[derive(Default)]
+pub struct Decoded {
+    pub items: Vec<InboxItem>,
+    /// `None` for an item without a key
+    pub skipped: Vec<Option<ContentKey>>,
+}
+
+/// Decodes a listing of inbox items one item at a time, so that an item the
+/// model no longer fits is left out instead of failing the whole listing.
+/// What was left out is logged with the shape of its payload, which shows
+/// how the API has drifted without logging the contents of the inbox.
+pub fn inbox_listing(json: &str) -> Result<Decoded, Error> {
+    let values: Vec<Value> = serde_json::from_str(json)?;
+    let total = values.len();
+    let mut decoded = Decoded::default();
+    for value in values {
+        let key = value.get("key").and_then(Value::as_str).map(String::from);
+        let shape = shape(&value);
+        match serde_json::from_value(value) {
+            Ok(item) => decoded.items.push(item),
+            Err(error) => {
+                let name = key.as_deref().unwrap_or("<no key>");
+                warn!("skipped inbox item {name}: {error}, shape: {shape}");
+                decoded.skipped.push(key);
+            }
+        }
+    }
+    if !decoded.skipped.is_empty() {
+        let skipped = decoded.skipped.len();
+        warn!("skipped {skipped} of {total} inbox items that did not decode");
+    }
+    Ok(decoded)
+}
+
+/// The items of a listing that decoded
+pub fn inbox_items(json: &str) -> Result<Vec<InboxItem>, Error> {
+    Ok(inbox_listing(json)?.items)
+}
+
+/// The value with its fields kept and everything else replaced by the name
+/// of its type. Arrays are shown by their first element.
+fn shape(value: &Value) -> Value {
+    match value {
+        Value::Null => "null".into(),
+        Value::Bool(_) => "bool".into(),
+        Value::Number(_) => "number".into(),
+        Value::String(_) => "string".into(),
+        Value::Array(values) => {
+            Value::Array(values.first().map(shape).into_iter().collect())
+        }
+        Value::Object(fields) => Value::Object(
+            fields
+                .iter()
+                .map(|(name, value)| (name.clone(), shape(value)))
+                .collect(),
+        ),
+    }
+}
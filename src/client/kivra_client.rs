@@ -1,11 +1,31 @@
 use bytes::Bytes;
-use reqwest::blocking::{RequestBuilder, Response};
-use tracing::instrument;
+use reqwest::{
+    blocking::{RequestBuilder, Response},
+    header::{ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED},
+    Certificate, NoProxy, Proxy, StatusCode,
+};
+use std::{
+    fs,
+    io::Read,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc::{self, RecvTimeoutError},
+    },
+    thread,
+    time::{Duration, Instant},
+};
+use tracing::{debug, debug_span, warn};
 
 use super::session::{self, Session};
-use super::{Client, Error};
-use crate::model::{auth::*, content::*, Config};
-use crate::tui;
+use super::tape::Tape;
+use super::worker::{self, Cancel, CANCEL_POLL};
+use super::{
+    decode, AuthClient, ContentClient, Error, LoginOptions, Page, SessionStore,
+};
+use crate::cache::{AttachmentCache, CachedResponse, ResponseCache};
+use crate::config::NetworkConfig;
+use crate::model::{auth::*, content::*, receipt::*, user::*, Config};
+use crate::{cli, index, journal, progress, tui};
 
 const API_URL: &str = "https://app.api.kivra.com";
 const ACCOUNTS_URL: &str = "https://accounts.kivra.com";
@@ -22,39 +42,212 @@ macro_rules! post {
     };
 }
 
+#[cfg(feature = "unverified")]
+macro_rules! put {
+    ($self:ident, $pattern:literal) => {
+        $self.client.put(format!($pattern))
+    };
+}
+
 macro_rules! delete {
     ($self:ident, $pattern:literal) => {
         $self.client.delete(format!($pattern))
     };
 }
 
+/// Numbers the requests of a run, so that what is logged about each can be
+/// told apart when they overlap
+static NEXT_REQUEST_ID: AtomicU64 = AtomicU64::new(1);
+
 trait Request {
-    fn try_send(self) -> reqwest::Result<Response>;
+    fn try_send(self, tape: Option<&Tape>) -> Result<Response, Error>;
+}
+
+impl Request for RequestBuilder {
+    /// Logs only the method and URL of the request, as its headers and body
+    /// may hold tokens
+    fn try_send(self, tape: Option<&Tape>) -> Result<Response, Error> {
+        let (client, request) = self.build_split();
+        let request = request?;
+        let id = NEXT_REQUEST_ID.fetch_add(1, Ordering::Relaxed);
+        let span = debug_span!(
+            "request",
+            id,
+            method = %request.method(),
+            url = %request.url(),
+        );
+        let _entered = span.enter();
+        let started = Instant::now();
+        let request = RequestBuilder::from_parts(client, request);
+        let response = match tape {
+            Some(tape) => tape.send(request),
+            None => match worker::current() {
+                Some(cancel) => send_cancellable(request, &cancel),
+                None => request.send().map_err(Error::from),
+            },
+        }
+        .inspect_err(|error| debug!("request failed: {error}"))?;
+        let elapsed = started.elapsed().as_millis();
+        debug!(status = response.status().as_u16(), elapsed_ms = elapsed);
+        Ok(response.error_for_status()?)
+    }
 }
 
-impl Request for reqwest::blocking::RequestBuilder {
-    #[instrument(level = "DEBUG")]
-    fn try_send(self) -> reqwest::Result<Response> {
-        self.send()?.error_for_status()
+/// Sends the request from a thread of its own, which is left to finish
+/// alone if the worker is cancelled in the meantime
+fn send_cancellable(
+    request: RequestBuilder,
+    cancel: &Cancel,
+) -> Result<Response, Error> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || _ = sender.send(request.send()));
+    loop {
+        if cancel.is_cancelled() {
+            return Err(Error::Cancelled);
+        }
+        match receiver.recv_timeout(CANCEL_POLL) {
+            Ok(response) => return Ok(response?),
+            Err(RecvTimeoutError::Timeout) => continue,
+            Err(RecvTimeoutError::Disconnected) => {
+                return Err(Error::Cancelled)
+            }
+        }
     }
 }
 
 pub struct KivraClient {
     client: reqwest::blocking::Client,
     session: Option<Session>,
+    profile: String,
+    login_options: LoginOptions,
+    /// Whether `login` may prompt the user, through the terminal
+    interactive: bool,
+    attachment_cache: Option<AttachmentCache>,
+    response_cache: Option<ResponseCache>,
+    tape: Option<Tape>,
+    actor: Actor,
+    offline: bool,
 }
 
 impl KivraClient {
-    pub fn new() -> Result<KivraClient, Error> {
-        let client =
-            reqwest::blocking::Client::builder().use_native_tls().build()?;
-        Ok(KivraClient { client, session: None })
+    pub fn new(network: &NetworkConfig) -> Result<KivraClient, Error> {
+        let client = http_client(network)?;
+        Ok(KivraClient {
+            client,
+            session: None,
+            profile: session::DEFAULT_PROFILE.to_string(),
+            login_options: LoginOptions::default(),
+            interactive: true,
+            attachment_cache: None,
+            response_cache: None,
+            tape: None,
+            actor: Actor::User,
+            offline: false,
+        })
+    }
+
+    pub fn with_profile(self, profile: &str) -> Self {
+        KivraClient { profile: profile.to_string(), ..self }
+    }
+
+    pub fn with_login_options(self, login_options: LoginOptions) -> Self {
+        KivraClient { login_options, ..self }
+    }
+
+    /// Never prompts for a login, so that calls that need one fail with
+    /// `Error::LoginRequired` instead. For use as a library, where there may
+    /// be no terminal.
+    pub fn non_interactive(self) -> Self {
+        KivraClient { interactive: false, ..self }
+    }
+
+    pub fn with_attachment_cache(self, cache: AttachmentCache) -> Self {
+        KivraClient { attachment_cache: Some(cache), ..self }
+    }
+
+    pub fn with_response_cache(self, cache: ResponseCache) -> Self {
+        KivraClient { response_cache: Some(cache), ..self }
+    }
+
+    /// Records all traffic to the tape, or replays it from there. A replay
+    /// runs as the user that was logged in while recording.
+    pub fn with_tape(self, tape: Tape) -> Result<Self, Error> {
+        let session = tape.replay_session()?.or(self.session);
+        Ok(KivraClient { tape: Some(tape), session, ..self })
+    }
+
+    fn is_replaying(&self) -> bool {
+        self.tape.as_ref().is_some_and(Tape::is_replay)
+    }
+
+    /// The part of content URLs telling whose inbox it is
+    fn owner(&self, session: &Session) -> String {
+        match &self.actor {
+            Actor::User => format!("user/{}", session.user_info.kivra_user_id),
+            Actor::Company(company) => format!("company/{}", company.key),
+        }
+    }
+
+    /// Sends the validators of the cached response along with the request,
+    /// and serves the cached body if Kivra answers that nothing changed.
+    fn conditional_request(
+        &mut self,
+        request: RequestBuilder,
+    ) -> Result<String, Error> {
+        let url = request
+            .try_clone()
+            .and_then(|request| request.build().ok())
+            .map(|request| request.url().to_string());
+        if self.offline {
+            let cached = (self.response_cache.as_mut())
+                .zip(url)
+                .and_then(|(cache, url)| cache.get(&url).cloned());
+            return cached.map(|cached| cached.body).ok_or(Error::Offline);
+        }
+        let (Some(cache), Some(url)) = (&mut self.response_cache, url) else {
+            return Ok(self.auth_request(request)?.text()?);
+        };
+
+        let cached = cache.get(&url).cloned();
+        let mut request = request;
+        if let Some(cached) = &cached {
+            if let Some(etag) = &cached.etag {
+                request = request.header(IF_NONE_MATCH, etag);
+            }
+            if let Some(last_modified) = &cached.last_modified {
+                request = request.header(IF_MODIFIED_SINCE, last_modified);
+            }
+        }
+
+        let response = self.auth_request(request)?;
+        if let (StatusCode::NOT_MODIFIED, Some(cached)) =
+            (response.status(), cached)
+        {
+            return Ok(cached.body);
+        }
+        let header = |name| {
+            let value = response.headers().get(name)?.to_str().ok()?;
+            Some(value.to_string())
+        };
+        let etag = header(ETAG);
+        let last_modified = header(LAST_MODIFIED);
+        let body = response.text()?;
+        if let Some(cache) = &mut self.response_cache {
+            if etag.is_some() || last_modified.is_some() {
+                let response = CachedResponse { etag, last_modified, body };
+                return Ok(cache.insert(url, response).body.clone());
+            }
+        }
+        Ok(body)
     }
 
     pub fn auth_request(
         &mut self,
         request: RequestBuilder,
     ) -> Result<Response, Error> {
+        if self.offline {
+            return Err(Error::Offline);
+        }
         let req_clone = request.try_clone().ok_or(Error::CloneError)?;
         match self.try_with_session(req_clone) {
             Ok(response) => Ok(response),
@@ -75,19 +268,23 @@ impl KivraClient {
         request: RequestBuilder,
     ) -> Result<Response, Error> {
         let session = self.session.as_ref().ok_or(Error::NoSession)?;
-        request.bearer_auth(&session.access_token).try_send().map_err(|err| {
-            if err.status() == Some(reqwest::StatusCode::UNAUTHORIZED) {
+        let request = request.bearer_auth(&session.access_token);
+        request.try_send(self.tape.as_ref()).map_err(|err| match err {
+            Error::HttpError(err)
+                if err.status() == Some(StatusCode::UNAUTHORIZED) =>
+            {
                 Error::SessionExpired
-            } else {
-                err.into()
             }
+            err => err,
         })
     }
 }
 
-impl Client for KivraClient {
+impl AuthClient for KivraClient {
     fn get_config(&self) -> Result<Config, Error> {
-        Ok(get!(self, "{ACCOUNTS_URL}/config.json").try_send()?.json()?)
+        Ok(get!(self, "{ACCOUNTS_URL}/config.json")
+            .try_send(self.tape.as_ref())?
+            .json()?)
     }
 
     fn start_auth(
@@ -107,17 +304,19 @@ impl Client for KivraClient {
         };
         let response = get!(self, "{API_URL}/v2/oauth2/authorize")
             .query(&auth_request)
-            .try_send()?
+            .try_send(self.tape.as_ref())?
             .json()?;
         Ok((verifier, response))
     }
 
     fn check_auth(&self, poll_url: &str) -> Result<AuthStatus, Error> {
-        Ok(get!(self, "{API_URL}{poll_url}").try_send()?.json()?)
+        Ok(get!(self, "{API_URL}{poll_url}")
+            .try_send(self.tape.as_ref())?
+            .json()?)
     }
 
     fn abort_auth(&self, poll_url: &str) -> Result<(), Error> {
-        delete!(self, "{API_URL}{poll_url}").try_send()?;
+        delete!(self, "{API_URL}{poll_url}").try_send(self.tape.as_ref())?;
         Ok(())
     }
 
@@ -138,11 +337,14 @@ impl Client for KivraClient {
 
         Ok(post!(self, "{API_URL}/v2/oauth2/token")
             .json(&token_request)
-            .try_send()?
+            .try_send(self.tape.as_ref())?
             .json()?)
     }
 
     fn revoke_auth_token(&mut self) -> Result<(), Error> {
+        if self.offline {
+            return Err(Error::Offline);
+        }
         if let Some(session) = self.get_or_load_session()? {
             let body = RevokeRequest {
                 token: session.access_token.clone(),
@@ -150,18 +352,72 @@ impl Client for KivraClient {
             };
             post!(self, "{API_URL}/v2/oauth2/token/revoke")
                 .json(&body)
-                .try_send()?;
+                .try_send(self.tape.as_ref())?;
         }
         Ok(())
     }
+}
 
+impl ContentClient for KivraClient {
     fn get_inbox_listing(&mut self) -> Result<InboxListing, Error> {
         let session = self.get_session_or_login()?;
-        let user_id = &session.user_info.kivra_user_id;
-        let request = get!(self, "{API_URL}/v3/user/{user_id}/content")
+        let owner = self.owner(&session);
+        let request = get!(self, "{API_URL}/v3/{owner}/content")
+            .query(&[("listing", "all")]);
+        let decoded =
+            decode::inbox_listing(&self.conditional_request(request)?)?;
+        let listing = self.number_items(decoded.items);
+        // A replayed inbox is not the user's own, and the journal follows
+        // only the user's own inbox as it is now
+        if self.is_replaying() || self.actor != Actor::User || self.offline {
+            return Ok(listing);
+        }
+        if let Err(error) =
+            journal::record(&self.profile, &listing, &decoded.skipped)
+        {
+            warn!("failed to update the inbox journal: {error}");
+        }
+        Ok(listing)
+    }
+
+    fn number_items(&mut self, items: Vec<InboxItem>) -> InboxListing {
+        // A replayed inbox must not hand out ids in the user's index
+        if self.is_replaying() {
+            return InboxListing::from_content_specs(items);
+        }
+        match index::item_ids(&self.profile, &items) {
+            Ok(ids) => InboxListing::numbered(items, |item| ids[&item.key]),
+            Err(error) => {
+                warn!("failed to number items by the index: {error}");
+                InboxListing::from_content_specs(items)
+            }
+        }
+    }
+
+    #[cfg(feature = "unverified")]
+    fn get_inbox_page(&mut self, page: Page) -> Result<Vec<InboxItem>, Error> {
+        let session = self.get_session_or_login()?;
+        let owner = self.owner(&session);
+        let request = get!(self, "{API_URL}/v3/{owner}/content").query(&[
+            ("listing", "all".to_string()),
+            ("limit", page.limit.to_string()),
+            ("offset", page.offset.to_string()),
+        ]);
+        decode::inbox_items(&self.conditional_request(request)?)
+    }
+
+    // Pages are cut from the whole listing, as it is not known how Kivra
+    // pages it
+    #[cfg(not(feature = "unverified"))]
+    fn get_inbox_page(&mut self, page: Page) -> Result<Vec<InboxItem>, Error> {
+        let session = self.get_session_or_login()?;
+        let owner = self.owner(&session);
+        let request = get!(self, "{API_URL}/v3/{owner}/content")
             .query(&[("listing", "all")]);
-        let listing = self.auth_request(request)?.json()?;
-        Ok(InboxListing::from_content_specs(listing))
+        let mut items =
+            decode::inbox_items(&self.conditional_request(request)?)?;
+        items.sort_by_key(|item| std::cmp::Reverse(item.created_at));
+        Ok(items.into_iter().skip(page.offset).take(page.limit).collect())
     }
 
     fn get_item_details(
@@ -169,62 +425,253 @@ impl Client for KivraClient {
         item_key: &str,
     ) -> Result<ItemDetails, Error> {
         let session = self.get_session_or_login()?;
-        let user_id = &session.user_info.kivra_user_id;
-        let response = self.auth_request(get!(
-            self,
-            "{API_URL}/v3/user/{user_id}/content/{item_key}"
-        ))?;
-        Ok(response.json()?)
+        let owner = self.owner(&session);
+        let request = get!(self, "{API_URL}/v3/{owner}/content/{item_key}");
+        let details: ItemDetails =
+            serde_json::from_str(&self.conditional_request(request)?)?;
+        if !self.is_replaying() {
+            let sizes = details.parts.iter().map(|part| part.size).collect();
+            if let Err(error) =
+                index::attachment_sizes(&self.profile, item_key, sizes)
+            {
+                warn!(
+                    "failed to record attachment sizes in the index: {error}"
+                );
+            }
+        }
+        Ok(details)
     }
 
     fn mark_as_read(&mut self, item_key: &str) -> Result<(), Error> {
         let session = self.get_session_or_login()?;
-        let user_id = &session.user_info.kivra_user_id;
+        let owner = self.owner(&session);
         self.auth_request(
-            post!(self, "{API_URL}/v2/user/{user_id}/content/{item_key}/view")
+            post!(self, "{API_URL}/v2/{owner}/content/{item_key}/view")
                 .header("content-type", "application/json"),
         )?;
         Ok(())
     }
 
+    #[cfg(feature = "unverified")]
+    fn set_label(&mut self, item_key: &str, label: &str) -> Result<(), Error> {
+        let session = self.get_session_or_login()?;
+        let owner = self.owner(&session);
+        self.auth_request(put!(
+            self,
+            "{API_URL}/v2/{owner}/content/{item_key}/label/{label}"
+        ))?;
+        Ok(())
+    }
+
+    #[cfg(feature = "unverified")]
+    fn remove_label(
+        &mut self,
+        item_key: &str,
+        label: &str,
+    ) -> Result<(), Error> {
+        let session = self.get_session_or_login()?;
+        let owner = self.owner(&session);
+        self.auth_request(delete!(
+            self,
+            "{API_URL}/v2/{owner}/content/{item_key}/label/{label}"
+        ))?;
+        Ok(())
+    }
+
+    // Labels are read from the listing, but how they are set is not known
+    #[cfg(not(feature = "unverified"))]
+    fn set_label(
+        &mut self,
+        _item_key: &str,
+        _label: &str,
+    ) -> Result<(), Error> {
+        Err(Error::Unverified("label changes"))
+    }
+
+    #[cfg(not(feature = "unverified"))]
+    fn remove_label(
+        &mut self,
+        _item_key: &str,
+        _label: &str,
+    ) -> Result<(), Error> {
+        Err(Error::Unverified("label changes"))
+    }
+
     fn download_attachment(
         &mut self,
         item_key: &str,
         attachment_key: &str,
     ) -> Result<Bytes, Error> {
+        let cache = self.attachment_cache.as_ref();
+        if let Some(cached) =
+            cache.and_then(|c| c.get(item_key, attachment_key))
+        {
+            return Ok(cached);
+        }
+
         let session = self.get_session_or_login()?;
-        let user_id = &session.user_info.kivra_user_id;
+        let owner = self.owner(&session);
         let req = get!(
             self,
-            "{API_URL}/v1/user/{user_id}/content/{item_key}/file/{attachment_key}/raw"
+            "{API_URL}/v1/{owner}/content/{item_key}/file/{attachment_key}/raw"
         );
-        Ok(self.auth_request(req)?.bytes()?)
+        let contents = read_reporting(self.auth_request(req)?)?;
+        if let Some(cache) = &self.attachment_cache {
+            if let Err(error) = cache.put(item_key, attachment_key, &contents) {
+                warn!("failed to cache attachment: {error}");
+            }
+        }
+        Ok(contents)
+    }
+
+    // Icons are served to anyone, without a session
+    fn get_sender_icon(&mut self, url: &str) -> Result<Bytes, Error> {
+        if self.offline {
+            return Err(Error::Offline);
+        }
+        let request = self.client.get(url);
+        Ok(request.try_send(self.tape.as_ref())?.bytes()?)
     }
 
+    #[cfg(feature = "unverified")]
+    fn get_receipts(&mut self) -> Result<ReceiptListing, Error> {
+        let session = self.get_session_or_login()?;
+        let user_id = &session.user_info.kivra_user_id;
+        let request = get!(self, "{API_URL}/v1/user/{user_id}/receipts");
+        let receipts =
+            serde_json::from_str(&self.conditional_request(request)?)?;
+        Ok(ReceiptListing::from_receipts(receipts))
+    }
+
+    #[cfg(feature = "unverified")]
+    fn get_receipt_details(
+        &mut self,
+        receipt_key: &str,
+    ) -> Result<ReceiptDetails, Error> {
+        let session = self.get_session_or_login()?;
+        let user_id = &session.user_info.kivra_user_id;
+        let request =
+            get!(self, "{API_URL}/v1/user/{user_id}/receipts/{receipt_key}");
+        Ok(serde_json::from_str(&self.conditional_request(request)?)?)
+    }
+
+    #[cfg(not(feature = "unverified"))]
+    fn get_receipts(&mut self) -> Result<ReceiptListing, Error> {
+        Err(Error::Unverified("receipts"))
+    }
+
+    #[cfg(not(feature = "unverified"))]
+    fn get_receipt_details(
+        &mut self,
+        _receipt_key: &str,
+    ) -> Result<ReceiptDetails, Error> {
+        Err(Error::Unverified("receipts"))
+    }
+
+    #[cfg(feature = "unverified")]
+    fn get_user_profile(&mut self) -> Result<UserProfile, Error> {
+        let session = self.get_session_or_login()?;
+        let user_id = &session.user_info.kivra_user_id;
+        let request = get!(self, "{API_URL}/v1/user/{user_id}");
+        Ok(serde_json::from_str(&self.conditional_request(request)?)?)
+    }
+
+    #[cfg(feature = "unverified")]
+    fn get_companies(&mut self) -> Result<Vec<Company>, Error> {
+        let session = self.get_session_or_login()?;
+        let user_id = &session.user_info.kivra_user_id;
+        let request = get!(self, "{API_URL}/v1/user/{user_id}/companies");
+        Ok(serde_json::from_str(&self.conditional_request(request)?)?)
+    }
+
+    #[cfg(not(feature = "unverified"))]
+    fn get_user_profile(&mut self) -> Result<UserProfile, Error> {
+        Err(Error::Unverified("user profiles"))
+    }
+
+    #[cfg(not(feature = "unverified"))]
+    fn get_companies(&mut self) -> Result<Vec<Company>, Error> {
+        Err(Error::Unverified("company inboxes"))
+    }
+
+    fn actor(&self) -> Actor {
+        self.actor.clone()
+    }
+
+    fn set_actor(&mut self, actor: Actor) {
+        self.actor = actor;
+    }
+
+    fn is_offline(&self) -> bool {
+        self.offline
+    }
+
+    fn set_offline(&mut self, offline: bool) {
+        self.offline = offline;
+    }
+}
+
+impl SessionStore for KivraClient {
     fn get_session(&self) -> Option<Session> {
         self.session.clone()
     }
 
     fn set_session(&mut self, session: Session) {
+        if let Some(tape) = &mut self.tape {
+            if let Err(error) = tape.set_user(&session.user_info) {
+                warn!("failed to anonymize the recording: {error}");
+            }
+        }
         self.session = Some(session);
     }
 
+    fn profile(&self) -> &str {
+        &self.profile
+    }
+
+    // Offline, an expired session still tells whose local data to read
+    fn get_or_load_session(&mut self) -> Result<Option<Session>, Error> {
+        let session = self.get_session().or(session::try_load(&self.profile)?);
+        match session {
+            Some(session) if self.offline || !session.is_expired() => {
+                self.set_session(session.clone());
+                Ok(Some(session))
+            }
+            _ => Ok(None),
+        }
+    }
+
     fn login(&mut self) -> Result<Session, Error> {
-        let to_dyn_boxed = |error: tui::Error| -> Box<dyn std::error::Error> {
-            Box::new(error)
+        if self.offline {
+            return Err(Error::Offline);
+        }
+        let options = self.login_options;
+        // A worker of the UI leaves the login to the UI's own thread
+        let auth_response = if !self.interactive || worker::current().is_some()
+        {
+            return Err(Error::LoginRequired);
+        } else if options.headless {
+            Some(cli::login::login(self, options).map_err(boxed)?)
+        } else if options.prompt {
+            match cli::login::confirm().map_err(boxed)? {
+                true => Some(cli::login::login(self, options).map_err(boxed)?),
+                false => None,
+            }
+        } else {
+            let mut terminal = tui::terminal::load().map_err(boxed)?;
+            let mut view =
+                tui::login::LoginView::make(self, options).map_err(boxed)?;
+            let header = tui::Header::default();
+            tui::show(&mut view, &mut terminal, &header, None).map_err(boxed)?
         };
 
-        let mut terminal = tui::terminal::load().map_err(to_dyn_boxed)?;
-        let mut view =
-            tui::login::LoginView::make(self).map_err(to_dyn_boxed)?;
-
-        match tui::show(&mut view, &mut terminal, None).map_err(to_dyn_boxed)? {
+        match auth_response {
             Some(auth_response) => {
                 let session = session::make(
                     auth_response.access_token,
                     auth_response.id_token,
                 )?;
-                session::save(&session)?;
+                session::save(&session, &self.profile)?;
                 self.set_session(session.clone());
                 Ok(session)
             }
@@ -232,3 +679,54 @@ impl Client for KivraClient {
         }
     }
 }
+
+/// The body of a response, with how much of it is in reported to the
+/// progress bar as it arrives
+fn read_reporting(mut response: Response) -> Result<Bytes, Error> {
+    let total = response.content_length();
+    let mut body = Vec::with_capacity(total.unwrap_or_default() as usize);
+    let mut chunk = [0; 64 * 1024];
+    loop {
+        let read = response.read(&mut chunk)?;
+        if read == 0 {
+            return Ok(body.into());
+        }
+        body.extend_from_slice(&chunk[..read]);
+        progress::transferred(body.len() as u64, total);
+    }
+}
+
+fn http_client(
+    network: &NetworkConfig,
+) -> Result<reqwest::blocking::Client, Error> {
+    let seconds = |seconds| (seconds > 0).then(|| Duration::from_secs(seconds));
+    let mut builder = reqwest::blocking::Client::builder()
+        .use_native_tls()
+        .timeout(seconds(network.timeout));
+    if let Some(timeout) = seconds(network.connect_timeout) {
+        builder = builder.connect_timeout(timeout);
+    }
+    if let Some(proxy) = &network.proxy {
+        let no_proxy =
+            network.no_proxy.as_deref().and_then(NoProxy::from_string);
+        builder = builder.proxy(Proxy::all(proxy)?.no_proxy(no_proxy));
+    }
+    for path in &network.ca_certificates {
+        let pem = fs::read(path).map_err(|error| {
+            std::io::Error::new(
+                error.kind(),
+                format!("CA certificate {}: {error}", path.display()),
+            )
+        })?;
+        for certificate in Certificate::from_pem_bundle(&pem)? {
+            builder = builder.add_root_certificate(certificate);
+        }
+    }
+    Ok(builder.build()?)
+}
+
+fn boxed(
+    error: impl std::error::Error + Send + Sync + 'static,
+) -> Box<dyn std::error::Error + Send + Sync> {
+    Box::new(error)
+}
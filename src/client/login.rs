@@ -0,0 +1,262 @@
+use chrono::{DateTime, TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+use super::{session, AuthClient, Error};
+use crate::model::{
+    auth::{AuthCode, AuthTokenResponse, CodeVerifier},
+    Config,
+};
+
+/// How long BankID keeps an order that no one has scanned or opened
+const UNSTARTED_LIFETIME: Duration = Duration::from_secs(30);
+
+/// How long BankID keeps an order once the user has started on it
+const STARTED_LIFETIME: Duration = Duration::from_secs(180);
+
+/// An in-flight BankID order, polled until the user has signed
+pub struct LoginFlow<'a, C: AuthClient> {
+    client: &'a C,
+    config: Config,
+    auth_code: AuthCode,
+    code_verifier: CodeVerifier,
+    pub auto_start_token: String,
+    pub qr_code: String,
+    next_poll_url: String,
+    retry_after: u32,
+    /// What BankID asks the user to do next
+    pub message: &'static str,
+    /// Why the previous order failed, until the user gets going with the
+    /// one that replaced it
+    pub failure: Option<&'static str>,
+    started_at: Instant,
+    /// Whether the user has scanned the QR code or opened BankID
+    user_started: bool,
+}
+
+/// The state of a `LoginFlow`, saved between runs so that one process can
+/// start a login and others poll it. Why an earlier order failed is not
+/// kept, only what BankID says at the next poll.
+#[derive(Deserialize, Serialize)]
+pub struct PendingLogin {
+    config: Config,
+    auth_code: AuthCode,
+    code_verifier: CodeVerifier,
+    auto_start_token: String,
+    qr_code: String,
+    next_poll_url: String,
+    retry_after: u32,
+    started_at: DateTime<Utc>,
+    user_started: bool,
+}
+
+impl<'a, C: AuthClient> LoginFlow<'a, C> {
+    pub fn start(client: &'a C) -> Result<LoginFlow<'a, C>, Error> {
+        let config = client.get_config()?;
+        let (verifier, auth_resp) = client.start_auth(&config)?;
+
+        Ok(LoginFlow {
+            client,
+            config,
+            auth_code: auth_resp.code,
+            code_verifier: verifier,
+            auto_start_token: auth_resp.auto_start_token,
+            qr_code: auth_resp.qr_code,
+            next_poll_url: auth_resp.next_poll_url,
+            retry_after: 1,
+            message: user_message("RFA1", ""),
+            failure: None,
+            started_at: Instant::now(),
+            user_started: false,
+        })
+    }
+
+    pub fn retry_after(&self) -> Duration {
+        Duration::from_secs(self.retry_after.into())
+    }
+
+    /// Time left before BankID gives up on the order, which is longer once
+    /// the user has started on it
+    pub fn expires_in(&self) -> Duration {
+        let lifetime = match self.user_started {
+            true => STARTED_LIFETIME,
+            false => UNSTARTED_LIFETIME,
+        };
+        lifetime.saturating_sub(self.started_at.elapsed())
+    }
+
+    pub fn is_user_started(&self) -> bool {
+        self.user_started
+    }
+
+    /// Checks on the order. One that failed, for example because it was
+    /// cancelled in the BankID app, or that has expired is replaced
+    /// by a new one, which has a new QR code and auto start token.
+    pub fn poll(&mut self) -> Result<Option<AuthTokenResponse>, Error> {
+        if self.expires_in().is_zero() {
+            if let Err(error) = self.abort() {
+                warn!("failed to abort expired BankID order: {error}");
+            }
+            let failure = match self.user_started {
+                true => Some("The login timed out"),
+                false => self.failure,
+            };
+            return self.restart(failure);
+        }
+        let check = self.client.check_auth(&self.next_poll_url)?;
+        let message = user_message(&check.message_code, &check.progress_status);
+        if check.status == "failed" {
+            return self.restart(Some(message));
+        }
+        match check.ssn {
+            None => {
+                self.qr_code = check.qr_code;
+                self.next_poll_url =
+                    check.next_poll_url.unwrap_or(self.next_poll_url.clone());
+                self.retry_after =
+                    check.retry_after.unwrap_or(self.retry_after);
+                self.message = message;
+                if check.progress_status != "outstanding_transaction" {
+                    self.failure = None;
+                    self.user_started = true;
+                }
+                Ok(None)
+            }
+            Some(_) => {
+                let auth_token = self.client.get_auth_token(
+                    &self.config,
+                    self.auth_code.clone(),
+                    self.code_verifier.clone(),
+                )?;
+                Ok(Some(auth_token))
+            }
+        }
+    }
+
+    fn restart(
+        &mut self,
+        failure: Option<&'static str>,
+    ) -> Result<Option<AuthTokenResponse>, Error> {
+        *self = LoginFlow { failure, ..LoginFlow::start(self.client)? };
+        Ok(None)
+    }
+
+    /// Why the previous order failed, or else what BankID asks for
+    pub fn status(&self) -> String {
+        match self.failure {
+            Some(failure) => format!("{failure}, try again"),
+            None => self.message.to_string(),
+        }
+    }
+
+    pub fn abort(&self) -> Result<(), Error> {
+        self.client.abort_auth(&self.next_poll_url)
+    }
+
+    /// The state of the order, to go on with by `resume`
+    pub fn pending(&self) -> PendingLogin {
+        let elapsed = TimeDelta::from_std(self.started_at.elapsed());
+        PendingLogin {
+            config: self.config.clone(),
+            auth_code: self.auth_code.clone(),
+            code_verifier: self.code_verifier.clone(),
+            auto_start_token: self.auto_start_token.clone(),
+            qr_code: self.qr_code.clone(),
+            next_poll_url: self.next_poll_url.clone(),
+            retry_after: self.retry_after,
+            started_at: Utc::now() - elapsed.unwrap_or_default(),
+            user_started: self.user_started,
+        }
+    }
+
+    /// Goes on with an order started elsewhere, e.g. by another process
+    pub fn resume(client: &'a C, pending: PendingLogin) -> LoginFlow<'a, C> {
+        let elapsed = (Utc::now() - pending.started_at).to_std();
+        let started_at = Instant::now()
+            .checked_sub(elapsed.unwrap_or_default())
+            .unwrap_or_else(Instant::now);
+        LoginFlow {
+            client,
+            config: pending.config,
+            auth_code: pending.auth_code,
+            code_verifier: pending.code_verifier,
+            auto_start_token: pending.auto_start_token,
+            qr_code: pending.qr_code,
+            next_poll_url: pending.next_poll_url,
+            retry_after: pending.retry_after,
+            message: user_message("", "outstanding_transaction"),
+            failure: None,
+            started_at,
+            user_started: pending.user_started,
+        }
+    }
+
+    /// Starts a login for the profile that is saved to be polled by
+    /// `poll_saved`, with the token returned
+    pub fn start_saved(
+        client: &'a C,
+        profile: &str,
+    ) -> Result<(String, LoginFlow<'a, C>), Error> {
+        if let Err(error) = session::prune_pending() {
+            warn!("failed to remove old pending logins: {error}");
+        }
+        let flow = LoginFlow::start(client)?;
+        let token = session::new_token();
+        session::save_pending(&flow.pending(), profile, &token)?;
+        Ok((token, flow))
+    }
+
+    /// Polls a login started by `start_saved` once. It is saved again until
+    /// the user has signed, and then forgotten.
+    pub fn poll_saved(
+        client: &'a C,
+        profile: &str,
+        token: &str,
+    ) -> Result<(LoginFlow<'a, C>, Option<AuthTokenResponse>), Error> {
+        let pending = session::load_pending(profile, token)?;
+        let mut flow = LoginFlow::resume(client, pending);
+        let auth_token = flow.poll()?;
+        match auth_token {
+            Some(_) => session::delete_pending(profile, token)?,
+            None => session::save_pending(&flow.pending(), profile, token)?,
+        }
+        Ok((flow, auth_token))
+    }
+}
+
+pub fn bankid_url(auto_start_token: &str) -> String {
+    format!("bankid:///?autostarttoken={auto_start_token}&redirect=null")
+}
+
+/// The text BankID recommends showing for a message code, or failing that
+/// for the progress of the order
+fn user_message(message_code: &str, progress_status: &str) -> &'static str {
+    match (message_code, progress_status) {
+        ("RFA1", _) | (_, "outstanding_transaction") => "Start your BankID app",
+        ("RFA2", _) => "The BankID app is not installed",
+        ("RFA3", _) | ("RFA6", _) | (_, "user_cancel" | "cancelled") => {
+            "The login was cancelled"
+        }
+        ("RFA4", _) => "A login for this personal number is already started",
+        ("RFA8", _) | (_, "expired_transaction") => {
+            "The BankID app did not respond in time"
+        }
+        ("RFA9", _) | (_, "user_sign") => {
+            "Enter your security code in the BankID app and select Identify"
+        }
+        ("RFA13", _) | (_, "started") => "Trying to start your BankID app",
+        ("RFA16", _) | (_, "certificate_err") => {
+            "The BankID is blocked or too old, use another or get a new one \
+             from your bank"
+        }
+        ("RFA17", _) | (_, "start_failed") => {
+            "The BankID app could not be started or did not find the QR code"
+        }
+        ("RFA21", _) => "Login in progress",
+        ("RFA23", _) | (_, "user_mrtd") => {
+            "Scan your passport or ID card with the BankID app"
+        }
+        _ => "Waiting for BankID",
+    }
+}
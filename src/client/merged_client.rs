@@ -0,0 +1,240 @@
+use bytes::Bytes;
+use std::{collections::HashMap, thread};
+use tracing::warn;
+
+use super::{
+    AuthClient, Client, ContentClient, Error, Page, Session, SessionStore,
+};
+use crate::model::{auth::*, content::*, receipt::*, user::*, Config};
+
+/// Shows the inboxes of several profiles as one. Listings are fetched from
+/// every profile at once and merged, and the calls for an item go to the
+/// profile it was listed from. Logins, receipts and everything else that is
+/// not about items are those of the first profile.
+pub struct MergedClient {
+    clients: Vec<(String, Box<dyn Client>)>,
+    /// Which of the clients each item listed so far belongs to
+    owners: HashMap<ContentKey, usize>,
+}
+
+impl MergedClient {
+    /// Takes the clients with the names of their profiles, the first one
+    /// being the profile that was asked for
+    pub fn new(clients: Vec<(String, Box<dyn Client>)>) -> Self {
+        assert!(!clients.is_empty(), "there is at least one profile");
+        MergedClient { clients, owners: HashMap::new() }
+    }
+
+    fn primary(&self) -> &dyn Client {
+        self.clients[0].1.as_ref()
+    }
+
+    fn primary_mut(&mut self) -> &mut dyn Client {
+        self.clients[0].1.as_mut()
+    }
+
+    /// The client the item was listed from, listing the inboxes again if it
+    /// has not been seen
+    fn owner(&mut self, item_key: &str) -> Result<&mut dyn Client, Error> {
+        if !self.owners.contains_key(item_key) {
+            self.get_inbox_listing()?;
+        }
+        match self.owners.get(item_key) {
+            Some(&index) => Ok(self.clients[index].1.as_mut()),
+            None => Err(Error::UnknownItem(item_key.to_string())),
+        }
+    }
+}
+
+impl AuthClient for MergedClient {
+    fn get_config(&self) -> Result<Config, Error> {
+        self.primary().get_config()
+    }
+
+    fn start_auth(
+        &self,
+        config: &Config,
+    ) -> Result<(CodeVerifier, AuthResponse), Error> {
+        self.primary().start_auth(config)
+    }
+
+    fn check_auth(&self, poll_url: &str) -> Result<AuthStatus, Error> {
+        self.primary().check_auth(poll_url)
+    }
+
+    fn abort_auth(&self, poll_url: &str) -> Result<(), Error> {
+        self.primary().abort_auth(poll_url)
+    }
+
+    fn get_auth_token(
+        &self,
+        config: &Config,
+        auth_code: AuthCode,
+        verifier: CodeVerifier,
+    ) -> Result<AuthTokenResponse, Error> {
+        self.primary().get_auth_token(config, auth_code, verifier)
+    }
+
+    fn revoke_auth_token(&mut self) -> Result<(), Error> {
+        self.primary_mut().revoke_auth_token()
+    }
+}
+
+impl ContentClient for MergedClient {
+    /// Profiles that fail are left out with a warning, unless they all do
+    fn get_inbox_listing(&mut self) -> Result<InboxListing, Error> {
+        let results: Vec<_> = thread::scope(|scope| {
+            let handles: Vec<_> = self
+                .clients
+                .iter_mut()
+                .map(|(_, client)| {
+                    scope.spawn(move || client.get_inbox_listing())
+                })
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().expect("listing thread panicked"))
+                .collect()
+        });
+
+        let mut listings = Vec::new();
+        let mut first_error = None;
+        for (index, result) in results.into_iter().enumerate() {
+            let profile = &self.clients[index].0;
+            match result {
+                Ok(listing) => {
+                    for entry in listing.iter() {
+                        self.owners.insert(entry.item.key.clone(), index);
+                    }
+                    listings.push((profile.clone(), listing));
+                }
+                Err(error) => {
+                    warn!("failed to list the inbox of {profile}: {error}");
+                    first_error.get_or_insert(error);
+                }
+            }
+        }
+        match (listings.is_empty(), first_error) {
+            (true, Some(error)) => Err(error),
+            _ => Ok(InboxListing::merged(listings)),
+        }
+    }
+
+    fn invalidate(&mut self) {
+        for (_, client) in &mut self.clients {
+            client.invalidate();
+        }
+    }
+
+    // Pages cannot be had from each profile without knowing how the others
+    // interleave, so they are cut from the merged listing
+    fn get_inbox_page(&mut self, page: Page) -> Result<Vec<InboxItem>, Error> {
+        let listing = self.get_inbox_listing()?;
+        let newest_first = listing.iter().rev().skip(page.offset);
+        Ok(newest_first.take(page.limit).map(|e| e.item.clone()).collect())
+    }
+
+    fn number_items(&mut self, items: Vec<InboxItem>) -> InboxListing {
+        let mut by_owner: Vec<Vec<InboxItem>> =
+            self.clients.iter().map(|_| Vec::new()).collect();
+        for item in items {
+            let index = self.owners.get(&item.key).copied().unwrap_or(0);
+            by_owner[index].push(item);
+        }
+        let listings = (self.clients.iter_mut().zip(by_owner))
+            .map(|((profile, client), items)| {
+                (profile.clone(), client.number_items(items))
+            })
+            .collect();
+        InboxListing::merged(listings)
+    }
+
+    fn get_item_details(
+        &mut self,
+        item_key: &str,
+    ) -> Result<ItemDetails, Error> {
+        self.owner(item_key)?.get_item_details(item_key)
+    }
+
+    fn mark_as_read(&mut self, item_key: &str) -> Result<(), Error> {
+        self.owner(item_key)?.mark_as_read(item_key)
+    }
+
+    fn set_label(&mut self, item_key: &str, label: &str) -> Result<(), Error> {
+        self.owner(item_key)?.set_label(item_key, label)
+    }
+
+    fn remove_label(
+        &mut self,
+        item_key: &str,
+        label: &str,
+    ) -> Result<(), Error> {
+        self.owner(item_key)?.remove_label(item_key, label)
+    }
+
+    fn download_attachment(
+        &mut self,
+        item_key: &str,
+        attachment_key: &str,
+    ) -> Result<Bytes, Error> {
+        self.owner(item_key)?.download_attachment(item_key, attachment_key)
+    }
+
+    fn get_sender_icon(&mut self, url: &str) -> Result<Bytes, Error> {
+        self.primary_mut().get_sender_icon(url)
+    }
+
+    fn get_receipts(&mut self) -> Result<ReceiptListing, Error> {
+        self.primary_mut().get_receipts()
+    }
+
+    fn get_receipt_details(
+        &mut self,
+        receipt_key: &str,
+    ) -> Result<ReceiptDetails, Error> {
+        self.primary_mut().get_receipt_details(receipt_key)
+    }
+
+    fn get_user_profile(&mut self) -> Result<UserProfile, Error> {
+        self.primary_mut().get_user_profile()
+    }
+
+    // Company inboxes are switched to one profile at a time
+    fn get_companies(&mut self) -> Result<Vec<Company>, Error> {
+        Ok(Vec::new())
+    }
+
+    fn actor(&self) -> Actor {
+        Actor::User
+    }
+
+    fn set_actor(&mut self, _: Actor) {}
+
+    fn is_offline(&self) -> bool {
+        self.primary().is_offline()
+    }
+
+    fn set_offline(&mut self, offline: bool) {
+        for (_, client) in &mut self.clients {
+            client.set_offline(offline);
+        }
+    }
+}
+
+impl SessionStore for MergedClient {
+    fn set_session(&mut self, session: Session) {
+        self.primary_mut().set_session(session)
+    }
+
+    fn get_session(&self) -> Option<Session> {
+        self.primary().get_session()
+    }
+
+    fn profile(&self) -> &str {
+        self.primary().profile()
+    }
+
+    fn login(&mut self) -> Result<Session, Error> {
+        self.primary_mut().login()
+    }
+}
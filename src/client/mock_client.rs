@@ -1,19 +1,112 @@
 use bytes::Bytes;
-use std::cell::RefCell;
-use std::include_str;
+use reqwest::{blocking::Response, StatusCode};
+use std::{cell::RefCell, fs, path::PathBuf, time::Duration};
 
-use super::{Client, Error, Session};
-use crate::model::{auth::*, content::*, Config};
+use super::{
+    decode, worker, AuthClient, ContentClient, Error, Page, Session,
+    SessionStore,
+};
+use crate::model::{auth::*, content::*, receipt::*, user::*, Config};
+
+/// Fixtures and failures for the mock client, so that error paths can be
+/// tried without Kivra
+#[derive(Clone, Debug, Default)]
+pub struct MockOptions {
+    /// Directory with fixtures named like those in `test_data`. Missing
+    /// files fall back to the built-in ones.
+    pub data_dir: Option<PathBuf>,
+    /// Delay before every call that needs a session
+    pub latency: Duration,
+    /// Fail every nth call that needs a session with HTTP 500
+    pub fail_every: Option<u32>,
+    /// Fail calls that need a session as if it had expired
+    pub expired_session: bool,
+    pub empty_inbox: bool,
+    /// Fail calls that need a session as if there were no network, unless
+    /// offline
+    pub unreachable: bool,
+}
 
 #[derive(Default)]
 pub struct MockClient {
     check_auth_calls: RefCell<u32>,
+    options: MockOptions,
+    calls: u32,
+    actor: Actor,
+    offline: bool,
 }
 
-impl Client for MockClient {
+impl MockClient {
+    pub fn with_options(self, options: MockOptions) -> Self {
+        MockClient { options, ..self }
+    }
+
+    /// A fixture from the data dir if it has one, otherwise the built-in
+    fn fixture(&self, name: &str, builtin: &str) -> Result<String, Error> {
+        let path = self.options.data_dir.as_ref().map(|dir| dir.join(name));
+        match path.filter(|path| path.exists()) {
+            Some(path) => Ok(fs::read_to_string(path)?),
+            None => Ok(builtin.to_string()),
+        }
+    }
+
+    /// Waits and fails the way the options ask for
+    fn inject(&mut self) -> Result<(), Error> {
+        worker::sleep(self.options.latency)?;
+        if self.options.expired_session {
+            return Err(Error::SessionExpired);
+        }
+        if self.options.unreachable && !self.offline {
+            return Err(unreachable_error());
+        }
+        self.calls += 1;
+        match self.options.fail_every {
+            Some(every) if self.calls.is_multiple_of(every) => {
+                Err(server_error())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn inbox_items(&self) -> Result<Vec<InboxItem>, Error> {
+        if self.options.empty_inbox {
+            return Ok(Vec::new());
+        }
+        let input = match self.actor {
+            Actor::User => self
+                .fixture("inbox.json", include_str!("test_data/inbox.json"))?,
+            Actor::Company(_) => self.fixture(
+                "company_inbox.json",
+                include_str!("test_data/company_inbox.json"),
+            )?,
+        };
+        decode::inbox_items(&input)
+    }
+}
+
+/// The error a real request gets back from a failing server
+fn server_error() -> Error {
+    let mut response = http::Response::new(Vec::new());
+    *response.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
+    Response::from(response)
+        .error_for_status()
+        .expect_err("500 is an error status")
+        .into()
+}
+
+/// The error a real request gets when there is no network
+fn unreachable_error() -> Error {
+    // Nothing listens on port 1 of the loopback interface
+    reqwest::blocking::get("http://127.0.0.1:1")
+        .expect_err("nothing listens on port 1")
+        .into()
+}
+
+impl AuthClient for MockClient {
     fn get_config(&self) -> Result<Config, Error> {
-        let input = include_str!("test_data/config.json");
-        let config = serde_json::from_str(input)?;
+        let input =
+            self.fixture("config.json", include_str!("test_data/config.json"))?;
+        let config = serde_json::from_str(&input)?;
         Ok(config)
     }
 
@@ -22,16 +115,22 @@ impl Client for MockClient {
         _config: &Config,
     ) -> Result<(CodeVerifier, AuthResponse), Error> {
         let verifier = pkce::code_verifier(48);
-        let input = include_str!("test_data/auth_response.json");
-        let response = serde_json::from_str(input)?;
+        let input = self.fixture(
+            "auth_response.json",
+            include_str!("test_data/auth_response.json"),
+        )?;
+        let response = serde_json::from_str(&input)?;
         Ok((verifier, response))
     }
 
     fn check_auth(&self, _poll_url: &str) -> Result<AuthStatus, Error> {
         let mut updates = self.check_auth_calls.borrow_mut();
         (*updates) += 1;
-        let input = include_str!("test_data/auth_status.json");
-        let status = serde_json::from_str(input)?;
+        let input = self.fixture(
+            "auth_status.json",
+            include_str!("test_data/auth_status.json"),
+        )?;
+        let status = serde_json::from_str(&input)?;
 
         if (*updates) > 3 {
             Ok(AuthStatus { ssn: Some("195208152712".to_string()), ..status })
@@ -50,32 +149,70 @@ impl Client for MockClient {
         _auth_code: String,
         _verifier: CodeVerifier,
     ) -> Result<AuthTokenResponse, Error> {
-        let input = include_str!("test_data/auth_token_response.json");
-        let response = serde_json::from_str(input)?;
+        let input = self.fixture(
+            "auth_token_response.json",
+            include_str!("test_data/auth_token_response.json"),
+        )?;
+        let response = serde_json::from_str(&input)?;
         Ok(response)
     }
 
     fn revoke_auth_token(&mut self) -> Result<(), Error> {
         Ok(())
     }
+}
 
+impl ContentClient for MockClient {
     fn get_inbox_listing(&mut self) -> Result<InboxListing, Error> {
-        let input = include_str!("test_data/inbox.json");
-        let listing = serde_json::from_str(input)?;
+        self.inject()?;
+        let listing = self.inbox_items()?;
         Ok(InboxListing::from_content_specs(listing))
     }
 
+    fn get_inbox_page(&mut self, page: Page) -> Result<Vec<InboxItem>, Error> {
+        self.inject()?;
+        let mut items = self.inbox_items()?;
+        items.sort_by_key(|item| std::cmp::Reverse(item.created_at));
+        Ok(items.into_iter().skip(page.offset).take(page.limit).collect())
+    }
+
     fn get_item_details(
         &mut self,
         _item_key: &str,
     ) -> Result<ItemDetails, Error> {
-        let input = include_str!("test_data/details.json");
-        let details = serde_json::from_str(input)?;
+        self.inject()?;
+        let input = self
+            .fixture("details.json", include_str!("test_data/details.json"))?;
+        let details = serde_json::from_str(&input)?;
         Ok(details)
     }
 
     fn mark_as_read(&mut self, _item_key: &str) -> Result<(), Error> {
-        Ok(())
+        self.inject()
+    }
+
+    fn set_label(
+        &mut self,
+        _item_key: &str,
+        _label: &str,
+    ) -> Result<(), Error> {
+        self.inject()?;
+        match cfg!(feature = "unverified") {
+            true => Ok(()),
+            false => Err(Error::Unverified("label changes")),
+        }
+    }
+
+    fn remove_label(
+        &mut self,
+        _item_key: &str,
+        _label: &str,
+    ) -> Result<(), Error> {
+        self.inject()?;
+        match cfg!(feature = "unverified") {
+            true => Ok(()),
+            false => Err(Error::Unverified("label changes")),
+        }
     }
 
     fn download_attachment(
@@ -83,15 +220,115 @@ impl Client for MockClient {
         _item_key: &str,
         _attachment_key: &str,
     ) -> Result<Bytes, Error> {
+        self.inject()?;
         Ok(Bytes::from_static(b"tjena"))
     }
 
+    fn get_sender_icon(&mut self, _url: &str) -> Result<Bytes, Error> {
+        self.inject()?;
+        Ok(Bytes::from_static(include_bytes!("test_data/icon.png")))
+    }
+
+    // The fixtures are made up, as are the endpoints they stand in for
+    #[cfg(feature = "unverified")]
+    fn get_receipts(&mut self) -> Result<ReceiptListing, Error> {
+        self.inject()?;
+        let input = self.fixture(
+            "receipts.json",
+            include_str!("test_data/receipts.json"),
+        )?;
+        let receipts = serde_json::from_str(&input)?;
+        Ok(ReceiptListing::from_receipts(receipts))
+    }
+
+    #[cfg(feature = "unverified")]
+    fn get_receipt_details(
+        &mut self,
+        _receipt_key: &str,
+    ) -> Result<ReceiptDetails, Error> {
+        self.inject()?;
+        let input = self.fixture(
+            "receipt_details.json",
+            include_str!("test_data/receipt_details.json"),
+        )?;
+        let details = serde_json::from_str(&input)?;
+        Ok(details)
+    }
+
+    #[cfg(not(feature = "unverified"))]
+    fn get_receipts(&mut self) -> Result<ReceiptListing, Error> {
+        self.inject()?;
+        Err(Error::Unverified("receipts"))
+    }
+
+    #[cfg(not(feature = "unverified"))]
+    fn get_receipt_details(
+        &mut self,
+        _receipt_key: &str,
+    ) -> Result<ReceiptDetails, Error> {
+        self.inject()?;
+        Err(Error::Unverified("receipts"))
+    }
+
+    #[cfg(feature = "unverified")]
+    fn get_user_profile(&mut self) -> Result<UserProfile, Error> {
+        self.inject()?;
+        let input =
+            self.fixture("user.json", include_str!("test_data/user.json"))?;
+        Ok(serde_json::from_str(&input)?)
+    }
+
+    #[cfg(feature = "unverified")]
+    fn get_companies(&mut self) -> Result<Vec<Company>, Error> {
+        self.inject()?;
+        let input = self.fixture(
+            "companies.json",
+            include_str!("test_data/companies.json"),
+        )?;
+        Ok(serde_json::from_str(&input)?)
+    }
+
+    // Failed right away, as the real client sends nothing for these
+    #[cfg(not(feature = "unverified"))]
+    fn get_user_profile(&mut self) -> Result<UserProfile, Error> {
+        Err(Error::Unverified("user profiles"))
+    }
+
+    #[cfg(not(feature = "unverified"))]
+    fn get_companies(&mut self) -> Result<Vec<Company>, Error> {
+        Err(Error::Unverified("company inboxes"))
+    }
+
+    fn actor(&self) -> Actor {
+        self.actor.clone()
+    }
+
+    fn set_actor(&mut self, actor: Actor) {
+        self.actor = actor;
+    }
+
+    fn is_offline(&self) -> bool {
+        self.offline
+    }
+
+    // The fixtures are local anyway
+    fn set_offline(&mut self, offline: bool) {
+        self.offline = offline;
+    }
+}
+
+impl SessionStore for MockClient {
     fn get_session(&self) -> Option<Session> {
         None
     }
 
     fn set_session(&mut self, _: Session) {}
 
+    // Keeps mock runs out of the real profile's local files
+    fn profile(&self) -> &str {
+        "mock"
+    }
+
     fn login(&mut self) -> Result<Session, Error> {
         Err(Error::NoSession)
     }
@@ -1,14 +1,26 @@
 use bytes::Bytes;
+use chrono::TimeDelta;
 use thiserror::Error;
 
-use super::model::{auth::*, content::*, Config};
+use super::model::{auth::*, content::*, receipt::*, user::*, Config};
+use crate::tui::qr::QrRenderer;
 
+mod caching_client;
+mod decode;
 mod kivra_client;
+pub mod login;
+mod merged_client;
 mod mock_client;
+mod pager;
 pub mod session;
+pub mod tape;
+pub mod worker;
 
+pub use caching_client::CachingClient;
 pub use kivra_client::KivraClient;
-pub use mock_client::MockClient;
+pub use merged_client::MergedClient;
+pub use mock_client::{MockClient, MockOptions};
+pub use pager::{InboxPager, Page};
 use session::Session;
 
 #[derive(Debug, Error)]
@@ -34,17 +46,73 @@ pub enum Error {
     #[error("login aborted")]
     LoginAborted,
 
+    #[error("login required")]
+    LoginRequired,
+
+    #[error("cancelled")]
+    Cancelled,
+
+    #[error("no profile has an item with the key {0}")]
+    UnknownItem(String),
+
+    #[error("working offline, and this was not kept locally")]
+    Offline,
+
+    #[error(
+        "{0} are not supported, Kivra's endpoints for them are unverified"
+    )]
+    Unverified(&'static str),
+
     #[error("session error: {0}")]
     SessionError(#[from] session::Error),
 
     #[error("tui error: {0}")]
-    TuiError(#[from] Box<dyn std::error::Error>),
+    TuiError(#[from] Box<dyn std::error::Error + Send + Sync>),
 
     #[error("request body is not cloneable")]
     CloneError,
+
+    #[error("record/replay error: {0}")]
+    TapeError(#[from] tape::Error),
+
+    #[error("IO error: {0}")]
+    IOError(#[from] std::io::Error),
 }
 
-pub trait Client {
+impl Error {
+    /// Whether Kivra could not be reached at all, for lack of a network or
+    /// a name server, as opposed to it answering with an error
+    pub fn is_unreachable(&self) -> bool {
+        match self {
+            Error::HttpError(error) => error.is_connect() || error.is_timeout(),
+            // A login in the terminal UI fails with the error of the view
+            Error::TuiError(error) => error
+                .downcast_ref::<crate::tui::Error>()
+                .is_some_and(|error| match error {
+                    crate::tui::Error::ClientError(error) => {
+                        error.is_unreachable()
+                    }
+                    _ => false,
+                }),
+            _ => false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LoginOptions {
+    /// Launch BankID on this device instead of showing a QR code
+    pub same_device: bool,
+    /// Print the QR code to stdout instead of using the terminal UI
+    pub headless: bool,
+    /// Ask before logging in, then log in without the terminal UI. Used
+    /// when a command other than `login` finds that there is no session.
+    pub prompt: bool,
+    pub qr_renderer: QrRenderer,
+}
+
+/// The OAuth and BankID calls that a login is made of
+pub trait AuthClient: Send {
     fn get_config(&self) -> Result<Config, Error>;
 
     fn start_auth(
@@ -64,9 +132,84 @@ pub trait Client {
     ) -> Result<AuthTokenResponse, Error>;
 
     fn revoke_auth_token(&mut self) -> Result<(), Error>;
+}
 
+/// Where the session of a profile is kept, and how a new one is had
+pub trait SessionStore: Send {
+    fn set_session(&mut self, session: Session);
+
+    fn get_session(&self) -> Option<Session>;
+
+    /// Name of the profile whose session this client uses
+    fn profile(&self) -> &str;
+
+    fn login(&mut self) -> Result<Session, Error>;
+
+    fn get_or_load_session(&mut self) -> Result<Option<Session>, Error> {
+        let opt_session =
+            self.get_session().or(session::try_load(self.profile())?);
+        match opt_session {
+            Some(session) if !session.is_expired() => {
+                self.set_session(session.clone());
+                Ok(Some(session))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Logs in again up front if the session expires within `margin`, so
+    /// that long-running operations are not interrupted by a login prompt.
+    fn refresh_session_if_expiring(
+        &mut self,
+        margin: TimeDelta,
+    ) -> Result<Session, Error> {
+        match self.get_or_load_session()? {
+            Some(session) if !session.expires_within(margin) => Ok(session),
+            _ => self.login(),
+        }
+    }
+
+    fn get_session_or_login(&mut self) -> Result<Session, Error> {
+        if let Some(session) = self.get_or_load_session()? {
+            Ok(session)
+        } else {
+            self.login()
+        }
+    }
+}
+
+/// The inbox and receipts, read and changed with a session that the client
+/// takes care of
+pub trait ContentClient: Send {
     fn get_inbox_listing(&mut self) -> Result<InboxListing, Error>;
 
+    /// Forgets the listing the client keeps, if any, so that the next one
+    /// is asked of Kivra
+    fn invalidate(&mut self) {}
+
+    /// The listing as Kivra has it now, for refreshes that must not be
+    /// answered with a kept one
+    fn refresh_inbox(&mut self) -> Result<InboxListing, Error> {
+        self.invalidate();
+        self.get_inbox_listing()
+    }
+
+    /// Items in the page, newest first
+    fn get_inbox_page(&mut self, page: Page) -> Result<Vec<InboxItem>, Error>;
+
+    /// Gives the items their ids. Those only hold for this listing unless
+    /// the client keeps them elsewhere.
+    fn number_items(&mut self, items: Vec<InboxItem>) -> InboxListing {
+        InboxListing::from_content_specs(items)
+    }
+
+    fn inbox_pager(&mut self, page_size: usize) -> InboxPager<'_, Self>
+    where
+        Self: Sized,
+    {
+        InboxPager::new(self, page_size)
+    }
+
     fn get_item_details(
         &mut self,
         item_key: &str,
@@ -74,38 +217,80 @@ pub trait Client {
 
     fn mark_as_read(&mut self, item_key: &str) -> Result<(), Error>;
 
+    fn set_label(&mut self, item_key: &str, label: &str) -> Result<(), Error>;
+
+    fn remove_label(
+        &mut self,
+        item_key: &str,
+        label: &str,
+    ) -> Result<(), Error>;
+
     fn download_attachment(
         &mut self,
         item_key: &str,
         attachment_key: &str,
     ) -> Result<Bytes, Error>;
 
-    fn set_session(&mut self, session: Session);
+    /// The icon at the `sender_icon_url` of an item
+    fn get_sender_icon(&mut self, url: &str) -> Result<Bytes, Error>;
 
-    fn get_session(&self) -> Option<Session>;
+    fn get_receipts(&mut self) -> Result<ReceiptListing, Error>;
 
-    fn login(&mut self) -> Result<Session, Error>;
+    fn get_receipt_details(
+        &mut self,
+        receipt_key: &str,
+    ) -> Result<ReceiptDetails, Error>;
 
-    fn get_or_load_session(&mut self) -> Result<Option<Session>, Error> {
-        let opt_session = self.get_session().or(session::try_load()?);
-        if let Some(session) = opt_session {
-            self.set_session(session.clone());
-            Ok(Some(session.clone()))
-        } else {
-            Ok(None)
-        }
+    /// Everything Kivra has on the logged in user
+    fn get_user_profile(&mut self) -> Result<UserProfile, Error>;
+
+    /// The company inboxes the logged in user can switch to
+    fn get_companies(&mut self) -> Result<Vec<Company>, Error>;
+
+    /// Whose inbox the calls for items read. Receipts are always the
+    /// user's own.
+    fn actor(&self) -> Actor;
+
+    fn set_actor(&mut self, actor: Actor);
+
+    /// Whether what was kept locally is served instead of asking Kivra,
+    /// and everything else fails with `Error::Offline`
+    fn is_offline(&self) -> bool;
+
+    fn set_offline(&mut self, offline: bool);
+
+    fn get_folder_listing(
+        &mut self,
+        folder: Folder,
+    ) -> Result<InboxListing, Error> {
+        let listing = self.get_inbox_listing()?;
+        Ok(listing.filtered(|entry| entry.item.folder() == folder))
     }
 
-    fn get_session_or_login(&mut self) -> Result<Session, Error> {
-        if let Some(session) = self.get_or_load_session()? {
-            Ok(session)
-        } else {
-            self.login()
+    /// Moving back to the inbox clears both the archive and trash labels
+    fn move_to_folder(
+        &mut self,
+        item_key: &str,
+        folder: Folder,
+    ) -> Result<(), Error> {
+        for other in [Folder::Archive, Folder::Trash] {
+            if other != folder {
+                self.remove_label(item_key, other.label())?;
+            }
+        }
+        match folder {
+            Folder::Inbox => Ok(()),
+            _ => self.set_label(item_key, folder.label()),
         }
     }
 }
 
-impl Client for Box<dyn Client> {
+/// Everything a Kivra client does, for code that needs all of it
+pub trait Client: AuthClient + SessionStore + ContentClient {}
+
+impl<C: AuthClient + SessionStore + ContentClient> Client for C {}
+
+impl AuthClient for Box<dyn Client> {
     fn get_config(&self) -> Result<Config, Error> {
         (**self).get_config()
     }
@@ -137,11 +322,25 @@ impl Client for Box<dyn Client> {
     fn revoke_auth_token(&mut self) -> Result<(), Error> {
         (**self).revoke_auth_token()
     }
+}
 
+impl ContentClient for Box<dyn Client> {
     fn get_inbox_listing(&mut self) -> Result<InboxListing, Error> {
         (**self).get_inbox_listing()
     }
 
+    fn invalidate(&mut self) {
+        (**self).invalidate()
+    }
+
+    fn get_inbox_page(&mut self, page: Page) -> Result<Vec<InboxItem>, Error> {
+        (**self).get_inbox_page(page)
+    }
+
+    fn number_items(&mut self, items: Vec<InboxItem>) -> InboxListing {
+        (**self).number_items(items)
+    }
+
     fn get_item_details(
         &mut self,
         item_key: &str,
@@ -153,6 +352,18 @@ impl Client for Box<dyn Client> {
         (**self).mark_as_read(item_key)
     }
 
+    fn set_label(&mut self, item_key: &str, label: &str) -> Result<(), Error> {
+        (**self).set_label(item_key, label)
+    }
+
+    fn remove_label(
+        &mut self,
+        item_key: &str,
+        label: &str,
+    ) -> Result<(), Error> {
+        (**self).remove_label(item_key, label)
+    }
+
     fn download_attachment(
         &mut self,
         item_key: &str,
@@ -161,6 +372,47 @@ impl Client for Box<dyn Client> {
         (**self).download_attachment(item_key, attachment_key)
     }
 
+    fn get_sender_icon(&mut self, url: &str) -> Result<Bytes, Error> {
+        (**self).get_sender_icon(url)
+    }
+
+    fn get_receipts(&mut self) -> Result<ReceiptListing, Error> {
+        (**self).get_receipts()
+    }
+
+    fn get_receipt_details(
+        &mut self,
+        receipt_key: &str,
+    ) -> Result<ReceiptDetails, Error> {
+        (**self).get_receipt_details(receipt_key)
+    }
+
+    fn get_user_profile(&mut self) -> Result<UserProfile, Error> {
+        (**self).get_user_profile()
+    }
+
+    fn get_companies(&mut self) -> Result<Vec<Company>, Error> {
+        (**self).get_companies()
+    }
+
+    fn actor(&self) -> Actor {
+        (**self).actor()
+    }
+
+    fn set_actor(&mut self, actor: Actor) {
+        (**self).set_actor(actor)
+    }
+
+    fn is_offline(&self) -> bool {
+        (**self).is_offline()
+    }
+
+    fn set_offline(&mut self, offline: bool) {
+        (**self).set_offline(offline)
+    }
+}
+
+impl SessionStore for Box<dyn Client> {
     fn login(&mut self) -> Result<Session, Error> {
         (**self).login()
     }
@@ -172,4 +424,8 @@ impl Client for Box<dyn Client> {
     fn set_session(&mut self, session: Session) {
         (**self).set_session(session)
     }
+
+    fn profile(&self) -> &str {
+        (**self).profile()
+    }
 }
@@ -0,0 +1,55 @@
+use super::{ContentClient, Error};
+use crate::model::content::InboxItem;
+
+/// A slice of the inbox, counted from the newest item
+#[derive(Clone, Copy, Debug)]
+pub struct Page {
+    pub offset: usize,
+    pub limit: usize,
+}
+
+/// Fetches the inbox one page at a time, newest items first. Stops after the
+/// first short page or error.
+pub struct InboxPager<'a, C: ContentClient> {
+    client: &'a mut C,
+    offset: usize,
+    page_size: usize,
+    done: bool,
+}
+
+impl<'a, C: ContentClient> InboxPager<'a, C> {
+    pub fn new(client: &'a mut C, page_size: usize) -> InboxPager<'a, C> {
+        InboxPager { client, offset: 0, page_size, done: false }
+    }
+
+    /// Skip the items that have already been fetched
+    pub fn starting_at(self, offset: usize) -> Self {
+        InboxPager { offset, ..self }
+    }
+}
+
+impl<'a, C: ContentClient> Iterator for InboxPager<'a, C> {
+    type Item = Result<Vec<InboxItem>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let page = Page { offset: self.offset, limit: self.page_size };
+        match self.client.get_inbox_page(page) {
+            Ok(items) => {
+                self.offset += items.len();
+                self.done = items.len() < self.page_size;
+                if items.is_empty() {
+                    None
+                } else {
+                    Some(Ok(items))
+                }
+            }
+            Err(error) => {
+                self.done = true;
+                Some(Err(error))
+            }
+        }
+    }
+}
@@ -1,12 +1,24 @@
 use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use chacha20poly1305::aead::{rand_core::RngCore, OsRng};
+use chrono::{DateTime, TimeDelta, Utc};
 use serde::{Deserialize, Serialize};
-use std::fs::File;
+use std::fs::{self, File};
+use std::io::ErrorKind;
 use std::path::PathBuf;
+use std::time::Duration;
 use thiserror::Error;
 
+use super::login::PendingLogin;
 use crate::model::UserId;
 
-#[derive(Clone, Deserialize, Debug)]
+/// Random bytes in the token of a pending login
+const TOKEN_BYTES: usize = 16;
+
+/// How long a pending login is kept without being polled. BankID gives up
+/// on its orders long before.
+const PENDING_LIFETIME: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Clone, Deserialize, Serialize, Debug)]
 pub struct UserInfo {
     pub kivra_user_id: UserId,
     pub name: String,
@@ -16,11 +28,27 @@ pub struct UserInfo {
     pub email: String,
 }
 
+#[derive(Deserialize)]
+struct ExpiryClaims {
+    exp: Option<i64>,
+}
+
 #[derive(Clone, Debug)]
 pub struct Session {
     pub user_info: UserInfo,
     pub access_token: String,
     pub id_token: String,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl Session {
+    pub fn is_expired(&self) -> bool {
+        self.expires_within(TimeDelta::zero())
+    }
+
+    pub fn expires_within(&self, margin: TimeDelta) -> bool {
+        self.expires_at.is_some_and(|expiry| expiry <= Utc::now() + margin)
+    }
 }
 
 #[derive(Clone, Deserialize, Serialize, Debug)]
@@ -45,17 +73,20 @@ pub enum Error {
 
     #[error("base64 decode failed - {0}")]
     Base64Error(#[from] base64::DecodeError),
+
+    #[error("no login is in progress with that token")]
+    UnknownLogin,
+
+    #[error(
+        "invalid profile name '{0}', only letters, digits, - and _ are allowed"
+    )]
+    InvalidProfile(String),
 }
 
 impl TryInto<Session> for StoredSession {
     type Error = Error;
     fn try_into(self) -> Result<Session, Error> {
-        let user_info = extract_user_info(&self.id_token)?;
-        Ok(Session {
-            user_info,
-            access_token: self.access_token,
-            id_token: self.id_token,
-        })
+        make(self.access_token, self.id_token)
     }
 }
 
@@ -68,14 +99,35 @@ impl From<Session> for StoredSession {
     }
 }
 
-fn default_session_path() -> Result<PathBuf, Error> {
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// Profile names end up in the names of files, of sessions, indexes and the
+/// like, and are checked wherever they are given
+pub fn check_profile(profile: &str) -> Result<(), Error> {
+    let allowed = |c: char| c.is_ascii_alphanumeric() || c == '-' || c == '_';
+    match !profile.is_empty() && profile.chars().all(allowed) {
+        true => Ok(()),
+        false => Err(Error::InvalidProfile(profile.to_string())),
+    }
+}
+
+/// For `--profile`
+pub fn parse_profile(profile: &str) -> Result<String, Error> {
+    check_profile(profile)?;
+    Ok(profile.to_string())
+}
+
+fn session_path(profile: &str) -> Result<PathBuf, Error> {
     let mut path = dirs::data_local_dir().ok_or(Error::CannotFindLocalDir)?;
-    path.push("kivinge.session");
+    match profile {
+        DEFAULT_PROFILE => path.push("kivinge.session"),
+        _ => path.push(format!("kivinge.{profile}.session")),
+    }
     Ok(path)
 }
 
-pub fn try_load() -> Result<Option<Session>, Error> {
-    let session_path = default_session_path()?;
+pub fn try_load(profile: &str) -> Result<Option<Session>, Error> {
+    let session_path = session_path(profile)?;
     if !session_path.exists() {
         return Ok(None);
     }
@@ -85,28 +137,105 @@ pub fn try_load() -> Result<Option<Session>, Error> {
     Ok(Some(stored_session.try_into()?))
 }
 
-pub fn save(session: &Session) -> Result<(), Error> {
-    let session_path = default_session_path()?;
+pub fn save(session: &Session, profile: &str) -> Result<(), Error> {
+    let session_path = session_path(profile)?;
     let session_file = File::create(session_path)?;
     let stored_session: StoredSession = session.clone().into();
     serde_json::to_writer(session_file, &stored_session)?;
     Ok(())
 }
 
-pub fn delete_saved() -> Result<(), Error> {
-    let session_path = default_session_path()?;
+pub fn delete_saved(profile: &str) -> Result<(), Error> {
+    let session_path = session_path(profile)?;
     Ok(std::fs::remove_file(session_path)?)
 }
 
+/// A new token to poll a pending login with
+pub fn new_token() -> String {
+    let mut bytes = [0; TOKEN_BYTES];
+    OsRng.fill_bytes(&mut bytes);
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+fn pending_path(profile: &str, token: &str) -> Result<PathBuf, Error> {
+    check_profile(profile)?;
+    // Anything but a token made by `new_token` could point anywhere
+    if token.len() != TOKEN_BYTES * 2
+        || !token.chars().all(|c| c.is_ascii_hexdigit())
+    {
+        return Err(Error::UnknownLogin);
+    }
+    let mut path = dirs::data_local_dir().ok_or(Error::CannotFindLocalDir)?;
+    match profile {
+        DEFAULT_PROFILE => path.push(format!("kivinge.{token}.login")),
+        _ => path.push(format!("kivinge.{profile}.{token}.login")),
+    }
+    Ok(path)
+}
+
+/// Saves the state of a login for `load_pending` to pick up, only readable
+/// by the user on Unix as it is enough to finish the login with
+pub fn save_pending(
+    login: &PendingLogin,
+    profile: &str,
+    token: &str,
+) -> Result<(), Error> {
+    let path = pending_path(profile, token)?;
+    let mut options = File::options();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let file = options.open(path)?;
+    serde_json::to_writer(file, login)?;
+    Ok(())
+}
+
+pub fn load_pending(profile: &str, token: &str) -> Result<PendingLogin, Error> {
+    match File::open(pending_path(profile, token)?) {
+        Ok(file) => Ok(serde_json::from_reader(file)?),
+        Err(error) if error.kind() == ErrorKind::NotFound => {
+            Err(Error::UnknownLogin)
+        }
+        Err(error) => Err(error.into()),
+    }
+}
+
+pub fn delete_pending(profile: &str, token: &str) -> Result<(), Error> {
+    Ok(fs::remove_file(pending_path(profile, token)?)?)
+}
+
+/// Removes pending logins of every profile that have been left alone for
+/// long enough that no one is going to poll them again
+pub fn prune_pending() -> Result<(), Error> {
+    let dir = dirs::data_local_dir().ok_or(Error::CannotFindLocalDir)?;
+    for entry in fs::read_dir(dir)?.filter_map(Result::ok) {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if !name.starts_with("kivinge.") || !name.ends_with(".login") {
+            continue;
+        }
+        let modified = entry.metadata().and_then(|meta| meta.modified())?;
+        if modified.elapsed().unwrap_or_default() > PENDING_LIFETIME {
+            fs::remove_file(entry.path())?;
+        }
+    }
+    Ok(())
+}
+
 pub fn make(access_token: String, id_token: String) -> Result<Session, Error> {
-    let user_info = extract_user_info(&id_token)?;
-    Ok(Session { user_info, access_token, id_token })
+    let claims = extract_claims(&id_token)?;
+    let user_info = serde_json::from_slice(&claims)?;
+    let expiry: ExpiryClaims = serde_json::from_slice(&claims)?;
+    let expires_at =
+        expiry.exp.and_then(|exp| DateTime::from_timestamp(exp, 0));
+    Ok(Session { user_info, access_token, id_token, expires_at })
 }
 
-fn extract_user_info(id_token: &str) -> Result<UserInfo, Error> {
+fn extract_claims(id_token: &str) -> Result<Vec<u8>, Error> {
     let sections = id_token.split('.').collect::<Vec<&str>>();
     let claims_base64 =
         sections.get(1).ok_or(Error::JWTError("Too few sections"))?;
-    let claims_json = URL_SAFE_NO_PAD.decode(claims_base64)?;
-    Ok(serde_json::from_slice(claims_json.as_slice())?)
+    Ok(URL_SAFE_NO_PAD.decode(claims_base64)?)
 }
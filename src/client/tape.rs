@@ -0,0 +1,311 @@
+use reqwest::{
+    blocking::{Client, Request, RequestBuilder, Response},
+    header::{HeaderName, CONTENT_TYPE, ETAG, LAST_MODIFIED},
+    ResponseBuilderExt, Url,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, HashMap, VecDeque},
+    fs,
+    path::{Path, PathBuf},
+};
+use thiserror::Error;
+
+use super::session::{Session, UserInfo};
+
+const SESSION_FILE: &str = "session.json";
+const REDACTED: &str = "REDACTED";
+
+// Values of these keys tell how a body is shaped rather than what it says,
+// and are kept along with dates and other keys. All other text and numbers
+// are redacted wherever they appear in a body, e.g. subjects, sender names,
+// inline bodies, amounts and the OCR, account and QR data of payments.
+const STRUCTURAL_KEYS: [&str; 7] = [
+    "content_type",
+    "currency",
+    "key",
+    "payment_status",
+    "sender",
+    "status",
+    "type",
+];
+
+// Only headers the client looks at are kept
+const KEPT_HEADERS: [HeaderName; 3] = [CONTENT_TYPE, ETAG, LAST_MODIFIED];
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("no recorded response for {0}")]
+    NotRecorded(String),
+
+    #[error("(de)serialization error")]
+    SerializationError(#[from] serde_json::Error),
+
+    #[error("invalid recorded response: {0}")]
+    InvalidResponse(#[from] http::Error),
+
+    #[error("IO error")]
+    IOError(#[from] std::io::Error),
+}
+
+/// One request and the response to it. Bodies that are not JSON, such as
+/// attachments, are left out.
+#[derive(Clone, Deserialize, Serialize)]
+struct Exchange {
+    method: String,
+    url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    request: Option<Value>,
+    status: u16,
+    #[serde(default)]
+    headers: BTreeMap<String, String>,
+    #[serde(default)]
+    body: Value,
+}
+
+enum Mode {
+    Record { count: usize },
+    // Recorded exchanges by method and URL, in the order they were made
+    Replay { exchanges: HashMap<(String, String), VecDeque<Exchange>> },
+}
+
+/// Requests and responses kept as one anonymized JSON file each, so that a
+/// session can be replayed later without Kivra, e.g. to reproduce a bug.
+pub struct Tape {
+    dir: PathBuf,
+    mode: RefCell<Mode>,
+    // Real values of the user and the made up ones that replace them
+    replacements: Vec<(String, String)>,
+}
+
+impl Tape {
+    pub fn record(dir: &Path) -> Result<Tape, Error> {
+        fs::create_dir_all(dir)?;
+        let count = exchange_files(dir)?.len();
+        Ok(Tape {
+            dir: dir.to_path_buf(),
+            mode: RefCell::new(Mode::Record { count }),
+            replacements: Vec::new(),
+        })
+    }
+
+    pub fn replay(dir: &Path) -> Result<Tape, Error> {
+        let mut exchanges: HashMap<_, VecDeque<_>> = HashMap::new();
+        for path in exchange_files(dir)? {
+            let exchange: Exchange = serde_json::from_slice(&fs::read(path)?)?;
+            let key = (exchange.method.clone(), exchange.url.clone());
+            exchanges.entry(key).or_default().push_back(exchange);
+        }
+        Ok(Tape {
+            dir: dir.to_path_buf(),
+            mode: RefCell::new(Mode::Replay { exchanges }),
+            replacements: Vec::new(),
+        })
+    }
+
+    pub fn is_replay(&self) -> bool {
+        matches!(*self.mode.borrow(), Mode::Replay { .. })
+    }
+
+    /// The made up user that was logged in when the tape was recorded
+    pub fn replay_session(&self) -> Result<Option<Session>, Error> {
+        let path = self.dir.join(SESSION_FILE);
+        if !self.is_replay() || !path.exists() {
+            return Ok(None);
+        }
+        let user_info = serde_json::from_slice(&fs::read(path)?)?;
+        Ok(Some(Session {
+            user_info,
+            access_token: REDACTED.to_string(),
+            id_token: REDACTED.to_string(),
+            expires_at: None,
+        }))
+    }
+
+    /// Starts replacing the details of the logged in user in what is
+    /// recorded from now on
+    pub fn set_user(&mut self, user_info: &UserInfo) -> Result<(), Error> {
+        if self.is_replay() {
+            return Ok(());
+        }
+        let anonymous = UserInfo {
+            kivra_user_id: "1000000000000001".to_string(),
+            name: "Tolvan Tolvansson".to_string(),
+            first_name: "Tolvan".to_string(),
+            last_name: "Tolvansson".to_string(),
+            ssn: "191212121212".to_string(),
+            email: "tolvan@example.com".to_string(),
+        };
+        self.replacements = [
+            (&user_info.kivra_user_id, anonymous.kivra_user_id.clone()),
+            (&user_info.name, anonymous.name.clone()),
+            (&user_info.first_name, anonymous.first_name.clone()),
+            (&user_info.last_name, anonymous.last_name.clone()),
+            (&user_info.ssn, anonymous.ssn.clone()),
+            (&user_info.email, anonymous.email.clone()),
+        ]
+        .into_iter()
+        .filter(|(real, _)| !real.is_empty())
+        .map(|(real, fake)| (real.clone(), fake))
+        .collect();
+        // Longer values first, so that a full name is replaced as a whole
+        self.replacements.sort_by_key(|(real, _)| usize::MAX - real.len());
+        let session = serde_json::to_vec_pretty(&anonymous)?;
+        Ok(fs::write(self.dir.join(SESSION_FILE), session)?)
+    }
+
+    pub fn send(
+        &self,
+        request: RequestBuilder,
+    ) -> Result<Response, super::Error> {
+        let (client, request) = request.build_split();
+        let request = request?;
+        match &mut *self.mode.borrow_mut() {
+            Mode::Replay { exchanges } => Ok(replay(exchanges, &request)?),
+            Mode::Record { count } => {
+                *count += 1;
+                self.record_exchange(&client, request, *count)
+            }
+        }
+    }
+
+    fn record_exchange(
+        &self,
+        client: &Client,
+        request: Request,
+        number: usize,
+    ) -> Result<Response, super::Error> {
+        let url = request.url().clone();
+        let method = request.method().to_string();
+        let request_body = request
+            .body()
+            .and_then(|body| body.as_bytes())
+            .and_then(|body| serde_json::from_slice(body).ok());
+        let response = client.execute(request)?;
+        let headers = response
+            .headers()
+            .iter()
+            .filter(|(name, _)| KEPT_HEADERS.contains(name))
+            .filter_map(|(name, value)| {
+                Some((name.to_string(), value.to_str().ok()?.to_string()))
+            })
+            .collect();
+        let status = response.status().as_u16();
+        let bytes = response.bytes()?;
+        let exchange = Exchange {
+            method,
+            url: url.to_string(),
+            request: request_body,
+            status,
+            headers,
+            body: serde_json::from_slice(&bytes).unwrap_or_default(),
+        };
+
+        let anonymized = serde_json::to_vec_pretty(&self.anonymize(&exchange))?;
+        let path = self.dir.join(format!("{number:04}.json"));
+        fs::write(path, anonymized).map_err(Error::from)?;
+        // The caller gets the real response, not the anonymized one
+        Ok(to_response(&exchange, url, bytes.to_vec())?)
+    }
+
+    fn anonymize(&self, exchange: &Exchange) -> Exchange {
+        let mut exchange = exchange.clone();
+        exchange.url = self.replace(&exchange.url);
+        if let Some(request) = &mut exchange.request {
+            self.anonymize_value(request, false);
+        }
+        self.anonymize_value(&mut exchange.body, false);
+        exchange
+    }
+
+    /// Redacts the value unless it is `structural`, keeping its type so
+    /// that the recording still decodes when it is replayed
+    fn anonymize_value(&self, value: &mut Value, structural: bool) {
+        match value {
+            Value::String(string) if structural => {
+                *string = self.replace(string)
+            }
+            // Amounts may be given as text
+            Value::String(string) if string.parse::<f64>().is_ok() => {
+                *string = "0".to_string()
+            }
+            Value::String(string) => *string = REDACTED.to_string(),
+            Value::Number(_) if !structural => *value = Value::from(0),
+            Value::Array(values) => values
+                .iter_mut()
+                .for_each(|value| self.anonymize_value(value, structural)),
+            Value::Object(map) => {
+                for (key, value) in map.iter_mut() {
+                    self.anonymize_value(value, is_structural(key));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn replace(&self, string: &str) -> String {
+        self.replacements
+            .iter()
+            .fold(string.to_string(), |string, (real, fake)| {
+                string.replace(real, fake)
+            })
+    }
+}
+
+fn is_structural(key: &str) -> bool {
+    STRUCTURAL_KEYS.contains(&key)
+        || key.ends_with("_key")
+        || key.ends_with("_at")
+        || key.ends_with("_date")
+}
+
+fn replay(
+    exchanges: &mut HashMap<(String, String), VecDeque<Exchange>>,
+    request: &Request,
+) -> Result<Response, Error> {
+    let key = (request.method().to_string(), request.url().to_string());
+    let not_recorded = || Error::NotRecorded(format!("{} {}", key.0, key.1));
+    let recorded = exchanges.get_mut(&key).ok_or_else(not_recorded)?;
+    // Repeated requests, like polls, get the last response again
+    let exchange = match recorded.len() {
+        1 => recorded[0].clone(),
+        _ => recorded.pop_front().ok_or_else(not_recorded)?,
+    };
+    let body = match &exchange.body {
+        Value::Null => Vec::new(),
+        body => serde_json::to_vec(body)?,
+    };
+    to_response(&exchange, request.url().clone(), body)
+}
+
+fn to_response(
+    exchange: &Exchange,
+    url: Url,
+    body: Vec<u8>,
+) -> Result<Response, Error> {
+    let mut response =
+        http::Response::builder().status(exchange.status).url(url);
+    for (name, value) in &exchange.headers {
+        response = response.header(name, value);
+    }
+    Ok(response.body(body)?.into())
+}
+
+/// Recorded exchanges in the order they were made
+fn exchange_files(dir: &Path) -> Result<Vec<PathBuf>, Error> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let is_exchange = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .is_some_and(|stem| stem.chars().all(|c| c.is_ascii_digit()));
+        if is_exchange {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
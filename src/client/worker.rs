@@ -0,0 +1,66 @@
+//! Calls made on a worker thread of the terminal UI. Those never log in,
+//! since the login view can only be shown by the thread of the UI, and are
+//! given up on when the view waiting for them goes away.
+
+use std::{
+    cell::RefCell,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use super::Error;
+
+/// How often a waiting worker checks whether it is cancelled
+pub const CANCEL_POLL: Duration = Duration::from_millis(50);
+
+thread_local! {
+    static CURRENT: RefCell<Option<Cancel>> = const { RefCell::new(None) };
+}
+
+/// Gives up on the calls of the worker it runs
+#[derive(Clone, Default)]
+pub struct Cancel(Arc<AtomicBool>);
+
+impl Cancel {
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    /// Runs `f` as a worker on this thread
+    pub fn run<T>(&self, f: impl FnOnce() -> T) -> T {
+        CURRENT.set(Some(self.clone()));
+        let result = f();
+        CURRENT.set(None);
+        result
+    }
+}
+
+/// The `Cancel` of the worker running on this thread, if any
+pub fn current() -> Option<Cancel> {
+    CURRENT.with_borrow(Clone::clone)
+}
+
+/// Sleeps for `duration`, unless a worker on this thread is cancelled first
+pub fn sleep(duration: Duration) -> Result<(), Error> {
+    let Some(cancel) = current() else {
+        thread::sleep(duration);
+        return Ok(());
+    };
+    let deadline = Instant::now() + duration;
+    while !cancel.is_cancelled() {
+        let left = deadline.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            return Ok(());
+        }
+        thread::sleep(left.min(CANCEL_POLL));
+    }
+    Err(Error::Cancelled)
+}
@@ -0,0 +1,422 @@
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fs, path::PathBuf};
+use thiserror::Error;
+
+use crate::{
+    cli::copy::DEFAULT_TEMPLATE, client::session, filename, i18n::Language,
+    table::Timestamps, tui::qr::QrRenderer,
+};
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("failed to determine config dir")]
+    CannotFindConfigDir,
+
+    #[error("IO error: {0}")]
+    IOError(#[from] std::io::Error),
+
+    #[error("invalid config file: {0}")]
+    ParseError(#[from] toml::de::Error),
+
+    #[error("invalid download.template: {0}")]
+    InvalidTemplate(String),
+
+    #[error("invalid [profiles]: {0}")]
+    InvalidProfile(#[from] session::Error),
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Config {
+    pub language: Language,
+    /// How `list` and the TUI inbox show when items were created
+    pub timestamps: Timestamps,
+    pub login: LoginConfig,
+    pub network: NetworkConfig,
+    pub download: DownloadConfig,
+    pub tui: TuiConfig,
+    pub copy: CopyConfig,
+    pub cache: CacheConfig,
+    pub storage: StorageConfig,
+    pub notify: NotifyConfig,
+    pub open: OpenConfig,
+    pub encryption: EncryptionConfig,
+    pub profiles: BTreeMap<String, ProfileConfig>,
+}
+
+impl Config {
+    /// Where attachments are downloaded to unless told otherwise
+    pub fn download_dir(&self) -> PathBuf {
+        (self.download.dir.clone())
+            .or_else(|| self.tui.download_dir.clone())
+            .or_else(dirs::download_dir)
+            .unwrap_or(".".into())
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ProfileConfig {
+    /// Header color, e.g. "blue" or "#ff8800"
+    pub color: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct LoginConfig {
+    pub qr_renderer: QrRenderer,
+}
+
+/// How requests to Kivra are made. Proxies in HTTPS_PROXY, ALL_PROXY and
+/// NO_PROXY are used unless `proxy` is set.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct NetworkConfig {
+    /// Seconds to wait for a connection, 0 to wait as long as it takes
+    pub connect_timeout: u64,
+    /// Seconds to wait for a whole response, 0 to wait as long as it takes
+    pub timeout: u64,
+    /// Proxy for every request, e.g. "http://proxy:3128" or
+    /// "socks5h://localhost:1080"
+    pub proxy: Option<String>,
+    /// Hosts to reach without the proxy, comma separated
+    pub no_proxy: Option<String>,
+    /// PEM files with CA certificates to trust besides the system's
+    pub ca_certificates: Vec<PathBuf>,
+}
+
+impl Default for NetworkConfig {
+    fn default() -> NetworkConfig {
+        NetworkConfig {
+            connect_timeout: 10,
+            timeout: 30,
+            proxy: None,
+            no_proxy: None,
+            ca_certificates: Vec::new(),
+        }
+    }
+}
+
+/// Where `download`, the TUI and exports put attachments
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct DownloadConfig {
+    /// The user's download directory if not set
+    pub dir: Option<PathBuf>,
+    /// Path of each attachment in the directory, with the placeholders of
+    /// `filename::PLACEHOLDERS`
+    pub template: String,
+    /// What to do when a download would replace a file
+    pub existing: Existing,
+}
+
+impl Default for DownloadConfig {
+    fn default() -> DownloadConfig {
+        DownloadConfig {
+            dir: None,
+            template: filename::DEFAULT_TEMPLATE.to_string(),
+            existing: Existing::default(),
+        }
+    }
+}
+
+/// What to do when a download would replace a file. A file that already
+/// holds what would be written is left as it is whatever the policy.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Existing {
+    /// Fail the download
+    #[default]
+    Fail,
+    Overwrite,
+    /// Leave the file and skip the download
+    Skip,
+    /// Download to the first free name with a `-2`, `-3`, ... suffix
+    Rename,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct TuiConfig {
+    pub startup_view: StartupView,
+    /// Seconds between background refreshes of the inbox, 0 to disable
+    pub sync_interval: u64,
+    /// Inbox items fetched at a time, 0 to fetch the whole inbox at once
+    pub page_size: usize,
+    /// Replaced by `download.dir`, and only used if that is not set
+    pub download_dir: Option<PathBuf>,
+    /// Header color for profiles without one of their own
+    pub color: Option<String>,
+    /// Mark unread items as read when they are opened
+    pub mark_read_on_open: bool,
+    /// How image attachments and sender icons are drawn
+    pub images: Images,
+}
+
+impl Default for TuiConfig {
+    fn default() -> TuiConfig {
+        TuiConfig {
+            startup_view: StartupView::default(),
+            sync_interval: 300,
+            page_size: 100,
+            download_dir: None,
+            color: None,
+            mark_read_on_open: false,
+            images: Images::default(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct CacheConfig {
+    /// Keep downloaded attachments on disk so they are only fetched once
+    pub attachments: bool,
+    /// Store cached attachments zstd compressed
+    pub compress: bool,
+    /// Keep API responses so unchanged listings are not downloaded again
+    pub responses: bool,
+    /// Seconds a fetched inbox listing is reused, 0 to always fetch it
+    pub inbox_ttl: u64,
+    /// Seconds fetched item details are reused, 0 to always fetch them
+    pub details_ttl: u64,
+}
+
+impl Default for CacheConfig {
+    fn default() -> CacheConfig {
+        CacheConfig {
+            attachments: false,
+            compress: true,
+            responses: false,
+            inbox_ttl: 60,
+            details_ttl: 3600,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct CopyConfig {
+    /// Snippet placed on the clipboard by `copy` and the TUI `Y` key
+    pub template: String,
+}
+
+impl Default for CopyConfig {
+    fn default() -> CopyConfig {
+        CopyConfig { template: DEFAULT_TEMPLATE.to_string() }
+    }
+}
+
+/// What happens to the copies of attachments written out to be opened
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct OpenConfig {
+    /// Seconds a copy is kept before the next run removes it, 0 to keep
+    /// them until `clean-temp`
+    pub max_age: u64,
+    /// Remove the copies opened from the TUI when it exits
+    pub clean_on_exit: bool,
+}
+
+impl Default for OpenConfig {
+    fn default() -> OpenConfig {
+        OpenConfig { max_age: 86400, clean_on_exit: true }
+    }
+}
+
+/// Encryption of the local index and the on-disk caches
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct EncryptionConfig {
+    pub enabled: bool,
+    /// Prints the passphrase, e.g. to take it from the keyring. Without one
+    /// it is taken from KIVINGE_PASSPHRASE or asked for.
+    pub passphrase_command: Option<String>,
+}
+
+/// Credentials for remote `backup` targets
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct StorageConfig {
+    pub webdav: WebDavConfig,
+    pub s3: S3Config,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct WebDavConfig {
+    pub username: Option<String>,
+    pub password: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct S3Config {
+    /// Any S3 compatible service, e.g. "https://s3.eu-north-1.amazonaws.com"
+    pub endpoint: String,
+    pub region: String,
+    pub access_key_id: Option<String>,
+    pub secret_access_key: Option<String>,
+}
+
+impl Default for S3Config {
+    fn default() -> S3Config {
+        S3Config {
+            endpoint: "https://s3.amazonaws.com".to_string(),
+            region: "us-east-1".to_string(),
+            access_key_id: None,
+            secret_access_key: None,
+        }
+    }
+}
+
+/// How `watch` tells other programs about new items
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct NotifyConfig {
+    /// Times a notification is tried before the item is given up on
+    pub attempts: u32,
+    pub webhook: WebhookConfig,
+    pub ntfy: NtfyConfig,
+    pub gotify: GotifyConfig,
+    pub smtp: SmtpConfig,
+}
+
+impl Default for NotifyConfig {
+    fn default() -> NotifyConfig {
+        NotifyConfig {
+            attempts: 3,
+            webhook: WebhookConfig::default(),
+            ntfy: NtfyConfig::default(),
+            gotify: GotifyConfig::default(),
+            smtp: SmtpConfig::default(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct WebhookConfig {
+    /// Body to post instead of the default JSON, with the placeholders of
+    /// the copy template and {key}
+    pub template: Option<String>,
+    /// Sent with the template. Values filled into a JSON template are
+    /// escaped for it.
+    pub content_type: String,
+    /// Extra headers, e.g. an Authorization header
+    pub headers: BTreeMap<String, String>,
+}
+
+impl Default for WebhookConfig {
+    fn default() -> WebhookConfig {
+        WebhookConfig {
+            template: None,
+            content_type: "application/json".to_string(),
+            headers: BTreeMap::new(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct NtfyConfig {
+    pub server: String,
+    pub topic: Option<String>,
+    /// Access token, for servers that require one
+    pub token: Option<String>,
+    /// From 1 (min) to 5 (max)
+    pub priority: u8,
+}
+
+impl Default for NtfyConfig {
+    fn default() -> NtfyConfig {
+        NtfyConfig {
+            server: "https://ntfy.sh".to_string(),
+            topic: None,
+            token: None,
+            priority: 3,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct GotifyConfig {
+    pub server: Option<String>,
+    /// Token of the Gotify application to send as
+    pub token: Option<String>,
+    pub priority: u8,
+}
+
+impl Default for GotifyConfig {
+    fn default() -> GotifyConfig {
+        GotifyConfig { server: None, token: None, priority: 5 }
+    }
+}
+
+/// The mail server `watch --forward` sends through
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct SmtpConfig {
+    pub server: Option<String>,
+    /// Defaults to 465 with TLS, 587 with STARTTLS and 25 without either
+    pub port: Option<u16>,
+    pub security: SmtpSecurity,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// Address forwarded items are sent from, defaults to the username
+    pub from: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpSecurity {
+    Tls,
+    #[default]
+    StartTls,
+    /// Only for servers on the local machine
+    None,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum StartupView {
+    #[default]
+    Inbox,
+    Unread,
+    Payments,
+    Last,
+}
+
+/// The terminal graphics protocol to draw images with. `Auto` picks the one
+/// the terminal is known to support, if any.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Images {
+    #[default]
+    Auto,
+    Kitty,
+    Iterm,
+    Off,
+}
+
+pub fn default_config_path() -> Result<PathBuf, Error> {
+    let mut path = dirs::config_dir().ok_or(Error::CannotFindConfigDir)?;
+    path.push("kivinge");
+    path.push("config.toml");
+    Ok(path)
+}
+
+pub fn load() -> Result<Config, Error> {
+    let config_path = default_config_path()?;
+    if !config_path.exists() {
+        return Ok(Config::default());
+    }
+    let config: Config = toml::from_str(&fs::read_to_string(config_path)?)?;
+    filename::check_template(&config.download.template)
+        .map_err(Error::InvalidTemplate)?;
+    for profile in config.profiles.keys() {
+        session::check_profile(profile)?;
+    }
+    Ok(config)
+}
@@ -0,0 +1,170 @@
+//! Encryption at rest of the local index, the journal, the on-disk caches
+//! and downloaded attachments, for those who sync them to cloud storage.
+//! Sealed files hold the salt the key was derived with, so they can be read
+//! wherever the passphrase is known, and are told apart from plain ones by
+//! their header. Plain files are read as before and sealed the next time
+//! they are written.
+
+use chacha20poly1305::{
+    aead::{rand_core::RngCore, Aead, AeadCore, KeyInit, OsRng},
+    ChaCha20Poly1305, Key, Nonce,
+};
+use std::{
+    collections::HashMap,
+    io::{stdin, IsTerminal},
+    process::Command,
+    sync::{Mutex, OnceLock},
+};
+use thiserror::Error;
+use tracing::debug;
+
+use crate::config::EncryptionConfig;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error(
+        "data is encrypted but no passphrase was given, set \
+         encryption.passphrase_command or {PASSPHRASE_VAR}"
+    )]
+    NoPassphrase,
+
+    #[error("passphrase command failed: {0}")]
+    CommandError(String),
+
+    #[error("key derivation failed: {0}")]
+    KdfError(String),
+
+    #[error("failed to decrypt, wrong passphrase or damaged file")]
+    DecryptError,
+
+    #[error("IO error: {0}")]
+    IOError(#[from] std::io::Error),
+}
+
+/// Environment variable the passphrase is taken from when there is no
+/// passphrase command
+pub const PASSPHRASE_VAR: &str = "KIVINGE_PASSPHRASE";
+
+const MAGIC: &[u8] = b"KIVSEAL1";
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+
+static CONFIG: OnceLock<EncryptionConfig> = OnceLock::new();
+static PASSPHRASE: Mutex<Option<String>> = Mutex::new(None);
+/// Keys by the salt they were derived with, as deriving one is slow
+static KEYS: Mutex<Option<HashMap<[u8; SALT_LEN], ChaCha20Poly1305>>> =
+    Mutex::new(None);
+/// The salt of the files written by this process
+static SALT: OnceLock<[u8; SALT_LEN]> = OnceLock::new();
+
+/// Sets up sealing as configured. With encryption enabled the passphrase is
+/// asked for now, if it has to be and `prompt` allows, since the terminal
+/// may be taken by the TUI later. Encrypted files are still read with it
+/// disabled, given a passphrase command or variable.
+pub fn init(config: &EncryptionConfig, prompt: bool) -> Result<(), Error> {
+    // Set up before forking, for a mount
+    if CONFIG.set(config.clone()).is_err() || !config.enabled {
+        return Ok(());
+    }
+    let passphrase = match passphrase(config)? {
+        Some(passphrase) => passphrase,
+        None if prompt && stdin().is_terminal() => {
+            rpassword::prompt_password("Passphrase for the local archive: ")?
+        }
+        None => return Err(Error::NoPassphrase),
+    };
+    *PASSPHRASE.lock().expect("passphrase lock") = Some(passphrase);
+    // Derive the key now rather than at the first write
+    key(salt()).map(drop)
+}
+
+fn enabled() -> bool {
+    CONFIG.get().is_some_and(|config| config.enabled)
+}
+
+fn passphrase(config: &EncryptionConfig) -> Result<Option<String>, Error> {
+    if let Some(command) = &config.passphrase_command {
+        let output = Command::new("sh").arg("-c").arg(command).output()?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(Error::CommandError(stderr.trim().to_string()));
+        }
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        return Ok(Some(stdout.trim_end_matches(['\r', '\n']).to_string()));
+    }
+    Ok(std::env::var(PASSPHRASE_VAR).ok())
+}
+
+fn salt() -> [u8; SALT_LEN] {
+    *SALT.get_or_init(|| {
+        let mut salt = [0; SALT_LEN];
+        OsRng.fill_bytes(&mut salt);
+        salt
+    })
+}
+
+fn key(salt: [u8; SALT_LEN]) -> Result<ChaCha20Poly1305, Error> {
+    let mut keys = KEYS.lock().expect("key lock");
+    let keys = keys.get_or_insert_with(HashMap::new);
+    if let Some(key) = keys.get(&salt) {
+        return Ok(key.clone());
+    }
+
+    let mut passphrase = PASSPHRASE.lock().expect("passphrase lock");
+    if passphrase.is_none() {
+        let config = CONFIG.get().cloned().unwrap_or_default();
+        *passphrase = self::passphrase(&config)?;
+    }
+    let passphrase = passphrase.as_ref().ok_or(Error::NoPassphrase)?;
+    let mut bytes = [0; 32];
+    argon2::Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), &salt, &mut bytes)
+        .map_err(|error| Error::KdfError(error.to_string()))?;
+    debug!("derived an encryption key");
+    let key = ChaCha20Poly1305::new(Key::from_slice(&bytes));
+    keys.insert(salt, key.clone());
+    Ok(key)
+}
+
+pub fn is_sealed(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+/// Encrypts data about to be written, when encryption is enabled
+pub fn seal(data: Vec<u8>) -> Result<Vec<u8>, Error> {
+    if !enabled() {
+        return Ok(data);
+    }
+    let salt = salt();
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = key(salt)?
+        .encrypt(&nonce, data.as_slice())
+        .expect("encrypting in memory does not fail");
+    let mut sealed = MAGIC.to_vec();
+    sealed.extend_from_slice(&salt);
+    sealed.extend_from_slice(&nonce);
+    sealed.extend(ciphertext);
+    Ok(sealed)
+}
+
+/// Decrypts data that was read, if it was sealed
+pub fn unseal(data: Vec<u8>) -> Result<Vec<u8>, Error> {
+    let Some(rest) = data.strip_prefix(MAGIC) else {
+        return Ok(data);
+    };
+    if rest.len() < SALT_LEN + NONCE_LEN {
+        return Err(Error::DecryptError);
+    }
+    let (salt, rest) = rest.split_at(SALT_LEN);
+    let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+    let salt = salt.try_into().expect("split at the salt length");
+    key(salt)?
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| Error::DecryptError)
+}
+
+/// Whether data read is stored otherwise than it would be written now, so
+/// that it may be rewritten
+pub fn needs_migration(data: &[u8]) -> bool {
+    is_sealed(data) != enabled()
+}
@@ -0,0 +1,171 @@
+use std::{
+    collections::HashMap,
+    sync::{mpsc::Sender, Arc, Mutex, PoisonError},
+};
+use thiserror::Error;
+use zbus::{blocking::Connection, fdo, interface, zvariant::Value};
+
+use crate::{
+    client::session::DEFAULT_PROFILE,
+    model::content::{InboxEntry, InboxFilter},
+    watch::{Request, Status},
+};
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("session bus error: {0}")]
+    BusError(#[from] zbus::Error),
+}
+
+pub const BUS_NAME: &str = "org.kivinge.Inbox";
+pub const OBJECT_PATH: &str = "/org/kivinge/Inbox";
+const INTERFACE: &str = "org.kivinge.Inbox1";
+
+struct InboxService {
+    status: Arc<Mutex<Status>>,
+    requests: Sender<Request>,
+}
+
+impl InboxService {
+    fn status(&self) -> Status {
+        self.status.lock().unwrap_or_else(PoisonError::into_inner).clone()
+    }
+
+    fn request(&self, request: Request) -> fdo::Result<()> {
+        self.requests
+            .send(request)
+            .map_err(|_| fdo::Error::Failed("watcher has stopped".to_string()))
+    }
+}
+
+#[interface(name = "org.kivinge.Inbox1")]
+impl InboxService {
+    #[zbus(property)]
+    fn unread_count(&self) -> u32 {
+        self.status().unread
+    }
+
+    #[zbus(property)]
+    fn profile(&self) -> String {
+        self.status().profile
+    }
+
+    #[zbus(property)]
+    fn user_name(&self) -> String {
+        self.status().user_name
+    }
+
+    /// Every item in the inbox as (id, key, sender, subject, created,
+    /// unread), oldest first, with the time in RFC 3339
+    fn list_items(&self) -> Vec<(u32, String, String, String, String, bool)> {
+        let items = self.status().items.into_iter();
+        items
+            .map(|item| {
+                let created = item.created_at.to_rfc3339();
+                (
+                    item.id,
+                    item.key,
+                    item.sender,
+                    item.subject,
+                    created,
+                    item.unread,
+                )
+            })
+            .collect()
+    }
+
+    /// Poll Kivra now rather than at the next interval
+    fn refresh(&self) -> fdo::Result<()> {
+        self.request(Request::Refresh)
+    }
+
+    /// Open the first attachment of the item with this id
+    fn open_item(&self, id: u32) -> fdo::Result<()> {
+        self.request(Request::OpenItem(id))
+    }
+}
+
+/// The watcher's presence on the session bus
+pub struct Service {
+    connection: Connection,
+    status: Arc<Mutex<Status>>,
+}
+
+impl Service {
+    pub fn register(
+        profile: &str,
+        requests: Sender<Request>,
+    ) -> Result<Service, Error> {
+        let status = Arc::new(Mutex::new(Status::default()));
+        let service = InboxService { status: status.clone(), requests };
+        let connection = zbus::blocking::connection::Builder::session()?
+            .name(bus_name(profile))?
+            .serve_at(OBJECT_PATH, service)?
+            .build()?;
+        Ok(Service { connection, status })
+    }
+
+    /// Updates the properties, signalling the ones that changed
+    pub fn publish(&self, status: Status) -> Result<(), Error> {
+        let old = {
+            let mut current =
+                self.status.lock().unwrap_or_else(PoisonError::into_inner);
+            std::mem::replace(&mut *current, status.clone())
+        };
+        let mut changed: HashMap<&str, Value> = HashMap::new();
+        if old.unread != status.unread {
+            changed.insert("UnreadCount", status.unread.into());
+        }
+        if old.profile != status.profile {
+            changed.insert("Profile", status.profile.into());
+        }
+        if old.user_name != status.user_name {
+            changed.insert("UserName", status.user_name.into());
+        }
+        if changed.is_empty() {
+            return Ok(());
+        }
+        let invalidated: &[&str] = &[];
+        self.connection.emit_signal(
+            None::<()>,
+            OBJECT_PATH,
+            "org.freedesktop.DBus.Properties",
+            "PropertiesChanged",
+            &(INTERFACE, changed, invalidated),
+        )?;
+        Ok(())
+    }
+}
+
+impl Service {
+    /// Signals `NewItem(id, sender, subject, unread)` for an item that has
+    /// just arrived
+    pub fn announce(&self, entry: &InboxEntry) -> Result<(), Error> {
+        let item = &entry.item;
+        let unread = InboxFilter::Unread.matches(item);
+        self.connection.emit_signal(
+            None::<()>,
+            OBJECT_PATH,
+            INTERFACE,
+            "NewItem",
+            &(entry.id, &item.sender_name, &item.subject, unread),
+        )?;
+        Ok(())
+    }
+}
+
+/// Every profile gets its own name so several watchers can run at once
+fn bus_name(profile: &str) -> String {
+    if profile == DEFAULT_PROFILE {
+        return BUS_NAME.to_string();
+    }
+    let mut element: String = profile
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    // Name elements must not start with a digit
+    if element.starts_with(|c: char| c.is_ascii_digit()) {
+        element.insert(0, '_');
+    }
+    format!("{BUS_NAME}.{element}")
+}
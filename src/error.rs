@@ -1,3 +1,4 @@
+use std::path::PathBuf;
 use thiserror;
 
 #[derive(thiserror::Error, Debug)]
@@ -8,21 +9,80 @@ pub enum Error {
     #[error("HTTP client error: {0}")]
     ClientError(#[from] super::client::Error),
 
+    #[error("record/replay error: {0}")]
+    TapeError(#[from] super::client::tape::Error),
+
     #[error("TUI error: {0}")]
     TuiError(#[from] super::tui::Error),
 
+    #[error("cache error: {0}")]
+    CacheError(#[from] super::cache::Error),
+
+    #[error("config error: {0}")]
+    ConfigError(#[from] super::config::Error),
+
+    #[error("encryption error: {0}")]
+    CryptoError(#[from] super::crypto::Error),
+
+    #[error("state error: {0}")]
+    StateError(#[from] super::state::Error),
+
+    #[error("index error: {0}")]
+    IndexError(#[from] super::index::Error),
+
+    #[error("journal error: {0}")]
+    JournalError(#[from] super::journal::Error),
+
+    #[error("logging error: {0}")]
+    LoggingError(#[from] super::logging::Error),
+
+    #[error("D-Bus error: {0}")]
+    DBusError(#[from] super::dbus::Error),
+
+    #[error("storage error: {0}")]
+    StorageError(#[from] super::storage::Error),
+
+    #[error("notification error: {0}")]
+    NotifyError(#[from] super::notify::Error),
+
+    #[cfg(all(
+        feature = "fuse",
+        any(target_os = "linux", target_os = "macos")
+    ))]
     #[error("FUSE error: {0}")]
     FuseError(#[from] super::fuse::Error),
 
+    #[cfg(all(
+        feature = "fuse",
+        any(target_os = "linux", target_os = "macos")
+    ))]
+    #[error("mount registry error: {0}")]
+    MountsError(#[from] super::mounts::Error),
+
+    #[error("temp file error: {0}")]
+    TempError(#[from] super::temp::Error),
+
+    #[error("table error: {0}")]
+    TableError(#[from] super::table::Error),
+
+    #[error("JSON error: {0}")]
+    JsonError(#[from] serde_json::Error),
+
     #[error("IO error encountered - {0}")]
     IOError(#[from] std::io::Error),
 
     #[error("Failed to open attachment - {0}")]
     OpenError(#[from] opener::OpenError),
 
+    #[error("Failed to access the clipboard - {0}")]
+    ClipboardError(#[from] arboard::Error),
+
     #[error("Application error - {0}")]
     AppError(&'static str),
 
     #[error("User error - {0}")]
     UserError(&'static str),
+
+    #[error("File already exists - {}", .0.display())]
+    FileExists(PathBuf),
 }
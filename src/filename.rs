@@ -0,0 +1,207 @@
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+};
+
+use crate::cli::copy::unknown_placeholders;
+
+/// Longest name, in bytes, that common file systems allow
+pub const MAX_LEN: usize = 255;
+
+/// Download path template that names attachments as they always have been
+pub const DEFAULT_TEMPLATE: &str = "{name}";
+
+// Keep in sync with the fields filled in by `ItemDetails::attachment_path`
+pub const PLACEHOLDERS: [&str; 9] = [
+    "{name}",
+    "{sender}",
+    "{subject}",
+    "{date}",
+    "{year}",
+    "{month}",
+    "{day}",
+    "{n}",
+    "{ext}",
+];
+
+/// Not allowed in names on Windows, or in the case of `/` anywhere
+const RESERVED: [char; 9] = ['<', '>', ':', '"', '/', '\\', '|', '?', '*'];
+
+/// Names Windows keeps for devices, whatever extension follows them
+const RESERVED_NAMES: [&str; 22] = [
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6",
+    "COM7", "COM8", "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6",
+    "LPT7", "LPT8", "LPT9",
+];
+
+/// A file name that works on Linux, macOS and Windows. Spaces, reserved and
+/// control characters become `-`, and names are cut to `MAX_LEN` bytes
+/// with the extension kept. Sanitizing a sanitized name changes nothing.
+pub fn sanitize(name: &str) -> String {
+    let (stem, extension) = split_extension(name);
+    let extension = extension.map(replace_reserved);
+    let mut stem = replace_reserved(stem);
+    if RESERVED_NAMES.contains(&stem.to_uppercase().as_str()) {
+        stem.insert(0, '_');
+    }
+    let room = match &extension {
+        Some(extension) => MAX_LEN.saturating_sub(extension.len() + 1),
+        None => MAX_LEN,
+    };
+    truncate(&mut stem, room);
+    // Windows drops trailing dots, which would make names collide
+    let stem = stem.trim_end_matches('.');
+    let stem = if stem.is_empty() { "_" } else { stem };
+    match extension {
+        Some(extension) => format!("{stem}.{extension}"),
+        None => stem.to_string(),
+    }
+}
+
+/// Gives later duplicates a `-2`, `-3`, ... suffix before their extension.
+/// Names are compared ignoring case, as on macOS and Windows, and the
+/// suffixes follow the order of the names, so the same names always get
+/// the same ones.
+pub fn deduplicate(names: Vec<String>) -> Vec<String> {
+    let mut taken: HashSet<String> = HashSet::new();
+    names
+        .into_iter()
+        .map(|name| {
+            let mut unique = name.clone();
+            let mut count = 1;
+            while !taken.insert(unique.to_lowercase()) {
+                count += 1;
+                unique = with_suffix(&name, &format!("-{count}"));
+            }
+            unique
+        })
+        .collect()
+}
+
+/// The name with `suffix` before its extension, cut to `MAX_LEN` bytes
+pub fn with_suffix(name: &str, suffix: &str) -> String {
+    let (stem, extension) = split_extension(name);
+    let extension = extension.map(|ext| format!(".{ext}")).unwrap_or_default();
+    let mut stem = stem.to_string();
+    truncate(&mut stem, MAX_LEN.saturating_sub(suffix.len() + extension.len()));
+    format!("{stem}{suffix}{extension}")
+}
+
+/// The name before its last dot and what follows it, unless either is empty
+fn split_extension(name: &str) -> (&str, Option<&str>) {
+    match name.rsplit_once('.') {
+        Some((stem, ext)) if !stem.is_empty() && !ext.is_empty() => {
+            (stem, Some(ext))
+        }
+        _ => (name, None),
+    }
+}
+
+fn replace_reserved(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            ' ' => '-',
+            c if RESERVED.contains(&c) || c.is_control() => '-',
+            c => c,
+        })
+        .collect()
+}
+
+/// Cuts to at most `len` bytes without splitting a character
+fn truncate(text: &mut String, len: usize) {
+    if text.len() <= len {
+        return;
+    }
+    let mut end = len;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    text.truncate(end);
+}
+
+/// What is wrong with a download path template, if anything. Templates are
+/// relative to the download directory and cannot lead out of it.
+pub fn check_template(template: &str) -> Result<(), String> {
+    if template.trim().is_empty() {
+        return Err("the template is empty".to_string());
+    }
+    if let Some(placeholder) =
+        unknown_placeholders(template, &PLACEHOLDERS).first()
+    {
+        return Err(format!("unknown placeholder {placeholder}"));
+    }
+    let path = Path::new(template);
+    if path.has_root() || path.is_absolute() {
+        return Err("the template must be a relative path".to_string());
+    }
+    if template.split(['/', '\\']).any(|part| part == "..") {
+        return Err("the template cannot lead out of the directory".to_string());
+    }
+    Ok(())
+}
+
+/// Fills in a download path template. Each `/` of the template starts a
+/// directory, and the values are sanitized like names so that none of them
+/// can start one of its own.
+pub fn fill_template(template: &str, fields: &[(&str, String)]) -> PathBuf {
+    (template.split('/'))
+        .filter(|part| !part.is_empty() && *part != ".")
+        .map(|part| {
+            let filled = fields.iter().fold(part.to_string(), |part, field| {
+                let (key, value) = field;
+                part.replace(key, &replace_reserved(value))
+            });
+            sanitize(&filled)
+        })
+        .collect()
+}
+
+/// The extension files of a MIME type usually have, if it is a known one.
+/// Parameters such as `; charset=utf-8` are ignored.
+pub fn extension(content_type: &str) -> Option<&'static str> {
+    let essence = content_type.split(';').next().unwrap_or_default();
+    match essence.trim().to_lowercase().as_str() {
+        "application/pdf" => Some("pdf"),
+        "text/html" | "application/xhtml+xml" => Some("html"),
+        "text/plain" => Some("txt"),
+        "image/png" => Some("png"),
+        "image/jpeg" | "image/jpg" => Some("jpg"),
+        "image/gif" => Some("gif"),
+        "application/xml" | "text/xml" => Some("xml"),
+        "text/calendar" => Some("ics"),
+        "text/csv" => Some("csv"),
+        "application/zip" | "application/x-zip-compressed" => Some("zip"),
+        "application/json" => Some("json"),
+        _ => None,
+    }
+}
+
+/// The extension for the kind of file that `contents` starts like, for
+/// when the content type does not tell
+pub fn sniff_extension(contents: &[u8]) -> Option<&'static str> {
+    const MAGIC: [(&[u8], &str); 6] = [
+        (b"%PDF-", "pdf"),
+        (b"\x89PNG\r\n\x1a\n", "png"),
+        (b"\xff\xd8\xff", "jpg"),
+        (b"GIF8", "gif"),
+        (b"PK\x03\x04", "zip"),
+        (b"BEGIN:VCALENDAR", "ics"),
+    ];
+    if let Some((_, ext)) =
+        MAGIC.iter().find(|(magic, _)| contents.starts_with(magic))
+    {
+        return Some(ext);
+    }
+    // Text formats may start with a byte order mark or whitespace
+    let start = contents.strip_prefix(b"\xef\xbb\xbf").unwrap_or(contents);
+    let start = start.trim_ascii_start();
+    let start = &start[..start.len().min(64)];
+    let start = String::from_utf8_lossy(start).to_lowercase();
+    if start.starts_with("<!doctype html") || start.starts_with("<html") {
+        Some("html")
+    } else if start.starts_with("<?xml") {
+        Some("xml")
+    } else {
+        None
+    }
+}
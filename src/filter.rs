@@ -0,0 +1,477 @@
+//! Filter expressions for picking inbox items, used by `list --filter`,
+//! `download --filter` and the search in the TUI. For example
+//! `sender=Skatteverket && year=2024` or `unread || (payable && !label=paid)`.
+//! `content` looks into the text of attachments, as far as the local index
+//! has it, see `with_contents`.
+//!
+//! ```text
+//! expr  = and ("||" and)*
+//! and   = not ("&&" not)*
+//! not   = "!" not | "(" expr ")" | term
+//! term  = "unread" | "read" | "payable" | field op value
+//! field = "sender" | "subject" | "content" | "label" | "folder" | "year"
+//!       | "date"
+//! op    = "=" | "!=" | "<" | "<=" | ">" | ">="
+//! ```
+//!
+//! Values with spaces or any of `()!=<>&|` in them are quoted, as in
+//! `sender="Svea Ekonomi"`. Sender, subject and content match when they
+//! contain the value, ignoring case. Years and dates, given as YYYY-MM-DD, also take a
+//! range such as `date=2024-01-01..2024-03-31`, where either end may be left
+//! out.
+
+use chrono::{Datelike, Local, NaiveDate};
+use std::{cmp::Ordering, fmt::Display, str::FromStr, sync::Arc};
+use thiserror::Error;
+
+use crate::{
+    index::Contents,
+    model::content::{Folder, InboxItem, Status},
+};
+
+#[derive(Debug, Error)]
+#[error("{message} at column {}:\n  {expression}\n  {}^", .column + 1, " ".repeat(*.column))]
+pub struct Error {
+    pub message: String,
+    /// Counted in characters from 0
+    pub column: usize,
+    pub expression: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct Filter {
+    expression: String,
+    root: Expr,
+    contents: Option<Arc<Contents>>,
+}
+
+impl Filter {
+    pub fn parse(expression: &str) -> Result<Filter, Error> {
+        let error = |message: String, offset: usize| Error {
+            message,
+            column: expression[..offset].chars().count(),
+            expression: expression.to_string(),
+        };
+        let tokens = tokenize(expression).map_err(|(m, o)| error(m, o))?;
+        let mut parser = Parser { tokens, position: 0, end: expression.len() };
+        let root = parser.parse().map_err(|(m, o)| error(m, o))?;
+        Ok(Filter { expression: expression.to_string(), root, contents: None })
+    }
+
+    pub fn matches(&self, item: &InboxItem) -> bool {
+        self.root.matches(item, self.contents.as_deref())
+    }
+
+    /// Whether the filter has a `content` term, which matches nothing until
+    /// the contents are given with `with_contents`
+    pub fn searches_contents(&self) -> bool {
+        self.root.searches_contents()
+    }
+
+    pub fn with_contents(mut self, contents: Arc<Contents>) -> Filter {
+        self.contents = Some(contents);
+        self
+    }
+}
+
+impl FromStr for Filter {
+    type Err = Error;
+
+    fn from_str(expression: &str) -> Result<Filter, Error> {
+        Filter::parse(expression)
+    }
+}
+
+impl Display for Filter {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        self.expression.fmt(formatter)
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Expr {
+    Or(Box<Expr>, Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Unread,
+    Payable,
+    Sender(String),
+    Subject(String),
+    /// In lower case, as the contents are
+    Content(String),
+    Label(String),
+    Folder(Folder),
+    Year(Op, i32),
+    Date(Op, NaiveDate),
+}
+
+impl Expr {
+    fn matches(&self, item: &InboxItem, contents: Option<&Contents>) -> bool {
+        let contains = |field: &str, text: &str| {
+            field.to_lowercase().contains(&text.to_lowercase())
+        };
+        let created = || item.created_at.with_timezone(&Local).date_naive();
+        match self {
+            Expr::Or(a, b) => {
+                a.matches(item, contents) || b.matches(item, contents)
+            }
+            Expr::And(a, b) => {
+                a.matches(item, contents) && b.matches(item, contents)
+            }
+            Expr::Not(expr) => !expr.matches(item, contents),
+            Expr::Unread => item.status == Status::Unread,
+            Expr::Payable => item.payable,
+            Expr::Sender(text) => {
+                item.sender == *text || contains(&item.sender_name, text)
+            }
+            Expr::Subject(text) => contains(&item.subject, text),
+            Expr::Content(text) => contents
+                .and_then(|contents| contents.get(&item.key))
+                .is_some_and(|content| content.contains(text.as_str())),
+            Expr::Label(label) => item.labels.get(label) == Some(&true),
+            Expr::Folder(folder) => item.folder() == *folder,
+            Expr::Year(op, year) => op.holds(created().year().cmp(year)),
+            Expr::Date(op, date) => op.holds(created().cmp(date)),
+        }
+    }
+
+    fn searches_contents(&self) -> bool {
+        match self {
+            Expr::Or(a, b) | Expr::And(a, b) => {
+                a.searches_contents() || b.searches_contents()
+            }
+            Expr::Not(expr) => expr.searches_contents(),
+            Expr::Content(_) => true,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Op {
+    /// Whether a value that compares to the one given as `ordering` passes
+    fn holds(&self, ordering: Ordering) -> bool {
+        match self {
+            Op::Eq => ordering.is_eq(),
+            Op::Ne => ordering.is_ne(),
+            Op::Lt => ordering.is_lt(),
+            Op::Le => ordering.is_le(),
+            Op::Gt => ordering.is_gt(),
+            Op::Ge => ordering.is_ge(),
+        }
+    }
+}
+
+impl Display for Op {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Op::Eq => "=",
+            Op::Ne => "!=",
+            Op::Lt => "<",
+            Op::Le => "<=",
+            Op::Gt => ">",
+            Op::Ge => ">=",
+        }
+        .fmt(formatter)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    And,
+    Or,
+    Not,
+    Open,
+    Close,
+    Op(Op),
+    Word(String),
+}
+
+impl Display for Token {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Token::And => write!(formatter, "'&&'"),
+            Token::Or => write!(formatter, "'||'"),
+            Token::Not => write!(formatter, "'!'"),
+            Token::Open => write!(formatter, "'('"),
+            Token::Close => write!(formatter, "')'"),
+            Token::Op(op) => write!(formatter, "'{op}'"),
+            Token::Word(word) => write!(formatter, "'{word}'"),
+        }
+    }
+}
+
+/// A message and the byte offset it is about
+type Failure = (String, usize);
+
+/// The tokens with the byte offsets they start at
+fn tokenize(text: &str) -> Result<Vec<(Token, usize)>, Failure> {
+    let is_special = |c: char| "()!=<>&|\"".contains(c) || c.is_whitespace();
+    let mut tokens = Vec::new();
+    let mut chars = text.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        let mut followed_by =
+            |next: char| chars.next_if(|&(_, c)| c == next).is_some();
+        let token = match c {
+            c if c.is_whitespace() => continue,
+            '(' => Token::Open,
+            ')' => Token::Close,
+            '&' if followed_by('&') => Token::And,
+            '|' if followed_by('|') => Token::Or,
+            '&' => return Err(("expected '&&'".to_string(), start)),
+            '|' => return Err(("expected '||'".to_string(), start)),
+            '!' if followed_by('=') => Token::Op(Op::Ne),
+            '!' => Token::Not,
+            '=' => Token::Op(Op::Eq),
+            '<' if followed_by('=') => Token::Op(Op::Le),
+            '<' => Token::Op(Op::Lt),
+            '>' if followed_by('=') => Token::Op(Op::Ge),
+            '>' => Token::Op(Op::Gt),
+            '"' => {
+                let mut word = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, '"')) => break,
+                        Some((_, '\\')) if chars.peek().is_some() => {
+                            word.extend(chars.next().map(|(_, c)| c));
+                        }
+                        Some((_, c)) => word.push(c),
+                        None => {
+                            let message = "unterminated quote".to_string();
+                            return Err((message, start));
+                        }
+                    }
+                }
+                Token::Word(word)
+            }
+            c => {
+                let mut word = c.to_string();
+                while let Some((_, c)) = chars.next_if(|&(_, c)| !is_special(c))
+                {
+                    word.push(c);
+                }
+                Token::Word(word)
+            }
+        };
+        tokens.push((token, start));
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    position: usize,
+    // Where errors about a missing token point
+    end: usize,
+}
+
+impl Parser {
+    fn parse(&mut self) -> Result<Expr, Failure> {
+        if self.tokens.is_empty() {
+            return Err(("empty filter".to_string(), 0));
+        }
+        let expr = self.or()?;
+        match self.next() {
+            None => Ok(expr),
+            Some((Token::Close, offset)) => {
+                Err(("')' without a matching '('".to_string(), offset))
+            }
+            Some((token, offset)) => Err((
+                format!("expected '&&', '||' or the end, found {token}"),
+                offset,
+            )),
+        }
+    }
+
+    fn next(&mut self) -> Option<(Token, usize)> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn next_if(&mut self, token: &Token) -> bool {
+        let found = self.tokens.get(self.position).map(|(t, _)| t);
+        if found == Some(token) {
+            self.position += 1;
+        }
+        found == Some(token)
+    }
+
+    fn or(&mut self) -> Result<Expr, Failure> {
+        let mut expr = self.and()?;
+        while self.next_if(&Token::Or) {
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr, Failure> {
+        let mut expr = self.not()?;
+        while self.next_if(&Token::And) {
+            expr = Expr::And(Box::new(expr), Box::new(self.not()?));
+        }
+        Ok(expr)
+    }
+
+    fn not(&mut self) -> Result<Expr, Failure> {
+        match self.next() {
+            Some((Token::Not, _)) => Ok(Expr::Not(Box::new(self.not()?))),
+            Some((Token::Open, offset)) => {
+                let expr = self.or()?;
+                match self.next_if(&Token::Close) {
+                    true => Ok(expr),
+                    false => Err(("unclosed '('".to_string(), offset)),
+                }
+            }
+            Some((Token::Word(field), offset)) => self.term(&field, offset),
+            Some((token, offset)) => Err((
+                format!(
+                    "expected a field such as sender or year, found {token}"
+                ),
+                offset,
+            )),
+            None => Err((
+                "expected a field such as sender or year".to_string(),
+                self.end,
+            )),
+        }
+    }
+
+    fn term(&mut self, field: &str, offset: usize) -> Result<Expr, Failure> {
+        let op = match self.tokens.get(self.position) {
+            Some(&(Token::Op(op), _)) => {
+                self.position += 1;
+                op
+            }
+            _ => {
+                return match field {
+                    "unread" => Ok(Expr::Unread),
+                    "read" => Ok(Expr::Not(Box::new(Expr::Unread))),
+                    "payable" => Ok(Expr::Payable),
+                    _ if FIELDS.contains(&field) => Err((
+                        format!("expected an operator after '{field}'"),
+                        offset + field.len(),
+                    )),
+                    _ => Err((unknown(field), offset)),
+                };
+            }
+        };
+        let text = |expr: Expr| match op {
+            Op::Eq => Ok(expr),
+            Op::Ne => Ok(Expr::Not(Box::new(expr))),
+            _ => Err((format!("'{field}' only takes = and !="), offset)),
+        };
+        if matches!(field, "unread" | "read" | "payable") {
+            return Err((format!("'{field}' takes no value"), offset));
+        }
+        if !FIELDS.contains(&field) {
+            return Err((unknown(field), offset));
+        }
+        let (value, value_offset) = self.value(field)?;
+        match field {
+            "sender" => text(Expr::Sender(value)),
+            "subject" => text(Expr::Subject(value)),
+            "content" => text(Expr::Content(value.to_lowercase())),
+            "label" => text(Expr::Label(value)),
+            "folder" => match parse_folder(&value) {
+                Some(folder) => text(Expr::Folder(folder)),
+                None => Err((unknown_folder(&value), value_offset)),
+            },
+            "year" => compare(op, &value, value_offset, |year| {
+                let year = year.parse().ok()?;
+                Some(move |op| Expr::Year(op, year))
+            }),
+            _ => compare(op, &value, value_offset, |date| {
+                let date = NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()?;
+                Some(move |op| Expr::Date(op, date))
+            }),
+        }
+    }
+
+    fn value(&mut self, field: &str) -> Result<(String, usize), Failure> {
+        match self.next() {
+            Some((Token::Word(value), offset)) => Ok((value, offset)),
+            Some((token, offset)) => Err((
+                format!("expected a value for '{field}', found {token}"),
+                offset,
+            )),
+            None => Err((format!("expected a value for '{field}'"), self.end)),
+        }
+    }
+}
+
+/// A comparison with a year or date, or with = and != a range of them
+fn compare<F: Fn(Op) -> Expr>(
+    op: Op,
+    value: &str,
+    offset: usize,
+    parse: impl Fn(&str) -> Option<F>,
+) -> Result<Expr, Failure> {
+    let bound = |text: &str| {
+        let message = match text.contains('-') {
+            true => format!("invalid date '{text}', expected YYYY-MM-DD"),
+            false => format!("invalid year '{text}'"),
+        };
+        parse(text).ok_or((message, offset))
+    };
+    let Some((from, to)) = value.split_once("..") else {
+        return Ok(bound(value)?(op));
+    };
+    let mut range = Vec::new();
+    if !from.is_empty() {
+        range.push(bound(from)?(Op::Ge));
+    }
+    if !to.is_empty() {
+        range.push(bound(to)?(Op::Le));
+    }
+    let range = range
+        .into_iter()
+        .reduce(|a, b| Expr::And(Box::new(a), Box::new(b)))
+        .ok_or(("a range needs at least one end".to_string(), offset))?;
+    match op {
+        Op::Eq => Ok(range),
+        Op::Ne => Ok(Expr::Not(Box::new(range))),
+        _ => Err((format!("a range only takes = and !=, not {op}"), offset)),
+    }
+}
+
+/// A value as the filter reads it, quoted if it has to be
+pub fn quote(value: &str) -> String {
+    let is_special = |c: char| "()!=<>&|\"\\".contains(c) || c.is_whitespace();
+    if !value.is_empty() && !value.contains(is_special) {
+        return value.to_string();
+    }
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+const FIELDS: [&str; 7] =
+    ["sender", "subject", "content", "label", "folder", "year", "date"];
+
+fn unknown(name: &str) -> String {
+    format!(
+        "unknown field '{name}', expected unread, read, payable or one of {}",
+        FIELDS.join(", ")
+    )
+}
+
+fn parse_folder(name: &str) -> Option<Folder> {
+    match name.to_lowercase().as_str() {
+        "inbox" => Some(Folder::Inbox),
+        "archive" | "archived" => Some(Folder::Archive),
+        "trash" | "trashed" => Some(Folder::Trash),
+        _ => None,
+    }
+}
+
+fn unknown_folder(name: &str) -> String {
+    format!("unknown folder '{name}', expected inbox, archive or trash")
+}
@@ -0,0 +1,222 @@
+//! The text of attachments, kept in the local index so that the `content`
+//! filter field and `kivinge search --content` can look into PDF, HTML and
+//! plain text attachments without fetching them again
+
+use std::{cmp::Reverse, collections::BTreeMap, panic};
+use tracing::{debug, warn};
+
+use crate::{
+    client::Client,
+    error::Error,
+    filename, index,
+    model::content::{Folder, InboxEntry, InboxItem, InboxListing},
+    util::get_attachment_body,
+};
+
+/// Items indexed per sync, so that the first sync does not wait for the
+/// whole inbox to be fetched
+pub const SYNC_BATCH: usize = 5;
+
+/// The text of an attachment, for the kinds that have any, with runs of
+/// whitespace made single spaces. The kind is taken from the content type,
+/// or from the contents when the content type does not tell.
+pub fn extract(content_type: &str, contents: &[u8]) -> Option<String> {
+    let kind = filename::extension(content_type)
+        .or_else(|| filename::sniff_extension(contents));
+    let text = match kind? {
+        "pdf" => pdf_text(contents)?,
+        "html" => html_text(&String::from_utf8_lossy(contents)),
+        "txt" => String::from_utf8_lossy(contents).to_string(),
+        _ => return None,
+    };
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    (!text.is_empty()).then_some(text)
+}
+
+/// Whether an attachment of the content type may have text to extract, so
+/// that images and the like are not fetched for nothing
+fn may_have_text(content_type: &str) -> bool {
+    match filename::extension(content_type) {
+        Some(kind) => ["pdf", "html", "txt"].contains(&kind),
+        // Only the contents can tell
+        None => true,
+    }
+}
+
+fn pdf_text(contents: &[u8]) -> Option<String> {
+    // The parser panics on some malformed files
+    match panic::catch_unwind(|| pdf_extract::extract_text_from_mem(contents)) {
+        Ok(Ok(text)) => Some(text),
+        Ok(Err(error)) => {
+            debug!("failed to extract text from a PDF: {error}");
+            None
+        }
+        Err(_) => {
+            debug!("failed to extract text from a malformed PDF");
+            None
+        }
+    }
+}
+
+/// The text between the tags, leaving out scripts and styles
+fn html_text(html: &str) -> String {
+    // Byte offsets in the ASCII lower case copy are the same as in the HTML
+    let lower = html.to_ascii_lowercase();
+    let mut text = String::new();
+    let mut position = 0;
+    while let Some(start) = lower[position..].find('<') {
+        let start = position + start;
+        text.push_str(&decode_entities(&html[position..start]));
+        let tag = &lower[start..];
+        let skipped = ["script", "style"]
+            .into_iter()
+            .find(|name| tag[1..].starts_with(name))
+            .and_then(|name| tag.find(&format!("</{name}")))
+            .unwrap_or(0);
+        position = match tag[skipped..].find('>') {
+            Some(end) => start + skipped + end + 1,
+            None => html.len(),
+        };
+        text.push(' ');
+    }
+    text.push_str(&decode_entities(&html[position..]));
+    text
+}
+
+/// Replaces character references, and the named ones likely in Swedish text
+fn decode_entities(text: &str) -> String {
+    let mut decoded = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let entity = rest
+            .find(';')
+            .filter(|&end| end <= 10)
+            .and_then(|end| Some((decode_entity(&rest[1..end])?, end)));
+        match entity {
+            Some((c, end)) => {
+                decoded.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+fn decode_entity(name: &str) -> Option<char> {
+    if let Some(number) = name.strip_prefix('#') {
+        let code = match number.strip_prefix(['x', 'X']) {
+            Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+            None => number.parse().ok()?,
+        };
+        return char::from_u32(code);
+    }
+    Some(match name {
+        "amp" => '&',
+        "lt" => '<',
+        "gt" => '>',
+        "quot" => '"',
+        "apos" => '\'',
+        "nbsp" => ' ',
+        "aring" => 'å',
+        "auml" => 'ä',
+        "ouml" => 'ö',
+        "Aring" => 'Å',
+        "Auml" => 'Ä',
+        "Ouml" => 'Ö',
+        "eacute" => 'é',
+        _ => return None,
+    })
+}
+
+/// Extracts the text of the attachments of up to `limit` items that the
+/// index has none for, newest first, leaving out the trash. Returns how many
+/// items were indexed.
+pub fn index_new(
+    client: &mut impl Client,
+    listing: &InboxListing,
+    limit: usize,
+) -> Result<usize, Error> {
+    let profile = client.profile().to_string();
+    let index = index::load(&profile)?;
+    let mut new: Vec<&InboxEntry> = listing
+        .iter()
+        .filter(|entry| entry.item.folder() != Folder::Trash)
+        .filter(|entry| !index.texts.contains_key(&entry.item.key))
+        .collect();
+    new.sort_by_key(|entry| Reverse(entry.item.created_at));
+
+    // Recorded all at once, so that the index is not held up while the
+    // attachments are downloaded
+    let mut indexed = Vec::new();
+    for entry in new.into_iter().take(limit) {
+        match item_texts(client, &entry.item) {
+            Ok(texts) => indexed.push((&entry.item.key, texts)),
+            Err(error) => warn!(
+                "failed to index the contents of item {}: {error}",
+                entry.id
+            ),
+        }
+    }
+    let count = indexed.len();
+    if count > 0 {
+        debug!("indexed the contents of {count} item(s)");
+        index::update(&profile, |index| {
+            for (key, texts) in indexed {
+                index.record_texts(key, texts);
+            }
+            ((), true)
+        })?;
+    }
+    Ok(count)
+}
+
+fn item_texts(
+    client: &mut impl Client,
+    item: &InboxItem,
+) -> Result<BTreeMap<u32, String>, Error> {
+    let details = client.get_item_details(&item.key)?;
+    let mut texts = BTreeMap::new();
+    for (num, part) in details.parts.iter().enumerate() {
+        if !may_have_text(&part.content_type) {
+            continue;
+        }
+        let num = num as u32;
+        let contents = get_attachment_body(client, item, &details, num)?;
+        if let Some(text) = extract(&part.content_type, &contents) {
+            texts.insert(num, text);
+        }
+    }
+    Ok(texts)
+}
+
+/// Some of the text around the first place `query` is found in, ignoring
+/// case, with an ellipsis where it is cut
+pub fn snippet(text: &str, query: &str, width: usize) -> Option<String> {
+    let chars: Vec<char> = text.chars().collect();
+    let lower = |c: &char| c.to_lowercase().next().unwrap_or(*c);
+    let query: Vec<char> = query.chars().map(|c| lower(&c)).collect();
+    let found = (0..chars.len()).find(|&start| {
+        chars.len() - start >= query.len()
+            && chars[start..start + query.len()]
+                .iter()
+                .map(lower)
+                .eq(query.iter().copied())
+    })?;
+    let start = found.saturating_sub(width.saturating_sub(query.len()) / 2);
+    let end = (start + width).min(chars.len());
+    let mut snippet: String = chars[start..end].iter().collect();
+    if start > 0 {
+        snippet.insert(0, '…');
+    }
+    if end < chars.len() {
+        snippet.push('…');
+    }
+    Some(snippet)
+}
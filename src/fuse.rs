@@ -1,27 +1,50 @@
 use std::{
     cmp::min,
-    collections::HashMap,
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
     ffi::OsStr,
     fmt::{Display, Formatter},
     ops::{Shl, Shr},
     path::Path,
     process,
-    time::{Duration, UNIX_EPOCH},
+    sync::{mpsc, Arc, Mutex},
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 
 use bytes::Bytes;
-use cached::{Cached, SizedCache, TimedCache, TimedSizedCache};
+use chrono::{DateTime, NaiveDate, NaiveTime, TimeDelta, Utc};
 use fuser::{
-    mount2, FileAttr, FileType, Filesystem, MountOption, ReplyData,
-    ReplyDirectory, Request,
+    spawn_mount2, BackgroundSession, FileAttr, FileType, Filesystem,
+    MountOption, Notifier, ReplyData, ReplyDirectory, ReplyEmpty, ReplyOpen,
+    ReplyStatfs, ReplyXattr, Request,
 };
-use libc::{EFAULT, EINVAL, EIO, EISDIR, ENOENT, ENOTDIR};
+use libc::{
+    EFAULT, EINVAL, EIO, EISDIR, ENOENT, ENOTDIR, ENOTSUP, EPERM, ERANGE,
+    EROFS, O_ACCMODE, O_RDONLY,
+};
+// What getxattr answers for a missing attribute
+#[cfg(target_os = "macos")]
+use libc::ENOATTR;
+#[cfg(target_os = "linux")]
+use libc::ENODATA as ENOATTR;
 use thiserror::Error;
-use tracing::{debug, error, warn};
+use tracing::{debug, error, info, warn};
 
 use crate::{
-    client::Client,
-    model::content::{Attachment, InboxEntry, ItemDetails},
+    cache::{
+        Cache, ATTACHMENT_POLICY, DETAILS_POLICY, INBOX_POLICY,
+        RECEIPTS_POLICY, RECEIPT_POLICY,
+    },
+    cli::receipts::format_details,
+    client::{self, ContentClient},
+    filename,
+    model::{
+        content::{
+            Attachment, Folder, InboxEntry, InboxListing, ItemDetails, Status,
+        },
+        receipt::ReceiptEntry,
+    },
+    util::parse_age,
 };
 
 #[derive(Debug, Error)]
@@ -43,6 +66,24 @@ pub enum Error {
 
     #[error("inode is not directory")]
     IsNotDir,
+
+    #[error("no such extended attribute")]
+    NoAttribute,
+
+    #[error("buffer too small")]
+    TooSmall,
+
+    #[error("not permitted")]
+    NotPermitted,
+
+    #[error("read-only filesystem")]
+    ReadOnly,
+
+    #[error("request failed: {0}")]
+    RequestFailed(String),
+
+    #[error("not supported: {0}")]
+    NotSupported(String),
 }
 
 impl Error {
@@ -77,49 +118,548 @@ impl Error {
                 debug!("{}", self);
                 ENOTDIR
             }
+
+            Error::NoAttribute => {
+                debug!("{}", self);
+                ENOATTR
+            }
+
+            Error::TooSmall => {
+                debug!("{}", self);
+                ERANGE
+            }
+
+            Error::NotPermitted => {
+                debug!("{}", self);
+                EPERM
+            }
+
+            Error::ReadOnly => {
+                debug!("{}", self);
+                EROFS
+            }
+
+            // Unlike failed reads, which stop the filesystem, a failed change
+            // is only reported to whoever made it
+            Error::RequestFailed(_) => {
+                error!("{}", self);
+                EIO
+            }
+
+            Error::NotSupported(_) => {
+                warn!("{}", self);
+                ENOTSUP
+            }
         }
     }
 }
 
-const INBOX_TTL: Duration = Duration::from_secs(60);
-const DETAILS_TTL: Duration = Duration::from_mins(60);
 const FILESYSTEM_TTL: Duration = Duration::from_secs(60);
+const BLOCK_SIZE: u32 = 512;
+// The listing is fetched again as often as it goes stale in the cache
+const REFRESH_INTERVAL: Duration = match INBOX_POLICY.ttl {
+    Some(ttl) => ttl,
+    None => FILESYSTEM_TTL,
+};
+
+// Signals that make the daemon unmount and exit
+const STOP_SIGNALS: [i32; 3] = [libc::SIGTERM, libc::SIGINT, libc::SIGHUP];
+
+const RECEIPTS_DIR_NAME: &str = "receipts";
+const METADATA_FILE_NAME: &str = "metadata.json";
+// The metadata file takes the last attachment id of its item
+const METADATA_ID: u32 = u32::MAX - 1;
+// Removing this file of an item moves the item to the trash
+const TRASH_FILE_NAME: &str = ".trash";
+const TRASH_ID: u32 = u32::MAX - 2;
+// Receipt and group inodes live outside the range used by inbox entries
+const RECEIPT_INODE_FLAG: u64 = 1 << 63;
+const GROUP_INODE_FLAG: u64 = 1 << 62;
+
+/// How items are laid out under the mountpoint
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Layout {
+    /// A directory per item
+    #[default]
+    Item,
+    /// A directory per sender, with a directory per item in it
+    Sender,
+    /// A directory per month, with a directory per item in it
+    Date,
+    /// Every attachment right under the mountpoint
+    Flat,
+}
 
-pub fn mount(client: impl Client, mountpoint: &Path) -> Result<(), Error> {
+/// What `-o` asks for
+#[derive(Clone, Debug, Default)]
+pub struct MountOptions {
+    /// Hide items from before this date, from `since=2024-01-01`
+    pub since: Option<DateTime<Utc>>,
+    /// Hide items older than this when the listing is fetched, from `last=90d`
+    pub last: Option<TimeDelta>,
+    /// Owner of every file, from `uid=` and `gid=`. The mounting user by
+    /// default.
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+    /// How long the kernel may keep names and attributes, from `ttl=` in
+    /// seconds
+    pub ttl: Option<Duration>,
+    pub layout: Layout,
+    /// Fetch the details and attachments of this many of the newest items
+    /// before mounting, from `prefetch=`
+    pub prefetch: usize,
+    /// How often the listing is fetched again, so that new items show up,
+    /// from `refresh=` in seconds. 0 turns it off.
+    pub refresh: Option<Duration>,
+    /// Let removing an item directory, or its `.trash` file, move the item
+    /// to the trash, from `allow_delete`
+    pub allow_delete: bool,
+    /// Mark an item as read when one of its attachments is opened, from
+    /// `mark_read_on_open`
+    pub mark_read_on_open: bool,
+    /// Standard options, such as `allow_other`, handed to FUSE as they are.
+    /// `allow_other` and `allow_root` need `user_allow_other` in
+    /// /etc/fuse.conf.
+    pub fuse: Vec<MountOption>,
+}
+
+impl MountOptions {
+    fn shows(&self, created_at: DateTime<Utc>) -> bool {
+        let since = self.since.is_none_or(|since| created_at >= since);
+        let last = self.last.is_none_or(|last| created_at >= Utc::now() - last);
+        since && last
+    }
+}
+
+/// Parses comma separated options, e.g. `since=2024-01-01,last=90d`
+pub fn parse_mount_options(opts: &str) -> Result<MountOptions, String> {
+    let mut options = MountOptions::default();
+    let number = |value: &str| {
+        value.parse::<u32>().map_err(|_| format!("invalid number '{value}'"))
+    };
+    for opt in opts.split(',').filter(|opt| !opt.is_empty()) {
+        match opt.split_once('=') {
+            // Read-only unless allow_delete asks for removing items
+            None if opt == "ro" => {}
+            None if opt == "rw" => {
+                let error = "the filesystem is read-only, see allow_delete";
+                return Err(error.to_string());
+            }
+            None if opt == "allow_other" => {
+                options.fuse.push(MountOption::AllowOther)
+            }
+            None if opt == "allow_root" => {
+                options.fuse.push(MountOption::AllowRoot)
+            }
+            None if opt == "allow_delete" => options.allow_delete = true,
+            None if opt == "mark_read_on_open" => {
+                options.mark_read_on_open = true
+            }
+            None if opt == "auto_unmount" => {
+                options.fuse.push(MountOption::AutoUnmount)
+            }
+            Some(("fsname", name)) => {
+                options.fuse.push(MountOption::FSName(name.to_string()))
+            }
+            // The name Finder shows for the mount
+            #[cfg(target_os = "macos")]
+            Some(("volname", _)) => {
+                options.fuse.push(MountOption::CUSTOM(opt.to_string()))
+            }
+            Some(("since", date)) => {
+                let date = NaiveDate::parse_from_str(date, "%Y-%m-%d")
+                    .map_err(|_| format!("invalid date '{date}'"))?;
+                options.since = Some(date.and_time(NaiveTime::MIN).and_utc());
+            }
+            Some(("last", age)) => options.last = Some(parse_age(age)?),
+            Some(("uid", uid)) => options.uid = Some(number(uid)?),
+            Some(("gid", gid)) => options.gid = Some(number(gid)?),
+            Some(("ttl", seconds)) => {
+                let seconds = number(seconds)?.into();
+                options.ttl = Some(Duration::from_secs(seconds));
+            }
+            Some(("refresh", seconds)) => {
+                let seconds = number(seconds)?.into();
+                options.refresh = Some(Duration::from_secs(seconds));
+            }
+            Some(("layout", layout)) => {
+                options.layout = match layout {
+                    "item" => Layout::Item,
+                    "sender" => Layout::Sender,
+                    "date" => Layout::Date,
+                    "flat" => Layout::Flat,
+                    _ => return Err(format!("unknown layout '{layout}'")),
+                }
+            }
+            Some(("prefetch", count)) => {
+                options.prefetch = number(count)? as usize
+            }
+            _ => return Err(format!("unknown mount option '{opt}'")),
+        }
+    }
+    Ok(options)
+}
+
+/// Serves the inbox at `mountpoint` until it is unmounted, or the process is
+/// asked to stop
+pub fn mount(
+    client: impl ContentClient + 'static,
+    mountpoint: &Path,
+    options: MountOptions,
+) -> Result<(), Error> {
+    // SAFETY: getuid and getgid always succeed and touch no memory
+    let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+    let mut mount_options = vec![MountOption::DefaultPermissions];
+    if !options.allow_delete {
+        mount_options.push(MountOption::RO);
+    }
+    let named = |opt: &MountOption| matches!(opt, MountOption::FSName(_));
+    if !options.fuse.iter().any(named) {
+        mount_options.push(MountOption::FSName("kivinge".to_string()));
+    }
+    #[cfg(target_os = "linux")]
+    mount_options.push(MountOption::NoAtime);
+    // Keep Finder from storing ._ files and its own attributes, which the
+    // filesystem has no room for, and name the volume it shows
+    #[cfg(target_os = "macos")]
+    {
+        let custom = |opt: &str| MountOption::CUSTOM(opt.to_string());
+        mount_options.push(custom("noappledouble"));
+        mount_options.push(custom("noapplexattr"));
+        let volname = |opt: &MountOption| match opt {
+            MountOption::CUSTOM(opt) => opt.starts_with("volname="),
+            _ => false,
+        };
+        if !options.fuse.iter().any(volname) {
+            mount_options.push(custom("volname=Kivra"));
+        }
+    }
+    mount_options.extend(options.fuse.iter().cloned());
     let mut filesystem = KivraFS {
         client,
-        inbox_cache: TimedSizedCache::with_size_and_lifespan(1, INBOX_TTL),
-        details_cache: TimedCache::with_lifespan(DETAILS_TTL),
-        attachment_cache: SizedCache::with_size(10),
+        uid: options.uid.unwrap_or(uid),
+        gid: options.gid.unwrap_or(gid),
+        ttl: options.ttl.unwrap_or(FILESYSTEM_TTL),
+        options,
+        inbox_cache: Cache::new("inbox", INBOX_POLICY),
+        details_cache: Cache::new("details", DETAILS_POLICY),
+        attachment_cache: Cache::new("attachments", ATTACHMENT_POLICY),
+        receipts_cache: Cache::new("receipts", RECEIPTS_POLICY),
+        receipt_cache: Cache::new("receipt", RECEIPT_POLICY),
+        handles: HashMap::new(),
+        next_handle: 1,
     };
     _ = filesystem.inbox_index()?; // Trigger inbox listing and auth if needed
-    let mount_options = [
-        MountOption::FSName("kivinge".to_string()),
-        MountOption::DefaultPermissions,
-        MountOption::RO,
-        MountOption::NoAtime,
-    ];
-    mount2(filesystem, mountpoint, &mount_options)?;
+    filesystem.prefetch();
+    let refresh = filesystem.options.refresh.unwrap_or(REFRESH_INTERVAL);
+    let filesystem = Arc::new(Mutex::new(filesystem));
+    let signals = block_stop_signals();
+    let session =
+        spawn_mount2(SharedFS(filesystem.clone()), mountpoint, &mount_options)?;
+    serve(signals, &session, &filesystem, refresh);
+    session.join();
     Ok(())
 }
 
+/// Blocks the signals that unmount the filesystem in this thread and those
+/// it starts, so that `wait_for_stop` takes them instead of them ending the
+/// process with the filesystem still mounted
+fn block_stop_signals() -> libc::sigset_t {
+    // SAFETY: the set is initialised by sigemptyset before it is used
+    unsafe {
+        let mut signals: libc::sigset_t = std::mem::zeroed();
+        libc::sigemptyset(&mut signals);
+        for signal in STOP_SIGNALS {
+            libc::sigaddset(&mut signals, signal);
+        }
+        libc::pthread_sigmask(libc::SIG_BLOCK, &signals, std::ptr::null_mut());
+        signals
+    }
+}
+
+/// Refreshes the listing every `refresh` until one of the signals arrives,
+/// or the filesystem is unmounted some other way, e.g. by `umount`
+fn serve<C: ContentClient>(
+    signals: libc::sigset_t,
+    session: &BackgroundSession,
+    filesystem: &Mutex<KivraFS<C>>,
+    refresh: Duration,
+) {
+    let lock = || filesystem.lock().expect("filesystem lock is not poisoned");
+    let notifier = session.notifier();
+    let mut snapshot = lock().snapshot().unwrap_or_default();
+    let mut refreshed = Instant::now();
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let mut signal = 0;
+        // SAFETY: both pointers are to locals that outlive the call
+        unsafe { libc::sigwait(&signals, &mut signal) };
+        _ = sender.send(signal);
+    });
+    loop {
+        match receiver.recv_timeout(Duration::from_secs(1)) {
+            Ok(signal) => {
+                info!("unmounting on signal {signal}");
+                return;
+            }
+            Err(_) if session.guard.is_finished() => return,
+            Err(_) => {}
+        }
+        if refresh.is_zero() || refreshed.elapsed() < refresh {
+            continue;
+        }
+        refreshed = Instant::now();
+        let fresh = lock().refresh();
+        match fresh {
+            // Sent without the lock, since the kernel may wait for
+            // requests that need it before it takes the notifications
+            Ok(fresh) => {
+                invalidate(&notifier, snapshot.stale(&fresh));
+                snapshot = fresh;
+            }
+            Err(error) => warn!("failed to refresh the listing: {error}"),
+        }
+    }
+}
+
+/// Makes the kernel forget what it has cached of the stale entries
+fn invalidate(notifier: &Notifier, stale: Vec<Stale>) {
+    for stale in stale {
+        let result = match &stale {
+            Stale::Entry(parent, name) => {
+                notifier.inval_entry(*parent, OsStr::new(name))
+            }
+            Stale::Inode(inode) => notifier.inval_inode(*inode, 0, 0),
+        };
+        // The kernel tells when it has nothing cached to forget
+        if let Err(error) = result {
+            debug!("failed to invalidate {stale:?}: {error}");
+        }
+    }
+}
+
+/// What the kernel may have cached of the listing
+#[derive(Default)]
+struct Snapshot {
+    // Parent, name and inode of every item directory, group and listed file
+    entries: HashSet<(u64, String, u64)>,
+    // The metadata of each item, which changes with its status and labels
+    items: HashMap<u32, Vec<u8>>,
+}
+
+#[derive(Debug)]
+enum Stale {
+    Entry(u64, String),
+    Inode(u64),
+}
+
+impl Snapshot {
+    /// What changed between this snapshot and a fresher one
+    fn stale(&self, fresh: &Snapshot) -> Vec<Stale> {
+        let mut stale: Vec<Stale> = self
+            .entries
+            .difference(&fresh.entries)
+            .map(|(parent, name, _)| Stale::Entry(*parent, name.clone()))
+            .collect();
+        if self.entries != fresh.entries {
+            let parents: BTreeSet<u64> = self
+                .entries
+                .iter()
+                .chain(&fresh.entries)
+                .map(|(parent, _, _)| *parent)
+                .collect();
+            stale.extend(parents.into_iter().map(Stale::Inode));
+        }
+        for (&entry_id, metadata) in &fresh.items {
+            if self.items.get(&entry_id).is_some_and(|old| old != metadata) {
+                let directory = Inode::InboxEntry { entry_id };
+                let metadata = Inode::Metadata { entry_id, size: 0 };
+                stale.push(Stale::Inode(directory.to_u64()));
+                stale.push(Stale::Inode(metadata.to_u64()));
+            }
+        }
+        stale
+    }
+}
+
+/// Lets the refresher in `serve` share the filesystem with the session
+struct SharedFS<C: ContentClient>(Arc<Mutex<KivraFS<C>>>);
+
+impl<C: ContentClient> SharedFS<C> {
+    fn lock(&self) -> std::sync::MutexGuard<'_, KivraFS<C>> {
+        self.0.lock().expect("filesystem lock is not poisoned")
+    }
+}
+
+impl<C: ContentClient> Filesystem for SharedFS<C> {
+    fn lookup(
+        &mut self,
+        req: &Request<'_>,
+        parent: u64,
+        name: &OsStr,
+        reply: fuser::ReplyEntry,
+    ) {
+        self.lock().lookup(req, parent, name, reply)
+    }
+
+    fn getattr(
+        &mut self,
+        req: &Request<'_>,
+        ino: u64,
+        reply: fuser::ReplyAttr,
+    ) {
+        self.lock().getattr(req, ino, reply)
+    }
+
+    fn open(
+        &mut self,
+        req: &Request<'_>,
+        ino: u64,
+        flags: i32,
+        reply: ReplyOpen,
+    ) {
+        self.lock().open(req, ino, flags, reply)
+    }
+
+    fn release(
+        &mut self,
+        req: &Request<'_>,
+        ino: u64,
+        fh: u64,
+        flags: i32,
+        lock_owner: Option<u64>,
+        flush: bool,
+        reply: ReplyEmpty,
+    ) {
+        self.lock().release(req, ino, fh, flags, lock_owner, flush, reply)
+    }
+
+    fn opendir(
+        &mut self,
+        req: &Request<'_>,
+        ino: u64,
+        flags: i32,
+        reply: ReplyOpen,
+    ) {
+        self.lock().opendir(req, ino, flags, reply)
+    }
+
+    fn releasedir(
+        &mut self,
+        req: &Request<'_>,
+        ino: u64,
+        fh: u64,
+        flags: i32,
+        reply: ReplyEmpty,
+    ) {
+        self.lock().releasedir(req, ino, fh, flags, reply)
+    }
+
+    fn statfs(&mut self, req: &Request<'_>, ino: u64, reply: ReplyStatfs) {
+        self.lock().statfs(req, ino, reply)
+    }
+
+    fn read(
+        &mut self,
+        req: &Request,
+        ino: u64,
+        fh: u64,
+        offset: i64,
+        size: u32,
+        flags: i32,
+        lock: Option<u64>,
+        reply: ReplyData,
+    ) {
+        self.lock().read(req, ino, fh, offset, size, flags, lock, reply)
+    }
+
+    fn unlink(
+        &mut self,
+        req: &Request<'_>,
+        parent: u64,
+        name: &OsStr,
+        reply: ReplyEmpty,
+    ) {
+        self.lock().unlink(req, parent, name, reply)
+    }
+
+    fn rmdir(
+        &mut self,
+        req: &Request<'_>,
+        parent: u64,
+        name: &OsStr,
+        reply: ReplyEmpty,
+    ) {
+        self.lock().rmdir(req, parent, name, reply)
+    }
+
+    fn getxattr(
+        &mut self,
+        req: &Request<'_>,
+        ino: u64,
+        name: &OsStr,
+        size: u32,
+        reply: ReplyXattr,
+    ) {
+        self.lock().getxattr(req, ino, name, size, reply)
+    }
+
+    fn listxattr(
+        &mut self,
+        req: &Request<'_>,
+        ino: u64,
+        size: u32,
+        reply: ReplyXattr,
+    ) {
+        self.lock().listxattr(req, ino, size, reply)
+    }
+
+    fn readdir(
+        &mut self,
+        req: &Request<'_>,
+        ino: u64,
+        fh: u64,
+        offset: i64,
+        reply: ReplyDirectory,
+    ) {
+        self.lock().readdir(req, ino, fh, offset, reply)
+    }
+}
+
 #[derive(Clone, Debug)]
 enum Inode {
     Root,
+    Receipts,
+    // A sender or month directory of the layout
+    Group { group_id: u32 },
     InboxEntry { entry_id: u32 },
     Attachment { entry_id: u32, attachment_id: u32, size: u64 },
+    Metadata { entry_id: u32, size: u64 },
+    Trash { entry_id: u32 },
+    Receipt { receipt_id: u32, size: u64 },
 }
 
 impl Inode {
     fn to_u64(&self) -> u64 {
         match self {
             Inode::Root => 1,
+            Inode::Receipts => 2,
+            Inode::Group { group_id } => GROUP_INODE_FLAG | *group_id as u64,
             Inode::InboxEntry { entry_id, .. } => {
                 (*entry_id as u64 + 1).shl(32)
             }
             Inode::Attachment { entry_id, attachment_id, .. } => {
                 (*entry_id as u64 + 1).shl(32) + (*attachment_id as u64 + 1)
             }
+            Inode::Metadata { entry_id, .. } => {
+                (*entry_id as u64 + 1).shl(32) + (METADATA_ID as u64 + 1)
+            }
+            Inode::Trash { entry_id } => {
+                (*entry_id as u64 + 1).shl(32) + (TRASH_ID as u64 + 1)
+            }
+            Inode::Receipt { receipt_id, .. } => {
+                RECEIPT_INODE_FLAG | *receipt_id as u64
+            }
         }
     }
 
@@ -137,28 +677,39 @@ impl Inode {
         }
     }
 
-    fn attr(&self) -> FileAttr {
-        let (kind, perm, size, nlink) = match self {
-            Inode::Root => (FileType::Directory, 0o500, 0u64, 2),
-            Inode::InboxEntry { .. } => (FileType::Directory, 0o500, 0u64, 2),
-            Inode::Attachment { size, .. } => {
-                (FileType::RegularFile, 0o400, *size, 1)
-            }
+    fn kind(&self) -> FileType {
+        match self {
+            Inode::Root
+            | Inode::Receipts
+            | Inode::Group { .. }
+            | Inode::InboxEntry { .. } => FileType::Directory,
+            _ => FileType::RegularFile,
+        }
+    }
+
+    /// Attributes of the inode, with every time set to `time`
+    fn attr(&self, time: SystemTime, uid: u32, gid: u32) -> FileAttr {
+        let (perm, size, nlink) = match self {
+            Inode::Attachment { size, .. }
+            | Inode::Metadata { size, .. }
+            | Inode::Receipt { size, .. } => (0o400, *size, 1),
+            Inode::Trash { .. } => (0o400, 0, 1),
+            _ => (0o500, 0u64, 2),
         };
-        let blksize = 512u32;
+        let blksize = BLOCK_SIZE;
         FileAttr {
             ino: self.to_u64(),
             size,
             blocks: size.div_ceil(blksize as u64),
-            atime: UNIX_EPOCH, // 1970-01-01 00:00:00
-            mtime: UNIX_EPOCH,
-            ctime: UNIX_EPOCH,
-            crtime: UNIX_EPOCH,
-            kind,
+            atime: time,
+            mtime: time,
+            ctime: time,
+            crtime: time,
+            kind: self.kind(),
             perm,
             nlink,
-            uid: 1000,
-            gid: 1001,
+            uid,
+            gid,
             rdev: 0,
             flags: 0,
             blksize,
@@ -175,29 +726,58 @@ impl Display for Inode {
 struct InboxIndex {
     pub by_name: HashMap<String, InboxEntry>,
     pub by_id: HashMap<u32, InboxEntry>,
+    // Directories of the layout by name, with the ids of their entries
+    pub groups: Vec<(String, Vec<u32>)>,
+}
+
+struct ReceiptIndex {
+    pub by_name: HashMap<String, ReceiptEntry>,
+    pub by_id: HashMap<u32, ReceiptEntry>,
 }
 
-struct KivraFS<C: Client> {
+struct KivraFS<C: ContentClient> {
     client: C,
-    inbox_cache: TimedSizedCache<(), InboxIndex>,
-    details_cache: TimedCache<u32, ItemDetails>,
-    attachment_cache: SizedCache<(u32, u32), Bytes>,
+    // Owner of every inode
+    uid: u32,
+    gid: u32,
+    ttl: Duration,
+    options: MountOptions,
+    inbox_cache: Cache<(), InboxIndex>,
+    details_cache: Cache<u32, ItemDetails>,
+    attachment_cache: Cache<(u32, u32), Bytes>,
+    receipts_cache: Cache<(), ReceiptIndex>,
+    receipt_cache: Cache<u32, Bytes>,
+    handles: HashMap<u64, Handle>,
+    next_handle: u64,
 }
 
-impl<C: Client> KivraFS<C> {
+/// What an open file or directory refers to
+enum Handle {
+    File(Inode),
+    // The children as the directory was opened, so that reading it in
+    // several calls sees the same listing
+    Directory(Vec<(String, Inode)>),
+}
+
+impl<C: ContentClient> KivraFS<C> {
     fn inbox_index(&mut self) -> Result<&InboxIndex, Error> {
-        let listing = self.inbox_cache.cache_try_get_or_set_with((), || {
+        let listing = self.inbox_cache.try_get_or_insert_with((), || {
             let inbox = self
                 .client
                 .get_inbox_listing()
                 .map_err(|err| Error::InternalError(err.to_string()))?;
+            let inbox = inbox.filtered(|entry| {
+                entry.item.folder() != Folder::Trash
+                    && self.options.shows(entry.item.created_at)
+            });
             let by_name = inbox
                 .iter()
                 .map(|entry| (entry.to_string(), entry.clone()))
                 .collect();
             let by_id =
                 inbox.iter().map(|entry| (entry.id, entry.clone())).collect();
-            Ok::<InboxIndex, Error>(InboxIndex { by_name, by_id })
+            let groups = groups(self.options.layout, &inbox);
+            Ok::<InboxIndex, Error>(InboxIndex { by_name, by_id, groups })
         })?;
         Ok(listing)
     }
@@ -209,7 +789,7 @@ impl<C: Client> KivraFS<C> {
     fn details(&mut self, entry_id: u32) -> Result<&ItemDetails, Error> {
         let item_key = self.inbox_item(entry_id)?.item.key.clone();
         let details =
-            self.details_cache.cache_try_get_or_set_with(entry_id, || {
+            self.details_cache.try_get_or_insert_with(entry_id, || {
                 self.client
                     .get_item_details(&item_key)
                     .map_err(|err| Error::InternalError(err.to_string()))
@@ -217,6 +797,242 @@ impl<C: Client> KivraFS<C> {
         Ok(details)
     }
 
+    /// Fills the caches with the newest items, so that they open at once.
+    /// What fails is left to be fetched when it is read.
+    fn prefetch(&mut self) {
+        let count = self.options.prefetch;
+        let Ok(index) = self.inbox_index() else {
+            return;
+        };
+        let mut entries: Vec<&InboxEntry> = index.by_id.values().collect();
+        entries.sort_by_key(|entry| std::cmp::Reverse(entry.item.created_at));
+        let ids: Vec<u32> =
+            entries.into_iter().take(count).map(|entry| entry.id).collect();
+        for entry_id in ids {
+            let attachments = match self.details(entry_id) {
+                Ok(details) => details.parts.len() as u32,
+                Err(error) => {
+                    warn!("failed to prefetch item {entry_id}: {error}");
+                    continue;
+                }
+            };
+            for attachment_id in 0..attachments {
+                if let Err(error) =
+                    self.attachment_contents(entry_id, attachment_id)
+                {
+                    warn!("failed to prefetch item {entry_id}: {error}");
+                }
+            }
+        }
+    }
+
+    fn attr(&mut self, inode: &Inode) -> FileAttr {
+        let time = self.created_at(inode).map(SystemTime::from);
+        // The epoch, as before, if the listing cannot be had
+        let mut attr =
+            inode.attr(time.unwrap_or(UNIX_EPOCH), self.uid, self.gid);
+        // Removing a file takes write permission on its directory
+        let holds_items = matches!(
+            inode,
+            Inode::Root | Inode::Group { .. } | Inode::InboxEntry { .. }
+        );
+        if self.options.allow_delete && holds_items {
+            attr.perm |= 0o200;
+        }
+        attr
+    }
+
+    /// When what the inode shows was created. Directories take the time of
+    /// their newest item, so that they sort by it.
+    fn created_at(&mut self, inode: &Inode) -> Option<DateTime<Utc>> {
+        match inode {
+            Inode::Root => {
+                let index = self.inbox_index().ok()?;
+                index.by_id.values().map(|entry| entry.item.created_at).max()
+            }
+            Inode::Receipts => {
+                let index = self.receipt_index().ok()?;
+                let receipts = index.by_id.values();
+                receipts.map(|entry| entry.receipt.purchase_date).max()
+            }
+            Inode::Group { group_id } => {
+                let index = self.inbox_index().ok()?;
+                let (_, ids) = index.groups.get(*group_id as usize)?;
+                let entries = ids.iter().filter_map(|id| index.by_id.get(id));
+                entries.map(|entry| entry.item.created_at).max()
+            }
+            Inode::InboxEntry { entry_id }
+            | Inode::Attachment { entry_id, .. }
+            | Inode::Metadata { entry_id, .. }
+            | Inode::Trash { entry_id } => {
+                Some(self.inbox_item(*entry_id).ok()?.item.created_at)
+            }
+            Inode::Receipt { receipt_id, .. } => {
+                let index = self.receipt_index().ok()?;
+                Some(index.by_id.get(receipt_id)?.receipt.purchase_date)
+            }
+        }
+    }
+
+    /// The item as JSON, as `backup` stores it
+    fn metadata(&mut self, entry_id: u32) -> Result<Vec<u8>, Error> {
+        let item = &self.inbox_item(entry_id)?.item;
+        serde_json::to_vec_pretty(item)
+            .map_err(|err| Error::InternalError(err.to_string()))
+    }
+
+    /// Extended attributes of an item directory and the files in it
+    fn xattrs(
+        &mut self,
+        inode_id: u64,
+    ) -> Result<Vec<(String, String)>, Error> {
+        let entry_id = match self.inode(inode_id)? {
+            Inode::InboxEntry { entry_id }
+            | Inode::Attachment { entry_id, .. }
+            | Inode::Metadata { entry_id, .. }
+            | Inode::Trash { entry_id } => entry_id,
+            _ => return Ok(Vec::new()),
+        };
+        let item = &self.inbox_item(entry_id)?.item;
+        let mut attrs = vec![
+            ("key", item.key.clone()),
+            ("sender", item.sender_name.clone()),
+            ("subject", item.subject.clone()),
+        ];
+        if let Some(amount) = item.amount {
+            let currency = item.currency.as_deref().unwrap_or_default();
+            attrs.push((
+                "amount",
+                format!("{amount} {currency}").trim_end().into(),
+            ));
+        }
+        if let Some(date) = &item.due_date {
+            attrs.push(("due_date", date.0.to_string()));
+        }
+        let labels = item.active_labels();
+        if !labels.is_empty() {
+            attrs.push(("labels", labels.join(",")));
+        }
+        Ok(attrs
+            .into_iter()
+            .map(|(name, value)| (format!("user.kivinge.{name}"), value))
+            .collect())
+    }
+
+    /// Moves the item to the trash, after which it is no longer listed
+    fn trash(&mut self, entry_id: u32) -> Result<(), Error> {
+        let item_key = self.inbox_item(entry_id)?.item.key.clone();
+        self.client.move_to_folder(&item_key, Folder::Trash).map_err(
+            |err| match err {
+                client::Error::Unverified(_) => {
+                    Error::NotSupported(err.to_string())
+                }
+                _ => Error::RequestFailed(err.to_string()),
+            },
+        )?;
+        self.inbox_cache.remove(&());
+        Ok(())
+    }
+
+    fn open_handle(&mut self, handle: Handle) -> u64 {
+        let fh = self.next_handle;
+        self.next_handle += 1;
+        self.handles.insert(fh, handle);
+        fh
+    }
+
+    /// Items and the attachments whose sizes are known, and the size of
+    /// those attachments. Details are not fetched for this.
+    fn usage(&mut self) -> (u64, u64) {
+        let Ok(index) = self.inbox_index() else {
+            return (0, 0);
+        };
+        let ids: Vec<u32> = index.by_id.keys().copied().collect();
+        let mut files = ids.len() as u64;
+        let mut bytes = 0;
+        for entry_id in ids {
+            if let Some(details) = self.details_cache.get(&entry_id) {
+                files += details.parts.len() as u64;
+                let sizes = details.parts.iter().map(|part| part.size as u64);
+                bytes += sizes.sum::<u64>();
+            }
+        }
+        (files, bytes)
+    }
+
+    /// Every directory entry of the listing, as `serve` compares them
+    fn snapshot(&mut self) -> Result<Snapshot, Error> {
+        let mut snapshot = Snapshot::default();
+        let mut directories = vec![Inode::Root.to_u64()];
+        while let Some(parent) = directories.pop() {
+            for (name, inode) in self.inode_children(parent)? {
+                match inode {
+                    Inode::Receipts => continue,
+                    Inode::Group { .. } => directories.push(inode.to_u64()),
+                    _ => {}
+                }
+                snapshot.entries.insert((parent, name, inode.to_u64()));
+            }
+        }
+        let ids: Vec<u32> = self.inbox_index()?.by_id.keys().copied().collect();
+        for entry_id in ids {
+            snapshot.items.insert(entry_id, self.metadata(entry_id)?);
+        }
+        Ok(snapshot)
+    }
+
+    /// Fetches the listing again, leaving details and attachments cached
+    fn refresh(&mut self) -> Result<Snapshot, Error> {
+        self.client.invalidate();
+        self.inbox_cache.remove(&());
+        self.snapshot()
+    }
+
+    /// Marks the item as read in Kivra if it is not already. A failure is
+    /// only logged, since the attachment can be read all the same.
+    fn mark_as_read(&mut self, entry_id: u32) {
+        let item = match self.inbox_item(entry_id) {
+            Ok(entry) if entry.item.status == Status::Unread => &entry.item,
+            _ => return,
+        };
+        let item_key = item.key.clone();
+        match self.client.mark_as_read(&item_key) {
+            // Listed again so that the item is not marked twice
+            Ok(()) => self.inbox_cache.remove(&()),
+            Err(error) => warn!("failed to mark item {entry_id} read: {error}"),
+        }
+    }
+
+    /// Removes a file or directory, which only item directories and their
+    /// `.trash` files truly can be. Removing the other files of an item
+    /// succeeds without doing anything, so that `rm -r` and file managers
+    /// get to remove the directory itself.
+    fn remove(
+        &mut self,
+        parent_id: u64,
+        name: &str,
+        directory: bool,
+    ) -> Result<(), Error> {
+        if !self.options.allow_delete {
+            return Err(Error::ReadOnly);
+        }
+        let inode = self.inode_by_name(parent_id, name)?;
+        match (inode, directory) {
+            (Inode::InboxEntry { entry_id }, true)
+            | (Inode::Trash { entry_id }, false) => self.trash(entry_id),
+            (Inode::Attachment { .. } | Inode::Metadata { .. }, false) => {
+                Ok(())
+            }
+            (inode, true) if inode.kind() != FileType::Directory => {
+                Err(Error::IsNotDir)
+            }
+            (inode, false) if inode.kind() == FileType::Directory => {
+                Err(Error::IsDir)
+            }
+            _ => Err(Error::NotPermitted),
+        }
+    }
+
     fn attachment(
         &mut self,
         entry_id: u32,
@@ -235,7 +1051,7 @@ impl<C: Client> KivraFS<C> {
         let attachment = self.attachment(entry_id, attachment_id)?;
         let attachment_key = attachment.key.clone();
         let attachment_body = attachment.body.clone();
-        let bytes = self.attachment_cache.cache_try_get_or_set_with(
+        let bytes = self.attachment_cache.try_get_or_insert_with(
             (entry_id, attachment_id),
             || match (attachment_body, attachment_key) {
                 (Some(inline_body), _) => Ok(inline_body.into_bytes().into()),
@@ -249,10 +1065,77 @@ impl<C: Client> KivraFS<C> {
         Ok(bytes)
     }
 
+    fn receipt_index(&mut self) -> Result<&ReceiptIndex, Error> {
+        let index = self.receipts_cache.try_get_or_insert_with((), || {
+            let receipts =
+                self.client.get_receipts().map_err(|err| match err {
+                    client::Error::Unverified(_) => {
+                        Error::NotSupported(err.to_string())
+                    }
+                    _ => Error::InternalError(err.to_string()),
+                })?;
+            let receipts = receipts.iter().filter(|entry| {
+                self.options.shows(entry.receipt.purchase_date)
+            });
+            let by_name = receipts
+                .clone()
+                .map(|entry| (format!("{entry}.txt"), entry.clone()))
+                .collect();
+            let by_id =
+                receipts.map(|entry| (entry.id, entry.clone())).collect();
+            Ok::<ReceiptIndex, Error>(ReceiptIndex { by_name, by_id })
+        })?;
+        Ok(index)
+    }
+
+    fn receipt_contents(&mut self, receipt_id: u32) -> Result<&Bytes, Error> {
+        let receipt_key = self
+            .receipt_index()?
+            .by_id
+            .get(&receipt_id)
+            .ok_or(Error::NotFound)?
+            .receipt
+            .key
+            .clone();
+        let contents =
+            self.receipt_cache.try_get_or_insert_with(receipt_id, || {
+                let details = self
+                    .client
+                    .get_receipt_details(&receipt_key)
+                    .map_err(|err| Error::InternalError(err.to_string()))?;
+                Ok::<Bytes, Error>(format_details(&details).into())
+            })?;
+        Ok(contents)
+    }
+
     fn inode(&mut self, inode_id: u64) -> Result<Inode, Error> {
+        if inode_id & RECEIPT_INODE_FLAG != 0 {
+            let receipt_id = inode_id as u32;
+            let size = self.receipt_contents(receipt_id)?.len() as u64;
+            return Ok(Inode::Receipt { receipt_id, size });
+        }
+        if inode_id & GROUP_INODE_FLAG != 0 {
+            let group_id = inode_id as u32;
+            let groups = &self.inbox_index()?.groups;
+            return match (group_id as usize) < groups.len() {
+                true => Ok(Inode::Group { group_id }),
+                false => Err(Error::NotFound),
+            };
+        }
         match (Inode::entry_id(inode_id), Inode::attachment_id(inode_id)) {
+            (None, _) if inode_id == Inode::Receipts.to_u64() => {
+                Ok(Inode::Receipts)
+            }
             (None, _) => Ok(Inode::Root),
             (Some(entry_id), None) => Ok(Inode::InboxEntry { entry_id }),
+            (Some(entry_id), Some(METADATA_ID)) => {
+                let size = self.metadata(entry_id)?.len() as u64;
+                Ok(Inode::Metadata { entry_id, size })
+            }
+            (Some(entry_id), Some(TRASH_ID)) if self.options.allow_delete => {
+                self.inbox_item(entry_id)?;
+                Ok(Inode::Trash { entry_id })
+            }
             (Some(entry_id), Some(attachment_id)) => {
                 let attachment = self.attachment(entry_id, attachment_id)?;
                 Ok(Inode::Attachment {
@@ -269,48 +1152,157 @@ impl<C: Client> KivraFS<C> {
         parent_id: u64,
     ) -> Result<Vec<(String, Inode)>, Error> {
         match self.inode(parent_id)? {
-            Inode::Root => Ok(self
-                .inbox_index()?
-                .by_id
-                .iter()
-                .map(|(&entry_id, entry)| {
-                    (entry.to_string(), Inode::InboxEntry { entry_id })
-                })
-                .collect()),
-            Inode::InboxEntry { entry_id } => {
-                let details = self.details(entry_id)?;
-                Ok(details
-                    .parts
+            Inode::Root => {
+                // Only built with the unverified endpoints
+                let receipts = cfg!(feature = "unverified")
+                    .then(|| (RECEIPTS_DIR_NAME.to_string(), Inode::Receipts));
+                let layout = self.options.layout;
+                let index = self.inbox_index()?;
+                let children: Vec<(String, Inode)> = match layout {
+                    Layout::Item => index
+                        .by_id
+                        .iter()
+                        .map(|(&entry_id, entry)| {
+                            (entry.to_string(), Inode::InboxEntry { entry_id })
+                        })
+                        .collect(),
+                    Layout::Sender | Layout::Date => index
+                        .groups
+                        .iter()
+                        .zip(0..)
+                        .map(|((name, _), group_id)| {
+                            (name.clone(), Inode::Group { group_id })
+                        })
+                        .collect(),
+                    Layout::Flat => {
+                        let mut ids: Vec<u32> =
+                            index.by_id.keys().copied().collect();
+                        ids.sort();
+                        let mut files = Vec::new();
+                        for entry_id in ids {
+                            files.extend(self.attachments(entry_id)?);
+                        }
+                        let (names, inodes): (Vec<_>, Vec<_>) =
+                            files.into_iter().unzip();
+                        let names = filename::deduplicate(names);
+                        names.into_iter().zip(inodes).collect()
+                    }
+                };
+                Ok(receipts.into_iter().chain(children).collect())
+            }
+            Inode::Group { group_id } => {
+                let index = self.inbox_index()?;
+                let (_, ids) = index
+                    .groups
+                    .get(group_id as usize)
+                    .ok_or(Error::NotFound)?;
+                Ok(ids
+                    .iter()
+                    .filter_map(|id| index.by_id.get(id))
+                    .map(|entry| {
+                        let entry_id = entry.id;
+                        (entry.to_string(), Inode::InboxEntry { entry_id })
+                    })
+                    .collect())
+            }
+            Inode::Receipts => {
+                let names: Vec<(String, u32)> = self
+                    .receipt_index()?
+                    .by_name
                     .iter()
-                    .enumerate()
-                    .filter_map(|(idx, attachment)| {
-                        let name = details.attachment_name(idx).ok()?;
-                        let inode = Inode::Attachment {
-                            entry_id,
-                            attachment_id: idx as u32,
-                            size: attachment.size as u64,
-                        };
-                        Some((name, inode))
+                    .map(|(name, entry)| (name.clone(), entry.id))
+                    .collect();
+                names
+                    .into_iter()
+                    .map(|(name, receipt_id)| {
+                        let size =
+                            self.receipt_contents(receipt_id)?.len() as u64;
+                        Ok((name, Inode::Receipt { receipt_id, size }))
                     })
+                    .collect()
+            }
+            Inode::InboxEntry { entry_id } => {
+                let size = self.metadata(entry_id)?.len() as u64;
+                let metadata = (
+                    METADATA_FILE_NAME.to_string(),
+                    Inode::Metadata { entry_id, size },
+                );
+                let trash = self.options.allow_delete.then(|| {
+                    (TRASH_FILE_NAME.to_string(), Inode::Trash { entry_id })
+                });
+                let attachments = self.attachments(entry_id)?;
+                Ok([metadata]
+                    .into_iter()
+                    .chain(trash)
+                    .chain(attachments)
                     .collect())
             }
-            Inode::Attachment { .. } => Err(Error::IsNotDir),
+            Inode::Attachment { .. }
+            | Inode::Metadata { .. }
+            | Inode::Trash { .. }
+            | Inode::Receipt { .. } => Err(Error::IsNotDir),
         }
     }
 
+    /// The attachments of an item, named as in its directory
+    fn attachments(
+        &mut self,
+        entry_id: u32,
+    ) -> Result<Vec<(String, Inode)>, Error> {
+        let details = self.details(entry_id)?;
+        let (names, inodes): (Vec<String>, Vec<Inode>) = details
+            .parts
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, attachment)| {
+                let name = details.attachment_name(idx).ok()?;
+                let inode = Inode::Attachment {
+                    entry_id,
+                    attachment_id: idx as u32,
+                    size: attachment.size as u64,
+                };
+                Some((name, inode))
+            })
+            .unzip();
+        // Lookups go through the listing, so they see the same names
+        let names = filename::deduplicate(names);
+        Ok(names.into_iter().zip(inodes).collect())
+    }
+
     fn inode_by_name(
         &mut self,
         parent_id: u64,
         name: &str,
     ) -> Result<Inode, Error> {
+        // Finder looks for AppleDouble files next to everything it shows,
+        // and no listed name starts like them
+        if name.starts_with("._") {
+            return Err(Error::NotFound);
+        }
         match self.inode(parent_id)? {
-            Inode::Root => self
+            Inode::Root
+                if name == RECEIPTS_DIR_NAME
+                    && cfg!(feature = "unverified") =>
+            {
+                Ok(Inode::Receipts)
+            }
+            Inode::Root if self.options.layout == Layout::Item => self
                 .inbox_index()?
                 .by_name
                 .get(name)
                 .map(|entry| Inode::InboxEntry { entry_id: entry.id })
                 .ok_or(Error::NotFound),
-            Inode::InboxEntry { .. } => {
+            Inode::Receipts => {
+                let receipt_id = self
+                    .receipt_index()?
+                    .by_name
+                    .get(name)
+                    .ok_or(Error::NotFound)?
+                    .id;
+                let size = self.receipt_contents(receipt_id)?.len() as u64;
+                Ok(Inode::Receipt { receipt_id, size })
+            }
+            Inode::Root | Inode::Group { .. } | Inode::InboxEntry { .. } => {
                 let children = self.inode_children(parent_id)?;
                 children
                     .iter()
@@ -318,12 +1310,15 @@ impl<C: Client> KivraFS<C> {
                     .map(|entry| entry.1.clone())
                     .ok_or(Error::NotFound)
             }
-            Inode::Attachment { .. } => Err(Error::IsNotDir),
+            Inode::Attachment { .. }
+            | Inode::Metadata { .. }
+            | Inode::Trash { .. }
+            | Inode::Receipt { .. } => Err(Error::IsNotDir),
         }
     }
 }
 
-impl<C: Client> Filesystem for KivraFS<C> {
+impl<C: ContentClient> Filesystem for KivraFS<C> {
     fn lookup(
         &mut self,
         _req: &Request<'_>,
@@ -334,7 +1329,8 @@ impl<C: Client> Filesystem for KivraFS<C> {
         match self.inode_by_name(parent, &name.to_string_lossy()) {
             Ok(inode) => {
                 debug!("found inode {inode } by name {name:?}");
-                reply.entry(&FILESYSTEM_TTL, &inode.attr(), 0);
+                let attr = self.attr(&inode);
+                reply.entry(&self.ttl, &attr, 0);
             }
             Err(error) => {
                 reply.error(error.error_code());
@@ -349,50 +1345,237 @@ impl<C: Client> Filesystem for KivraFS<C> {
         reply: fuser::ReplyAttr,
     ) {
         match self.inode(ino) {
-            Ok(inode) => reply.attr(&FILESYSTEM_TTL, &inode.attr()),
+            Ok(inode) => {
+                let attr = self.attr(&inode);
+                reply.attr(&self.ttl, &attr)
+            }
             Err(error) => reply.error(error.error_code()),
         }
     }
 
+    fn open(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        flags: i32,
+        reply: ReplyOpen,
+    ) {
+        let inode = match self.inode(ino) {
+            Ok(inode) if inode.kind() == FileType::Directory => {
+                Err(Error::IsDir)
+            }
+            // Writable with allow_delete, but only for removing items
+            Ok(_) if flags & O_ACCMODE != O_RDONLY => Err(Error::ReadOnly),
+            result => result,
+        };
+        match inode {
+            Ok(inode) => {
+                if let Inode::Attachment { entry_id, .. } = inode {
+                    if self.options.mark_read_on_open {
+                        self.mark_as_read(entry_id);
+                    }
+                }
+                let fh = self.open_handle(Handle::File(inode));
+                reply.opened(fh, 0)
+            }
+            Err(error) => reply.error(error.error_code()),
+        }
+    }
+
+    fn release(
+        &mut self,
+        _req: &Request<'_>,
+        _ino: u64,
+        fh: u64,
+        _flags: i32,
+        _lock_owner: Option<u64>,
+        _flush: bool,
+        reply: ReplyEmpty,
+    ) {
+        self.handles.remove(&fh);
+        reply.ok();
+    }
+
+    fn opendir(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        _flags: i32,
+        reply: ReplyOpen,
+    ) {
+        match self.inode_children(ino) {
+            Ok(children) => {
+                let fh = self.open_handle(Handle::Directory(children));
+                reply.opened(fh, 0)
+            }
+            Err(error) => reply.error(error.error_code()),
+        }
+    }
+
+    fn releasedir(
+        &mut self,
+        _req: &Request<'_>,
+        _ino: u64,
+        fh: u64,
+        _flags: i32,
+        reply: ReplyEmpty,
+    ) {
+        self.handles.remove(&fh);
+        reply.ok();
+    }
+
+    /// Nothing can be written, so nothing is free
+    fn statfs(&mut self, _req: &Request<'_>, _ino: u64, reply: ReplyStatfs) {
+        let (files, bytes) = self.usage();
+        reply.statfs(
+            bytes.div_ceil(BLOCK_SIZE as u64),
+            0,
+            0,
+            files,
+            0,
+            BLOCK_SIZE,
+            filename::MAX_LEN as u32,
+            BLOCK_SIZE,
+        );
+    }
+
     fn read(
         &mut self,
         _req: &Request,
         ino: u64,
-        _fh: u64,
+        fh: u64,
         offset: i64,
         size: u32,
         _flags: i32,
         _lock: Option<u64>,
         reply: ReplyData,
     ) {
-        match self.inode(ino) {
+        let inode = match self.handles.get(&fh) {
+            Some(Handle::File(inode)) => Ok(inode.clone()),
+            _ => self.inode(ino),
+        };
+        match inode {
             Err(error) => reply.error(error.error_code()),
             Ok(Inode::Attachment { entry_id, attachment_id, .. }) => {
                 let res = self.attachment_contents(entry_id, attachment_id);
                 match res {
                     Ok(data) => {
-                        let start = offset as usize;
+                        let start = min(data.len(), offset as usize);
+                        let end = min(data.len(), start + size as usize);
+                        reply.data(&data[start..end]);
+                    }
+                    Err(error) => reply.error(error.error_code()),
+                }
+            }
+            Ok(Inode::Metadata { entry_id, .. }) => {
+                match self.metadata(entry_id) {
+                    Ok(data) => {
+                        let start = min(data.len(), offset as usize);
+                        let end = min(data.len(), start + size as usize);
+                        reply.data(&data[start..end]);
+                    }
+                    Err(error) => reply.error(error.error_code()),
+                }
+            }
+            Ok(Inode::Receipt { receipt_id, .. }) => {
+                match self.receipt_contents(receipt_id) {
+                    Ok(data) => {
+                        let start = min(data.len(), offset as usize);
                         let end = min(data.len(), start + size as usize);
                         reply.data(&data[start..end]);
                     }
                     Err(error) => reply.error(error.error_code()),
                 }
             }
+            Ok(Inode::Trash { .. }) => reply.data(&[]),
             Ok(_) => {
                 reply.error(Error::IsDir.error_code());
             }
         }
     }
 
+    fn unlink(
+        &mut self,
+        _req: &Request<'_>,
+        parent: u64,
+        name: &OsStr,
+        reply: ReplyEmpty,
+    ) {
+        match self.remove(parent, &name.to_string_lossy(), false) {
+            Ok(()) => reply.ok(),
+            Err(error) => reply.error(error.error_code()),
+        }
+    }
+
+    fn rmdir(
+        &mut self,
+        _req: &Request<'_>,
+        parent: u64,
+        name: &OsStr,
+        reply: ReplyEmpty,
+    ) {
+        match self.remove(parent, &name.to_string_lossy(), true) {
+            Ok(()) => reply.ok(),
+            Err(error) => reply.error(error.error_code()),
+        }
+    }
+
+    fn getxattr(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        name: &OsStr,
+        size: u32,
+        reply: ReplyXattr,
+    ) {
+        let value = self.xattrs(ino).and_then(|attrs| {
+            attrs
+                .into_iter()
+                .find(|(attr, _)| name == OsStr::new(attr))
+                .map(|(_, value)| value)
+                .ok_or(Error::NoAttribute)
+        });
+        match value {
+            Ok(value) => reply_xattr(reply, value.as_bytes(), size),
+            Err(error) => reply.error(error.error_code()),
+        }
+    }
+
+    fn listxattr(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        size: u32,
+        reply: ReplyXattr,
+    ) {
+        match self.xattrs(ino) {
+            Ok(attrs) => {
+                // Names end with a NUL each
+                let names: Vec<u8> = attrs
+                    .into_iter()
+                    .flat_map(|(name, _)| {
+                        name.into_bytes().into_iter().chain([0])
+                    })
+                    .collect();
+                reply_xattr(reply, &names, size)
+            }
+            Err(error) => reply.error(error.error_code()),
+        }
+    }
+
     fn readdir(
         &mut self,
         _req: &Request<'_>,
         ino: u64,
-        _fh: u64,
+        fh: u64,
         offset: i64,
         mut reply: ReplyDirectory,
     ) {
-        let children = match self.inode_children(ino) {
+        let children = match self.handles.get(&fh) {
+            Some(Handle::Directory(children)) => Ok(children.clone()),
+            _ => self.inode_children(ino),
+        };
+        let children = match children {
             Err(error) => {
                 reply.error(error.error_code());
                 return;
@@ -400,7 +1583,7 @@ impl<C: Client> Filesystem for KivraFS<C> {
             Ok(children) => children,
         };
 
-        let after_offset = &children[(offset as usize)..];
+        let after_offset = children.get(offset as usize..).unwrap_or_default();
         debug!(
             "{} children, offset {}, {} entries left",
             children.len(),
@@ -412,7 +1595,7 @@ impl<C: Client> Filesystem for KivraFS<C> {
             if reply.add(
                 inode.to_u64(),
                 add_offset,
-                inode.attr().kind,
+                inode.kind(),
                 OsStr::new(&name),
             ) {
                 debug!("output buffer full, stopping");
@@ -422,3 +1605,39 @@ impl<C: Client> Filesystem for KivraFS<C> {
         reply.ok();
     }
 }
+
+/// A size of 0 asks how large a buffer the data needs
+fn reply_xattr(reply: ReplyXattr, data: &[u8], size: u32) {
+    match size {
+        0 => reply.size(data.len() as u32),
+        size if (size as usize) < data.len() => {
+            reply.error(Error::TooSmall.error_code())
+        }
+        _ => reply.data(data),
+    }
+}
+
+/// The directories of the layout, sorted by name, with the ids of the
+/// entries in each
+fn groups(layout: Layout, inbox: &InboxListing) -> Vec<(String, Vec<u32>)> {
+    // Keyed by sender key or month, since senders may share a name
+    let mut groups: BTreeMap<String, (String, Vec<u32>)> = BTreeMap::new();
+    for entry in inbox.iter() {
+        let item = &entry.item;
+        let (key, name) = match layout {
+            Layout::Sender => (item.sender.clone(), item.sender_name.clone()),
+            Layout::Date => {
+                let month = item.created_at.format("%Y-%m").to_string();
+                (month.clone(), month)
+            }
+            Layout::Item | Layout::Flat => return Vec::new(),
+        };
+        let group = groups.entry(key).or_insert_with(|| (name, Vec::new()));
+        group.1.push(entry.id);
+    }
+    let mut groups: Vec<(String, Vec<u32>)> = groups.into_values().collect();
+    groups.sort_by(|(a, _), (b, _)| a.cmp(b));
+    let names = groups.iter().map(|(name, _)| filename::sanitize(name));
+    let names = filename::deduplicate(names.collect());
+    names.into_iter().zip(groups).map(|(name, (_, ids))| (name, ids)).collect()
+}
@@ -0,0 +1,478 @@
+//! Swedish and English for what kivinge says to the user. English text is
+//! written out where it is used, wrapped in `tr!`, and is also what its
+//! translations are looked up by, so that text without one is shown in
+//! English. Where the same English needs different translations it is
+//! prefixed with what it is, e.g. `tr!("status|Unread")`. Error messages,
+//! logs and machine readable output stay English.
+
+use chrono::{DateTime, Datelike, Local, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Language {
+    /// From LC_ALL, LC_MESSAGES or LANG, English unless they are Swedish
+    #[default]
+    Auto,
+    En,
+    Sv,
+}
+
+static LANGUAGE: OnceLock<Language> = OnceLock::new();
+
+/// Sets the language as configured, before anything is said
+pub fn init(language: Language) {
+    let language = match language {
+        Language::Auto => detect(),
+        language => language,
+    };
+    _ = LANGUAGE.set(language);
+}
+
+fn detect() -> Language {
+    let locale = ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|value| !value.is_empty())
+        .unwrap_or_default();
+    match locale.starts_with("sv") {
+        true => Language::Sv,
+        false => Language::En,
+    }
+}
+
+pub fn language() -> Language {
+    *LANGUAGE.get_or_init(detect)
+}
+
+/// Translates text, e.g. `tr!("Moved to trash")`, or a template with named
+/// placeholders filled in, e.g. `tr!("Saved {name}", name = setting.label)`
+#[macro_export]
+macro_rules! tr {
+    ($text:expr) => {
+        $crate::i18n::translate($text)
+    };
+    ($text:expr, $($name:ident = $value:expr),+ $(,)?) => {
+        $crate::i18n::fill(
+            $crate::i18n::translate($text),
+            &[$((stringify!($name), $value.to_string())),+],
+        )
+    };
+}
+
+pub fn translate(text: &str) -> &str {
+    let translation = match language() {
+        Language::Sv => swedish(text),
+        Language::Auto | Language::En => None,
+    };
+    translation.unwrap_or_else(|| {
+        text.split_once('|').map_or(text, |(_context, text)| text)
+    })
+}
+
+pub fn fill(template: &str, values: &[(&str, String)]) -> String {
+    values.iter().fold(template.to_string(), |text, (name, value)| {
+        text.replace(&format!("{{{name}}}"), value)
+    })
+}
+
+const MONTHS_EN: [&str; 12] = [
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+
+const MONTHS_SV: [&str; 12] = [
+    "januari",
+    "februari",
+    "mars",
+    "april",
+    "maj",
+    "juni",
+    "juli",
+    "augusti",
+    "september",
+    "oktober",
+    "november",
+    "december",
+];
+
+fn month_name(month0: u32) -> &'static str {
+    match language() {
+        Language::Sv => MONTHS_SV[month0 as usize],
+        Language::Auto | Language::En => MONTHS_EN[month0 as usize],
+    }
+}
+
+/// A date and time as written in text, e.g. "5 August 2024 18:12" or
+/// "5 augusti 2024 kl. 18.12"
+pub fn long_date_time(at: NaiveDateTime) -> String {
+    let date =
+        format!("{} {} {}", at.day(), month_name(at.month0()), at.year());
+    match language() {
+        Language::Sv => format!("{date} kl. {}", at.format("%H.%M")),
+        Language::Auto | Language::En => {
+            format!("{date} {}", at.format("%H:%M"))
+        }
+    }
+}
+
+/// A month in short, e.g. "Aug 24" or "aug 24", for where room is scarce
+pub fn short_month(date: impl Datelike) -> String {
+    let name: String = month_name(date.month0()).chars().take(3).collect();
+    format!("{name} {:02}", date.year() % 100)
+}
+
+/// How long ago a time was, e.g. "2 h ago", "yesterday" or "3 weeks ago"
+pub fn time_ago(at: DateTime<Local>, now: DateTime<Local>) -> String {
+    let elapsed = now.signed_duration_since(at);
+    let days = (now.date_naive() - at.date_naive()).num_days();
+    let (n, one, many) = match elapsed.num_minutes() {
+        ..=0 => return tr!("just now").to_string(),
+        minutes @ ..=59 => (minutes, "{n} min ago", "{n} min ago"),
+        _ if days == 0 => (elapsed.num_hours(), "{n} h ago", "{n} h ago"),
+        _ if days == 1 => return tr!("yesterday").to_string(),
+        _ if days < 7 => (days, "{n} day ago", "{n} days ago"),
+        _ if days < 30 => (days / 7, "{n} week ago", "{n} weeks ago"),
+        _ if days < 365 => (days / 30, "{n} month ago", "{n} months ago"),
+        _ => (days / 365, "{n} year ago", "{n} years ago"),
+    };
+    tr!(if n == 1 { one } else { many }, n = n)
+}
+
+fn swedish(text: &str) -> Option<&'static str> {
+    Some(match text {
+        // Tabs and views
+        "Inbox" => "Inkorg",
+        "Unread" => "Olästa",
+        "Payments" => "Betalningar",
+        "Archive" => "Arkiv",
+        "Trash" => "Papperskorg",
+        "Receipts" => "Kvitton",
+        "Dashboard" => "Översikt",
+        "Due" => "Att betala",
+        "Items per month" => "Försändelser per månad",
+        "Error" => "Fel",
+        "Caused by:" => "Orsakat av:",
+        "Network unreachable" => "Nätverket kan inte nås",
+        "Confirm" => "Bekräfta",
+        "Help" => "Hjälp",
+        "Sender" => "Avsändare",
+        "Inbox of" => "Inkorg för",
+        "All senders" => "Alla avsändare",
+        "Your own inbox" => "Din egen inkorg",
+        "OFFLINE" => "FRÅNKOPPLAD",
+        " Press any key to close" => " Tryck på valfri tangent för att stänga",
+        "Loading {what}…" => "Laddar {what}…",
+        "Loading more…" => "Laddar fler…",
+        "Loading…" => "Laddar…",
+        "Failed to load the item: {error}" => {
+            "Kunde inte ladda försändelsen: {error}"
+        }
+        "the inbox" => "inkorgen",
+        "the item" => "försändelsen",
+        "attachments" => "bilagor",
+        "Settings in {path}" => "Inställningar i {path}",
+        "not set" => "inte satt",
+        "on" => "på",
+        "off" => "av",
+        "on restart" => "vid omstart",
+
+        // The item view
+        "Sender:" => "Avsändare:",
+        "Status:" => "Status:",
+        "Created at:" => "Skapad:",
+        "Subject:" => "Ämne:",
+        "Details:" => "Detaljer:",
+        "Details: (m to show)" => "Detaljer: (m för att visa)",
+        "Attachments:" => "Bilagor:",
+        "Preview:" => "Förhandsvisning:",
+        "status|Read" => "Läst",
+        "status|Unread" => "Oläst",
+        "Amount" => "Belopp",
+        "Variable amount" => "Valfritt belopp",
+        "Yes" => "Ja",
+        "Due date" => "Förfallodag",
+        "Pay date" => "Betaldag",
+        "Payment status" => "Betalstatus",
+        "Agreement" => "Avtal",
+        "Labels" => "Etiketter",
+        "Type" => "Typ",
+        "Form" => "Formulär",
+        "answer it in the Kivra app" => "besvara det i Kivra-appen",
+
+        // Text output of single things
+        "Name" => "Namn",
+        "Email" => "E-post",
+        "Phone" => "Telefon",
+        "User id" => "Användar-id",
+        "Token" => "Token",
+        "QR data" => "QR-data",
+        "BankID" => "BankID",
+        "Status" => "Status",
+        "Store" => "Butik",
+        "Purchased" => "Köpt",
+        "Purchased At" => "Köpt",
+        "Total" => "Totalt",
+        "Paid with" => "Betalt med",
+        "Items:" => "Artiklar:",
+
+        // Table headers
+        "New" => "Ny",
+        "Profile" => "Profil",
+        "Id" => "Id",
+        "Subject" => "Ämne",
+        "Due Date" => "Förfallodag",
+        "Created At" => "Skapad",
+        "Created" => "Skapad",
+        "Attachments" => "Bilagor",
+        "Size" => "Storlek",
+
+        // Login
+        "Authenticate with BankID" => "Legitimera dig med BankID",
+        "Press 'o' to use BankID on this device or 'q' to abort login" => {
+            "Tryck 'o' för BankID på den här enheten eller 'q' för att avbryta"
+        }
+        "Waiting for BankID on this device\n\n{status}\n\nPress 'o' to open \
+         BankID again or 'q' to abort" => {
+            "Väntar på BankID på den här enheten\n\n{status}\n\nTryck 'o' för \
+             att öppna BankID igen eller 'q' för att avbryta"
+        }
+        "Login aborted" => "Inloggningen avbröts",
+        "Login Successful" => "Inloggad",
+
+        // Questions
+        "Move '{subject}' to trash?" => "Flytta '{subject}' till papperskorgen?",
+        "Log out and forget the saved session?" => {
+            "Logga ut och glöm den sparade sessionen?"
+        }
+        "Mark all {count} unread items as read?" => {
+            "Markera alla {count} olästa som lästa?"
+        }
+
+        // Messages
+        "Moved to trash" => "Flyttad till papperskorgen",
+        "Marked as read" => "Markerad som läst",
+        "Opened the attachment" => "Öppnade bilagan",
+        "Back online" => "Ansluten igen",
+        "Working offline from what is kept locally" => {
+            "Arbetar frånkopplat med det som finns sparat lokalt"
+        }
+        "Logging out needs Kivra, which is not reachable" => {
+            "Utloggning kräver Kivra, som inte kan nås"
+        }
+        "Showing the inbox of {name}" => "Visar inkorgen för {name}",
+        "Showing your own inbox" => "Visar din egen inkorg",
+        "Copied the {field} to the clipboard" => "Kopierade {field}",
+        "summary" => "sammanfattningen",
+        "subject" => "ämnet",
+        "sender" => "avsändaren",
+        "content key" => "innehållsnyckeln",
+        "attachment path" => "sökvägen till bilagan",
+        "Failed to load receipts: {error}" => {
+            "Kunde inte ladda kvitton: {error}"
+        }
+        "Failed to load the receipt: {error}" => {
+            "Kunde inte ladda kvittot: {error}"
+        }
+        "Failed to load the inbox: {error}" => {
+            "Kunde inte ladda inkorgen: {error}"
+        }
+        "Failed to load more of the inbox: {error}" => {
+            "Kunde inte ladda mer av inkorgen: {error}"
+        }
+        "Sync failed: {error}" => "Synkningen misslyckades: {error}",
+        "No company inboxes to switch to" => "Inga företagsinkorgar att byta till",
+        "No unread items" => "Inga olästa",
+        "Saved {name}" => "Sparade {name}",
+        "Saved, {name} changes the next time kivinge starts" => {
+            "Sparat, {name} ändras nästa gång kivinge startar"
+        }
+        "Downloading to {dir}" => "Laddar ner till {dir}",
+        "Marking as read" => "Markerar som lästa",
+        "Downloaded {done} item(s) to {dir}" => {
+            "Laddade ner {done} försändelse(r) till {dir}"
+        }
+        "Marked {done} item(s) as read" => {
+            "Markerade {done} försändelse(r) som lästa"
+        }
+        ", {failed} failed" => ", {failed} misslyckades",
+        ", {cancelled} cancelled" => ", {cancelled} avbrutna",
+        "Press 'q' to cancel" => "Tryck 'q' för att avbryta",
+        "No items match" => "Inga försändelser matchar",
+        "Session token deleted" => "Sessionen raderades",
+        "No company inboxes" => "Inga företagsinkorgar",
+        "No step failed" => "Inget steg misslyckades",
+        "Config is valid" => "Konfigurationen är giltig",
+        "{items} item(s) backed up" => "{items} försändelse(r) säkerhetskopierade",
+        "Unmounted {path}" => "Avmonterade {path}",
+        "Removed {removed} file(s) from {dir}" => {
+            "Tog bort {removed} fil(er) från {dir}"
+        }
+        "{path} already exists, skipped" => "{path} finns redan, hoppade över",
+        "{items} item(s) exported to {path}" => {
+            "{items} försändelse(r) exporterade till {path}"
+        }
+        "{migrated} attachment(s) and {converted} other cache file(s) \
+         converted" => {
+            "Konverterade {migrated} bilagor och {converted} andra cachefiler"
+        }
+        "{path} does not exist, defaults are used" => {
+            "{path} finns inte, standardvärden används"
+        }
+        "\nKivra could not be reached:\n{error}\n\n(r)etry, work (o)ffline \
+         from what is kept locally, or (q)uit" => {
+            "\nKivra kunde inte nås:\n{error}\n\n(r) försök igen, arbeta \
+             (o) frånkopplat med det som finns lokalt, eller (q) avsluta"
+        }
+        "{done} done, {failed} failed, {cancelled} cancelled - press any key" => {
+            "{done} klara, {failed} misslyckades, {cancelled} avbrutna - tryck \
+             på valfri tangent"
+        }
+        "{items} item(s) in the inbox, {unread} unread, {bills} bill(s) to \
+         pay" => {
+            "{items} försändelse(r) i inkorgen, {unread} olästa, {bills} \
+             räkning(ar) att betala"
+        }
+        "{name} ({items} items, {unread} unread)" => {
+            "{name} ({items} försändelser, {unread} olästa)"
+        }
+        "{unread} unread" => "{unread} olästa",
+
+        // Key hints
+        "move" => "flytta",
+        "open" => "öppna",
+        "back" => "tillbaka",
+        "quit" => "avsluta",
+        "help" => "hjälp",
+        "move up" => "flytta upp",
+        "move down" => "flytta ner",
+        "select or open" => "välj eller öppna",
+        "go back" => "gå tillbaka",
+        "quit or close" => "avsluta eller stäng",
+        "show this help" => "visa den här hjälpen",
+        "show or hide the log" => "visa eller dölj loggen",
+        "retry" => "försök igen",
+        "abort" => "avbryt",
+        "close" => "stäng",
+        "copy" => "kopiera",
+        "details" => "detaljer",
+        "pick" => "välj",
+        "yes" => "ja",
+        "no" => "nej",
+        "unread" => "olästa",
+        "open BankID" => "öppna BankID",
+        "open attachment" => "öppna bilaga",
+        "open bill" => "öppna räkning",
+        "show month" => "visa månad",
+        "bills/months" => "räkningar/månader",
+        "go back to the inbox" => "gå tillbaka till inkorgen",
+        "show the unread items" => "visa olästa",
+        "move between the bills and the chart" => {
+            "flytta mellan räkningarna och diagrammet"
+        }
+        "open the bill, or show the month's items" => {
+            "öppna räkningen, eller visa månadens försändelser"
+        }
+        "select a bill, or a later or earlier month" => {
+            "välj en räkning, eller en senare eller tidigare månad"
+        }
+        "work offline" => "arbeta frånkopplat",
+        "change" => "ändra",
+        "reset to default" => "återställ",
+        "save" => "spara",
+        "cancel" => "avbryt",
+        "search" => "sök",
+        "mark" => "markera",
+        "download" => "ladda ner",
+        "mark read" => "markera läst",
+        "trash" => "släng",
+        "copy…" => "kopiera…",
+        "tabs" => "flikar",
+        "dashboard" => "översikt",
+        "settings" => "inställningar",
+        "move a screen down or up" => "flytta en skärm ner eller upp",
+        "go to the first or last item" => "gå till första eller sista",
+        "go to the next unread item" => "gå till nästa olästa",
+        "mark or unmark for a bulk action" => "markera eller avmarkera",
+        "download attachments of marked items" => {
+            "ladda ner bilagor för markerade"
+        }
+        "mark marked (or all unread) items as read" => {
+            "markera markerade (eller alla olästa) som lästa"
+        }
+        "copy a summary of the item" => "kopiera en sammanfattning",
+        "copy the subject, sender or content key" => {
+            "kopiera ämne, avsändare eller innehållsnyckel"
+        }
+        "move the item to trash" => "flytta till papperskorgen",
+        "show or hide more of the item" => "visa eller dölj mer om försändelsen",
+        "show or hide attachment counts and sizes" => {
+            "visa eller dölj antal och storlek på bilagor"
+        }
+        "show items from one sender" => "visa en avsändares försändelser",
+        "search, e.g. sender=skatteverket && year=2024" => {
+            "sök, t.ex. sender=skatteverket && year=2024"
+        }
+        "load new items" => "ladda nya försändelser",
+        "show all, unread or payable items" => {
+            "visa alla, olästa eller att betala"
+        }
+        "show archived or trashed items" => "visa arkiverade eller slängda",
+        "show receipts" => "visa kvitton",
+        "show the dashboard" => "visa översikten",
+        "change the settings" => "ändra inställningarna",
+        "switch to a company inbox and back" => "byt till en företagsinkorg",
+        "log out" => "logga ut",
+
+        // Settings
+        "Download directory" => "Nedladdningsmapp",
+        "Download path template" => "Mall för nedladdade filer",
+        "Existing files" => "Befintliga filer",
+        "Theme color" => "Temafärg",
+        "Mark read on open" => "Markera läst vid öppning",
+        "Images" => "Bilder",
+        "Startup view" => "Startvy",
+        "Sync interval (seconds)" => "Synkintervall (sekunder)",
+        "Page size" => "Sidstorlek",
+        "Inbox cache TTL (seconds)" => "Cachetid för inkorgen (sekunder)",
+        "Details cache TTL (seconds)" => "Cachetid för detaljer (sekunder)",
+        "Notification attempts" => "Försök per avisering",
+        "ntfy server" => "ntfy-server",
+        "ntfy topic" => "ntfy-ämne",
+        "ntfy priority" => "ntfy-prioritet",
+        "Gotify server" => "Gotify-server",
+        "Gotify priority" => "Gotify-prioritet",
+        "SMTP server" => "SMTP-server",
+        "SMTP sender" => "SMTP-avsändare",
+        "Language" => "Språk",
+        "Timestamps" => "Tidsangivelser",
+
+        // Times
+        "just now" => "nyss",
+        "yesterday" => "i går",
+        "{n} min ago" => "för {n} min sedan",
+        "{n} h ago" => "för {n} h sedan",
+        "{n} day ago" => "för {n} dag sedan",
+        "{n} days ago" => "för {n} dagar sedan",
+        "{n} week ago" => "för {n} vecka sedan",
+        "{n} weeks ago" => "för {n} veckor sedan",
+        "{n} month ago" => "för {n} månad sedan",
+        "{n} months ago" => "för {n} månader sedan",
+        "{n} year ago" => "för {n} år sedan",
+        "{n} years ago" => "för {n} år sedan",
+        "{time} left" => "{time} kvar",
+
+        _ => return None,
+    })
+}
@@ -0,0 +1,272 @@
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    path::{Path, PathBuf},
+};
+use thiserror::Error;
+
+use crate::{
+    client::session::DEFAULT_PROFILE,
+    crypto,
+    model::content::{ContentKey, Folder, InboxFilter, InboxItem},
+    util,
+};
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("failed to determine local data dir")]
+    CannotFindLocalDir,
+
+    #[error("(de)serialization error")]
+    SerializationError(#[from] serde_json::Error),
+
+    #[error("IO error")]
+    IOError(#[from] std::io::Error),
+
+    #[error("encryption error: {0}")]
+    CryptoError(#[from] crypto::Error),
+}
+
+/// Local knowledge about the inbox that Kivra does not keep for us
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Index {
+    pub downloads: BTreeMap<ContentKey, Vec<Download>>,
+    /// The id each item was given when first seen, which it keeps for good
+    pub ids: BTreeMap<ContentKey, u32>,
+    /// What each item was like when last listed, for `stats`
+    pub items: BTreeMap<ContentKey, IndexedItem>,
+    /// The text of the attachments of each item, by attachment number, for
+    /// content search. Items whose attachments had no text have an empty
+    /// entry, so that they are not looked at again.
+    pub texts: BTreeMap<ContentKey, BTreeMap<u32, String>>,
+}
+
+/// The text of the attachments of each item in lower case, which the
+/// `content` filter field searches
+pub type Contents = BTreeMap<ContentKey, String>;
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct IndexedItem {
+    pub sender: String,
+    pub subject: String,
+    pub created_at: DateTime<Utc>,
+    pub unread: bool,
+    pub trashed: bool,
+    pub amount: Option<Decimal>,
+    pub currency: Option<String>,
+    /// Sizes of the attachments, once the details of the item are fetched
+    #[serde(default)]
+    pub attachment_sizes: Option<Vec<usize>>,
+}
+
+impl IndexedItem {
+    pub fn new(item: &InboxItem) -> IndexedItem {
+        IndexedItem {
+            sender: item.sender_name.clone(),
+            subject: item.subject.clone(),
+            created_at: item.created_at,
+            unread: InboxFilter::Unread.matches(item),
+            trashed: item.folder() == Folder::Trash,
+            amount: item.amount,
+            currency: item.currency.clone(),
+            attachment_sizes: None,
+        }
+    }
+}
+
+/// An attachment that exists as a file on disk
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Download {
+    pub attachment: u32,
+    pub path: PathBuf,
+    pub sha256: String,
+    pub recorded_at: DateTime<Utc>,
+}
+
+impl Index {
+    pub fn record_download(
+        &mut self,
+        item_key: &str,
+        attachment: u32,
+        path: &Path,
+        sha256: String,
+    ) {
+        let path = path.canonicalize().unwrap_or(path.to_path_buf());
+        let downloads = self.downloads.entry(item_key.to_string()).or_default();
+        downloads.retain(|download| download.path != path);
+        downloads.push(Download {
+            attachment,
+            path,
+            sha256,
+            recorded_at: Utc::now(),
+        });
+    }
+
+    pub fn is_recorded(&self, path: &Path) -> bool {
+        let path = path.canonicalize().unwrap_or(path.to_path_buf());
+        self.downloads.values().flatten().any(|download| download.path == path)
+    }
+
+    /// Gives the items not seen before the ids following the highest one
+    /// handed out, oldest first. Returns whether any was new.
+    pub fn assign_ids(&mut self, items: &[InboxItem]) -> bool {
+        let mut new: Vec<&InboxItem> = items
+            .iter()
+            .filter(|item| !self.ids.contains_key(&item.key))
+            .collect();
+        new.sort_by_key(|item| item.created_at);
+        let next = self.ids.values().max().map_or(1, |id| id + 1);
+        for (item, id) in new.iter().zip(next..) {
+            self.ids.insert(item.key.clone(), id);
+        }
+        !new.is_empty()
+    }
+
+    /// Keeps what the items are like now, along with the attachment sizes
+    /// already known. Returns whether anything changed.
+    pub fn record_items(&mut self, items: &[InboxItem]) -> bool {
+        let mut changed = false;
+        for item in items {
+            let mut indexed = IndexedItem::new(item);
+            let old = self.items.get(&item.key);
+            indexed.attachment_sizes =
+                old.and_then(|old| old.attachment_sizes.clone());
+            if old != Some(&indexed) {
+                self.items.insert(item.key.clone(), indexed);
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    /// Keeps the text of every attachment of an item
+    pub fn record_texts(
+        &mut self,
+        item_key: &str,
+        texts: BTreeMap<u32, String>,
+    ) {
+        self.texts.insert(item_key.to_string(), texts);
+    }
+
+    /// Keeps the text of one attachment, e.g. when it is downloaded
+    pub fn record_text(
+        &mut self,
+        item_key: &str,
+        attachment: u32,
+        text: String,
+    ) {
+        let texts = self.texts.entry(item_key.to_string()).or_default();
+        texts.insert(attachment, text);
+    }
+
+    pub fn contents(&self) -> Contents {
+        self.texts
+            .iter()
+            .map(|(key, texts)| {
+                let texts: Vec<&str> =
+                    texts.values().map(String::as_str).collect();
+                (key.clone(), texts.join("\n").to_lowercase())
+            })
+            .collect()
+    }
+
+    /// Returns whether the sizes were new. Items not yet listed are left
+    /// out until they are.
+    pub fn record_attachment_sizes(
+        &mut self,
+        item_key: &str,
+        sizes: Vec<usize>,
+    ) -> bool {
+        let Some(item) = self.items.get_mut(item_key) else {
+            return false;
+        };
+        let changed = item.attachment_sizes.as_ref() != Some(&sizes);
+        item.attachment_sizes = Some(sizes);
+        changed
+    }
+}
+
+pub fn sha256(data: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(data))
+}
+
+fn index_path(profile: &str) -> Result<PathBuf, Error> {
+    let mut path = dirs::data_local_dir().ok_or(Error::CannotFindLocalDir)?;
+    match profile {
+        DEFAULT_PROFILE => path.push("kivinge.index.json"),
+        _ => path.push(format!("kivinge.{profile}.index.json")),
+    }
+    Ok(path)
+}
+
+pub fn load(profile: &str) -> Result<Index, Error> {
+    let index_path = index_path(profile)?;
+    if !index_path.exists() {
+        return Ok(Index::default());
+    }
+    let data = crypto::unseal(fs::read(index_path)?)?;
+    Ok(serde_json::from_slice(&data)?)
+}
+
+/// Written next to the index and renamed over it, so that a crash leaves
+/// either the old or the new index
+fn save(index: &Index, profile: &str) -> Result<(), Error> {
+    let data = crypto::seal(serde_json::to_vec(index)?)?;
+    let path = index_path(profile)?;
+    let partial = util::partial_path(&path);
+    let written =
+        fs::write(&partial, data).and_then(|()| fs::rename(&partial, &path));
+    if written.is_err() {
+        _ = fs::remove_file(&partial);
+    }
+    Ok(written?)
+}
+
+/// Loads the index, has `change` change it and saves it if `change` says
+/// it did. Processes take turns at this, so that none of them overwrites
+/// what another one changed in the meantime.
+pub fn update<T>(
+    profile: &str,
+    change: impl FnOnce(&mut Index) -> (T, bool),
+) -> Result<T, Error> {
+    let mut lock_path = index_path(profile)?;
+    lock_path.set_extension("lock");
+    let lock = File::options().create(true).append(true).open(lock_path)?;
+    // Released when the file is closed
+    lock.lock()?;
+    let mut index = load(profile)?;
+    let (result, changed) = change(&mut index);
+    if changed {
+        save(&index, profile)?;
+    }
+    Ok(result)
+}
+
+/// The ids of the items in the index of the profile, which gets ids for
+/// those not seen before and records what the items are like
+pub fn item_ids(
+    profile: &str,
+    items: &[InboxItem],
+) -> Result<BTreeMap<ContentKey, u32>, Error> {
+    update(profile, |index| {
+        // Both, whether or not the first changed anything
+        let changed = index.assign_ids(items) | index.record_items(items);
+        (index.ids.clone(), changed)
+    })
+}
+
+/// Records the sizes of the attachments of an item, if they are new
+pub fn attachment_sizes(
+    profile: &str,
+    item_key: &str,
+    sizes: Vec<usize>,
+) -> Result<(), Error> {
+    update(profile, |index| {
+        ((), index.record_attachment_sizes(item_key, sizes))
+    })
+}
@@ -0,0 +1,241 @@
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fmt::Display,
+    fs::File,
+    io::{BufRead, BufReader, Write},
+    path::PathBuf,
+};
+use thiserror::Error;
+
+use crate::{
+    client::session::DEFAULT_PROFILE,
+    crypto,
+    model::content::{ContentKey, InboxItem, InboxListing, Status},
+};
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("failed to determine state dir")]
+    CannotFindStateDir,
+
+    #[error("(de)serialization error")]
+    SerializationError(#[from] serde_json::Error),
+
+    #[error("IO error")]
+    IOError(#[from] std::io::Error),
+
+    #[error("encryption error: {0}")]
+    CryptoError(#[from] crypto::Error),
+
+    #[error("encrypted event is not base64")]
+    DecodeError(#[from] base64::DecodeError),
+}
+
+/// A change to the inbox, as observed when the listing was fetched
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Event {
+    pub at: DateTime<Utc>,
+    pub key: ContentKey,
+    pub sender_name: String,
+    pub subject: String,
+    #[serde(flatten)]
+    pub kind: EventKind,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum EventKind {
+    Appeared { read: bool, payment_status: Option<String> },
+    MarkedRead,
+    PaymentStatusChanged { from: Option<String>, to: Option<String> },
+    Removed,
+}
+
+impl Display for EventKind {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let status = |status: &Option<String>| {
+            status.clone().unwrap_or_else(|| "none".to_string())
+        };
+        match self {
+            EventKind::Appeared { .. } => "appeared".to_string(),
+            EventKind::MarkedRead => "marked read".to_string(),
+            EventKind::PaymentStatusChanged { from, to } => {
+                format!("payment {} -> {}", status(from), status(to))
+            }
+            EventKind::Removed => "removed".to_string(),
+        }
+        .fmt(formatter)
+    }
+}
+
+/// What the journal last saw of an item
+struct Known {
+    read: bool,
+    payment_status: Option<String>,
+    sender_name: String,
+    subject: String,
+}
+
+fn journal_path(profile: &str) -> Result<PathBuf, Error> {
+    let mut path = dirs::state_dir()
+        .or_else(dirs::data_local_dir)
+        .ok_or(Error::CannotFindStateDir)?;
+    match profile {
+        DEFAULT_PROFILE => path.push("kivinge.journal"),
+        _ => path.push(format!("kivinge.{profile}.journal")),
+    }
+    Ok(path)
+}
+
+pub fn load(profile: &str) -> Result<Vec<Event>, Error> {
+    let path = journal_path(profile)?;
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let mut events = Vec::new();
+    for line in BufReader::new(File::open(path)?).lines() {
+        let line = line?;
+        if !line.trim().is_empty() {
+            events.push(decode(&line)?);
+        }
+    }
+    Ok(events)
+}
+
+/// An event as a line of JSON, or with encryption enabled as the sealed JSON
+/// in base64, so that events are still appended one line each
+fn encode(event: &Event) -> Result<String, Error> {
+    let data = crypto::seal(serde_json::to_vec(event)?)?;
+    match crypto::is_sealed(&data) {
+        true => Ok(STANDARD.encode(data)),
+        false => Ok(String::from_utf8_lossy(&data).into_owned()),
+    }
+}
+
+/// Lines of either kind are read, as encryption may have been enabled or
+/// disabled since earlier ones were written
+fn decode(line: &str) -> Result<Event, Error> {
+    if line.starts_with('{') {
+        return Ok(serde_json::from_str(line)?);
+    }
+    let data = crypto::unseal(STANDARD.decode(line)?)?;
+    Ok(serde_json::from_slice(&data)?)
+}
+
+fn replay(events: &[Event]) -> BTreeMap<ContentKey, Known> {
+    let mut known = BTreeMap::new();
+    for event in events {
+        match &event.kind {
+            EventKind::Appeared { read, payment_status } => {
+                let item = Known {
+                    read: *read,
+                    payment_status: payment_status.clone(),
+                    sender_name: event.sender_name.clone(),
+                    subject: event.subject.clone(),
+                };
+                known.insert(event.key.clone(), item);
+            }
+            EventKind::MarkedRead => {
+                if let Some(item) = known.get_mut(&event.key) {
+                    item.read = true;
+                }
+            }
+            EventKind::PaymentStatusChanged { to, .. } => {
+                if let Some(item) = known.get_mut(&event.key) {
+                    item.payment_status = to.clone();
+                }
+            }
+            EventKind::Removed => {
+                known.remove(&event.key);
+            }
+        }
+    }
+    known
+}
+
+fn changes(
+    known: &BTreeMap<ContentKey, Known>,
+    listing: &InboxListing,
+    skipped: &[Option<ContentKey>],
+) -> Vec<Event> {
+    let now = Utc::now();
+    let event = |item: &InboxItem, kind| Event {
+        at: now,
+        key: item.key.clone(),
+        sender_name: item.sender_name.clone(),
+        subject: item.subject.clone(),
+        kind,
+    };
+
+    let mut events = Vec::new();
+    for entry in listing.iter() {
+        let item = &entry.item;
+        let read = item.status == Status::Read;
+        let Some(previous) = known.get(&item.key) else {
+            let payment_status = item.payment_status.clone();
+            events.push(event(
+                item,
+                EventKind::Appeared { read, payment_status },
+            ));
+            continue;
+        };
+        if read && !previous.read {
+            events.push(event(item, EventKind::MarkedRead));
+        }
+        if item.payment_status != previous.payment_status {
+            let kind = EventKind::PaymentStatusChanged {
+                from: previous.payment_status.clone(),
+                to: item.payment_status.clone(),
+            };
+            events.push(event(item, kind));
+        }
+    }
+
+    // An item that did not decode is still there. Without its key none can
+    // be told to be gone.
+    if skipped.contains(&None) {
+        return events;
+    }
+    for (key, previous) in known {
+        if skipped.contains(&Some(key.clone())) {
+            continue;
+        }
+        if !listing.iter().any(|entry| &entry.item.key == key) {
+            events.push(Event {
+                at: now,
+                key: key.clone(),
+                sender_name: previous.sender_name.clone(),
+                subject: previous.subject.clone(),
+                kind: EventKind::Removed,
+            });
+        }
+    }
+    events
+}
+
+/// Compares a freshly fetched listing with the journal and appends whatever
+/// changed since the last time the inbox was seen. The keys of items left
+/// out of the listing for not decoding are not taken as removed.
+pub fn record(
+    profile: &str,
+    listing: &InboxListing,
+    skipped: &[Option<ContentKey>],
+) -> Result<(), Error> {
+    let known = replay(&load(profile)?);
+    let events = changes(&known, listing, skipped);
+    if events.is_empty() {
+        return Ok(());
+    }
+
+    let mut file = File::options()
+        .append(true)
+        .create(true)
+        .open(journal_path(profile)?)?;
+    for event in events {
+        writeln!(file, "{}", encode(&event)?)?;
+    }
+    Ok(())
+}
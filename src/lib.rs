@@ -1,7 +1,33 @@
+pub mod api;
+pub mod cache;
 pub mod cli;
 pub mod client;
+pub mod config;
+pub mod crypto;
+pub mod dbus;
 pub mod error;
+pub mod filename;
+pub mod filter;
+pub mod fulltext;
+#[cfg(all(feature = "fuse", any(target_os = "linux", target_os = "macos")))]
 pub mod fuse;
+pub mod i18n;
+pub mod index;
+pub mod journal;
+pub mod logging;
+pub mod mail;
 pub mod model;
+#[cfg(all(feature = "fuse", any(target_os = "linux", target_os = "macos")))]
+pub mod mounts;
+pub mod notify;
+pub mod progress;
+#[cfg(unix)]
+pub mod serve;
+pub mod settings;
+pub mod state;
+pub mod storage;
+pub mod table;
+pub mod temp;
 pub mod tui;
 pub mod util;
+pub mod watch;
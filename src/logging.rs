@@ -0,0 +1,272 @@
+use chrono::Local;
+use std::{
+    collections::VecDeque,
+    fs::{self, File},
+    io::{self, stderr, IsTerminal, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, OnceLock},
+};
+use thiserror::Error;
+
+use crate::tui::terminal;
+use tracing_subscriber::{
+    fmt::{self, format::FmtSpan, MakeWriter},
+    prelude::*,
+    EnvFilter,
+};
+
+/// Size at which the log is moved aside to `<name>.1` and started over, so
+/// that at most twice this is kept
+const MAX_LOG_SIZE: u64 = 10 * 1024 * 1024;
+
+/// Read for the level when `--log-level` is not given
+const LEVEL_ENV: &str = "LOGLEVEL";
+
+const REDACTED: &str = "REDACTED";
+
+/// What the TUI console shows, whatever the level of the log file, so that
+/// the requests being made can always be followed there
+const CONSOLE_LEVEL: &str = "warn,kivinge=debug";
+
+/// Lines the console keeps
+const CONSOLE_LINES: usize = 500;
+
+static CONSOLE: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+
+static LOG_PATH: OnceLock<PathBuf> = OnceLock::new();
+
+static VERBOSITY: OnceLock<Verbosity> = OnceLock::new();
+
+/// How much is printed besides the results, as given by `-q` and `-v`
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Verbosity {
+    /// Only the results, e.g. listings and the paths of downloads
+    Quiet,
+    #[default]
+    Normal,
+    /// The log as well, on stderr, in more detail for each `-v`
+    Verbose(u8),
+}
+
+impl Verbosity {
+    pub fn from_args(quiet: bool, verbose: u8) -> Verbosity {
+        match (quiet, verbose) {
+            (true, _) => Verbosity::Quiet,
+            (false, 0) => Verbosity::Normal,
+            (false, verbose) => Verbosity::Verbose(verbose),
+        }
+    }
+
+    /// What is mirrored to stderr
+    fn stderr_filter(self) -> Option<EnvFilter> {
+        let level = match self {
+            Verbosity::Quiet | Verbosity::Normal => return None,
+            Verbosity::Verbose(1) => "warn,kivinge=info",
+            Verbosity::Verbose(2) => "info,kivinge=debug",
+            Verbosity::Verbose(_) => "debug,kivinge=trace",
+        };
+        Some(EnvFilter::new(level))
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("invalid log level '{0}'")]
+    InvalidLevel(String),
+
+    #[error("IO error")]
+    IOError(#[from] io::Error),
+}
+
+/// Logs to `file`, or to `kivinge.log` in the state dir, at `level` or the
+/// one in `LOGLEVEL`. Levels are given as for `RUST_LOG`, e.g. `debug` or
+/// `kivinge::client=trace`. With `Verbosity::Verbose` the log is mirrored to
+/// stderr too.
+pub fn init(
+    level: Option<&str>,
+    file: Option<PathBuf>,
+    verbosity: Verbosity,
+) -> Result<(), Error> {
+    let filter = match level {
+        Some(level) => EnvFilter::try_new(level)
+            .map_err(|_| Error::InvalidLevel(level.to_string()))?,
+        None => EnvFilter::from_env(LEVEL_ENV),
+    };
+    let path = file.unwrap_or_else(|| {
+        dirs::state_dir().unwrap_or(".".into()).join("kivinge.log")
+    });
+    let file_layer = fmt::layer()
+        .with_writer(LogWriter::open(path.clone())?)
+        .with_ansi(false)
+        .with_span_events(FmtSpan::ENTER);
+    let console_layer = fmt::layer()
+        .with_writer(|| ConsoleWriter)
+        .with_ansi(false)
+        .with_target(false)
+        .without_time();
+    let stderr_layer = verbosity.stderr_filter().map(|filter| {
+        fmt::layer()
+            .with_writer(|| StderrWriter)
+            .with_ansi(stderr().is_terminal())
+            .with_target(verbosity != Verbosity::Verbose(1))
+            .without_time()
+            .with_filter(filter)
+    });
+    tracing_subscriber::registry()
+        .with(file_layer.with_filter(filter))
+        .with(console_layer.with_filter(EnvFilter::new(CONSOLE_LEVEL)))
+        .with(stderr_layer)
+        .init();
+    _ = LOG_PATH.set(path);
+    _ = VERBOSITY.set(verbosity);
+    Ok(())
+}
+
+/// As given when logging started
+pub fn verbosity() -> Verbosity {
+    VERBOSITY.get().copied().unwrap_or_default()
+}
+
+/// Whether only the results are to be printed
+pub fn quiet() -> bool {
+    verbosity() == Verbosity::Quiet
+}
+
+/// Where the log is written, once logging has started
+pub fn log_path() -> Option<&'static Path> {
+    LOG_PATH.get().map(PathBuf::as_path)
+}
+
+/// The last lines for the TUI console, oldest first. Debug lines from
+/// kivinge are among them even when the log file leaves them out.
+pub fn console_lines() -> Vec<String> {
+    let console = CONSOLE.lock().unwrap_or_else(|error| error.into_inner());
+    console.iter().cloned().collect()
+}
+
+struct LogFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+}
+
+impl LogFile {
+    fn open(path: PathBuf) -> io::Result<LogFile> {
+        let file = File::options().append(true).create(true).open(&path)?;
+        let size = file.metadata()?.len();
+        Ok(LogFile { path, file, size })
+    }
+
+    /// Replaces the previous rotated log with this one and starts a new one
+    fn rotate(&mut self) -> io::Result<()> {
+        fs::rename(&self.path, rotated_path(&self.path))?;
+        *self = LogFile::open(self.path.clone())?;
+        Ok(())
+    }
+}
+
+fn rotated_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".1");
+    path.with_file_name(name)
+}
+
+/// Writes log events with bearer tokens left out, rotating the file when it
+/// grows too large. Events are written whole, so an event is never split
+/// across two files.
+#[derive(Clone)]
+struct LogWriter(Arc<Mutex<LogFile>>);
+
+impl LogWriter {
+    fn open(path: PathBuf) -> io::Result<LogWriter> {
+        Ok(LogWriter(Arc::new(Mutex::new(LogFile::open(path)?))))
+    }
+}
+
+impl Write for LogWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut log = self.0.lock().map_err(|_| io::ErrorKind::Other)?;
+        let event = redact(&String::from_utf8_lossy(buf));
+        if log.size > 0 && log.size + event.len() as u64 > MAX_LOG_SIZE {
+            log.rotate()?;
+        }
+        log.file.write_all(event.as_bytes())?;
+        log.size += event.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let mut log = self.0.lock().map_err(|_| io::ErrorKind::Other)?;
+        log.file.flush()
+    }
+}
+
+impl<'a> MakeWriter<'a> for LogWriter {
+    type Writer = LogWriter;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        self.clone()
+    }
+}
+
+/// Keeps events for the console, each line with the local time in front
+struct ConsoleWriter;
+
+impl Write for ConsoleWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let event = redact(&String::from_utf8_lossy(buf));
+        let time = Local::now().format("%H:%M:%S");
+        let mut console =
+            CONSOLE.lock().unwrap_or_else(|error| error.into_inner());
+        for line in event.lines() {
+            if console.len() == CONSOLE_LINES {
+                console.pop_front();
+            }
+            console.push_back(format!("{time} {line}"));
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Mirrors log events to stderr, except while the TUI is shown, where they
+/// would be drawn over it. The TUI has the console for them.
+struct StderrWriter;
+
+impl Write for StderrWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if !terminal::is_loaded() {
+            let event = redact(&String::from_utf8_lossy(buf));
+            stderr().write_all(event.as_bytes())?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        stderr().flush()
+    }
+}
+
+/// The text with the token after every `Bearer` replaced
+fn redact(text: &str) -> String {
+    let is_token = |c: char| {
+        c.is_ascii_alphanumeric()
+            || matches!(c, '-' | '.' | '_' | '~' | '+' | '/' | '=')
+    };
+    let mut redacted = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("Bearer ") {
+        let (before, after) = rest.split_at(start + "Bearer ".len());
+        redacted.push_str(before);
+        let token_len = after.find(|c| !is_token(c)).unwrap_or(after.len());
+        if token_len > 0 {
+            redacted.push_str(REDACTED);
+        }
+        rest = &after[token_len..];
+    }
+    redacted.push_str(rest);
+    redacted
+}
@@ -0,0 +1,101 @@
+//! Inbox items as email messages, with the parts of the item as MIME parts
+
+use lettre::{
+    message::{
+        header::ContentType, Attachment, Mailbox, MultiPart, SinglePart,
+    },
+    Message,
+};
+use std::time::SystemTime;
+use thiserror::Error;
+
+use crate::model::content::{InboxEntry, ItemDetails};
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("item has {parts} parts but {contents} contents were given")]
+    MissingContents { parts: usize, contents: usize },
+
+    #[error("failed to build message: {0}")]
+    MessageError(#[from] lettre::error::Error),
+}
+
+/// The item as a message from `from` to `to`, with the name of the sender
+/// of the item in front of the `from` address. `contents` holds what each
+/// part of the item contains, in the order of `details.parts`. Parts that
+/// Kivra has inline become part of the text, the rest become attachments.
+pub fn message(
+    entry: &InboxEntry,
+    details: &ItemDetails,
+    contents: &[impl AsRef<[u8]>],
+    from: &Mailbox,
+    to: &[Mailbox],
+) -> Result<Message, Error> {
+    if contents.len() != details.parts.len() {
+        return Err(Error::MissingContents {
+            parts: details.parts.len(),
+            contents: contents.len(),
+        });
+    }
+    let item = &entry.item;
+    let sender =
+        Mailbox::new(Some(item.sender_name.clone()), from.email.clone());
+    let builder = Message::builder()
+        .from(sender)
+        .subject(&item.subject)
+        .date(SystemTime::from(item.created_at));
+    let builder = to.iter().fold(builder, |builder, to| builder.to(to.clone()));
+
+    let mut body =
+        MultiPart::mixed().singlepart(SinglePart::plain(intro(entry)));
+    for (index, (part, contents)) in
+        details.parts.iter().zip(contents).enumerate()
+    {
+        let contents = contents.as_ref().to_vec();
+        let content_type = content_type(&part.content_type);
+        body = body.singlepart(match part.body {
+            Some(_) => {
+                SinglePart::builder().content_type(content_type).body(contents)
+            }
+            None => {
+                let name = details
+                    .attachment_name_for(index, &contents)
+                    .expect("the part is one of the details");
+                Attachment::new(name).body(contents, content_type)
+            }
+        });
+    }
+    Ok(builder.multipart(body)?)
+}
+
+/// What the text of the message starts with
+fn intro(entry: &InboxEntry) -> String {
+    let item = &entry.item;
+    let mut intro = format!(
+        "{} sent this to Kivra on {}.\n",
+        item.sender_name,
+        item.created_at.format("%Y-%m-%d")
+    );
+    if let (Some(amount), Some(currency)) = (item.amount, &item.currency) {
+        intro.push_str(&format!("\nAmount: {amount} {currency}\n"));
+    }
+    if let Some(due_date) = &item.due_date {
+        intro.push_str(&format!("Due: {}\n", due_date.0));
+    }
+    intro
+}
+
+/// The content type of a part, which for text is taken to be UTF-8 unless it
+/// says otherwise
+fn content_type(content_type: &str) -> ContentType {
+    let content_type = match content_type.starts_with("text/")
+        && !content_type.contains("charset")
+    {
+        true => format!("{content_type}; charset=utf-8"),
+        false => content_type.to_string(),
+    };
+    ContentType::parse(&content_type).unwrap_or_else(|_| {
+        ContentType::parse("application/octet-stream")
+            .expect("the type is valid")
+    })
+}
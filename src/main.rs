@@ -1,26 +1,100 @@
-use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
+use chrono::{Local, TimeDelta, Utc};
+use clap::{ArgAction, Args, CommandFactory, Parser, Subcommand, ValueEnum};
 use clap_complete::{
     self,
     shells::{Bash, PowerShell, Zsh},
     Generator,
 };
+#[cfg(all(
+    feature = "fuse",
+    any(target_os = "linux", target_os = "macos")
+))]
 use fork::Fork;
-use std::{fs::File, path::PathBuf};
-use tracing_subscriber::{
-    fmt::{self, format::FmtSpan},
-    prelude::*,
-    EnvFilter,
+use lettre::message::Mailbox;
+use ratatui::style::Color;
+use reqwest::Url;
+use std::{
+    fs,
+    io::{stdin, IsTerminal},
+    path::PathBuf,
+    process,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
 };
+use tracing::warn;
 
+#[cfg(unix)]
+use kivinge::serve;
 use kivinge::{
-    cli,
-    client::{self, session, Client},
+    cache::{self, AttachmentCache},
+    cli::{self, bundle::Recipient},
+    client::{
+        self, login::LoginFlow, session, tape::Tape, CachingClient, Client,
+        LoginOptions, MergedClient, MockOptions,
+    },
+    config::{self, Config, Existing, StartupView},
+    crypto,
     error::Error,
-    fuse,
-    model::content::InboxItem,
-    tui::{self, inbox_item::ItemViewResult, terminal::LoadedTerminal},
-    util::{download_attachment, get_entry_by_id, open_attachment},
+    filter::Filter,
+    fulltext, i18n,
+    index::{self, Contents},
+    journal,
+    logging::{self, Verbosity},
+    model::{
+        content::{
+            Folder, InboxEntry, InboxFilter, ItemRef, SenderKey, Status,
+        },
+        user::Actor,
+    },
+    notify::{self, Forward, Notifier, Webhook},
+    progress::Progress,
+    settings::{self, SETTINGS},
+    state, storage, table, temp, tr,
+    tui::{
+        self,
+        bulk::{BulkAction, BulkView},
+        copy::CopyField,
+        dashboard::{DashboardView, DashboardViewResult},
+        image,
+        inbox::{InboxView, InboxViewResult},
+        inbox_item::{ItemView, ItemViewResult, Preview},
+        receipts::{ReceiptsView, ReceiptsViewResult},
+        settings::{SettingsView, SettingsViewResult},
+        stack::{Nav, Screen, ViewStack},
+        tabs::Tab,
+        terminal::LoadedTerminal,
+        unreachable::{UnreachableChoice, UnreachableView},
+        Data, Message, TuiView,
+    },
+    util::{
+        copy_to_clipboard, download_all_attachments, download_attachment,
+        free_path, get_attachment_body, get_entry, get_receipt_by_id,
+        open_attachment, parse_age, with_contents, Destination,
+    },
+    watch,
 };
+#[cfg(all(
+    feature = "fuse",
+    any(target_os = "linux", target_os = "macos")
+))]
+use kivinge::{fuse, mounts};
+
+// Sessions about to expire are renewed before mounting
+#[cfg(all(feature = "fuse", any(target_os = "linux", target_os = "macos")))]
+const LONG_OPERATION_MARGIN: TimeDelta = TimeDelta::minutes(30);
+
+/// Exit status when a login is needed but may not be asked for, for scripts
+/// to tell from other failures
+const EXIT_LOGIN_REQUIRED: i32 = 3;
+
+/// Exit status of `login --poll` while the login waits for BankID
+const EXIT_LOGIN_PENDING: i32 = 4;
+
+const ITEM_HELP: &str =
+    "Item id, content key, or words from sender and subject as sender/subject";
 
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
@@ -28,10 +102,166 @@ struct CliArgs {
     #[arg(long)]
     mock: bool,
 
+    #[arg(long, value_name = "DIR", conflicts_with_all = ["mock", "replay"], help = "Record anonymized API traffic to a directory")]
+    record: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "DIR",
+        conflicts_with = "mock",
+        help = "Replay API traffic recorded with --record"
+    )]
+    replay: Option<PathBuf>,
+
+    #[arg(long, global = true, default_value = session::DEFAULT_PROFILE, value_parser = session::parse_profile)]
+    profile: String,
+
+    #[arg(
+        long,
+        global = true,
+        value_name = "ORGNR",
+        help = "Use the inbox of a company you represent, see `kivinge companies`"
+    )]
+    company: Option<String>,
+
+    #[arg(
+        long,
+        global = true,
+        value_name = "LEVEL",
+        help = "Log at this level, e.g. debug, instead of the one in LOGLEVEL"
+    )]
+    log_level: Option<String>,
+
+    #[arg(
+        long,
+        global = true,
+        value_name = "PATH",
+        help = "Log here instead of to kivinge.log in the state dir"
+    )]
+    log_file: Option<PathBuf>,
+
+    #[arg(
+        long,
+        global = true,
+        help = "Never show the TUI or ask anything, failing instead, e.g. when not logged in"
+    )]
+    non_interactive: bool,
+
+    #[arg(
+        short,
+        long,
+        global = true,
+        help = "Answer yes to confirmations instead of asking"
+    )]
+    yes: bool,
+
+    #[arg(
+        short,
+        long,
+        global = true,
+        help = "Print only results, such as listings and the paths of downloads"
+    )]
+    quiet: bool,
+
+    #[arg(
+        short,
+        long,
+        global = true,
+        action = ArgAction::Count,
+        conflicts_with = "quiet",
+        help = "Print the log to stderr as well, -vv for more detail"
+    )]
+    verbose: u8,
+
+    // Last, as its help heading also applies to the arguments after it
+    #[command(flatten)]
+    mock_args: MockArgs,
+
     #[command(subcommand)]
     command: Command,
 }
 
+/// What to do about files where downloads go, download.existing in the
+/// config unless given
+#[derive(Args, Clone, Debug)]
+#[group(multiple = false)]
+struct ExistingArgs {
+    #[arg(long, help = "Replace files that are in the way")]
+    overwrite: bool,
+    #[arg(
+        long,
+        help = "Leave files that are in the way and skip those downloads"
+    )]
+    skip_existing: bool,
+    #[arg(long, help = "Download to a free name instead, e.g. name-2.pdf")]
+    rename: bool,
+}
+
+impl ExistingArgs {
+    fn policy(&self, config: &Config) -> Existing {
+        match (self.overwrite, self.skip_existing, self.rename) {
+            (true, _, _) => Existing::Overwrite,
+            (_, true, _) => Existing::Skip,
+            (_, _, true) => Existing::Rename,
+            _ => config.download.existing,
+        }
+    }
+}
+
+#[derive(Args, Clone, Debug)]
+#[command(next_help_heading = "Mock client")]
+struct MockArgs {
+    #[arg(
+        long,
+        value_name = "DIR",
+        requires = "mock",
+        help = "Read fixtures from a directory, named like those in src/client/test_data"
+    )]
+    mock_data: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "MS",
+        default_value_t = 0,
+        requires = "mock",
+        help = "Delay every mock API call"
+    )]
+    mock_latency: u64,
+
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..), requires = "mock", help = "Fail every Nth mock API call with HTTP 500")]
+    mock_fail_every: Option<u32>,
+
+    #[arg(
+        long,
+        requires = "mock",
+        help = "Fail mock API calls as if the session had expired"
+    )]
+    mock_expired: bool,
+
+    #[arg(long, requires = "mock", help = "Serve an empty inbox")]
+    mock_empty: bool,
+
+    #[arg(
+        long,
+        requires = "mock",
+        help = "Fail mock API calls as if there were no network"
+    )]
+    mock_unreachable: bool,
+}
+
+impl From<MockArgs> for MockOptions {
+    fn from(args: MockArgs) -> Self {
+        MockOptions {
+            data_dir: args.mock_data,
+            latency: Duration::from_millis(args.mock_latency),
+            fail_every: args.mock_fail_every,
+            expired_session: args.mock_expired,
+            empty_inbox: args.mock_empty,
+            unreachable: args.mock_unreachable,
+        }
+    }
+}
+
 #[derive(Subcommand, Debug)]
 enum Command {
     #[command(about = "Generate shell completion script")]
@@ -40,38 +270,438 @@ enum Command {
         shell: CompletionsShell,
     },
 
+    #[command(about = "Generate man pages")]
+    Manpage {
+        #[arg(default_value = ".")]
+        dir: PathBuf,
+    },
+
     #[command(about = "Log in to Kivra")]
-    Login,
+    Login {
+        #[arg(long, help = "Open BankID on this device instead of a QR code")]
+        this_device: bool,
+
+        #[arg(long, help = "Print the QR code to stdout instead of a TUI")]
+        no_tui: bool,
+
+        #[arg(
+            long,
+            conflicts_with_all = ["poll", "this_device", "no_tui"],
+            help = "Start a login and print the QR data and a token to poll it with"
+        )]
+        start: bool,
+
+        #[arg(
+            long,
+            value_name = "TOKEN",
+            conflicts_with_all = ["this_device", "no_tui"],
+            help = "Check on a login started with --start, exiting with 4 while it waits for BankID"
+        )]
+        poll: Option<String>,
+
+        #[arg(long, value_enum, default_value_t)]
+        output: cli::whoami::Format,
+    },
 
     #[command(about = "List all items in the inbox")]
-    List,
+    List {
+        #[arg(long, help = "Only list items from this sender key")]
+        sender: Option<String>,
+
+        #[arg(long, conflicts_with = "archive", help = "List trashed items")]
+        trash: bool,
+
+        #[arg(long, help = "List archived items")]
+        archive: bool,
+
+        #[arg(
+            long,
+            value_name = "EXPR",
+            help = "Only list items matching this, e.g. 'unread && year=2024'"
+        )]
+        filter: Option<Filter>,
+
+        #[arg(long, value_delimiter = ',', value_parser = table::parse_column::<InboxEntry>, default_value = cli::inbox::DEFAULT_COLUMNS, help = "Columns to show, in order")]
+        columns: Vec<String>,
+
+        #[arg(
+            long,
+            help = "List the inboxes of all configured profiles together, with a profile column"
+        )]
+        all_profiles: bool,
+
+        #[arg(
+            long,
+            value_enum,
+            default_value_t = table::Format::Table,
+            help = "Print a table, or CSV/TSV/JSON for spreadsheets and scripts"
+        )]
+        output: table::Format,
+
+        #[arg(
+            long,
+            value_enum,
+            help = "Show when items were created as a date or as how long ago, the config's timestamps by default (absolute for CSV/TSV/JSON)"
+        )]
+        timestamps: Option<table::Timestamps>,
+
+        #[arg(
+            long,
+            help = "Also show the number and total size of attachments, fetching the details of items not listed this way before"
+        )]
+        details: bool,
+    },
+
+    #[command(
+        about = "Find items by sender and subject, and with --content by the text of their attachments"
+    )]
+    Search {
+        words: String,
+
+        #[arg(
+            long,
+            help = "Also search the text of attachments that have been downloaded or indexed"
+        )]
+        content: bool,
+
+        #[arg(long, value_delimiter = ',', value_parser = table::parse_column::<cli::search::SearchHit>, default_value = cli::inbox::DEFAULT_COLUMNS, help = "Columns to show, in order, with match added for --content")]
+        columns: Vec<String>,
+
+        #[arg(
+            long,
+            value_enum,
+            default_value_t = table::Format::Table,
+            help = "Print a table, or CSV/TSV/JSON for spreadsheets and scripts"
+        )]
+        output: table::Format,
+    },
+
+    #[command(about = "List senders with item and unread counts")]
+    Senders,
+
+    #[command(
+        about = "Summarize the inbox: items per sender and year, billed amounts per month and the largest attachments"
+    )]
+    Stats {
+        #[arg(long, value_enum, default_value_t)]
+        output: cli::stats::Format,
+    },
+
+    #[command(
+        about = "List agreements such as autogiro, or the items of one agreement"
+    )]
+    Agreements { agreement_key: Option<String> },
 
     #[command(about = "View inbox item")]
-    View { item_id: u32 },
+    View {
+        #[arg(help = ITEM_HELP)]
+        item: ItemRef,
+        #[arg(
+            long,
+            help = "Copy the content key to the clipboard and print it instead"
+        )]
+        copy_key: bool,
+    },
 
     #[command(about = "Download attachment")]
     Download {
-        item_id: u32,
-        attachment_num: u32,
-        #[arg(default_value = ".")]
-        download_dir: PathBuf,
+        #[arg(required_unless_present = "filter", conflicts_with = "filter", help = ITEM_HELP)]
+        item: Option<ItemRef>,
+        #[arg(required_unless_present = "filter", conflicts_with = "filter")]
+        attachment_num: Option<u32>,
+        #[arg(
+            conflicts_with = "filter",
+            help = "Directory to download to, download.dir in the config by default"
+        )]
+        download_dir: Option<PathBuf>,
+        #[arg(
+            long,
+            value_name = "EXPR",
+            help = "Download every attachment of the items matching this, e.g. 'sender=Skatteverket && year=2024'"
+        )]
+        filter: Option<Filter>,
+        #[arg(
+            long,
+            value_name = "DIR",
+            requires = "filter",
+            conflicts_with = "item",
+            help = "Directory to download the items matching --filter to, download.dir in the config by default"
+        )]
+        to: Option<PathBuf>,
+        #[command(flatten)]
+        existing: ExistingArgs,
     },
 
     #[command(about = "Open attachment")]
-    Open { item_id: u32, attachment_num: u32 },
+    Open {
+        #[arg(help = ITEM_HELP)]
+        item: ItemRef,
+        attachment_num: u32,
+    },
+
+    #[command(
+        about = "Remove the copies of attachments written out to be opened"
+    )]
+    CleanTemp {
+        #[arg(long, value_parser = parse_age, help = "Only remove copies older than this, e.g. 12h")]
+        older_than: Option<TimeDelta>,
+    },
+
+    #[command(about = "Set or remove a label on an inbox item")]
+    Label {
+        #[arg(help = ITEM_HELP)]
+        item: ItemRef,
+        label: String,
+        #[arg(long, help = "Remove the label instead of setting it")]
+        remove: bool,
+    },
+
+    #[command(about = "Copy a summary of an inbox item to the clipboard")]
+    Copy {
+        #[arg(help = ITEM_HELP)]
+        item: ItemRef,
+    },
+
+    #[command(about = "List receipts, or view a receipt")]
+    Receipts { receipt_id: Option<u32> },
+
+    #[command(about = "Show the journal of observed inbox changes")]
+    Log {
+        #[arg(long, value_parser = parse_age, help = "Only show changes newer than this, e.g. 30d")]
+        since: Option<TimeDelta>,
+    },
+
+    #[command(about = "Archive all items to a local file, WebDAV or S3")]
+    Backup {
+        #[arg(value_parser = storage::parse_target, help = "Path, http(s)://, webdav(s):// or s3://<bucket>/<key>; a .zst name compresses the archive")]
+        target: storage::Target,
+    },
+
+    #[command(about = "Register previously downloaded files in the index")]
+    Import { dir: PathBuf },
+
+    #[command(about = "Export items for handing to someone else")]
+    Export {
+        #[command(subcommand)]
+        command: ExportCommand,
+    },
+
+    #[command(about = "List the items of an export bundle, or unpack it")]
+    ImportBundle {
+        path: PathBuf,
+        #[arg(
+            long,
+            short,
+            help = "age identity file to decrypt with, GPG finds its own keys"
+        )]
+        identity: Option<PathBuf>,
+        #[arg(long, value_name = "DIR", help = "Unpack the bundle into DIR")]
+        extract: Option<PathBuf>,
+    },
 
     #[command(about = "Log out from Kivra")]
     Logout,
 
+    #[command(
+        about = "Show the session, cache and mounts, failing if not logged in"
+    )]
+    Status,
+
+    #[command(about = "List the company inboxes you can switch to")]
+    Companies,
+
+    #[command(about = "Show who is logged in")]
+    Whoami {
+        #[arg(
+            long,
+            help = "Fetch the full user profile from Kivra instead of what the session holds"
+        )]
+        full: bool,
+
+        #[arg(long, value_enum, default_value_t)]
+        output: cli::whoami::Format,
+    },
+
     #[command(about = "Start interactive terminal UI")]
-    Tui,
+    Tui {
+        #[arg(
+            long,
+            help = "Show the inboxes of all configured profiles together"
+        )]
+        all_profiles: bool,
+    },
+
+    #[command(about = "Poll the inbox and print new items as they arrive")]
+    Watch {
+        #[arg(long, default_value_t = 300, help = "Seconds between polls")]
+        interval: u64,
+
+        #[arg(
+            long,
+            help = "Publish the unread count on the D-Bus session bus"
+        )]
+        dbus: bool,
+
+        #[arg(
+            long = "webhook",
+            value_name = "URL",
+            help = "POST every new item to this URL, see [notify.webhook] in the config"
+        )]
+        webhooks: Vec<Url>,
+
+        #[arg(
+            long,
+            value_enum,
+            value_delimiter = ',',
+            help = "Also send new items to these, as set up in [notify] in the config"
+        )]
+        notify: Vec<notify::Backend>,
+
+        #[arg(
+            long,
+            value_name = "ADDRESS",
+            help = "Email every new item with its attachments to this address, see [notify.smtp] in the config"
+        )]
+        forward: Vec<Mailbox>,
+    },
+
+    #[cfg(unix)]
+    #[command(
+        about = "Serve the inbox to other programs as JSON-RPC on a Unix socket"
+    )]
+    Serve {
+        #[arg(long, help = "Where to create the socket")]
+        socket: PathBuf,
+    },
 
     #[command(about = "Mount inbox as FUSE filesystem")]
     Mount {
         mountpoint: PathBuf,
-        #[arg(short = 'o', default_value = "")]
-        mount_opts: String,
+        #[cfg(all(
+            feature = "fuse",
+            any(target_os = "linux", target_os = "macos")
+        ))]
+        #[arg(short = 'o', default_value = "", value_parser = fuse::parse_mount_options, help = "Comma separated options, e.g. since=2024-01-01,layout=sender")]
+        mount_opts: fuse::MountOptions,
+        // Taken so that asking for options gets the error about FUSE
+        #[cfg(not(all(
+            feature = "fuse",
+            any(target_os = "linux", target_os = "macos")
+        )))]
+        #[arg(short = 'o', default_value = "", hide = true)]
+        _mount_opts: String,
+    },
+
+    #[command(
+        about = "Write systemd units that run watch, and optionally mount, in the background"
+    )]
+    InstallService {
+        #[arg(
+            long,
+            help = "Install for the current user instead of system-wide"
+        )]
+        user: bool,
+
+        #[arg(long, value_name = "DIR", help = "Also mount the inbox here")]
+        mount: Option<PathBuf>,
+
+        #[arg(long, help = "Print the units instead of writing them")]
+        dry_run: bool,
+
+        #[arg(
+            last = true,
+            value_name = "WATCH_ARGS",
+            help = "Arguments for watch, e.g. -- --dbus --notify ntfy"
+        )]
+        watch_args: Vec<String>,
+    },
+
+    #[command(about = "Unmount a filesystem mounted by kivinge")]
+    Unmount { mountpoint: PathBuf },
+
+    #[command(about = "List filesystems mounted by kivinge")]
+    Mounts,
+
+    #[command(about = "Manage the on-disk attachment cache")]
+    Cache {
+        #[command(subcommand)]
+        command: CacheCommand,
+    },
+
+    #[command(about = "Check the config file")]
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
     },
+
+    #[command(about = "Developer and troubleshooting tools")]
+    Dev {
+        #[command(subcommand)]
+        command: DevCommand,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum ExportCommand {
+    #[command(
+        about = "Write items with their attachments and metadata to one encrypted file"
+    )]
+    Bundle {
+        #[arg(required_unless_present = "filter", help = ITEM_HELP)]
+        items: Vec<ItemRef>,
+        #[arg(
+            long,
+            value_name = "EXPR",
+            help = "Also export the items matching this, e.g. 'year=2024 && label=tax'"
+        )]
+        filter: Option<Filter>,
+        #[arg(
+            long,
+            required = true,
+            value_name = "RECIPIENT",
+            help = "age public key (age1...), SSH key, or GPG key id or email to encrypt to"
+        )]
+        encrypt_to: Vec<Recipient>,
+        #[arg(
+            long,
+            short,
+            help = "File to write, kivinge-<date>.bundle in download.dir of the config by default"
+        )]
+        output: Option<PathBuf>,
+        #[command(flatten)]
+        existing: ExistingArgs,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum CacheCommand {
+    #[command(about = "Show how much space the cache uses")]
+    Info,
+
+    #[command(
+        about = "Convert cached attachments and responses to the configured format and encryption"
+    )]
+    Migrate,
+
+    #[command(about = "Delete all cached attachments and responses")]
+    Clear,
+}
+
+#[derive(Subcommand, Debug)]
+enum ConfigCommand {
+    #[command(
+        about = "Report unknown keys, bad values and conflicting options"
+    )]
+    Validate {
+        #[arg(help = "Config file to check instead of the default one")]
+        path: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum DevCommand {
+    #[command(about = "Check API access with a read-only sequence of calls")]
+    Selftest,
 }
 
 #[derive(ValueEnum, Debug, Clone)]
@@ -90,16 +720,32 @@ fn main() -> Result<(), Error> {
             Ok(())
         }
         Err(Error::ClientError(client::Error::LoginAborted)) => {
-            println!("Login aborted");
+            println!("{}", tr!("Login aborted"));
             Ok(())
         }
+        Err(Error::ClientError(client::Error::LoginRequired)) => {
+            eprintln!(
+                "Error: not logged in, and --non-interactive does not log in"
+            );
+            process::exit(EXIT_LOGIN_REQUIRED);
+        }
         Err(err) => Err(err),
     }
 }
 
 fn maybe_fork(cli_args: CliArgs) -> Result<Option<String>, Error> {
+    cli::prompt::init(cli_args.non_interactive, cli_args.yes);
+    #[cfg(all(
+        feature = "fuse",
+        any(target_os = "linux", target_os = "macos")
+    ))]
     if let Command::Mount { .. } = cli_args.command {
-        if let Fork::Parent(_) = fork::daemon(true, false)? {
+        // There is no terminal to ask for the passphrase on once detached
+        let prompt = !cli_args.non_interactive;
+        crypto::init(&config::load()?.encryption, prompt)?;
+        // Verbose, the log keeps going to the terminal after detaching
+        let keep_stdio = cli_args.verbose > 0;
+        if let Fork::Parent(_) = fork::daemon(true, keep_stdio)? {
             return Ok(None);
         }
     }
@@ -117,22 +763,55 @@ fn generate_completions<G: Generator>(gen: G) {
 }
 
 fn run(cli_args: CliArgs) -> Result<Option<String>, Error> {
-    let logpath = dirs::state_dir().unwrap_or(".".into()).join("kivinge.log");
-    let logfile = File::options().append(true).create(true).open(logpath)?;
-    tracing_subscriber::registry()
-        .with(
-            fmt::layer()
-                .with_writer(logfile.try_clone()?)
-                .with_span_events(FmtSpan::ENTER),
-        )
-        .with(EnvFilter::from_env("LOGLEVEL"))
-        .init();
-
-    let mut client: Box<dyn Client> = if cli_args.mock {
-        Box::new(client::MockClient::default())
-    } else {
-        Box::new(client::KivraClient::new()?)
+    let verbosity = Verbosity::from_args(cli_args.quiet, cli_args.verbose);
+    logging::init(
+        cli_args.log_level.as_deref(),
+        cli_args.log_file.clone(),
+        verbosity,
+    )?;
+
+    // Loading stops at the first problem, so check the file before that
+    if let Command::Config { command: ConfigCommand::Validate { path } } =
+        cli_args.command
+    {
+        return validate_config(path);
+    }
+
+    let config = config::load()?;
+    i18n::init(config.language);
+    crypto::init(&config.encryption, !cli_args.non_interactive)?;
+    if config.open.max_age > 0 {
+        let max_age = Duration::from_secs(config.open.max_age);
+        if let Err(error) = temp::clean(Some(max_age)) {
+            warn!("failed to remove old opened attachments: {error}");
+        }
+    }
+
+    let mut login_options = LoginOptions {
+        qr_renderer: config.login.qr_renderer,
+        ..LoginOptions::default()
     };
+    match cli_args.command {
+        Command::Login { this_device, no_tui, .. } => {
+            login_options.same_device = this_device;
+            login_options.headless = no_tui;
+        }
+        // These either show the terminal UI anyway or run detached
+        Command::Tui { .. } | Command::Mount { .. } => {}
+        _ => login_options.prompt = stdin().is_terminal(),
+    }
+
+    let mut client =
+        make_client(&cli_args, &config, &cli_args.profile, login_options)?;
+    if let Command::List { all_profiles: true, .. }
+    | Command::Tui { all_profiles: true } = cli_args.command
+    {
+        client = merge_profiles(client, &cli_args, &config, login_options)?;
+    }
+    if let Some(company) = &cli_args.company {
+        let company = cli::companies::find(client.get_companies()?, company)?;
+        client.set_actor(Actor::Company(company));
+    }
 
     match cli_args.command {
         Command::Completions { shell } => {
@@ -146,98 +825,1447 @@ fn run(cli_args: CliArgs) -> Result<Option<String>, Error> {
             Ok(None)
         }
 
-        Command::Login => {
+        Command::InstallService { user, mount, dry_run, watch_args } => {
+            let options = cli::service::Options {
+                profile: client.profile(),
+                user,
+                mount: mount.as_deref(),
+                watch_args: &watch_args,
+            };
+            let units = cli::service::units(&options)?;
+            let dir = cli::service::unit_dir(user)?;
+            if dry_run {
+                return Ok(Some(cli::service::format_dry_run(&units, &dir)));
+            }
+            let paths = cli::service::install(&units, &dir)?;
+            Ok(Some(cli::service::format_installed(&paths, user)))
+        }
+
+        Command::Manpage { dir } => {
+            clap_mangen::generate_to(CliArgs::command(), &dir)?;
+            Ok(Some(dir.to_string_lossy().to_string()))
+        }
+
+        Command::Login { start: true, output, .. } => {
+            let profile = client.profile().to_string();
+            let (token, flow) = LoginFlow::start_saved(&client, &profile)?;
+            Ok(Some(cli::login::format_started(&token, &flow, output)?))
+        }
+
+        Command::Login { poll: Some(token), output, .. } => {
+            let profile = client.profile().to_string();
+            let (flow, auth_token) =
+                LoginFlow::poll_saved(&client, &profile, &token)?;
+            let Some(auth_token) = auth_token else {
+                println!("{}", cli::login::format_pending(&flow, output)?);
+                process::exit(EXIT_LOGIN_PENDING);
+            };
+            let session =
+                session::make(auth_token.access_token, auth_token.id_token)?;
+            session::save(&session, &profile)?;
+            let user = &session.user_info;
+            Ok(Some(cli::login::format_logged_in(user, output)?))
+        }
+
+        Command::Login { .. } => {
+            // Before the session is revoked, as there is no one to log in
+            if cli_args.non_interactive {
+                return Err(client::Error::LoginRequired.into());
+            }
             client.revoke_auth_token()?;
             client.login()?;
-            Ok(Some("Login Successful".to_string()))
+            Ok(note(tr!("Login Successful")))
         }
 
-        Command::List => {
+        Command::List {
+            sender,
+            trash,
+            archive,
+            filter,
+            mut columns,
+            all_profiles,
+            output,
+            timestamps,
+            details,
+        } => {
+            let folder = match (trash, archive) {
+                (true, _) => Folder::Trash,
+                (_, true) => Folder::Archive,
+                _ => Folder::Inbox,
+            };
+            let mut inbox = client.get_folder_listing(folder)?;
+            if let Some(sender) = sender {
+                inbox = inbox.filtered(|entry| entry.item.sender == sender);
+            }
+            if let Some(filter) = filter {
+                let filter = with_contents(client.profile(), filter)?;
+                inbox = inbox.filtered(|entry| filter.matches(&entry.item));
+            }
+            if all_profiles && !columns.iter().any(|name| name == "profile") {
+                columns.insert(0, "profile".to_string());
+            }
+            if details {
+                for name in cli::inbox::DETAILS_COLUMNS {
+                    if !columns.iter().any(|column| column == name) {
+                        columns.push(name.to_string());
+                    }
+                }
+            }
+            // Details are only fetched for the columns that show them
+            if (columns.iter()).any(|name| {
+                cli::inbox::DETAILS_COLUMNS.contains(&name.as_str())
+            }) {
+                let mut cache = cache::summary_cache(client.profile())?;
+                inbox = cli::inbox::with_attachments(
+                    &mut client,
+                    &mut cache,
+                    inbox,
+                );
+            }
+            let timestamps = listing_timestamps(&config, output, timestamps);
+            let columns = timestamps.columns(&columns);
+            Ok(Some(cli::inbox::format(inbox, &columns, output)?))
+        }
+
+        Command::Search { words, content, columns, output } => {
+            let index = index::load(client.profile())?;
+            let filter = cli::search::filter(&words, content)
+                .with_contents(Arc::new(index.contents()));
+            let inbox = client.get_inbox_listing()?.filtered(|entry| {
+                entry.item.folder() != Folder::Trash
+                    && filter.matches(&entry.item)
+            });
+            if inbox.is_empty() {
+                return Ok(note(tr!("No items match")));
+            }
+            let timestamps = listing_timestamps(&config, output, None);
+            let columns = timestamps.columns(&columns);
+            let found = cli::search::format(
+                inbox, &index, &words, content, &columns, output,
+            )?;
+            Ok(Some(found))
+        }
+
+        Command::Senders => {
             let inbox = client.get_inbox_listing()?;
-            Ok(Some(cli::inbox::format(inbox)))
+            Ok(Some(cli::senders::format(inbox.senders())))
         }
 
-        Command::View { item_id } => {
+        Command::Stats { output } => {
+            let index = index::load(client.profile())?;
+            // A client that keeps no index has only its listing to go by
+            let items = match index.items.is_empty() {
+                true => {
+                    let listing = client.get_inbox_listing()?;
+                    cli::stats::items_from_listing(&listing, &index)
+                }
+                false => cli::stats::items_from_index(index),
+            };
+            let stats = cli::stats::compute(&items);
+            Ok(Some(cli::stats::format(&stats, output)?))
+        }
+
+        Command::Agreements { agreement_key: None } => {
+            let inbox = client.get_inbox_listing()?;
+            Ok(Some(cli::agreements::format(inbox.agreements())))
+        }
+
+        Command::Agreements { agreement_key: Some(key) } => {
+            let inbox = client.get_inbox_listing()?.filtered(|entry| {
+                entry.item.agreement_key.as_ref() == Some(&key)
+            });
+            if inbox.is_empty() {
+                return Err(Error::UserError(
+                    "No items refer to that agreement",
+                ));
+            }
+            let columns = cli::agreements::ITEM_COLUMNS.map(String::from);
+            Ok(Some(cli::inbox::format(inbox, &columns, table::Format::Table)?))
+        }
+
+        Command::View { item, copy_key } => {
             let inbox = client.get_inbox_listing()?;
-            let entry = get_entry_by_id(inbox, item_id)?;
+            let entry = get_entry(inbox, &item)?;
+            if copy_key {
+                copy_to_clipboard(&entry.item.key)?;
+                return Ok(Some(entry.item.key));
+            }
             let details = client.get_item_details(&entry.item.key)?;
-            Ok(Some(cli::inbox_item::format(details)?))
+            Ok(Some(cli::inbox_item::format(&entry.item, details)?))
+        }
+
+        Command::Download { filter: Some(filter), to, existing, .. } => {
+            let destination = Destination {
+                existing: existing.policy(&config),
+                ..Destination::new(&config, to)
+            };
+            let filter = with_contents(client.profile(), filter)?;
+            let inbox = client.get_inbox_listing()?;
+            let entries = inbox.filtered(|entry| filter.matches(&entry.item));
+            let mut progress = progress();
+            progress.start(entries.len());
+            let mut failed = 0;
+            for entry in entries {
+                progress.set_message(entry.to_string());
+                let downloaded = download_all_attachments(
+                    &mut client,
+                    &entry.item,
+                    &destination,
+                );
+                progress.suspend(|| match downloaded {
+                    Ok(paths) => {
+                        for path in paths {
+                            println!("{}", path.to_string_lossy());
+                        }
+                    }
+                    Err(error) => {
+                        eprintln!(
+                            "Failed to download item {}: {error}",
+                            entry.id
+                        );
+                        failed += 1;
+                    }
+                });
+                progress.advance();
+            }
+            match failed {
+                0 => Ok(None),
+                _ => Err(Error::UserError("Some items failed to download")),
+            }
         }
 
-        Command::Download { item_id, attachment_num, download_dir } => {
+        Command::Download {
+            item,
+            attachment_num,
+            download_dir,
+            existing,
+            ..
+        } => {
             let inbox = client.get_inbox_listing()?;
-            let entry = get_entry_by_id(inbox, item_id)?;
+            let item = item.ok_or(Error::AppError("Item is required"))?;
+            let attachment_num = attachment_num
+                .ok_or(Error::AppError("Attachment number is required"))?;
+            let entry = get_entry(inbox, &item)?;
+            let destination = Destination {
+                existing: existing.policy(&config),
+                ..Destination::new(&config, download_dir)
+            };
             let full_path = download_attachment(
                 &mut client,
                 &entry.item,
                 attachment_num,
-                download_dir,
+                &destination,
             )?;
             Ok(Some(full_path.to_string_lossy().to_string()))
         }
 
-        Command::Open { item_id, attachment_num } => {
+        Command::Open { item, attachment_num } => {
             let inbox = client.get_inbox_listing()?;
-            let entry = get_entry_by_id(inbox, item_id)?;
+            let entry = get_entry(inbox, &item)?;
             open_attachment(&mut client, &entry.item, attachment_num)?;
             Ok(None)
         }
 
+        Command::CleanTemp { older_than } => {
+            let age = older_than.map(|age| age.to_std().unwrap_or_default());
+            let removed = temp::clean(age)?;
+            Ok(note(tr!(
+                "Removed {removed} file(s) from {dir}",
+                removed = removed,
+                dir = temp::dir().display()
+            )))
+        }
+
+        Command::Label { item, label, remove } => {
+            let inbox = client.get_inbox_listing()?;
+            let entry = get_entry(inbox, &item)?;
+            match remove {
+                false => client.set_label(&entry.item.key, &label)?,
+                true => client.remove_label(&entry.item.key, &label)?,
+            }
+            Ok(None)
+        }
+
+        Command::Copy { item } => {
+            let inbox = client.get_inbox_listing()?;
+            let entry = get_entry(inbox, &item)?;
+            let snippet = cli::copy::format(&entry, &config.copy.template);
+            copy_to_clipboard(&snippet)?;
+            Ok(Some(snippet))
+        }
+
+        Command::Receipts { receipt_id: None } => {
+            let receipts = client.get_receipts()?;
+            Ok(Some(cli::receipts::format(receipts)))
+        }
+
+        Command::Receipts { receipt_id: Some(receipt_id) } => {
+            let receipts = client.get_receipts()?;
+            let entry = get_receipt_by_id(receipts, receipt_id)?;
+            let details = client.get_receipt_details(&entry.receipt.key)?;
+            Ok(Some(cli::receipts::format_details(&details)))
+        }
+
+        Command::Log { since } => {
+            let mut events = journal::load(client.profile())?;
+            if let Some(since) = since {
+                let cutoff = Utc::now() - since;
+                events.retain(|event| event.at >= cutoff);
+            }
+            Ok(Some(cli::log::format(&events)))
+        }
+
+        Command::Backup { target } => {
+            let compress = target.file_name().ends_with(".zst");
+            let upload = storage::open(&target, &config.storage)?;
+            let mut progress = progress();
+            let items =
+                cli::backup::run(&mut client, upload, compress, &mut progress)?;
+            Ok(note(tr!("{items} item(s) backed up", items = items)))
+        }
+
+        Command::Import { dir } => {
+            let mut progress = progress();
+            let imported = cli::import::run(&mut client, &dir, &mut progress)?;
+            Ok(Some(cli::import::format(&imported)))
+        }
+
+        Command::Export {
+            command:
+                ExportCommand::Bundle {
+                    items,
+                    filter,
+                    encrypt_to,
+                    output,
+                    existing,
+                },
+        } => {
+            let inbox = client.get_inbox_listing()?;
+            let mut entries = Vec::new();
+            for item in &items {
+                entries.push(get_entry(inbox.clone(), item)?);
+            }
+            if let Some(filter) = filter {
+                let filter = with_contents(client.profile(), filter)?;
+                entries.extend(
+                    inbox.filtered(|entry| filter.matches(&entry.item)),
+                );
+            }
+            entries.sort_by_key(|entry| entry.id);
+            entries.dedup_by_key(|entry| entry.id);
+            let output = match output {
+                Some(output) => output,
+                None => {
+                    let dir = config.download_dir();
+                    fs::create_dir_all(&dir)?;
+                    let date = Local::now().date_naive();
+                    dir.join(format!("kivinge-{date}.bundle"))
+                }
+            };
+            let Some(output) = free_path(&output, existing.policy(&config))?
+            else {
+                return Ok(note(tr!(
+                    "{path} already exists, skipped",
+                    path = output.display()
+                )));
+            };
+            cli::bundle::export(
+                &mut client,
+                &entries,
+                &encrypt_to,
+                &output,
+                &mut progress(),
+            )?;
+            if logging::quiet() {
+                return Ok(Some(output.display().to_string()));
+            }
+            Ok(Some(tr!(
+                "{items} item(s) exported to {path}",
+                items = entries.len(),
+                path = output.display()
+            )))
+        }
+
+        Command::ImportBundle { path, identity, extract } => {
+            let manifest = cli::bundle::import(
+                &path,
+                identity.as_deref(),
+                extract.as_deref(),
+            )?;
+            Ok(Some(cli::bundle::format(&manifest)))
+        }
+
         Command::Logout => {
-            client.revoke_auth_token()?;
-            session::delete_saved()?;
-            Ok(Some("Session token deleted".to_string()))
+            logout(&mut client)?;
+            Ok(note(tr!("Session token deleted")))
+        }
+
+        Command::Status => {
+            let session =
+                client.get_session().or(session::try_load(client.profile())?);
+            let responses = match config.cache.responses {
+                true => {
+                    let cache = cache::response_cache(client.profile())?;
+                    Some((cache.disk_usage()?.0, cache.last_stored()?))
+                }
+                false => None,
+            };
+            let status = cli::status::Status {
+                profile: client.profile().to_string(),
+                session,
+                responses,
+                mounts: mount_list()?,
+            };
+            println!("{}", cli::status::format(&status));
+            match status.is_logged_in() {
+                true => Ok(None),
+                false => Err(Error::UserError("Not logged in")),
+            }
+        }
+
+        Command::Companies => {
+            let companies = client.get_companies()?;
+            if companies.is_empty() {
+                return Ok(note(tr!("No company inboxes")));
+            }
+            Ok(Some(cli::companies::format(&companies, &client.actor())))
+        }
+
+        Command::Whoami { full, output } => {
+            let session = client.get_session_or_login()?;
+            let profile = match full {
+                true => Some(client.get_user_profile()?),
+                false => None,
+            };
+            let user = &session.user_info;
+            Ok(Some(cli::whoami::format(user, profile.as_ref(), output)?))
         }
 
-        Command::Tui => {
+        Command::Tui { .. } => {
             let mut terminal = tui::terminal::load()?;
-            show_inbox_tui(&mut terminal, &mut client)?;
+            let logged_out =
+                show_inbox_tui(&mut terminal, &mut client, &config);
+            if config.open.clean_on_exit {
+                temp::clean_opened();
+            }
+            match logged_out? {
+                true => Ok(note(tr!("Session token deleted"))),
+                false => Ok(None),
+            }
+        }
+
+        Command::Watch { interval, dbus, webhooks, notify, forward } => {
+            let config = &config.notify;
+            let mut notifiers = Vec::<Box<dyn Notifier>>::new();
+            for url in webhooks {
+                let webhook =
+                    Webhook::new(url, &config.webhook, config.attempts)?;
+                notifiers.push(Box::new(webhook));
+            }
+            for backend in notify {
+                notifiers.push(backend.notifier(config)?);
+            }
+            let forward = match forward.is_empty() {
+                true => None,
+                false => {
+                    Some(Forward::new(&config.smtp, forward, config.attempts)?)
+                }
+            };
+            let interval = Duration::from_secs(interval);
+            watch::run(
+                &mut client,
+                interval,
+                dbus,
+                &notifiers,
+                forward.as_ref(),
+            )?;
             Ok(None)
         }
 
-        Command::Mount { mountpoint, .. } => {
-            client.get_session_or_login()?;
-            fuse::mount(client, mountpoint.as_path())?;
+        #[cfg(unix)]
+        Command::Serve { socket } => {
+            serve::run(&mut client, &socket)?;
+            Ok(None)
+        }
+
+        #[cfg(all(
+            feature = "fuse",
+            any(target_os = "linux", target_os = "macos")
+        ))]
+        Command::Mount { mountpoint, mount_opts } => {
+            client.refresh_session_if_expiring(LONG_OPERATION_MARGIN)?;
+            let mountpoint = mountpoint.canonicalize()?;
+            mounts::register(&mountpoint, client.profile())?;
+            let result = fuse::mount(client, &mountpoint, mount_opts);
+            mounts::unregister(&mountpoint)?;
+            result?;
             Ok(None)
         }
+
+        #[cfg(all(
+            feature = "fuse",
+            any(target_os = "linux", target_os = "macos")
+        ))]
+        Command::Unmount { mountpoint } => {
+            let mountpoint = mountpoint.canonicalize().unwrap_or(mountpoint);
+            let Some(mount) = mounts::find(&mountpoint)? else {
+                return Err(Error::UserError(
+                    "Nothing is mounted there by kivinge",
+                ));
+            };
+            mount.stop()?;
+            Ok(note(tr!("Unmounted {path}", path = mountpoint.display())))
+        }
+
+        #[cfg(all(
+            feature = "fuse",
+            any(target_os = "linux", target_os = "macos")
+        ))]
+        Command::Mounts => Ok(Some(cli::mounts::format(mounts::list()?))),
+
+        #[cfg(not(all(
+            feature = "fuse",
+            any(target_os = "linux", target_os = "macos")
+        )))]
+        Command::Mount { .. } | Command::Unmount { .. } | Command::Mounts => {
+            Err(Error::AppError("kivinge was compiled without FUSE support"))
+        }
+
+        Command::Cache { command } => {
+            let cache =
+                AttachmentCache::open(client.profile(), config.cache.compress)?;
+            let mut responses = cache::response_cache(client.profile())?;
+            match command {
+                CacheCommand::Info => {
+                    let (entries, bytes) = cache.usage()?;
+                    let (responses, response_bytes) = responses.disk_usage()?;
+                    Ok(Some(format!(
+                        "{entries} attachment(s), {bytes} bytes\n\
+                         {responses} response(s), {response_bytes} bytes"
+                    )))
+                }
+                CacheCommand::Migrate => {
+                    let migrated = cache.migrate()?;
+                    let summaries = cache::summary_cache(client.profile())?;
+                    let converted =
+                        responses.migrate()? + summaries.migrate()?;
+                    Ok(note(tr!(
+                        "{migrated} attachment(s) and {converted} other \
+                         cache file(s) converted",
+                        migrated = migrated,
+                        converted = converted
+                    )))
+                }
+                CacheCommand::Clear => {
+                    cache.clear()?;
+                    responses.clear()?;
+                    cache::summary_cache(client.profile())?.clear()?;
+                    Ok(None)
+                }
+            }
+        }
+
+        Command::Config { .. } => Ok(None),
+
+        Command::Dev { command: DevCommand::Selftest } => {
+            let steps = cli::selftest::run(&mut client);
+            print!("{}", cli::selftest::format(&steps));
+            match steps.iter().any(cli::selftest::Step::failed) {
+                false => Ok(note(tr!("No step failed"))),
+                true => Err(Error::AppError("Self test failed")),
+            }
+        }
+    }
+}
+
+/// How a listing shows when items were created, unless `--timestamps` says
+fn listing_timestamps(
+    config: &Config,
+    output: table::Format,
+    timestamps: Option<table::Timestamps>,
+) -> table::Timestamps {
+    // Scripts reading CSV/TSV/JSON get dates unless they ask otherwise
+    timestamps.unwrap_or(match output {
+        table::Format::Table => config.timestamps,
+        table::Format::Csv | table::Format::Tsv | table::Format::Json => {
+            table::Timestamps::Absolute
+        }
+    })
+}
+
+/// Output that says how a command went rather than what it found, left out
+/// with `--quiet`
+fn note(text: impl Into<String>) -> Option<String> {
+    (!logging::quiet()).then(|| text.into())
+}
+
+/// A progress bar, or plain lines for logs with `--non-interactive` and
+/// among the log with `--verbose`
+fn progress() -> Progress {
+    match logging::verbosity() {
+        Verbosity::Quiet => Progress::hidden(),
+        Verbosity::Verbose(_) => Progress::lines(),
+        Verbosity::Normal if cli::prompt::non_interactive() => {
+            Progress::lines()
+        }
+        Verbosity::Normal => Progress::on_terminal(),
     }
 }
 
+/// Filesystems mounted by kivinge, with the profile of each
+#[cfg(all(feature = "fuse", any(target_os = "linux", target_os = "macos")))]
+fn mount_list() -> Result<Vec<(PathBuf, String)>, Error> {
+    let mounts = mounts::list()?;
+    Ok(mounts
+        .into_iter()
+        .map(|mount| (mount.mountpoint, mount.profile))
+        .collect())
+}
+
+#[cfg(not(all(
+    feature = "fuse",
+    any(target_os = "linux", target_os = "macos")
+)))]
+fn mount_list() -> Result<Vec<(PathBuf, String)>, Error> {
+    Ok(Vec::new())
+}
+
+fn validate_config(path: Option<PathBuf>) -> Result<Option<String>, Error> {
+    let path = match path {
+        Some(path) => path,
+        None => config::default_config_path()?,
+    };
+    if !path.exists() {
+        let path = path.display();
+        return Ok(note(tr!(
+            "{path} does not exist, defaults are used",
+            path = path
+        )));
+    }
+    let problems = cli::config::validate(&path)?;
+    print!("{}", cli::config::format(&path, &problems));
+    match problems.iter().any(|problem| problem.is_error()) {
+        false => Ok(note(tr!("Config is valid"))),
+        true => Err(Error::UserError("Config is invalid")),
+    }
+}
+
+fn tui_header(client: &impl Client, config: &Config) -> tui::Header {
+    let profile = client.profile();
+    let accent = config
+        .profiles
+        .get(profile)
+        .and_then(|profile_config| profile_config.color.as_ref())
+        .or(config.tui.color.as_ref())
+        .and_then(|color| color.parse().ok())
+        .unwrap_or(Color::Green);
+    let show_profile =
+        config.profiles.len() > 1 || profile != session::DEFAULT_PROFILE;
+    tui::Header {
+        user_name: client.get_session().map(|s| s.user_info.name),
+        profile: show_profile.then(|| profile.to_string()),
+        company: client.actor().company_name().map(str::to_string),
+        offline: client.is_offline(),
+        accent,
+    }
+}
+
+fn startup_filter(config: &Config) -> Result<InboxFilter, Error> {
+    Ok(match config.tui.startup_view {
+        StartupView::Inbox => InboxFilter::All,
+        StartupView::Unread => InboxFilter::Unread,
+        StartupView::Payments => InboxFilter::Payments,
+        StartupView::Last => state::load()?.last_view.unwrap_or_default(),
+    })
+}
+
+fn logout(client: &mut impl Client) -> Result<(), Error> {
+    client.revoke_auth_token()?;
+    session::delete_saved(client.profile())?;
+    Ok(())
+}
+
+fn make_client(
+    cli_args: &CliArgs,
+    config: &Config,
+    profile: &str,
+    login_options: LoginOptions,
+) -> Result<Box<dyn Client>, Error> {
+    let client: Box<dyn Client> = if cli_args.mock {
+        let options = MockOptions::from(cli_args.mock_args.clone());
+        Box::new(client::MockClient::default().with_options(options))
+    } else {
+        let mut client = client::KivraClient::new(&config.network)?
+            .with_profile(profile)
+            .with_login_options(login_options);
+        if cli_args.non_interactive {
+            client = client.non_interactive();
+        }
+        match (&cli_args.record, &cli_args.replay) {
+            (Some(dir), _) => client = client.with_tape(Tape::record(dir)?)?,
+            (_, Some(dir)) => client = client.with_tape(Tape::replay(dir)?)?,
+            // Requests served from disk would be missing from a recording
+            _ => {
+                if config.cache.attachments {
+                    let cache =
+                        AttachmentCache::open(profile, config.cache.compress)?;
+                    client = client.with_attachment_cache(cache);
+                }
+                if config.cache.responses {
+                    let cache = cache::response_cache(profile)?;
+                    client = client.with_response_cache(cache);
+                }
+            }
+        }
+        Box::new(client)
+    };
+    Ok(Box::new(
+        CachingClient::new(client)
+            .with_inbox_ttl(Duration::from_secs(config.cache.inbox_ttl))
+            .with_details_ttl(Duration::from_secs(config.cache.details_ttl)),
+    ))
+}
+
+/// A client for the inboxes of every configured profile, the one given by
+/// `--profile` first. Profiles that are not logged in are logged in one after
+/// another now, as the listings are fetched side by side.
+fn merge_profiles(
+    client: Box<dyn Client>,
+    cli_args: &CliArgs,
+    config: &Config,
+    login_options: LoginOptions,
+) -> Result<Box<dyn Client>, Error> {
+    if cli_args.company.is_some() {
+        return Err(Error::UserError(
+            "--company cannot be combined with --all-profiles",
+        ));
+    }
+    if cli_args.record.is_some() || cli_args.replay.is_some() {
+        return Err(Error::UserError(
+            "--record and --replay work with one profile at a time",
+        ));
+    }
+    let others: Vec<&String> = (config.profiles.keys())
+        .filter(|profile| **profile != cli_args.profile)
+        .collect();
+    if others.is_empty() {
+        return Err(Error::UserError(
+            "--all-profiles needs more profiles under [profiles] in the config",
+        ));
+    }
+
+    let mut clients = vec![(cli_args.profile.clone(), client)];
+    for profile in others {
+        let client = make_client(cli_args, config, profile, login_options)?;
+        clients.push((profile.clone(), client));
+    }
+    // The mock client has no sessions
+    if !cli_args.mock {
+        for (_, client) in &mut clients {
+            client.get_session_or_login()?;
+        }
+    }
+    Ok(Box::new(MergedClient::new(clients)))
+}
+
+/// What the screens of the TUI share
+struct App<'a, C> {
+    client: &'a mut C,
+    /// As changed in the settings, which are saved as they are changed
+    config: Config,
+}
+
+/// What a screen of the TUI hands the one under it when it is closed
+enum Outcome {
+    /// Back to the screen under it, with a note for its status bar
+    Back(Option<Message>),
+    /// The inbox, showing the tab
+    ShowTab(InboxFilter),
+    /// The inbox, showing the tab with what the search matches
+    ShowSearch(InboxFilter, Option<Filter>),
+    Quit,
+    LoggedOut,
+}
+
+type AppNav<'a, C> = Nav<App<'a, C>, Outcome, Error>;
+
+/// Returns whether the user logged out
 fn show_inbox_tui(
     terminal: &mut LoadedTerminal,
     client: &mut impl Client,
-) -> Result<(), Error> {
-    loop {
-        let user_info = client.get_session().map(|s| s.user_info);
-        let mut inbox_view = tui::inbox::InboxView::make(client)?;
-        let ret = tui::show(&mut inbox_view, terminal, user_info)?;
-        match ret {
-            Some(entry) => {
-                show_inbox_item_tui(terminal, client, entry.item)?;
+    config: &Config,
+) -> Result<bool, Error> {
+    let inbox = InboxScreen::new(client, config, startup_filter(config)?);
+    let mut app = App { client, config: config.clone() };
+    let out = ViewStack::new(Box::new(inbox)).run(&mut app, terminal)?;
+    Ok(matches!(out, Outcome::LoggedOut))
+}
+
+/// The inbox, which the other screens are opened over
+struct InboxScreen {
+    view: InboxView,
+    contents: Arc<Contents>,
+    actors: Vec<Actor>,
+}
+
+impl InboxScreen {
+    fn new(
+        client: &mut impl Client,
+        config: &Config,
+        filter: InboxFilter,
+    ) -> InboxScreen {
+        let contents = load_contents(client.profile());
+        let actors = load_actors(client);
+        let view =
+            make_inbox_view(config, filter, &contents, &actors, client.actor());
+        InboxScreen { view, contents, actors }
+    }
+
+    /// Makes the view anew, which loads the inbox again
+    fn reload(
+        &mut self,
+        app: &App<impl Client>,
+        filter: InboxFilter,
+        sender: Option<SenderKey>,
+        search: Option<Filter>,
+    ) {
+        let actor = app.client.actor();
+        let (contents, actors) = (&self.contents, &self.actors);
+        let config = &app.config;
+        self.view = make_inbox_view(config, filter, contents, actors, actor);
+        self.view.set_sender(sender);
+        self.view.set_search(search);
+    }
+
+    /// Shows the view while the worker syncs the listing, and indexes the
+    /// text of new items for searches
+    fn sync<C: Client>(
+        &mut self,
+        client: &mut C,
+        config: &Config,
+        terminal: &mut LoadedTerminal,
+        message: Option<Message>,
+    ) -> Result<InboxViewResult, Error> {
+        let header = tui_header(client, config);
+        // Offline, each sync tries whether Kivra can be reached again
+        let was_offline = client.is_offline();
+        client.set_offline(false);
+        let indexed = AtomicBool::new(false);
+        let load = || {
+            let listing = client.refresh_inbox();
+            if let Ok(listing) = &listing {
+                let batch = fulltext::SYNC_BATCH;
+                match fulltext::index_new(&mut *client, listing, batch) {
+                    Ok(0) => {}
+                    Ok(_) => indexed.store(true, Ordering::Relaxed),
+                    Err(error) => warn!("failed to index the inbox: {error}"),
+                }
+            }
+            Data::Sync(listing)
+        };
+        let view = &mut self.view;
+        let shown = tui::show_loading(view, terminal, &header, message, load);
+        let ret = match shown {
+            // A login view popping up on a timer would be a surprise, so it
+            // waits for something the user does
+            Err(tui::Error::LoginRequired) => {
+                InboxViewResult::Synced(Err(client::Error::LoginRequired))
+            }
+            shown => shown?,
+        };
+        match &ret {
+            InboxViewResult::Synced(Err(error))
+                if was_offline && error.is_unreachable() =>
+            {
+                client.set_offline(true);
+            }
+            InboxViewResult::Synced(Ok(_)) if was_offline => {
+                let text = tr!("Back online");
+                self.view.set_message(Message::info(text));
+            }
+            InboxViewResult::Synced(_) => {}
+            // The view returned before the listing was in
+            _ => client.set_offline(was_offline),
+        }
+        if indexed.into_inner() {
+            self.contents = load_contents(client.profile());
+            self.view.set_contents(self.contents.clone());
+        }
+        Ok(ret)
+    }
+
+    /// Closes the inbox and with it the TUI, remembering the tab shown
+    fn close<'a, C>(&self, out: Outcome) -> Result<AppNav<'a, C>, Error> {
+        let mut state = state::load()?;
+        state.last_view = Some(self.view.filter());
+        state::save(&state)?;
+        Ok(Nav::Pop(out))
+    }
+}
+
+impl<'a, C: Client> Screen<App<'a, C>, Outcome, Error> for InboxScreen {
+    fn show(
+        &mut self,
+        app: &mut App<'a, C>,
+        terminal: &mut LoadedTerminal,
+        message: Option<Message>,
+    ) -> Result<AppNav<'a, C>, Error> {
+        let config = &app.config;
+        let ret = match (self.view.is_syncing(), self.view.is_loaded()) {
+            (true, _) => self.sync(app.client, config, terminal, message)?,
+            (false, true) => {
+                let header = tui_header(app.client, config);
+                tui::show(&mut self.view, terminal, &header, message)?
+            }
+            (false, false) => show_loading_tui(
+                terminal,
+                app.client,
+                config,
+                &mut self.view,
+                message,
+                |client| InboxView::load(client, page_size(config)),
+            )?,
+        };
+        let filter = self.view.filter();
+        let mut sender = self.view.sender();
+        let mut search = self.view.search();
+        let client = &mut *app.client;
+        let message = match ret {
+            InboxViewResult::Open(entry) => {
+                let item = ItemScreen::open(client, config, *entry);
+                return Ok(Nav::Push(Box::new(item)));
+            }
+
+            // Nothing changed, so keep the view and its selection
+            InboxViewResult::Copy(entry, field) => {
+                let message = copy_field(client, config, &entry, field);
+                return Ok(Nav::Stay(Some(message)));
+            }
+
+            InboxViewResult::Bulk(action, entries) => {
+                let bulk = BulkScreen { action, entries };
+                return Ok(Nav::Push(Box::new(bulk)));
+            }
+
+            InboxViewResult::ShowReceipts => {
+                return Ok(Nav::Push(Box::new(ReceiptsScreen)));
+            }
+
+            InboxViewResult::ShowDashboard => {
+                return Ok(Nav::Push(Box::new(DashboardScreen)));
+            }
+
+            InboxViewResult::ShowSettings => {
+                let settings = SettingsScreen::new(config)?;
+                return Ok(Nav::Push(Box::new(settings)));
+            }
+
+            InboxViewResult::SwitchActor(actor) => {
+                let text = match actor.company_name() {
+                    Some(name) => {
+                        tr!("Showing the inbox of {name}", name = name)
+                    }
+                    None => tr!("Showing your own inbox").to_string(),
+                };
+                client.set_actor(actor);
+                sender = None;
+                search = None;
+                Some(Message::info(text))
+            }
+
+            InboxViewResult::Trash(entry) => {
+                let result =
+                    client.move_to_folder(&entry.item.key, Folder::Trash);
+                Some(outcome(result, tr!("Moved to trash")))
+            }
+
+            // Shown again, which fetches the listing
+            InboxViewResult::Sync => return Ok(Nav::Stay(None)),
+
+            InboxViewResult::Synced(listing) => {
+                self.view.sync_finished(listing);
+                return Ok(Nav::Stay(None));
+            }
+
+            InboxViewResult::LoadDetails(key) => {
+                let details = client.get_item_details(&key);
+                self.view.set_details(&key, details);
+                return Ok(Nav::Stay(None));
+            }
+
+            InboxViewResult::LoadAttachments(keys) => {
+                let mut cache = cache::summary_cache(client.profile())?;
+                let attachments = (keys.into_iter())
+                    .map(|key| {
+                        let summary =
+                            cli::inbox::attachments(client, &mut cache, &key);
+                        if let Err(error) = &summary {
+                            warn!(
+                                "failed to get the details of {key}: {error}"
+                            );
+                        }
+                        (key, summary.ok())
+                    })
+                    .collect();
+                self.view.set_attachments(attachments);
+                return Ok(Nav::Stay(None));
             }
 
-            None => return Ok(()),
+            InboxViewResult::LoadMore => {
+                if let Some(page) = self.view.next_page() {
+                    let mut pager =
+                        client.inbox_pager(page.limit).starting_at(page.offset);
+                    let items = pager.next().unwrap_or(Ok(Vec::new()));
+                    self.view
+                        .add_page(items, |items| client.number_items(items));
+                }
+                return Ok(Nav::Stay(None));
+            }
+
+            InboxViewResult::Unreachable(error) => {
+                match show_unreachable_tui(terminal, client, config, &error)? {
+                    UnreachableChoice::Retry => None,
+                    UnreachableChoice::WorkOffline => {
+                        client.set_offline(true);
+                        let text =
+                            tr!("Working offline from what is kept locally");
+                        Some(Message::info(text))
+                    }
+                    UnreachableChoice::Quit => {
+                        return self.close(Outcome::Quit)
+                    }
+                }
+            }
+
+            InboxViewResult::Logout if client.is_offline() => {
+                let text =
+                    tr!("Logging out needs Kivra, which is not reachable");
+                Some(Message::error(text))
+            }
+
+            InboxViewResult::Logout => {
+                logout(client)?;
+                return self.close(Outcome::LoggedOut);
+            }
+
+            InboxViewResult::Quit => return self.close(Outcome::Quit),
+        };
+        self.reload(app, filter, sender, search);
+        Ok(Nav::Stay(message))
+    }
+
+    fn resume(
+        &mut self,
+        app: &mut App<'a, C>,
+        out: Outcome,
+    ) -> Result<AppNav<'a, C>, Error> {
+        let mut filter = self.view.filter();
+        let mut sender = self.view.sender();
+        let mut search = self.view.search();
+        let message = match out {
+            Outcome::Back(message) => message,
+            Outcome::ShowTab(next) => {
+                filter = next;
+                None
+            }
+            Outcome::ShowSearch(next, next_search) => {
+                filter = next;
+                sender = None;
+                search = next_search;
+                None
+            }
+            Outcome::Quit | Outcome::LoggedOut => return self.close(out),
+        };
+        self.reload(app, filter, sender, search);
+        Ok(Nav::Stay(message))
+    }
+}
+
+/// Puts a field of the item on the clipboard. Attachments are downloaded
+/// first, so that there is something at the copied path.
+fn copy_field(
+    client: &mut impl Client,
+    config: &Config,
+    entry: &InboxEntry,
+    field: CopyField,
+) -> Message {
+    let item = &entry.item;
+    let text = match field {
+        CopyField::Summary => cli::copy::format(entry, &config.copy.template),
+        CopyField::Subject => item.subject.clone(),
+        CopyField::Sender => item.sender_name.clone(),
+        CopyField::Key => item.key.clone(),
+        CopyField::AttachmentPath(attachment_num) => {
+            let destination = Destination::new(config, None);
+            match download_attachment(
+                client,
+                item,
+                attachment_num,
+                &destination,
+            ) {
+                Ok(path) => path.to_string_lossy().to_string(),
+                Err(error) => return Message::error(error.to_string()),
+            }
         }
+    };
+    let done = tr!(
+        "Copied the {field} to the clipboard",
+        field = tr!(&field.to_string())
+    );
+    outcome(copy_to_clipboard(&text), &done)
+}
+
+/// How an action asked for in the TUI went, for its status bar
+fn outcome<E: std::fmt::Display>(result: Result<(), E>, done: &str) -> Message {
+    match result {
+        Ok(()) => Message::info(done),
+        Err(error) => Message::error(error.to_string()),
     }
 }
 
-fn show_inbox_item_tui(
+fn make_inbox_view(
+    config: &Config,
+    filter: InboxFilter,
+    contents: &Arc<Contents>,
+    actors: &[Actor],
+    actor: Actor,
+) -> InboxView {
+    let sync_interval = match config.tui.sync_interval {
+        0 => None,
+        seconds => Some(Duration::from_secs(seconds)),
+    };
+    let inbox_view = InboxView::new(filter, page_size(config));
+    (inbox_view.with_sync_interval(sync_interval))
+        .with_destination(Destination::new(config, None))
+        .with_timestamps(config.timestamps)
+        .with_contents(contents.clone())
+        .with_actors(actors.to_vec(), actor)
+}
+
+/// The user and the companies they can act for, to switch between in the
+/// TUI. Without the companies there is only the user's own inbox.
+fn load_actors(client: &mut impl Client) -> Vec<Actor> {
+    let companies = match client.get_companies() {
+        Ok(companies) => companies,
+        // Built without the company inboxes, there is nothing to warn about
+        Err(client::Error::Unverified(_)) => Vec::new(),
+        Err(error) => {
+            warn!("failed to get the companies: {error}");
+            Vec::new()
+        }
+    };
+    std::iter::once(Actor::User)
+        .chain(companies.into_iter().map(Actor::Company))
+        .collect()
+}
+
+/// The text of attachments for content search in the TUI, which without it
+/// still searches the rest
+fn load_contents(profile: &str) -> Arc<Contents> {
+    match index::load(profile) {
+        Ok(index) => Arc::new(index.contents()),
+        Err(error) => {
+            warn!("failed to load the contents from the index: {error}");
+            Arc::default()
+        }
+    }
+}
+
+/// Items the inbox view loads at a time, None to load the whole inbox
+fn page_size(config: &Config) -> Option<usize> {
+    match config.tui.page_size {
+        0 => None,
+        items => Some(items),
+    }
+}
+
+/// Shows a view while `load` fetches its data on a worker thread
+fn show_loading_tui<C: Client, Ret>(
     terminal: &mut LoadedTerminal,
-    client: &mut impl Client,
-    item: InboxItem,
-) -> Result<(), Error> {
-    let mut entry_view = tui::inbox_item::ItemView::make(client, item.clone())?;
+    client: &mut C,
+    config: &Config,
+    view: &mut impl TuiView<ReturnType = Ret>,
+    message: Option<Message>,
+    load: impl Fn(&mut C) -> Data + Sync,
+) -> Result<Ret, Error> {
+    // Logging in shows a view of its own, which must not happen on the worker
     loop {
-        let user_info = client.get_session().map(|s| s.user_info);
-        let ret = tui::show(&mut entry_view, terminal, user_info)?;
-        match ret {
-            ItemViewResult::Close => return Ok(()),
-            ItemViewResult::MarkRead => {
-                client.mark_as_read(&item.key)?;
+        match client.get_session_or_login() {
+            // The mock client has no session to log in to
+            Ok(_) | Err(client::Error::NoSession) => break,
+            Err(error) if error.is_unreachable() => {
+                match show_unreachable_tui(terminal, client, config, &error)? {
+                    UnreachableChoice::Retry => {}
+                    // A saved session is used even if it has expired
+                    UnreachableChoice::WorkOffline => client.set_offline(true),
+                    UnreachableChoice::Quit => return Err(error.into()),
+                }
             }
-            ItemViewResult::Open(attachment_num) => {
-                open_attachment(client, &item, attachment_num)?;
+            Err(error) => return Err(error.into()),
+        }
+    }
+    let mut message = message;
+    loop {
+        let header = tui_header(client, config);
+        let load = || load(&mut *client);
+        let shown =
+            tui::show_loading(view, terminal, &header, message.take(), load);
+        match shown {
+            // The session expired while loading
+            Err(tui::Error::LoginRequired) => _ = client.login()?,
+            shown => return Ok(shown?),
+        }
+    }
+}
+
+fn show_unreachable_tui(
+    terminal: &mut LoadedTerminal,
+    client: &impl Client,
+    config: &Config,
+    error: &impl ToString,
+) -> Result<UnreachableChoice, Error> {
+    let header = tui_header(client, config);
+    let mut view = UnreachableView::new(error.to_string());
+    Ok(tui::show(&mut view, terminal, &header, None)?)
+}
+
+struct ReceiptsScreen;
+
+impl<'a, C: Client> Screen<App<'a, C>, Outcome, Error> for ReceiptsScreen {
+    fn show(
+        &mut self,
+        app: &mut App<'a, C>,
+        terminal: &mut LoadedTerminal,
+        message: Option<Message>,
+    ) -> Result<AppNav<'a, C>, Error> {
+        let header = tui_header(app.client, &app.config);
+        let shown = ReceiptsView::make(app.client).and_then(|mut view| {
+            tui::show(&mut view, terminal, &header, message)
+        });
+        let out = match shown {
+            Ok(ReceiptsViewResult::ShowTab(Tab::Inbox(filter))) => {
+                Outcome::ShowTab(filter)
+            }
+            Ok(ReceiptsViewResult::ShowTab(Tab::Receipts)) => {
+                Outcome::Back(None)
+            }
+            Ok(ReceiptsViewResult::Quit) => Outcome::Quit,
+            Err(tui::Error::ClientError(error)) => {
+                let text =
+                    tr!("Failed to load receipts: {error}", error = error);
+                Outcome::Back(Some(Message::error(text)))
+            }
+            Err(error) => return Err(error.into()),
+        };
+        Ok(Nav::Pop(out))
+    }
+}
+
+struct DashboardScreen;
+
+impl<'a, C: Client> Screen<App<'a, C>, Outcome, Error> for DashboardScreen {
+    fn show(
+        &mut self,
+        app: &mut App<'a, C>,
+        terminal: &mut LoadedTerminal,
+        message: Option<Message>,
+    ) -> Result<AppNav<'a, C>, Error> {
+        let mut dashboard_view = DashboardView::new();
+        let ret = show_loading_tui(
+            terminal,
+            app.client,
+            &app.config,
+            &mut dashboard_view,
+            message,
+            DashboardView::load,
+        )?;
+        Ok(match ret {
+            // Closing the item goes back to the inbox
+            DashboardViewResult::Open(entry) => {
+                let item = ItemScreen::open(app.client, &app.config, *entry);
+                Nav::Replace(Box::new(item))
+            }
+            DashboardViewResult::ShowInbox(filter, search) => {
+                Nav::Pop(Outcome::ShowSearch(filter, search))
+            }
+            DashboardViewResult::ShowTab(Tab::Inbox(filter)) => {
+                Nav::Pop(Outcome::ShowTab(filter))
+            }
+            DashboardViewResult::ShowTab(Tab::Receipts) => {
+                Nav::Replace(Box::new(ReceiptsScreen))
+            }
+            DashboardViewResult::Back => Nav::Pop(Outcome::Back(None)),
+            DashboardViewResult::Quit => Nav::Pop(Outcome::Quit),
+        })
+    }
+}
+
+struct BulkScreen {
+    action: BulkAction,
+    entries: Vec<InboxEntry>,
+}
+
+impl<'a, C: Client> Screen<App<'a, C>, Outcome, Error> for BulkScreen {
+    fn show(
+        &mut self,
+        app: &mut App<'a, C>,
+        terminal: &mut LoadedTerminal,
+        message: Option<Message>,
+    ) -> Result<AppNav<'a, C>, Error> {
+        let header = tui_header(app.client, &app.config);
+        let entries = std::mem::take(&mut self.entries);
+        let mut bulk_view =
+            BulkView::make(app.client, self.action.clone(), entries);
+        let summary = tui::show(&mut bulk_view, terminal, &header, message)?;
+        let message = summary.message(&self.action);
+        Ok(Nav::Pop(Outcome::Back(Some(message))))
+    }
+}
+
+/// The settings, which are saved to the config file as they are changed
+struct SettingsScreen {
+    view: SettingsView,
+    path: PathBuf,
+}
+
+impl SettingsScreen {
+    fn new(config: &Config) -> Result<SettingsScreen, Error> {
+        let path = config::default_config_path()?;
+        let view = SettingsView::new(config, &path);
+        Ok(SettingsScreen { view, path })
+    }
+}
+
+impl<'a, C: Client> Screen<App<'a, C>, Outcome, Error> for SettingsScreen {
+    fn show(
+        &mut self,
+        app: &mut App<'a, C>,
+        terminal: &mut LoadedTerminal,
+        message: Option<Message>,
+    ) -> Result<AppNav<'a, C>, Error> {
+        let header = tui_header(app.client, &app.config);
+        let ret = tui::show(&mut self.view, terminal, &header, message)?;
+        let SettingsViewResult::Save(index, input) = ret else {
+            return Ok(Nav::Pop(Outcome::Back(None)));
+        };
+        let setting = &SETTINGS[index];
+        let saved = (setting.parse(&input))
+            .and_then(|value| settings::save(&self.path, setting, value));
+        let message = match saved {
+            Ok(config) => {
+                self.view.set_config(&config);
+                app.config = config;
+                match setting.restart {
+                    true => Message::info(tr!(
+                        "Saved, {name} changes the next time kivinge starts",
+                        name = tr!(setting.label)
+                    )),
+                    false => Message::info(tr!(
+                        "Saved {name}",
+                        name = tr!(setting.label)
+                    )),
+                }
+            }
+            Err(error) => Message::error(error.to_string()),
+        };
+        Ok(Nav::Stay(Some(message)))
+    }
+}
+
+/// An item of the inbox, which tells the inbox how it was left when closed
+struct ItemScreen {
+    entry: InboxEntry,
+    view: ItemView,
+}
+
+impl ItemScreen {
+    /// Marks the item as read first if the settings say so
+    fn open(
+        client: &mut impl Client,
+        config: &Config,
+        mut entry: InboxEntry,
+    ) -> ItemScreen {
+        if config.tui.mark_read_on_open && entry.item.status == Status::Unread {
+            match client.mark_as_read(&entry.item.key) {
+                Ok(()) => entry.item.status = Status::Read,
+                Err(error) => warn!("failed to mark the item as read: {error}"),
             }
         }
+        let protocol = image::Protocol::from_config(config.tui.images);
+        let view = ItemView::new(entry.item.clone(), protocol);
+        ItemScreen { entry, view }
+    }
+}
+
+impl<'a, C: Client> Screen<App<'a, C>, Outcome, Error> for ItemScreen {
+    fn show(
+        &mut self,
+        app: &mut App<'a, C>,
+        terminal: &mut LoadedTerminal,
+        message: Option<Message>,
+    ) -> Result<AppNav<'a, C>, Error> {
+        let (client, config) = (&mut *app.client, &app.config);
+        let item = &self.entry.item;
+        let ret = match self.view.is_loaded() {
+            true => {
+                let header = tui_header(client, config);
+                tui::show(&mut self.view, terminal, &header, message)?
+            }
+            false => show_loading_tui(
+                terminal,
+                client,
+                config,
+                &mut self.view,
+                message,
+                |client| ItemView::load(client, item),
+            )?,
+        };
+        let message = match ret {
+            ItemViewResult::Close => return Ok(Nav::Pop(Outcome::Back(None))),
+            ItemViewResult::LoadFailed(error) => {
+                let message = Message::error(error.to_string());
+                return Ok(Nav::Pop(Outcome::Back(Some(message))));
+            }
+            ItemViewResult::MarkRead => {
+                outcome(client.mark_as_read(&item.key), tr!("Marked as read"))
+            }
+            ItemViewResult::Open(attachment_num) => outcome(
+                open_attachment(client, item, attachment_num),
+                tr!("Opened the attachment"),
+            ),
+            ItemViewResult::Trash => {
+                let result = client.move_to_folder(&item.key, Folder::Trash);
+                match result {
+                    Ok(()) => {
+                        let message = Message::info(tr!("Moved to trash"));
+                        return Ok(Nav::Pop(Outcome::Back(Some(message))));
+                    }
+                    Err(error) => Message::error(error.to_string()),
+                }
+            }
+            ItemViewResult::Copy(field) => {
+                copy_field(client, config, &self.entry, field)
+            }
+            ItemViewResult::LoadImage(preview) => {
+                let data = match preview {
+                    Preview::SenderIcon => client
+                        .get_sender_icon(&item.sender_icon_url)
+                        .map_err(Error::from),
+                    Preview::Attachment(index) => client
+                        .get_item_details(&item.key)
+                        .map_err(Error::from)
+                        .and_then(|details| {
+                            let index = index as u32;
+                            get_attachment_body(client, item, &details, index)
+                        }),
+                };
+                if let Err(error) = &data {
+                    warn!("failed to load the image to preview: {error}");
+                }
+                self.view.set_image(preview, data.ok());
+                return Ok(Nav::Stay(None));
+            }
+        };
+        Ok(Nav::Stay(Some(message)))
     }
 }
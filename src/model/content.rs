@@ -1,17 +1,20 @@
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Local, Utc};
 use rust_decimal::Decimal;
-use serde::Deserialize;
-use std::{collections::BTreeMap, fmt::Display, ops::Deref};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap, fmt::Display, ops::Deref, path::PathBuf,
+    str::FromStr,
+};
 
 use super::Date;
-use crate::error::Error;
+use crate::{error::Error, filename};
 
 pub type ContentKey = String;
 pub type SenderKey = String;
 pub type AgreementKey = String;
 pub type ContentLabels = BTreeMap<String, bool>;
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct InboxItem {
     pub key: ContentKey,
     pub sender: SenderKey,
@@ -38,31 +41,231 @@ pub struct InboxItem {
     pub content_type: String,
     pub has_multiple_options: bool,
     pub sender_icon_url: String,
-    // Do not know how to decode these yet
+    #[serde(default)]
+    pub form: Option<Form>,
+    // Do not know how to decode this yet
     // pub tags: // null
-    // pub form: //null
+}
+
+impl InboxItem {
+    pub fn folder(&self) -> Folder {
+        let label =
+            |name: &str| self.labels.get(name).copied().unwrap_or(false);
+        if label(Folder::Trash.label()) {
+            Folder::Trash
+        } else if label(Folder::Archive.label()) {
+            Folder::Archive
+        } else {
+            Folder::Inbox
+        }
+    }
+
+    /// Labels that are set, except "viewed" which is shown as the status
+    pub fn active_labels(&self) -> Vec<&str> {
+        self.labels
+            .iter()
+            .filter(|(label, &set)| set && label.as_str() != "viewed")
+            .map(|(label, _)| label.as_str())
+            .collect()
+    }
+}
+
+/// A form the sender wants filled in. Its format is not documented, so it is
+/// kept as it came and only shown for reading.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(transparent)]
+pub struct Form(pub serde_json::Value);
+
+impl Form {
+    /// Every value of the form on a line of its own, after the names that
+    /// lead to it, e.g. "questions.0.text: Will you attend?"
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+        flatten(&self.0, String::new(), &mut lines);
+        lines
+    }
+}
+
+fn flatten(value: &serde_json::Value, path: String, lines: &mut Vec<String>) {
+    use serde_json::Value;
+    let join = |name: &dyn Display| match path.is_empty() {
+        true => name.to_string(),
+        false => format!("{path}.{name}"),
+    };
+    match value {
+        Value::Null => {}
+        Value::Object(fields) => {
+            for (name, value) in fields {
+                flatten(value, join(name), lines);
+            }
+        }
+        Value::Array(values) => {
+            for (index, value) in values.iter().enumerate() {
+                flatten(value, join(&index), lines);
+            }
+        }
+        Value::String(text) => lines.push(format!("{path}: {text}")),
+        value => lines.push(format!("{path}: {value}")),
+    }
 }
 
 impl Display for InboxItem {
     fn fmt(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
-        format!("{}_{}", self.created_at.date_naive(), self.sender_name)
-            .replace(' ', "-")
-            .replace('/', "-")
-            .fmt(formatter)
+        let name =
+            format!("{}_{}", self.created_at.date_naive(), self.sender_name);
+        filename::sanitize(&name).fmt(formatter)
     }
 }
 
-#[derive(Deserialize, Debug, PartialEq, Clone)]
-#[serde(rename_all(deserialize = "lowercase"))]
+#[derive(Deserialize, Serialize, Debug, PartialEq, Clone)]
+#[serde(rename_all = "lowercase")]
 pub enum Status {
     Unread,
     Read,
 }
 
+/// Where an item lives. Kivra keeps everything in one listing and tells
+/// archived and trashed items apart by their labels.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Folder {
+    Inbox,
+    Archive,
+    Trash,
+}
+
+impl Folder {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Folder::Inbox => "inbox",
+            Folder::Archive => "archived",
+            Folder::Trash => "trashed",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InboxFilter {
+    #[default]
+    All,
+    Unread,
+    Payments,
+    Archive,
+    Trash,
+}
+
+impl InboxFilter {
+    pub const ALL: [InboxFilter; 5] = [
+        InboxFilter::All,
+        InboxFilter::Unread,
+        InboxFilter::Payments,
+        InboxFilter::Archive,
+        InboxFilter::Trash,
+    ];
+
+    pub fn matches(&self, item: &InboxItem) -> bool {
+        match self {
+            InboxFilter::All => item.folder() == Folder::Inbox,
+            InboxFilter::Unread => {
+                item.folder() == Folder::Inbox && item.status == Status::Unread
+            }
+            InboxFilter::Payments => {
+                item.folder() == Folder::Inbox && item.payable
+            }
+            InboxFilter::Archive => item.folder() == Folder::Archive,
+            InboxFilter::Trash => item.folder() == Folder::Trash,
+        }
+    }
+}
+
+impl Display for InboxFilter {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            InboxFilter::All => "Inbox",
+            InboxFilter::Unread => "Unread",
+            InboxFilter::Payments => "Payments",
+            InboxFilter::Archive => "Archive",
+            InboxFilter::Trash => "Trash",
+        }
+        .fmt(formatter)
+    }
+}
+
+/// An item as given on the command line: its id, its content key, or words
+/// from its sender and subject as `sender/subject`
+#[derive(Clone, Debug, PartialEq)]
+pub enum ItemRef {
+    Id(u32),
+    Text(String),
+}
+
+impl ItemRef {
+    /// Whether the entry is the one referred to by id or content key
+    pub fn is(&self, entry: &InboxEntry) -> bool {
+        match self {
+            ItemRef::Id(id) => entry.id == *id,
+            ItemRef::Text(key) => entry.item.key == *key,
+        }
+    }
+
+    /// Whether the entry fits the words of the reference. Words before a
+    /// `/` are looked for in the sender and those after it in the subject,
+    /// without one they may be in either. Case is ignored.
+    pub fn resembles(&self, entry: &InboxEntry) -> bool {
+        let ItemRef::Text(text) = self else {
+            return false;
+        };
+        let has_words = |words: &str, field: &str| {
+            let field = field.to_lowercase();
+            words.split_whitespace().all(|word| field.contains(word))
+        };
+        let text = text.to_lowercase();
+        let item = &entry.item;
+        match text.split_once('/') {
+            Some((sender, subject)) => {
+                has_words(sender, &item.sender_name)
+                    && has_words(subject, &item.subject)
+            }
+            None => {
+                let both = format!("{} {}", item.sender_name, item.subject);
+                has_words(&text, &both)
+            }
+        }
+    }
+}
+
+impl Display for ItemRef {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ItemRef::Id(id) => id.fmt(formatter),
+            ItemRef::Text(text) => text.fmt(formatter),
+        }
+    }
+}
+
+impl FromStr for ItemRef {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<ItemRef, String> {
+        if text.trim().is_empty() {
+            return Err("expected an item id, content key or words".to_string());
+        }
+        Ok(match text.parse() {
+            Ok(id) => ItemRef::Id(id),
+            Err(_) => ItemRef::Text(text.to_string()),
+        })
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct InboxEntry {
     pub id: u32,
     pub item: InboxItem,
+    /// The profile the item was listed from, in a listing merged from those
+    /// of several profiles
+    pub profile: Option<String>,
+    /// Known once the details of the item have been fetched
+    pub attachments: Option<AttachmentSummary>,
 }
 
 impl Display for InboxEntry {
@@ -91,15 +294,143 @@ impl IntoIterator for InboxListing {
 }
 
 impl InboxListing {
+    /// Numbers the items by their place, oldest first. The numbers only
+    /// hold for this listing, see `index::item_ids` for lasting ones.
     pub fn from_content_specs(mut vec: Vec<InboxItem>) -> InboxListing {
         vec.sort_by_key(|item| item.created_at);
         let listing = vec
             .into_iter()
             .zip(1..)
-            .map(|(item, id)| InboxEntry { id, item })
+            .map(|(item, id)| InboxEntry {
+                id,
+                item,
+                profile: None,
+                attachments: None,
+            })
+            .collect();
+        InboxListing(listing)
+    }
+
+    /// Numbers the items with the ids `id` gives them, oldest first
+    pub fn numbered(
+        mut vec: Vec<InboxItem>,
+        id: impl Fn(&InboxItem) -> u32,
+    ) -> InboxListing {
+        vec.sort_by_key(|item| item.created_at);
+        let listing = vec
+            .into_iter()
+            .map(|item| InboxEntry {
+                id: id(&item),
+                item,
+                profile: None,
+                attachments: None,
+            })
+            .collect();
+        InboxListing(listing)
+    }
+
+    /// The listings of several profiles as one, oldest first, with each
+    /// entry telling which profile it came from. Ids are those each profile
+    /// gave, so they may repeat.
+    pub fn merged(listings: Vec<(String, InboxListing)>) -> InboxListing {
+        let mut listing: Vec<InboxEntry> = listings
+            .into_iter()
+            .flat_map(|(profile, listing)| {
+                listing.0.into_iter().map(move |entry| InboxEntry {
+                    profile: Some(profile.clone()),
+                    ..entry
+                })
+            })
             .collect();
+        listing.sort_by_key(|entry| entry.item.created_at);
         InboxListing(listing)
     }
+
+    /// Fills in the attachments of the entries that `attachments` knows
+    pub fn with_attachments(
+        self,
+        mut attachments: impl FnMut(&InboxItem) -> Option<AttachmentSummary>,
+    ) -> InboxListing {
+        let listing = self.0.into_iter().map(|entry| InboxEntry {
+            attachments: attachments(&entry.item),
+            ..entry
+        });
+        InboxListing(listing.collect())
+    }
+
+    /// Keeps only the matching entries. Ids are left as they were so they
+    /// still refer to the same items.
+    pub fn filtered(self, pred: impl Fn(&InboxEntry) -> bool) -> InboxListing {
+        InboxListing(self.0.into_iter().filter(pred).collect())
+    }
+
+    /// Everyone who has sent something to the inbox, sorted by name
+    pub fn senders(&self) -> Vec<SenderSummary> {
+        let mut senders: BTreeMap<&SenderKey, SenderSummary> = BTreeMap::new();
+        for entry in self.iter() {
+            let item = &entry.item;
+            let summary =
+                senders.entry(&item.sender).or_insert_with(|| SenderSummary {
+                    key: item.sender.clone(),
+                    name: item.sender_name.clone(),
+                    items: 0,
+                    unread: 0,
+                });
+            summary.items += 1;
+            if item.status == Status::Unread {
+                summary.unread += 1;
+            }
+        }
+        let mut senders: Vec<SenderSummary> = senders.into_values().collect();
+        senders.sort_by_key(|sender| sender.name.to_lowercase());
+        senders
+    }
+
+    /// Agreements, such as autogiro or e-faktura, that items refer to,
+    /// sorted by sender. Their status is that of the newest item.
+    pub fn agreements(&self) -> Vec<AgreementSummary> {
+        let mut agreements: BTreeMap<&AgreementKey, AgreementSummary> =
+            BTreeMap::new();
+        // Entries are oldest first, so later items overwrite the status
+        for entry in self.iter() {
+            let item = &entry.item;
+            let Some(key) = &item.agreement_key else {
+                continue;
+            };
+            let summary =
+                agreements.entry(key).or_insert_with(|| AgreementSummary {
+                    key: key.clone(),
+                    sender_name: item.sender_name.clone(),
+                    status: None,
+                    items: 0,
+                });
+            summary.items += 1;
+            if item.agreement_status.is_some() {
+                summary.status = item.agreement_status.clone();
+            }
+        }
+        let mut agreements: Vec<AgreementSummary> =
+            agreements.into_values().collect();
+        agreements
+            .sort_by_key(|agreement| agreement.sender_name.to_lowercase());
+        agreements
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SenderSummary {
+    pub key: SenderKey,
+    pub name: String,
+    pub items: usize,
+    pub unread: usize,
+}
+
+#[derive(Clone, Debug)]
+pub struct AgreementSummary {
+    pub key: AgreementKey,
+    pub sender_name: String,
+    pub status: Option<String>,
+    pub items: usize,
 }
 
 #[derive(Clone, Debug, Deserialize)]
@@ -112,26 +443,91 @@ pub struct ItemDetails {
 
 impl ItemDetails {
     pub fn attachment_name(&self, index: usize) -> Result<String, Error> {
+        self.name_attachment(index, None)
+    }
+
+    /// Like `attachment_name`, but an attachment of an unknown content type
+    /// gets the extension of what it contains
+    pub fn attachment_name_for(
+        &self,
+        index: usize,
+        contents: &[u8],
+    ) -> Result<String, Error> {
+        self.name_attachment(index, Some(contents))
+    }
+
+    /// Where the attachment goes under the download directory, by a
+    /// template of `filename::PLACEHOLDERS`
+    pub fn attachment_path(
+        &self,
+        template: &str,
+        index: usize,
+        contents: &[u8],
+    ) -> Result<PathBuf, Error> {
+        let name = self.attachment_name_for(index, contents)?;
+        let extension = self.attachment_extension(index, Some(contents))?;
+        let created = self.created_at.with_timezone(&Local).date_naive();
+        let fields = [
+            ("{name}", name),
+            ("{sender}", self.sender_name.clone()),
+            ("{subject}", self.subject.clone()),
+            ("{date}", created.to_string()),
+            ("{year}", created.format("%Y").to_string()),
+            ("{month}", created.format("%m").to_string()),
+            ("{day}", created.format("%d").to_string()),
+            ("{n}", index.to_string()),
+            ("{ext}", extension.to_string()),
+        ];
+        Ok(filename::fill_template(template, &fields))
+    }
+
+    fn attachment_extension(
+        &self,
+        index: usize,
+        contents: Option<&[u8]>,
+    ) -> Result<&'static str, Error> {
         let attachment = self
             .parts
             .get(index)
             .ok_or(Error::AppError("Attachment index out of bounds"))?;
-        let file_extension = match attachment.content_type.as_str() {
-            "application/pdf" => "pdf",
-            "text/html" => "html",
-            _ => "txt",
-        };
+        Ok(filename::extension(&attachment.content_type)
+            .or_else(|| contents.and_then(filename::sniff_extension))
+            .unwrap_or("txt"))
+    }
 
-        Ok(format!(
+    fn name_attachment(
+        &self,
+        index: usize,
+        contents: Option<&[u8]>,
+    ) -> Result<String, Error> {
+        let file_extension = self.attachment_extension(index, contents)?;
+
+        let name = format!(
             "{}-{}-{}-{}.{}",
             self.created_at.to_rfc3339(),
             index,
             self.sender_name,
             self.subject,
             file_extension
-        )
-        .replace(' ', "-")
-        .replace('/', "-"))
+        );
+        Ok(filename::sanitize(&name))
+    }
+}
+
+/// How many attachments an item has and how large they are together
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+pub struct AttachmentSummary {
+    pub count: usize,
+    /// In bytes
+    pub size: usize,
+}
+
+impl From<&ItemDetails> for AttachmentSummary {
+    fn from(details: &ItemDetails) -> AttachmentSummary {
+        AttachmentSummary {
+            count: details.parts.len(),
+            size: details.parts.iter().map(|part| part.size).sum(),
+        }
     }
 }
 
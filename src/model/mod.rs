@@ -1,12 +1,14 @@
 pub mod auth;
 pub mod content;
+pub mod receipt;
+pub mod user;
 
 use chrono::NaiveDate;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 pub type UserId = String;
 
-#[derive(Deserialize, Debug)]
+#[derive(Clone, Deserialize, Serialize, Debug)]
 pub struct Config {
     pub oauth_endpoint_url: String,
     pub oauth_default_client_id: String,
@@ -27,3 +29,12 @@ impl<'a> Deserialize<'a> for Date {
         Ok(Date(date))
     }
 }
+
+impl Serialize for Date {
+    fn serialize<Ser: serde::Serializer>(
+        &self,
+        s: Ser,
+    ) -> Result<Ser::Ok, Ser::Error> {
+        s.collect_str(&self.0.format("%Y-%m-%d"))
+    }
+}
@@ -0,0 +1,86 @@
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use std::{fmt::Display, ops::Deref};
+
+use crate::filename;
+
+pub type ReceiptKey = String;
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Receipt {
+    pub key: ReceiptKey,
+    pub store_name: String,
+    pub purchase_date: DateTime<Utc>,
+    pub total_amount: Decimal,
+    pub currency: String,
+}
+
+impl Display for Receipt {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let name =
+            format!("{}_{}", self.purchase_date.date_naive(), self.store_name);
+        filename::sanitize(&name).fmt(formatter)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ReceiptEntry {
+    pub id: u32,
+    pub receipt: Receipt,
+}
+
+impl Display for ReceiptEntry {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        format!("{:#04}_{}", self.id, self.receipt).fmt(formatter)
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct ReceiptListing(Vec<ReceiptEntry>);
+
+impl Deref for ReceiptListing {
+    type Target = Vec<ReceiptEntry>;
+    fn deref(&self) -> &Self::Target {
+        let ReceiptListing(listing) = self;
+        listing
+    }
+}
+
+impl IntoIterator for ReceiptListing {
+    type Item = ReceiptEntry;
+    type IntoIter = std::vec::IntoIter<Self::Item>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+impl ReceiptListing {
+    pub fn from_receipts(mut vec: Vec<Receipt>) -> ReceiptListing {
+        vec.sort_by_key(|receipt| receipt.purchase_date);
+        let listing = vec
+            .into_iter()
+            .zip(1..)
+            .map(|(receipt, id)| ReceiptEntry { id, receipt })
+            .collect();
+        ReceiptListing(listing)
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ReceiptDetails {
+    pub key: ReceiptKey,
+    pub store_name: String,
+    pub purchase_date: DateTime<Utc>,
+    pub total_amount: Decimal,
+    pub currency: String,
+    pub payment_method: Option<String>,
+    pub items: Vec<ReceiptLine>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ReceiptLine {
+    pub description: String,
+    pub quantity: Decimal,
+    pub amount: Decimal,
+}
@@ -0,0 +1,58 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+use super::UserId;
+
+/// The account as Kivra keeps it, which has more to it than the id token
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct UserProfile {
+    pub id: Option<UserId>,
+    pub first_name: Option<String>,
+    pub last_name: Option<String>,
+    pub email: Option<String>,
+    pub phone_number: Option<String>,
+    /// Whatever else Kivra has on the user, such as the address
+    #[serde(flatten)]
+    pub other: BTreeMap<String, Value>,
+}
+
+/// A company inbox that the logged in user can read, besides their own
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Company {
+    pub key: String,
+    pub name: String,
+    #[serde(rename = "orgnr")]
+    pub org_number: String,
+}
+
+impl Company {
+    /// Whether `reference` is the key or the organization number of the
+    /// company, the latter with or without a dash
+    pub fn is(&self, reference: &str) -> bool {
+        let digits = |text: &str| -> String {
+            text.chars().filter(char::is_ascii_digit).collect()
+        };
+        self.key == reference
+            || (!digits(reference).is_empty()
+                && digits(reference) == digits(&self.org_number))
+    }
+}
+
+/// Whose inbox the content calls read
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum Actor {
+    #[default]
+    User,
+    Company(Company),
+}
+
+impl Actor {
+    /// The company name, as the user's own name is shown anyway
+    pub fn company_name(&self) -> Option<&str> {
+        match self {
+            Actor::User => None,
+            Actor::Company(company) => Some(&company.name),
+        }
+    }
+}
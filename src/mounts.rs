@@ -0,0 +1,126 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, File},
+    io,
+    path::{Path, PathBuf},
+    thread,
+    time::{Duration, Instant},
+};
+use thiserror::Error;
+
+use crate::index::sha256;
+
+/// How long `stop` waits for a mount to go away
+const STOP_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("failed to determine state dir")]
+    CannotFindStateDir,
+
+    #[error("(de)serialization error")]
+    SerializationError(#[from] serde_json::Error),
+
+    #[error("IO error")]
+    IOError(#[from] std::io::Error),
+
+    #[error("process {0} did not stop")]
+    NotStopped(u32),
+}
+
+/// A filesystem served by a running `kivinge mount`
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Mount {
+    pub mountpoint: PathBuf,
+    pub pid: u32,
+    pub profile: String,
+    pub mounted_at: DateTime<Utc>,
+}
+
+impl Mount {
+    pub fn is_running(&self) -> bool {
+        // SAFETY: signal 0 only checks that the process exists
+        let result = unsafe { libc::kill(self.pid as i32, 0) };
+        result == 0
+            || io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+    }
+
+    /// Asks the daemon to unmount and waits for it to exit
+    pub fn stop(&self) -> Result<(), Error> {
+        // SAFETY: sending a signal touches no memory
+        if unsafe { libc::kill(self.pid as i32, libc::SIGTERM) } != 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+        let started = Instant::now();
+        while self.is_running() {
+            if started.elapsed() > STOP_TIMEOUT {
+                return Err(Error::NotStopped(self.pid));
+            }
+            thread::sleep(Duration::from_millis(100));
+        }
+        Ok(())
+    }
+}
+
+fn mounts_dir() -> Result<PathBuf, Error> {
+    let mut path = dirs::state_dir()
+        .or_else(dirs::data_local_dir)
+        .ok_or(Error::CannotFindStateDir)?;
+    path.push("kivinge.mounts");
+    Ok(path)
+}
+
+/// One file per mountpoint, named by a hash of its path
+fn mount_path(mountpoint: &Path) -> Result<PathBuf, Error> {
+    let name = sha256(mountpoint.as_os_str().as_encoded_bytes());
+    Ok(mounts_dir()?.join(format!("{name}.json")))
+}
+
+/// Records that this process serves `mountpoint`, which should be
+/// canonical so that it is found by the same path later
+pub fn register(mountpoint: &Path, profile: &str) -> Result<(), Error> {
+    fs::create_dir_all(mounts_dir()?)?;
+    let mount = Mount {
+        mountpoint: mountpoint.to_path_buf(),
+        pid: std::process::id(),
+        profile: profile.to_string(),
+        mounted_at: Utc::now(),
+    };
+    let file = File::create(mount_path(mountpoint)?)?;
+    Ok(serde_json::to_writer(file, &mount)?)
+}
+
+pub fn unregister(mountpoint: &Path) -> Result<(), Error> {
+    match fs::remove_file(mount_path(mountpoint)?) {
+        Err(error) if error.kind() != io::ErrorKind::NotFound => {
+            Err(error.into())
+        }
+        _ => Ok(()),
+    }
+}
+
+/// The mounts whose daemons are still running, sorted by mountpoint. Those
+/// left behind by daemons that did not exit cleanly are forgotten.
+pub fn list() -> Result<Vec<Mount>, Error> {
+    let dir = mounts_dir()?;
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut mounts = Vec::new();
+    for file in fs::read_dir(dir)? {
+        let path = file?.path();
+        let mount: Mount = serde_json::from_reader(File::open(&path)?)?;
+        match mount.is_running() {
+            true => mounts.push(mount),
+            false => fs::remove_file(path)?,
+        }
+    }
+    mounts.sort_by(|a, b| a.mountpoint.cmp(&b.mountpoint));
+    Ok(mounts)
+}
+
+pub fn find(mountpoint: &Path) -> Result<Option<Mount>, Error> {
+    let mounts = list()?;
+    Ok(mounts.into_iter().find(|mount| mount.mountpoint == mountpoint))
+}
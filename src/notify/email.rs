@@ -0,0 +1,77 @@
+use lettre::{
+    message::Mailbox, transport::smtp::authentication::Credentials,
+    SmtpTransport, Transport,
+};
+
+use super::{with_retries, Error, TIMEOUT};
+use crate::{
+    config::{SmtpConfig, SmtpSecurity},
+    mail,
+    model::content::{InboxEntry, ItemDetails},
+};
+
+/// Sends new items, attachments and all, as email through an SMTP server
+pub struct Forward {
+    transport: SmtpTransport,
+    from: Mailbox,
+    to: Vec<Mailbox>,
+    attempts: u32,
+}
+
+impl Forward {
+    pub fn new(
+        config: &SmtpConfig,
+        to: Vec<Mailbox>,
+        attempts: u32,
+    ) -> Result<Forward, Error> {
+        let server = config
+            .server
+            .as_ref()
+            .ok_or(Error::MissingConfig("smtp.server"))?;
+        let from = (config.from.as_ref())
+            .or(config.username.as_ref())
+            .ok_or(Error::MissingConfig("smtp.from"))?;
+        let from =
+            from.parse().map_err(|_| Error::InvalidAddress(from.clone()))?;
+        let mut builder = match config.security {
+            SmtpSecurity::Tls => SmtpTransport::relay(server)?,
+            SmtpSecurity::StartTls => SmtpTransport::starttls_relay(server)?,
+            SmtpSecurity::None => SmtpTransport::builder_dangerous(server),
+        }
+        .timeout(Some(TIMEOUT));
+        if let Some(port) = config.port {
+            builder = builder.port(port);
+        }
+        if let (Some(username), Some(password)) =
+            (&config.username, &config.password)
+        {
+            let credentials =
+                Credentials::new(username.clone(), password.clone());
+            builder = builder.credentials(credentials);
+        }
+        Ok(Forward { transport: builder.build(), from, to, attempts })
+    }
+
+    /// Who the items go to, for messages about them
+    pub fn name(&self) -> String {
+        let to: Vec<String> =
+            self.to.iter().map(|to| to.email.to_string()).collect();
+        to.join(", ")
+    }
+
+    /// `contents` holds what each part of the item contains, as for
+    /// `mail::message`
+    pub fn send(
+        &self,
+        entry: &InboxEntry,
+        details: &ItemDetails,
+        contents: &[impl AsRef<[u8]>],
+    ) -> Result<(), Error> {
+        let message =
+            mail::message(entry, details, contents, &self.from, &self.to)?;
+        with_retries(self.attempts, || {
+            self.transport.send(&message)?;
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,60 @@
+use reqwest::{blocking::Client, Url};
+use serde::Serialize;
+
+use super::{endpoint, http_client, with_retries, Error, Notifier, Summary};
+use crate::{config::GotifyConfig, model::content::InboxEntry};
+
+/// Sends every new item as a message to a Gotify server
+pub struct Gotify {
+    url: Url,
+    priority: u8,
+    attempts: u32,
+    client: Client,
+}
+
+#[derive(Serialize)]
+struct Message<'a> {
+    title: &'a str,
+    message: String,
+    priority: u8,
+}
+
+impl Gotify {
+    pub fn new(config: &GotifyConfig, attempts: u32) -> Result<Gotify, Error> {
+        let server = config
+            .server
+            .as_ref()
+            .ok_or(Error::MissingConfig("gotify.server"))?;
+        let token = config
+            .token
+            .as_ref()
+            .ok_or(Error::MissingConfig("gotify.token"))?;
+        let url = endpoint(server, "message")?;
+        // A header rather than the query keeps the token out of the log
+        let client = http_client([("X-Gotify-Key", token.as_str())])?;
+        Ok(Gotify { url, priority: config.priority, attempts, client })
+    }
+}
+
+impl Notifier for Gotify {
+    fn name(&self) -> String {
+        self.url.to_string()
+    }
+
+    fn send(&self, entry: &InboxEntry, summary: &Summary) -> Result<(), Error> {
+        let item = &entry.item;
+        let message = Message {
+            title: &item.sender_name,
+            message: format!("{}\n{}", item.subject, summary.line()),
+            priority: self.priority,
+        };
+        with_retries(self.attempts, || {
+            self.client
+                .post(self.url.clone())
+                .json(&message)
+                .send()?
+                .error_for_status()?;
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,252 @@
+//! Telling other programs about new items as `watch` finds them
+
+use chrono::{DateTime, Utc};
+use reqwest::{
+    blocking::Client,
+    header::{HeaderMap, HeaderName, HeaderValue},
+    Url,
+};
+use rust_decimal::Decimal;
+use serde::Serialize;
+use std::{thread, time::Duration};
+use thiserror::Error;
+use tracing::warn;
+
+use crate::{cli::copy, config::NotifyConfig, model::content::InboxEntry};
+
+mod email;
+mod gotify;
+mod ntfy;
+mod webhook;
+
+pub use email::Forward;
+pub use gotify::Gotify;
+pub use ntfy::Ntfy;
+pub use webhook::Webhook;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("missing {0} in the [notify] config")]
+    MissingConfig(&'static str),
+
+    #[error("invalid header '{0}'")]
+    InvalidHeader(String),
+
+    #[error("invalid URL: {0}")]
+    InvalidUrl(String),
+
+    #[error("invalid email address '{0}'")]
+    InvalidAddress(String),
+
+    #[error("HTTP error: {0}")]
+    HttpError(#[from] reqwest::Error),
+
+    #[error("JSON error: {0}")]
+    JsonError(#[from] serde_json::Error),
+
+    #[error("SMTP error: {0}")]
+    SmtpError(#[from] lettre::transport::smtp::Error),
+
+    #[error("email error: {0}")]
+    MailError(#[from] crate::mail::Error),
+}
+
+/// The services that `watch --notify` can send to, configured in their
+/// sections of `[notify]`
+#[derive(Clone, Copy, Debug, PartialEq, clap::ValueEnum)]
+pub enum Backend {
+    Ntfy,
+    Gotify,
+}
+
+impl Backend {
+    pub fn notifier(
+        &self,
+        config: &NotifyConfig,
+    ) -> Result<Box<dyn Notifier>, Error> {
+        Ok(match self {
+            Backend::Ntfy => {
+                Box::new(Ntfy::new(&config.ntfy, config.attempts)?)
+            }
+            Backend::Gotify => {
+                Box::new(Gotify::new(&config.gotify, config.attempts)?)
+            }
+        })
+    }
+}
+
+/// Somewhere new items are sent
+pub trait Notifier {
+    /// Where the notifications go, for messages about them
+    fn name(&self) -> String;
+
+    fn send(&self, entry: &InboxEntry, summary: &Summary) -> Result<(), Error>;
+}
+
+/// What is known about the inbox around a new item
+#[derive(Clone, Copy, Debug)]
+pub struct Summary {
+    /// None when the details of the item could not be fetched
+    pub attachments: Option<usize>,
+    pub unread: usize,
+}
+
+impl Summary {
+    /// A line such as "2 attachments, 5 unread"
+    pub fn line(&self) -> String {
+        let unread = format!("{} unread", self.unread);
+        match self.attachments {
+            None => unread,
+            Some(1) => format!("1 attachment, {unread}"),
+            Some(count) => format!("{count} attachments, {unread}"),
+        }
+    }
+}
+
+/// Wait after the first failed attempt, doubled after every other
+const RETRY_DELAY: Duration = Duration::from_secs(2);
+
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+// Keep in sync with the fields filled in by `fill`
+pub const PLACEHOLDERS: [&str; 10] = [
+    "{id}",
+    "{key}",
+    "{sender}",
+    "{subject}",
+    "{created}",
+    "{amount}",
+    "{currency}",
+    "{due_date}",
+    "{attachments}",
+    "{unread}",
+];
+
+/// What is sent about a new item unless a template says otherwise
+#[derive(Serialize)]
+pub struct NewItem<'a> {
+    pub id: u32,
+    pub key: &'a str,
+    pub sender: &'a str,
+    pub subject: &'a str,
+    pub created_at: DateTime<Utc>,
+    pub amount: Option<Decimal>,
+    pub currency: Option<&'a str>,
+    pub due_date: Option<String>,
+    pub attachments: Option<usize>,
+    pub unread: usize,
+}
+
+impl<'a> NewItem<'a> {
+    pub fn new(entry: &'a InboxEntry, summary: &Summary) -> NewItem<'a> {
+        let item = &entry.item;
+        NewItem {
+            id: entry.id,
+            key: &item.key,
+            sender: &item.sender_name,
+            subject: &item.subject,
+            created_at: item.created_at,
+            amount: item.amount,
+            currency: item.currency.as_deref(),
+            due_date: item.due_date.as_ref().map(|date| date.0.to_string()),
+            attachments: summary.attachments,
+            unread: summary.unread,
+        }
+    }
+}
+
+/// Fills in the placeholders of a template, with every value passed
+/// through `escape` first
+pub fn fill(
+    template: &str,
+    entry: &InboxEntry,
+    summary: &Summary,
+    escape: impl Fn(&str) -> String,
+) -> String {
+    let attachments = summary.attachments.map(|count| count.to_string());
+    let fields = copy::fields(entry).into_iter().chain([
+        ("{key}", entry.item.key.clone()),
+        ("{attachments}", attachments.unwrap_or_default()),
+        ("{unread}", summary.unread.to_string()),
+    ]);
+    fields.fold(template.to_string(), |text, (placeholder, value)| {
+        text.replace(placeholder, &escape(&value))
+    })
+}
+
+/// The text as it goes between the quotes of a JSON string
+pub fn escape_json(text: &str) -> String {
+    let quoted = serde_json::Value::from(text).to_string();
+    quoted[1..quoted.len() - 1].to_string()
+}
+
+/// `path` under the URL of a server, which may itself have a path
+fn endpoint(server: &str, path: &str) -> Result<Url, Error> {
+    let mut url = Url::parse(server)
+        .map_err(|error| Error::InvalidUrl(format!("{server}: {error}")))?;
+    if !url.path().ends_with('/') {
+        url.set_path(&format!("{}/", url.path()));
+    }
+    url.join(path)
+        .map_err(|error| Error::InvalidUrl(format!("{server}: {error}")))
+}
+
+/// A client that sends `headers` with every request
+fn http_client<'a>(
+    headers: impl IntoIterator<Item = (&'a str, &'a str)>,
+) -> Result<Client, Error> {
+    let mut map = HeaderMap::new();
+    for (name, value) in headers {
+        let invalid = || Error::InvalidHeader(name.to_string());
+        map.insert(
+            HeaderName::try_from(name).map_err(|_| invalid())?,
+            HeaderValue::try_from(value).map_err(|_| invalid())?,
+        );
+    }
+    let client = Client::builder()
+        .use_native_tls()
+        .timeout(TIMEOUT)
+        .default_headers(map)
+        .build()?;
+    Ok(client)
+}
+
+/// Calls `send` until it succeeds or has been tried `attempts` times.
+/// Errors that trying again will not fix are returned right away.
+fn with_retries(
+    attempts: u32,
+    mut send: impl FnMut() -> Result<(), Error>,
+) -> Result<(), Error> {
+    let mut delay = RETRY_DELAY;
+    for attempt in 1.. {
+        match send() {
+            Err(error) if attempt < attempts && is_transient(&error) => {
+                warn!("attempt {attempt} failed, trying again: {error}");
+                thread::sleep(delay);
+                delay *= 2;
+            }
+            result => return result,
+        }
+    }
+    unreachable!("the attempts end with a result")
+}
+
+/// Network trouble, server errors and rate limits tend to pass
+fn is_transient(error: &Error) -> bool {
+    match error {
+        Error::HttpError(error) => match error.status() {
+            Some(status) => status.is_server_error() || status.as_u16() == 429,
+            None => error.is_connect() || error.is_timeout(),
+        },
+        // What is not a reply, a TLS failure or a mistake on this side is
+        // the connection failing
+        Error::SmtpError(error) => {
+            error.is_transient()
+                || !(error.is_permanent()
+                    || error.is_response()
+                    || error.is_client()
+                    || error.is_tls())
+        }
+        _ => false,
+    }
+}
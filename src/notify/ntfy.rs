@@ -0,0 +1,48 @@
+use reqwest::{blocking::Client, Url};
+
+use super::{endpoint, http_client, with_retries, Error, Notifier, Summary};
+use crate::{config::NtfyConfig, model::content::InboxEntry};
+
+/// Publishes every new item to a topic on an ntfy server
+pub struct Ntfy {
+    url: Url,
+    priority: u8,
+    attempts: u32,
+    client: Client,
+}
+
+impl Ntfy {
+    pub fn new(config: &NtfyConfig, attempts: u32) -> Result<Ntfy, Error> {
+        let topic =
+            config.topic.as_ref().ok_or(Error::MissingConfig("ntfy.topic"))?;
+        let url = endpoint(&config.server, topic)?;
+        let authorization =
+            config.token.as_ref().map(|token| format!("Bearer {token}"));
+        let headers =
+            authorization.iter().map(|value| ("Authorization", value.as_str()));
+        let client = http_client(headers)?;
+        Ok(Ntfy { url, priority: config.priority, attempts, client })
+    }
+}
+
+impl Notifier for Ntfy {
+    fn name(&self) -> String {
+        self.url.to_string()
+    }
+
+    fn send(&self, entry: &InboxEntry, summary: &Summary) -> Result<(), Error> {
+        let item = &entry.item;
+        let message = format!("{}\n{}", item.subject, summary.line());
+        with_retries(self.attempts, || {
+            self.client
+                .post(self.url.clone())
+                .header("Title", &item.sender_name)
+                .header("Priority", self.priority.to_string())
+                .header("Tags", "envelope")
+                .body(message.clone())
+                .send()?
+                .error_for_status()?;
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,61 @@
+use reqwest::{blocking::Client, header::CONTENT_TYPE, Url};
+
+use super::{
+    escape_json, fill, http_client, with_retries, Error, NewItem, Notifier,
+    Summary,
+};
+use crate::{config::WebhookConfig, model::content::InboxEntry};
+
+/// POSTs every new item to a URL, as JSON or as the configured template
+pub struct Webhook {
+    url: Url,
+    config: WebhookConfig,
+    attempts: u32,
+    client: Client,
+}
+
+impl Webhook {
+    pub fn new(
+        url: Url,
+        config: &WebhookConfig,
+        attempts: u32,
+    ) -> Result<Webhook, Error> {
+        let headers = config.headers.iter();
+        let client = http_client(
+            headers.map(|(name, value)| (name.as_str(), value.as_str())),
+        )?;
+        Ok(Webhook { url, config: config.clone(), attempts, client })
+    }
+}
+
+impl Notifier for Webhook {
+    fn name(&self) -> String {
+        self.url.to_string()
+    }
+
+    fn send(&self, entry: &InboxEntry, summary: &Summary) -> Result<(), Error> {
+        let (content_type, body) = match &self.config.template {
+            None => {
+                let item = NewItem::new(entry, summary);
+                ("application/json", serde_json::to_string(&item)?)
+            }
+            Some(template) => {
+                let content_type = self.config.content_type.as_str();
+                let body = match content_type.contains("json") {
+                    true => fill(template, entry, summary, escape_json),
+                    false => fill(template, entry, summary, str::to_string),
+                };
+                (content_type, body)
+            }
+        };
+        with_retries(self.attempts, || {
+            self.client
+                .post(self.url.clone())
+                .header(CONTENT_TYPE, content_type)
+                .body(body.clone())
+                .send()?
+                .error_for_status()?;
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,209 @@
+//! A progress bar on stderr for long CLI operations, with what is being
+//! worked on and an estimate of the time left. It is only drawn when both
+//! stdout and stderr are terminals, so that piped output stays as it is.
+//! For logs there are plain lines instead, one per step.
+//!
+//! Downloads report the bytes of the file they fetch with `transferred`,
+//! which the bar shows next to the step it is on.
+
+use std::{
+    cell::RefCell,
+    io::{stderr, stdout, IsTerminal, Write},
+    rc::{Rc, Weak},
+    time::{Duration, Instant},
+};
+
+use crate::{table::file_size, tr};
+
+/// Columns of the bar itself
+const BAR_WIDTH: usize = 24;
+/// How often the bar is drawn again for the bytes of a file
+const TRANSFER_TICK: Duration = Duration::from_millis(100);
+
+thread_local! {
+    /// The bar last made on this thread, which downloads report to
+    static CURRENT: RefCell<Weak<RefCell<Bar>>> =
+        const { RefCell::new(Weak::new()) };
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Style {
+    Bar,
+    Lines,
+    Hidden,
+}
+
+pub struct Progress(Rc<RefCell<Bar>>);
+
+struct Bar {
+    style: Style,
+    total: usize,
+    done: usize,
+    started: Instant,
+    /// What is being worked on, e.g. the item being downloaded
+    message: String,
+    /// Bytes of the file being transferred, and how many there are if known
+    transfer: Option<(u64, Option<u64>)>,
+    drawn: bool,
+    drawn_at: Instant,
+}
+
+impl Progress {
+    /// A bar drawn once started, if the output is to a terminal
+    pub fn on_terminal() -> Progress {
+        match stdout().is_terminal() && stderr().is_terminal() {
+            true => Progress::new(Style::Bar),
+            false => Progress::new(Style::Hidden),
+        }
+    }
+
+    /// A line on stderr for each step begun, e.g. `2/10 <message>`
+    pub fn lines() -> Progress {
+        Progress::new(Style::Lines)
+    }
+
+    /// A bar that is never drawn
+    pub fn hidden() -> Progress {
+        Progress::new(Style::Hidden)
+    }
+
+    fn new(style: Style) -> Progress {
+        let bar = Rc::new(RefCell::new(Bar {
+            style,
+            total: 0,
+            done: 0,
+            started: Instant::now(),
+            message: String::new(),
+            transfer: None,
+            drawn: false,
+            drawn_at: Instant::now(),
+        }));
+        CURRENT.set(Rc::downgrade(&bar));
+        Progress(bar)
+    }
+
+    /// Counts from nothing to `total`, again for each stage of the operation
+    pub fn start(&mut self, total: usize) {
+        let mut bar = self.0.borrow_mut();
+        bar.total = total;
+        bar.done = 0;
+        bar.message.clear();
+        bar.transfer = None;
+        bar.started = Instant::now();
+        bar.draw();
+    }
+
+    /// Shows what is being worked on next
+    pub fn set_message(&mut self, message: impl Into<String>) {
+        let mut bar = self.0.borrow_mut();
+        bar.message = message.into();
+        bar.transfer = None;
+        if bar.style == Style::Lines {
+            let step = (bar.done + 1).min(bar.total);
+            eprintln!("{step}/{} {}", bar.total, bar.message);
+        }
+        bar.draw();
+    }
+
+    /// Counts one step as done
+    pub fn advance(&mut self) {
+        let mut bar = self.0.borrow_mut();
+        bar.done = (bar.done + 1).min(bar.total);
+        bar.transfer = None;
+        bar.draw();
+    }
+
+    /// Runs `f` with the bar out of the way, for printing
+    pub fn suspend<T>(&mut self, f: impl FnOnce() -> T) -> T {
+        self.0.borrow_mut().clear();
+        let result = f();
+        self.0.borrow_mut().draw();
+        result
+    }
+}
+
+impl Drop for Progress {
+    fn drop(&mut self) {
+        self.0.borrow_mut().clear();
+    }
+}
+
+/// Tells the bar of this thread, if any, how many bytes of the file being
+/// fetched are in
+pub fn transferred(done: u64, total: Option<u64>) {
+    let Some(bar) = CURRENT.with_borrow(Weak::upgrade) else {
+        return;
+    };
+    let Ok(mut bar) = bar.try_borrow_mut() else {
+        return;
+    };
+    bar.transfer = Some((done, total));
+    if bar.drawn_at.elapsed() >= TRANSFER_TICK || total == Some(done) {
+        bar.draw();
+    }
+}
+
+impl Bar {
+    fn clear(&mut self) {
+        if self.drawn {
+            eprint!("\r\x1b[2K");
+            _ = stderr().flush();
+            self.drawn = false;
+        }
+    }
+
+    fn draw(&mut self) {
+        if self.style != Style::Bar {
+            return;
+        }
+        let filled = match self.total {
+            0 => 0,
+            total => BAR_WIDTH * self.done / total,
+        };
+        let bar =
+            format!("{}{}", "█".repeat(filled), "░".repeat(BAR_WIDTH - filled));
+        let mut line = format!("{bar} {}/{}", self.done, self.total);
+        if let Some(left) = self.time_left() {
+            let left = format_duration(left);
+            line += &format!(" {}", tr!("{time} left", time = left));
+        }
+        if let Some((done, total)) = self.transfer {
+            line += &format!("  {}", file_size(done as usize));
+            if let Some(total) = total {
+                line += &format!("/{}", file_size(total as usize));
+            }
+        }
+        if !self.message.is_empty() {
+            line += &format!("  {}", self.message);
+        }
+        let width = match crossterm::terminal::size() {
+            Ok((columns, _)) if columns > 0 => columns as usize,
+            _ => 80,
+        };
+        // The cursor must not wrap to a new line
+        let line: String = line.chars().take(width.saturating_sub(1)).collect();
+        eprint!("\r\x1b[2K{line}");
+        _ = stderr().flush();
+        self.drawn = true;
+        self.drawn_at = Instant::now();
+    }
+
+    /// Going by how long the steps so far took
+    fn time_left(&self) -> Option<Duration> {
+        if self.done == 0 {
+            return None;
+        }
+        let per_step = self.started.elapsed() / self.done as u32;
+        Some(per_step * (self.total - self.done) as u32)
+    }
+}
+
+fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    match seconds / 3600 {
+        0 => format!("{}:{:02}", seconds / 60, seconds % 60),
+        hours => {
+            format!("{hours}:{:02}:{:02}", seconds / 60 % 60, seconds % 60)
+        }
+    }
+}
@@ -0,0 +1,379 @@
+//! `kivinge serve`: the client operations as JSON-RPC 2.0 on a Unix socket,
+//! so that other programs can share one session. Requests and responses are
+//! one JSON object per line.
+
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::{
+    fs::{self, DirBuilder, Permissions},
+    io::{BufRead, BufReader, ErrorKind, Write},
+    os::unix::{
+        fs::{DirBuilderExt, PermissionsExt},
+        net::{UnixListener, UnixStream},
+    },
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Sender},
+    thread,
+};
+use tracing::{debug, warn};
+
+use crate::{
+    client::Client,
+    error::Error,
+    filter::Filter,
+    logging,
+    model::content::{Folder, InboxEntry, InboxItem, ItemRef},
+    util::{
+        self, download_attachment, find_entries, get_attachment_body,
+        with_contents, Destination,
+    },
+};
+
+const PARSE_ERROR: i32 = -32700;
+const INVALID_REQUEST: i32 = -32600;
+const METHOD_NOT_FOUND: i32 = -32601;
+const INVALID_PARAMS: i32 = -32602;
+/// Anything that goes wrong while carrying out a valid request
+const FAILED: i32 = -32000;
+
+/// A line from a connection, and where to send the response to it
+type Call = (String, Sender<Option<String>>);
+
+/// Serves requests on `socket` until the process is stopped. Connections
+/// are read in threads of their own, but the requests are carried out one
+/// at a time with the one client.
+pub fn run(client: &mut impl Client, socket: &Path) -> Result<(), Error> {
+    // Log in while there is still a terminal to show the QR code in
+    client.get_session_or_login()?;
+    let listener = bind(socket)?;
+    if !logging::quiet() {
+        eprintln!("Serving on {}", socket.to_string_lossy());
+    }
+
+    let (calls, requests) = mpsc::channel::<Call>();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    let calls = calls.clone();
+                    thread::spawn(move || serve_connection(stream, calls));
+                }
+                Err(error) => warn!("failed to accept a connection: {error}"),
+            }
+        }
+    });
+    for (line, reply) in requests {
+        _ = reply.send(respond(client, &line));
+    }
+    Ok(())
+}
+
+/// A socket that only the user can connect to. A socket left behind by a
+/// server that is gone is replaced, one still in use is not. It is bound in
+/// a directory only the user can enter and moved into place once private,
+/// so nobody else can connect in between.
+fn bind(socket: &Path) -> Result<UnixListener, Error> {
+    if UnixStream::connect(socket).is_ok() {
+        return Err(Error::UserError("Something is already serving there"));
+    }
+    match fs::remove_file(socket) {
+        Err(error) if error.kind() != ErrorKind::NotFound => {
+            return Err(error.into())
+        }
+        _ => {}
+    }
+    let private = util::partial_path(socket);
+    DirBuilder::new().mode(0o700).create(&private)?;
+    let bound = private.join("socket");
+    let listener = UnixListener::bind(&bound).and_then(|listener| {
+        fs::set_permissions(&bound, Permissions::from_mode(0o600))?;
+        fs::rename(&bound, socket)?;
+        Ok(listener)
+    });
+    _ = fs::remove_file(&bound);
+    fs::remove_dir(&private)?;
+    Ok(listener?)
+}
+
+fn serve_connection(stream: UnixStream, calls: Sender<Call>) {
+    let mut writer = match stream.try_clone() {
+        Ok(writer) => writer,
+        Err(error) => {
+            warn!("failed to set up a connection: {error}");
+            return;
+        }
+    };
+    let (reply, responses) = mpsc::channel();
+    for line in BufReader::new(stream).lines() {
+        let Ok(line) = line else {
+            return;
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        if calls.send((line, reply.clone())).is_err() {
+            return;
+        }
+        let Ok(response) = responses.recv() else {
+            return;
+        };
+        // Notifications get no response
+        let Some(response) = response else {
+            continue;
+        };
+        if writeln!(writer, "{response}").is_err() {
+            return;
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct Request {
+    jsonrpc: String,
+    #[serde(default)]
+    id: Option<Value>,
+    method: String,
+    #[serde(default)]
+    params: Option<Value>,
+}
+
+#[derive(Serialize)]
+struct Response {
+    jsonrpc: &'static str,
+    id: Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<RpcError>,
+}
+
+#[derive(Serialize)]
+struct RpcError {
+    code: i32,
+    message: String,
+}
+
+impl RpcError {
+    fn new(code: i32, message: impl ToString) -> RpcError {
+        RpcError { code, message: message.to_string() }
+    }
+}
+
+/// The response to a line, unless it is a notification
+fn respond(client: &mut impl Client, line: &str) -> Option<String> {
+    let (id, result) = match serde_json::from_str::<Value>(line) {
+        Err(error) => (Value::Null, Err(RpcError::new(PARSE_ERROR, error))),
+        Ok(value) => match serde_json::from_value::<Request>(value) {
+            Err(error) => {
+                (Value::Null, Err(RpcError::new(INVALID_REQUEST, error)))
+            }
+            Ok(request) if request.jsonrpc != "2.0" => (
+                request.id.unwrap_or_default(),
+                Err(RpcError::new(INVALID_REQUEST, "jsonrpc must be \"2.0\"")),
+            ),
+            Ok(request) => {
+                debug!("serving {}", request.method);
+                let params = request.params.unwrap_or(json!({}));
+                let result = call(client, &request.method, params);
+                (request.id?, result)
+            }
+        },
+    };
+    let (result, error) = match result {
+        Ok(result) => (Some(result), None),
+        Err(error) => (None, Some(error)),
+    };
+    let response = Response { jsonrpc: "2.0", id, result, error };
+    Some(serde_json::to_string(&response).expect("responses serialize"))
+}
+
+fn call(
+    client: &mut impl Client,
+    method: &str,
+    params: Value,
+) -> Result<Value, RpcError> {
+    let result = match method {
+        "list" => {
+            let params: ListParams = parse_params(params)?;
+            let filter = (params.filter.as_deref().map(Filter::parse))
+                .transpose()
+                .map_err(|error| RpcError::new(INVALID_PARAMS, error))?;
+            list(client, params.folder, filter)
+        }
+        "details" => details(client, parse_params(params)?),
+        "download" => download(client, parse_params(params)?),
+        "mark_read" => mark_read(client, parse_params(params)?),
+        _ => {
+            let message = format!("no method '{method}'");
+            return Err(RpcError::new(METHOD_NOT_FOUND, message));
+        }
+    };
+    result.map_err(|error| match error {
+        Error::UserError(message) => RpcError::new(INVALID_PARAMS, message),
+        error => RpcError::new(FAILED, error),
+    })
+}
+
+fn parse_params<T: DeserializeOwned>(params: Value) -> Result<T, RpcError> {
+    serde_json::from_value(params)
+        .map_err(|error| RpcError::new(INVALID_PARAMS, error))
+}
+
+/// An item is given by id, content key or words of its sender and subject,
+/// as on the command line
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ItemParam {
+    Id(u32),
+    Text(String),
+}
+
+impl ItemParam {
+    fn entry(&self, client: &mut impl Client) -> Result<InboxEntry, Error> {
+        let item = match self {
+            ItemParam::Id(id) => ItemRef::Id(*id),
+            ItemParam::Text(text) => text
+                .parse()
+                .map_err(|_| Error::UserError("Item must not be empty"))?,
+        };
+        let mut candidates = find_entries(client.get_inbox_listing()?, &item);
+        match candidates.len() {
+            0 => Err(Error::UserError("Inbox item does not exist")),
+            1 => Ok(candidates.remove(0)),
+            _ => Err(Error::UserError("Several inbox items match, give an id")),
+        }
+    }
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ListParams {
+    folder: FolderParam,
+    /// A filter expression, as for `list --filter`
+    filter: Option<String>,
+}
+
+#[derive(Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum FolderParam {
+    #[default]
+    Inbox,
+    Archive,
+    Trash,
+}
+
+#[derive(Serialize)]
+struct Entry<'a> {
+    id: u32,
+    #[serde(flatten)]
+    item: &'a InboxItem,
+}
+
+fn list(
+    client: &mut impl Client,
+    folder: FolderParam,
+    filter: Option<Filter>,
+) -> Result<Value, Error> {
+    let folder = match folder {
+        FolderParam::Inbox => Folder::Inbox,
+        FolderParam::Archive => Folder::Archive,
+        FolderParam::Trash => Folder::Trash,
+    };
+    let filter = (filter.map(|filter| with_contents(client.profile(), filter)))
+        .transpose()?;
+    let listing = client.get_folder_listing(folder)?;
+    let entries: Vec<Entry> = listing
+        .iter()
+        .filter(|entry| filter.as_ref().is_none_or(|f| f.matches(&entry.item)))
+        .map(|entry| Entry { id: entry.id, item: &entry.item })
+        .collect();
+    Ok(serde_json::to_value(entries)?)
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ItemParams {
+    item: ItemParam,
+}
+
+#[derive(Serialize)]
+struct Part<'a> {
+    name: String,
+    content_type: &'a str,
+    size: usize,
+    /// The text of parts that Kivra has inline, such as letters
+    body: Option<&'a str>,
+}
+
+fn details(
+    client: &mut impl Client,
+    params: ItemParams,
+) -> Result<Value, Error> {
+    let entry = params.item.entry(client)?;
+    let details = client.get_item_details(&entry.item.key)?;
+    let parts = (details.parts.iter().enumerate())
+        .map(|(index, part)| {
+            Ok(Part {
+                name: details.attachment_name(index)?,
+                content_type: &part.content_type,
+                size: part.size,
+                body: part.body.as_deref(),
+            })
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+    Ok(json!({
+        "id": entry.id,
+        "key": entry.item.key,
+        "sender": details.sender_name,
+        "subject": details.subject,
+        "created_at": details.created_at,
+        "parts": parts,
+    }))
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DownloadParams {
+    item: ItemParam,
+    #[serde(default)]
+    attachment: u32,
+    /// Where to save the attachment. Without it the contents are returned,
+    /// base64 encoded.
+    dir: Option<PathBuf>,
+}
+
+fn download(
+    client: &mut impl Client,
+    params: DownloadParams,
+) -> Result<Value, Error> {
+    let entry = params.item.entry(client)?;
+    if let Some(dir) = params.dir {
+        let destination = Destination { dir, ..Destination::default() };
+        let path = download_attachment(
+            client,
+            &entry.item,
+            params.attachment,
+            &destination,
+        )?;
+        return Ok(json!({ "path": path }));
+    }
+    let details = client.get_item_details(&entry.item.key)?;
+    let contents =
+        get_attachment_body(client, &entry.item, &details, params.attachment)?;
+    let index = params.attachment as usize;
+    Ok(json!({
+        "name": details.attachment_name_for(index, &contents)?,
+        "content_type": details.parts[index].content_type,
+        "content": STANDARD.encode(&contents),
+    }))
+}
+
+fn mark_read(
+    client: &mut impl Client,
+    params: ItemParams,
+) -> Result<Value, Error> {
+    let entry = params.item.entry(client)?;
+    client.mark_as_read(&entry.item.key)?;
+    Ok(Value::Null)
+}
@@ -0,0 +1,280 @@
+//! The settings that can be changed from the TUI. They are written to the
+//! config file with `toml_edit`, which keeps the comments and layout of the
+//! rest of it.
+
+use std::{fs, io::ErrorKind, path::Path};
+use thiserror::Error;
+use toml_edit::{DocumentMut, Item, Table, TableLike, Value};
+
+use crate::{
+    cli::config::validate_text,
+    config::{self, Config},
+};
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("IO error: {0}")]
+    IOError(#[from] std::io::Error),
+
+    #[error(transparent)]
+    ConfigError(#[from] config::Error),
+
+    #[error("failed to parse the config file: {0}")]
+    ParseError(#[from] toml_edit::TomlError),
+
+    #[error("{0}")]
+    InvalidValue(String),
+}
+
+pub enum Kind {
+    /// On or off, toggled rather than typed
+    Flag,
+    Number,
+    Text,
+    /// One of the values, stepped through rather than typed
+    Choice(&'static [&'static str]),
+}
+
+pub struct Setting {
+    /// Where it is in the config file, e.g. `tui.page_size`
+    pub key: &'static str,
+    pub label: &'static str,
+    pub kind: Kind,
+    /// Whether it takes effect only the next time kivinge is started
+    pub restart: bool,
+}
+
+pub const SETTINGS: &[Setting] = &[
+    Setting {
+        key: "language",
+        label: "Language",
+        kind: Kind::Choice(&["auto", "en", "sv"]),
+        restart: true,
+    },
+    Setting {
+        key: "timestamps",
+        label: "Timestamps",
+        kind: Kind::Choice(&["absolute", "relative"]),
+        restart: false,
+    },
+    Setting {
+        key: "download.dir",
+        label: "Download directory",
+        kind: Kind::Text,
+        restart: false,
+    },
+    Setting {
+        key: "download.template",
+        label: "Download path template",
+        kind: Kind::Text,
+        restart: false,
+    },
+    Setting {
+        key: "download.existing",
+        label: "Existing files",
+        kind: Kind::Choice(&["fail", "overwrite", "skip", "rename"]),
+        restart: false,
+    },
+    Setting {
+        key: "tui.color",
+        label: "Theme color",
+        kind: Kind::Text,
+        restart: false,
+    },
+    Setting {
+        key: "tui.mark_read_on_open",
+        label: "Mark read on open",
+        kind: Kind::Flag,
+        restart: false,
+    },
+    Setting {
+        key: "tui.images",
+        label: "Images",
+        kind: Kind::Choice(&["auto", "kitty", "iterm", "off"]),
+        restart: false,
+    },
+    Setting {
+        key: "tui.startup_view",
+        label: "Startup view",
+        kind: Kind::Choice(&["inbox", "unread", "payments", "last"]),
+        restart: true,
+    },
+    Setting {
+        key: "tui.sync_interval",
+        label: "Sync interval (seconds)",
+        kind: Kind::Number,
+        restart: false,
+    },
+    Setting {
+        key: "tui.page_size",
+        label: "Page size",
+        kind: Kind::Number,
+        restart: false,
+    },
+    Setting {
+        key: "cache.inbox_ttl",
+        label: "Inbox cache TTL (seconds)",
+        kind: Kind::Number,
+        restart: true,
+    },
+    Setting {
+        key: "cache.details_ttl",
+        label: "Details cache TTL (seconds)",
+        kind: Kind::Number,
+        restart: true,
+    },
+    Setting {
+        key: "notify.attempts",
+        label: "Notification attempts",
+        kind: Kind::Number,
+        restart: false,
+    },
+    Setting {
+        key: "notify.ntfy.server",
+        label: "ntfy server",
+        kind: Kind::Text,
+        restart: false,
+    },
+    Setting {
+        key: "notify.ntfy.topic",
+        label: "ntfy topic",
+        kind: Kind::Text,
+        restart: false,
+    },
+    Setting {
+        key: "notify.ntfy.priority",
+        label: "ntfy priority",
+        kind: Kind::Number,
+        restart: false,
+    },
+    Setting {
+        key: "notify.gotify.server",
+        label: "Gotify server",
+        kind: Kind::Text,
+        restart: false,
+    },
+    Setting {
+        key: "notify.gotify.priority",
+        label: "Gotify priority",
+        kind: Kind::Number,
+        restart: false,
+    },
+    Setting {
+        key: "notify.smtp.server",
+        label: "SMTP server",
+        kind: Kind::Text,
+        restart: false,
+    },
+    Setting {
+        key: "notify.smtp.from",
+        label: "SMTP sender",
+        kind: Kind::Text,
+        restart: false,
+    },
+];
+
+impl Setting {
+    /// The value in effect, as it would be typed, empty if it is not set
+    pub fn value(&self, config: &Config) -> String {
+        let value = toml::Value::try_from(config).ok().and_then(|value| {
+            self.key
+                .split('.')
+                .try_fold(value, |value, key| value.get(key).cloned())
+        });
+        match value {
+            Some(toml::Value::String(text)) => text,
+            Some(value) => value.to_string(),
+            None => String::new(),
+        }
+    }
+
+    /// What `input` is written as, `None` to leave it to the default
+    pub fn parse(&self, input: &str) -> Result<Option<Value>, Error> {
+        let input = input.trim();
+        if input.is_empty() {
+            return Ok(None);
+        }
+        let value = match self.kind {
+            Kind::Flag => match input {
+                "true" => Value::from(true),
+                "false" => Value::from(false),
+                _ => return Err(self.invalid(input)),
+            },
+            Kind::Number => match input.parse::<i64>() {
+                Ok(number) if number >= 0 => Value::from(number),
+                _ => return Err(self.invalid(input)),
+            },
+            Kind::Text => Value::from(input),
+            Kind::Choice(choices) if choices.contains(&input) => {
+                Value::from(input)
+            }
+            Kind::Choice(_) => return Err(self.invalid(input)),
+        };
+        Ok(Some(value))
+    }
+
+    /// The value after the current one, for flags and choices
+    pub fn next(&self, current: &str) -> Option<String> {
+        match self.kind {
+            Kind::Flag => Some((current != "true").to_string()),
+            Kind::Choice(choices) => {
+                let index =
+                    choices.iter().position(|choice| *choice == current);
+                let next = index.map_or(0, |index| (index + 1) % choices.len());
+                Some(choices[next].to_string())
+            }
+            Kind::Number | Kind::Text => None,
+        }
+    }
+
+    fn invalid(&self, input: &str) -> Error {
+        Error::InvalidValue(format!("'{input}' is not a valid {}", self.key))
+    }
+}
+
+/// Writes the value of the setting to the config file, or removes it for
+/// `None`, and returns the config as it is then. Nothing is written if the
+/// config would be invalid.
+pub fn save(
+    path: &Path,
+    setting: &Setting,
+    value: Option<Value>,
+) -> Result<Config, Error> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(error) if error.kind() == ErrorKind::NotFound => String::new(),
+        Err(error) => return Err(error.into()),
+    };
+    let mut document: DocumentMut = text.parse()?;
+    let (tables, key) =
+        setting.key.rsplit_once('.').unwrap_or(("", setting.key));
+    let mut table: &mut dyn TableLike = document.as_table_mut();
+    for name in tables.split('.').filter(|name| !name.is_empty()) {
+        if !table.contains_key(name) {
+            let mut new = Table::new();
+            new.set_implicit(true);
+            table.insert(name, Item::Table(new));
+        }
+        table = (table.get_mut(name))
+            .and_then(Item::as_table_like_mut)
+            .ok_or_else(|| {
+                Error::InvalidValue(format!("{name} is not a table"))
+            })?;
+    }
+    match value {
+        Some(value) => _ = table.insert(key, Item::Value(value)),
+        None => _ = table.remove(key),
+    }
+
+    let text = document.to_string();
+    let problems = validate_text(&text);
+    if let Some(problem) = problems.iter().find(|problem| problem.is_error()) {
+        return Err(Error::InvalidValue(problem.message.clone()));
+    }
+    let config = toml::from_str(&text).map_err(config::Error::from)?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, text)?;
+    Ok(config)
+}
@@ -0,0 +1,45 @@
+use serde::{Deserialize, Serialize};
+use std::{fs::File, path::PathBuf};
+use thiserror::Error;
+
+use crate::model::content::InboxFilter;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("failed to determine state dir")]
+    CannotFindStateDir,
+
+    #[error("(de)serialization error")]
+    SerializationError(#[from] serde_json::Error),
+
+    #[error("IO error")]
+    IOError(#[from] std::io::Error),
+}
+
+/// UI state remembered between runs
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct State {
+    pub last_view: Option<InboxFilter>,
+}
+
+fn default_state_path() -> Result<PathBuf, Error> {
+    let mut path = dirs::state_dir()
+        .or_else(dirs::data_local_dir)
+        .ok_or(Error::CannotFindStateDir)?;
+    path.push("kivinge.state");
+    Ok(path)
+}
+
+pub fn load() -> Result<State, Error> {
+    let state_path = default_state_path()?;
+    if !state_path.exists() {
+        return Ok(State::default());
+    }
+    Ok(serde_json::from_reader(File::open(state_path)?)?)
+}
+
+pub fn save(state: &State) -> Result<(), Error> {
+    let state_file = File::create(default_state_path()?)?;
+    Ok(serde_json::to_writer(state_file, state)?)
+}
@@ -0,0 +1,141 @@
+use reqwest::Url;
+use std::{
+    fs::{self, File},
+    io::{BufWriter, Write},
+    path::PathBuf,
+};
+use thiserror::Error;
+
+use crate::{config::StorageConfig, util};
+
+mod s3;
+mod webdav;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("missing {0} in the [storage] config")]
+    MissingConfig(&'static str),
+
+    #[error("invalid S3 endpoint: {0}")]
+    InvalidEndpoint(String),
+
+    #[error("upload failed: {0}")]
+    UploadFailed(String),
+
+    #[error("HTTP error: {0}")]
+    HttpError(#[from] reqwest::Error),
+
+    #[error("IO error: {0}")]
+    IOError(#[from] std::io::Error),
+}
+
+/// Where an archive is written, parsed from a path or URL
+#[derive(Clone, Debug)]
+pub enum Target {
+    Local(PathBuf),
+    WebDav(Url),
+    S3 { bucket: String, key: String },
+}
+
+impl Target {
+    /// Name of the file at the target, used to pick the archive format
+    pub fn file_name(&self) -> &str {
+        let name = match self {
+            Target::Local(path) => path.to_str().unwrap_or_default(),
+            Target::WebDav(url) => url.path(),
+            Target::S3 { key, .. } => key,
+        };
+        name.rsplit('/').next().unwrap_or(name)
+    }
+}
+
+/// `s3://bucket/key`, `webdav(s)://` or `http(s)://` URLs, or a local path
+pub fn parse_target(target: &str) -> Result<Target, String> {
+    if let Some(location) = target.strip_prefix("s3://") {
+        return match location.split_once('/') {
+            Some((bucket, key)) if !bucket.is_empty() && !key.is_empty() => {
+                let (bucket, key) = (bucket.to_string(), key.to_string());
+                Ok(Target::S3 { bucket, key })
+            }
+            _ => Err(format!("expected s3://<bucket>/<key>, got '{target}'")),
+        };
+    }
+    let url = match target.split_once("://") {
+        Some(("webdav", rest)) => format!("http://{rest}"),
+        Some(("webdavs", rest)) => format!("https://{rest}"),
+        Some(("http" | "https", _)) => target.to_string(),
+        Some((scheme, _)) => {
+            return Err(format!("unsupported scheme {scheme}"))
+        }
+        None => return Ok(Target::Local(target.into())),
+    };
+    let url = Url::parse(&url).map_err(|error| error.to_string())?;
+    Ok(Target::WebDav(url))
+}
+
+/// A file being written to a target. Nothing is guaranteed to be stored
+/// until `finish` returns.
+pub trait Upload: Write {
+    fn finish(self: Box<Self>) -> Result<(), Error>;
+}
+
+/// Streams straight to the target without a local copy
+pub fn open(
+    target: &Target,
+    config: &StorageConfig,
+) -> Result<Box<dyn Upload>, Error> {
+    Ok(match target {
+        Target::Local(path) => Box::new(LocalUpload::create(path.clone())?),
+        Target::WebDav(url) => {
+            Box::new(webdav::WebDavUpload::start(url.clone(), &config.webdav)?)
+        }
+        Target::S3 { bucket, key } => {
+            Box::new(s3::S3Upload::start(bucket, key, &config.s3)?)
+        }
+    })
+}
+
+/// Written next to the target and renamed into place by `finish`, so that
+/// an interrupted backup leaves the previous archive, if any, as it was
+struct LocalUpload {
+    file: BufWriter<File>,
+    partial: PathBuf,
+    path: PathBuf,
+    finished: bool,
+}
+
+impl LocalUpload {
+    fn create(path: PathBuf) -> Result<Self, Error> {
+        let partial = util::partial_path(&path);
+        let file = BufWriter::new(File::create(&partial)?);
+        Ok(LocalUpload { file, partial, path, finished: false })
+    }
+}
+
+impl Write for LocalUpload {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.file.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.file.flush()
+    }
+}
+
+impl Upload for LocalUpload {
+    fn finish(mut self: Box<Self>) -> Result<(), Error> {
+        self.file.flush()?;
+        self.file.get_ref().sync_all()?;
+        fs::rename(&self.partial, &self.path)?;
+        self.finished = true;
+        Ok(())
+    }
+}
+
+impl Drop for LocalUpload {
+    fn drop(&mut self) {
+        if !self.finished {
+            _ = fs::remove_file(&self.partial);
+        }
+    }
+}
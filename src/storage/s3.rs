@@ -0,0 +1,263 @@
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use reqwest::{
+    blocking::{Client, Response},
+    header::{AUTHORIZATION, ETAG},
+    Method, Url,
+};
+use sha2::Sha256;
+use std::io::{self, Write};
+use tracing::warn;
+
+use super::{Error, Upload};
+use crate::{config::S3Config, index};
+
+// Every part but the last must be at least 5 MiB
+const PART_SIZE: usize = 8 * 1024 * 1024;
+const SIGNED_HEADERS: &str = "host;x-amz-content-sha256;x-amz-date";
+
+/// A multipart upload that sends a part whenever enough has been written, so
+/// at most one part is held in memory. Dropped before `finish` it is aborted.
+pub struct S3Upload {
+    client: Client,
+    config: S3Config,
+    url: Url,
+    upload_id: String,
+    buffer: Vec<u8>,
+    etags: Vec<String>,
+    completed: bool,
+}
+
+impl S3Upload {
+    pub fn start(
+        bucket: &str,
+        key: &str,
+        config: &S3Config,
+    ) -> Result<Self, Error> {
+        if config.access_key_id.is_none() {
+            return Err(Error::MissingConfig("s3.access_key_id"));
+        }
+        if config.secret_access_key.is_none() {
+            return Err(Error::MissingConfig("s3.secret_access_key"));
+        }
+
+        // Path style addressing works with every S3 compatible service
+        let mut url = Url::parse(&config.endpoint)
+            .map_err(|error| Error::InvalidEndpoint(error.to_string()))?;
+        let mut path = url.path().trim_end_matches('/').to_string();
+        for segment in [bucket].into_iter().chain(key.split('/')) {
+            path.push('/');
+            path.push_str(&uri_encode(segment));
+        }
+        url.set_path(&path);
+
+        let client =
+            Client::builder().use_native_tls().timeout(None).build()?;
+        let mut upload = S3Upload {
+            client,
+            config: config.clone(),
+            url,
+            upload_id: String::new(),
+            buffer: Vec::new(),
+            etags: Vec::new(),
+            // Nothing to abort until the upload exists
+            completed: true,
+        };
+        let response = upload.send(Method::POST, &[("uploads", "")], vec![])?;
+        upload.upload_id = xml_text(&response.text()?, "UploadId")
+            .ok_or(Error::UploadFailed("no upload id from S3".to_string()))?;
+        upload.completed = false;
+        Ok(upload)
+    }
+
+    fn upload_part(&mut self) -> Result<(), Error> {
+        let number = (self.etags.len() + 1).to_string();
+        let body = std::mem::take(&mut self.buffer);
+        let upload_id = self.upload_id.clone();
+        let query = [("partNumber", number.as_str()), ("uploadId", &upload_id)];
+        let response = self.send(Method::PUT, &query, body)?;
+        let etag = response
+            .headers()
+            .get(ETAG)
+            .and_then(|etag| etag.to_str().ok())
+            .ok_or(Error::UploadFailed("no ETag for part".to_string()))?;
+        self.etags.push(etag.to_string());
+        Ok(())
+    }
+
+    fn complete(&mut self) -> Result<(), Error> {
+        if !self.buffer.is_empty() || self.etags.is_empty() {
+            self.upload_part()?;
+        }
+        let parts: String = self
+            .etags
+            .iter()
+            .zip(1..)
+            .map(|(etag, number)| {
+                format!(
+                    "<Part><PartNumber>{number}</PartNumber>\
+                     <ETag>{etag}</ETag></Part>"
+                )
+            })
+            .collect();
+        let body = format!(
+            "<CompleteMultipartUpload>{parts}</CompleteMultipartUpload>"
+        );
+        let upload_id = self.upload_id.clone();
+        let query = [("uploadId", upload_id.as_str())];
+        let response = self.send(Method::POST, &query, body.into_bytes())?;
+        // Completing can fail after the response has started as 200 OK
+        let text = response.text()?;
+        if let Some(message) = xml_text(&text, "Message") {
+            return Err(Error::UploadFailed(message));
+        }
+        self.completed = true;
+        Ok(())
+    }
+
+    fn abort(&mut self) -> Result<(), Error> {
+        let upload_id = self.upload_id.clone();
+        let query = [("uploadId", upload_id.as_str())];
+        self.send(Method::DELETE, &query, vec![])?;
+        Ok(())
+    }
+
+    /// Sends a request signed with AWS Signature Version 4
+    fn send(
+        &self,
+        method: Method,
+        query: &[(&str, &str)],
+        body: Vec<u8>,
+    ) -> Result<Response, Error> {
+        let now = Utc::now();
+        let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let date = now.format("%Y%m%d").to_string();
+        let payload_hash = index::sha256(&body);
+
+        let host = match self.url.port() {
+            Some(port) => {
+                format!("{}:{port}", self.url.host_str().unwrap_or_default())
+            }
+            None => self.url.host_str().unwrap_or_default().to_string(),
+        };
+        let mut query: Vec<String> = query
+            .iter()
+            .map(|(name, value)| {
+                format!("{}={}", uri_encode(name), uri_encode(value))
+            })
+            .collect();
+        query.sort();
+        let query = query.join("&");
+
+        let canonical_request = format!(
+            "{method}\n{}\n{query}\nhost:{host}\n\
+             x-amz-content-sha256:{payload_hash}\nx-amz-date:{amz_date}\n\n\
+             {SIGNED_HEADERS}\n{payload_hash}",
+            self.url.path()
+        );
+        let region = &self.config.region;
+        let scope = format!("{date}/{region}/s3/aws4_request");
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{amz_date}\n{scope}\n{}",
+            index::sha256(canonical_request.as_bytes())
+        );
+        let secret = self.config.secret_access_key.clone().unwrap_or_default();
+        let signing_key = [date.as_str(), region, "s3", "aws4_request"]
+            .into_iter()
+            .fold(format!("AWS4{secret}").into_bytes(), |key, data| {
+                hmac(&key, data.as_bytes())
+            });
+        let signature = hmac(&signing_key, string_to_sign.as_bytes())
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect::<String>();
+        let access_key_id =
+            self.config.access_key_id.clone().unwrap_or_default();
+        let authorization = format!(
+            "AWS4-HMAC-SHA256 Credential={access_key_id}/{scope}, \
+             SignedHeaders={SIGNED_HEADERS}, Signature={signature}"
+        );
+
+        let mut url = self.url.clone();
+        url.set_query(Some(query.as_str()).filter(|query| !query.is_empty()));
+        let response = self
+            .client
+            .request(method, url)
+            .header("x-amz-date", amz_date)
+            .header("x-amz-content-sha256", payload_hash)
+            .header(AUTHORIZATION, authorization)
+            .body(body)
+            .send()?;
+        match response.status().is_success() {
+            true => Ok(response),
+            false => {
+                let status = response.status();
+                let text = response.text().unwrap_or_default();
+                let message = xml_text(&text, "Message").unwrap_or(text);
+                Err(Error::UploadFailed(format!("{status}: {message}")))
+            }
+        }
+    }
+}
+
+impl Write for S3Upload {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        if self.buffer.len() >= PART_SIZE {
+            self.upload_part().map_err(io::Error::other)?;
+        }
+        Ok(buf.len())
+    }
+
+    // Parts are sent once they are full, or by `finish`
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Upload for S3Upload {
+    fn finish(mut self: Box<Self>) -> Result<(), Error> {
+        self.complete()
+    }
+}
+
+impl Drop for S3Upload {
+    fn drop(&mut self) {
+        if !self.completed {
+            if let Err(error) = self.abort() {
+                warn!("failed to abort S3 upload {}: {error}", self.upload_id);
+            }
+        }
+    }
+}
+
+fn hmac(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(key).expect("HMAC takes any key size");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// Percent encodes everything but unreserved characters, as SigV4 wants
+fn uri_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z'
+            | b'a'..=b'z'
+            | b'0'..=b'9'
+            | b'-'
+            | b'_'
+            | b'.'
+            | b'~' => (byte as char).to_string(),
+            _ => format!("%{byte:02X}"),
+        })
+        .collect()
+}
+
+/// Text of the first `<tag>` element, enough for S3's simple responses
+fn xml_text(xml: &str, tag: &str) -> Option<String> {
+    let start = xml.find(&format!("<{tag}>"))? + tag.len() + 2;
+    let end = start + xml[start..].find(&format!("</{tag}>"))?;
+    Some(xml[start..end].to_string())
+}
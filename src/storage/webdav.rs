@@ -0,0 +1,101 @@
+use reqwest::{blocking::Body, Url};
+use std::{
+    io::{self, PipeReader, PipeWriter, Read, Write},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+};
+
+use super::{Error, Upload};
+use crate::config::WebDavConfig;
+
+/// A single PUT whose body is fed through a pipe while it is being sent.
+/// Dropped without `finish`, the body ends in an error rather than with
+/// what was written so far, so that the server is left without a truncated
+/// archive.
+pub struct WebDavUpload {
+    writer: PipeWriter,
+    request: Option<JoinHandle<Result<(), Error>>>,
+    finished: Arc<AtomicBool>,
+}
+
+impl WebDavUpload {
+    pub fn start(url: Url, config: &WebDavConfig) -> Result<Self, Error> {
+        let (reader, writer) = io::pipe()?;
+        let finished = Arc::new(AtomicBool::new(false));
+        let reader = PipeBody { reader, finished: finished.clone() };
+        let mut request = reqwest::blocking::Client::builder()
+            .use_native_tls()
+            .timeout(None)
+            .build()?
+            .put(url)
+            .body(Body::new(reader));
+        if let Some(username) = &config.username {
+            request = request.basic_auth(username, config.password.as_ref());
+        }
+        let request = thread::spawn(move || {
+            request.send()?.error_for_status()?;
+            Ok(())
+        });
+        Ok(WebDavUpload { writer, request: Some(request), finished })
+    }
+}
+
+/// The read end of the pipe, which only ends once the upload is finished
+struct PipeBody {
+    reader: PipeReader,
+    finished: Arc<AtomicBool>,
+}
+
+impl Read for PipeBody {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.reader.read(buf)? {
+            0 if !buf.is_empty() && !self.finished.load(Ordering::Relaxed) => {
+                Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "the upload was dropped before it was finished",
+                ))
+            }
+            read => Ok(read),
+        }
+    }
+}
+
+fn join(request: Option<JoinHandle<Result<(), Error>>>) -> Result<(), Error> {
+    match request.map(JoinHandle::join) {
+        None => Ok(()),
+        Some(Ok(result)) => result,
+        Some(Err(_)) => {
+            Err(Error::UploadFailed("upload thread panicked".to_string()))
+        }
+    }
+}
+
+impl Write for WebDavUpload {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self.writer.write(buf) {
+            // The server stopped reading, so the request has the real error
+            Err(error) if error.kind() == io::ErrorKind::BrokenPipe => {
+                join(self.request.take()).map_err(io::Error::other)?;
+                Err(error)
+            }
+            result => result,
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+impl Upload for WebDavUpload {
+    fn finish(self: Box<Self>) -> Result<(), Error> {
+        let WebDavUpload { writer, request, finished } = *self;
+        finished.store(true, Ordering::Relaxed);
+        // Closing the pipe ends the request body
+        drop(writer);
+        join(request)
+    }
+}
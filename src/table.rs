@@ -0,0 +1,586 @@
+use chrono::{Local, TimeZone};
+use clap::ValueEnum;
+use ratatui::{
+    layout::Constraint,
+    style::{self, Stylize},
+    widgets::{self, Cell, Row},
+};
+use serde::{ser::SerializeMap, Deserialize, Serialize, Serializer};
+use serde_json::Value;
+use tabled::{
+    builder::Builder,
+    settings::{object::Columns, width, Modify, Style},
+};
+use thiserror::Error;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+use crate::{
+    i18n,
+    model::content::{InboxEntry, Status},
+    tr,
+};
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("unknown column '{0}', available columns: {1}")]
+    UnknownColumn(String, String),
+}
+
+/// How a table is written out
+#[derive(Clone, Copy, Debug, Default, ValueEnum)]
+pub enum Format {
+    #[default]
+    Table,
+    Csv,
+    Tsv,
+    Json,
+}
+
+/// How listings show when items were created
+#[derive(
+    Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, ValueEnum,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Timestamps {
+    /// The date and time, e.g. 2024-08-05 18:12
+    #[default]
+    Absolute,
+    /// How long ago, e.g. "2 h ago" or "yesterday"
+    Relative,
+}
+
+impl Timestamps {
+    /// The named columns, with `created` shown as `age` if relative
+    pub fn columns(self, names: &[impl AsRef<str>]) -> Vec<String> {
+        let names = names.iter().map(|name| name.as_ref());
+        match self {
+            Timestamps::Absolute => names.map(String::from).collect(),
+            Timestamps::Relative => names
+                .map(|name| match name {
+                    "created" => "age".to_string(),
+                    name => name.to_string(),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// How the width of a column is decided
+#[derive(Clone, Copy, Debug)]
+pub enum Width {
+    /// As wide as the widest cell
+    Fit,
+    /// As wide as the widest cell if there is room, otherwise a share of what
+    /// the other columns leave, but never narrower than the given width
+    Fill(usize),
+}
+
+pub struct TableCell {
+    pub text: String,
+    pub bold: bool,
+}
+
+impl TableCell {
+    pub fn bold(self) -> TableCell {
+        TableCell { bold: true, ..self }
+    }
+}
+
+impl From<String> for TableCell {
+    fn from(text: String) -> TableCell {
+        TableCell { text, bold: false }
+    }
+}
+
+impl From<&str> for TableCell {
+    fn from(text: &str) -> TableCell {
+        TableCell::from(text.to_string())
+    }
+}
+
+pub struct Column<R> {
+    /// What the column is selected by
+    pub name: &'static str,
+    pub header: &'static str,
+    pub width: Width,
+    pub cell: fn(&R) -> TableCell,
+    /// What CSV, TSV and JSON have instead of the text of the cell, e.g. an
+    /// amount without its currency
+    pub value: Option<fn(&R) -> Value>,
+}
+
+/// Something that is listed as rows of a table
+pub trait TableRow: Sized {
+    /// Every column the rows can be shown with, in their default order
+    fn columns() -> Vec<Column<Self>>;
+}
+
+/// Rows with a selection of their columns, rendered either as text for the
+/// CLI or as a widget for the TUI. Both fit the columns to the width they
+/// get the same way.
+pub struct Table<'a, R: TableRow> {
+    rows: &'a [R],
+    columns: Vec<Column<R>>,
+}
+
+impl<'a, R: TableRow> Table<'a, R> {
+    pub fn new(rows: &'a [R]) -> Self {
+        Table { rows, columns: R::columns() }
+    }
+
+    /// Shows only the named columns, in the given order
+    pub fn with_columns(
+        self,
+        names: &[impl AsRef<str>],
+    ) -> Result<Self, Error> {
+        let columns = names
+            .iter()
+            .map(|name| column(name.as_ref()))
+            .collect::<Result<_, _>>()?;
+        Ok(Table { columns, ..self })
+    }
+
+    /// The table in the given format, with a header row
+    pub fn format(&self, format: Format, width: usize) -> String {
+        match format {
+            Format::Table => self.render(width),
+            Format::Csv => self.separated(',', csv_field),
+            Format::Tsv => self.separated('\t', tsv_field),
+            Format::Json => self.json(),
+        }
+    }
+
+    /// The table with a header and borders, at most `width` characters wide
+    /// unless the columns cannot be made to fit
+    pub fn render(&self, width: usize) -> String {
+        let cells = self.cells();
+        let widths = {
+            // A border before each column and after the last, and a space on
+            // each side of every cell
+            let count = self.columns.len();
+            let borders = (count + 1) + count * 2;
+            self.widths(&cells, true, width.saturating_sub(borders))
+        };
+
+        let mut builder = Builder::default();
+        builder
+            .push_record(self.columns.iter().map(|column| tr!(column.header)));
+        for row in cells {
+            builder.push_record(row.into_iter().map(|cell| cell.text));
+        }
+        let mut table = builder.build();
+        table.with(Style::modern());
+        for (index, width) in widths.into_iter().enumerate() {
+            table.with(
+                Modify::new(Columns::single(index))
+                    .with(width::Width::truncate(width).suffix("…")),
+            );
+        }
+        table.to_string()
+    }
+
+    /// The rows as a widget `width` characters wide, without a header.
+    /// Marked rows get a `*` in front of them. Rows made taller by
+    /// `row_height` have their cells on the first line, leaving room under
+    /// them for something else to be drawn.
+    pub fn widget(
+        &self,
+        width: u16,
+        is_marked: impl Fn(&R) -> bool,
+        row_style: impl Fn(&R) -> style::Style,
+        row_height: impl Fn(&R) -> u16,
+    ) -> widgets::Table<'static> {
+        let cells = self.cells();
+        // The mark, and a space between every two columns
+        let spacing = 1 + self.columns.len();
+        let widths = self.widths(
+            &cells,
+            false,
+            (width as usize).saturating_sub(spacing),
+        );
+
+        let rows = self.rows.iter().zip(cells).map(|(row, cells)| {
+            let mark = Cell::new(if is_marked(row) { "*" } else { " " }).bold();
+            let cells = cells.into_iter().zip(&widths).map(|(cell, width)| {
+                let text = truncate(&cell.text, *width);
+                match cell.bold {
+                    true => Cell::new(text).bold(),
+                    false => Cell::new(text),
+                }
+            });
+            Row::new([mark].into_iter().chain(cells))
+                .style(row_style(row))
+                .height(row_height(row))
+        });
+        // The last filling column takes what is left, so rows reach across
+        let last_fill = self
+            .columns
+            .iter()
+            .rposition(|column| matches!(column.width, Width::Fill(_)));
+        let constraints = widths.iter().enumerate().map(|(index, width)| {
+            match Some(index) == last_fill {
+                true => Constraint::Fill(1),
+                false => Constraint::Length(*width as u16),
+            }
+        });
+        let constraints =
+            [Constraint::Length(1)].into_iter().chain(constraints);
+        widgets::Table::new(rows, constraints).column_spacing(1)
+    }
+
+    /// One line per row with the fields made safe by `field`
+    fn separated(&self, separator: char, field: fn(&str) -> String) -> String {
+        let header = self.columns.iter().map(|column| field(column.header));
+        let rows = self.values().into_iter().map(|row| {
+            row.iter()
+                .map(|value| field(&value_text(value)))
+                .collect::<Vec<_>>()
+                .join(&separator.to_string())
+        });
+        let header = header.collect::<Vec<_>>().join(&separator.to_string());
+        [header].into_iter().chain(rows).collect::<Vec<_>>().join("\n")
+    }
+
+    /// An array with an object per row, keyed by the names of the columns
+    fn json(&self) -> String {
+        let rows: Vec<JsonRow> = self
+            .values()
+            .into_iter()
+            .map(|row| {
+                let names = self.columns.iter().map(|column| column.name);
+                JsonRow(names.zip(row).collect())
+            })
+            .collect();
+        serde_json::to_string_pretty(&rows).expect("JSON values serialize")
+    }
+
+    /// The rows for programs rather than people
+    fn values(&self) -> Vec<Vec<Value>> {
+        self.rows
+            .iter()
+            .map(|row| {
+                self.columns
+                    .iter()
+                    .map(|column| match column.value {
+                        Some(value) => value(row),
+                        None => Value::String((column.cell)(row).text),
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn cells(&self) -> Vec<Vec<TableCell>> {
+        self.rows
+            .iter()
+            .map(|row| {
+                self.columns.iter().map(|column| (column.cell)(row)).collect()
+            })
+            .collect()
+    }
+
+    /// Widths of the columns, sharing what `available` leaves after the
+    /// fitted ones between those that fill. Text has a header and may end up
+    /// wider than `available` rather than squeeze columns below their
+    /// minimum, which a widget cannot.
+    fn widths(
+        &self,
+        cells: &[Vec<TableCell>],
+        text: bool,
+        available: usize,
+    ) -> Vec<usize> {
+        let mut widths: Vec<usize> = self
+            .columns
+            .iter()
+            .enumerate()
+            .map(|(index, column)| {
+                let header = if text { tr!(column.header).width() } else { 0 };
+                cells
+                    .iter()
+                    .map(|row| row[index].text.width())
+                    .fold(header, usize::max)
+            })
+            .collect();
+
+        let mut filling = Vec::new();
+        let mut left = available;
+        for (index, column) in self.columns.iter().enumerate() {
+            match column.width {
+                Width::Fit => left = left.saturating_sub(widths[index]),
+                Width::Fill(min) => {
+                    filling.push((index, if text { min } else { 0 }))
+                }
+            }
+        }
+        // Narrow columns first, so that what they do not need goes to the
+        // wider ones
+        filling.sort_by_key(|(index, _)| widths[*index]);
+        for (filled, (index, min)) in filling.iter().enumerate() {
+            let share = left / (filling.len() - filled);
+            widths[*index] = widths[*index].min(share.max(*min));
+            left = left.saturating_sub(widths[*index]);
+        }
+        widths
+    }
+}
+
+/// Checks a column name given on the command line
+pub fn parse_column<R: TableRow>(name: &str) -> Result<String, String> {
+    column::<R>(name.trim())
+        .map(|column| column.name.to_string())
+        .map_err(|error| error.to_string())
+}
+
+fn column<R: TableRow>(name: &str) -> Result<Column<R>, Error> {
+    R::columns().into_iter().find(|column| column.name == name).ok_or_else(
+        || {
+            let available: Vec<_> =
+                R::columns().iter().map(|column| column.name).collect();
+            Error::UnknownColumn(name.to_string(), available.join(", "))
+        },
+    )
+}
+
+/// The fields of a row in the order of the columns, which a JSON object
+/// would sort
+struct JsonRow(Vec<(&'static str, Value)>);
+
+impl Serialize for JsonRow {
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.0.len()))?;
+        for (name, value) in &self.0 {
+            map.serialize_entry(name, value)?;
+        }
+        map.end()
+    }
+}
+
+/// A value as a CSV or TSV field, with nothing for a missing one
+fn value_text(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(text) => text.clone(),
+        value => value.to_string(),
+    }
+}
+
+/// A number as JSON has it, or null for none
+fn number(number: Option<impl ToString>) -> Value {
+    let number = number.and_then(|number| number.to_string().parse().ok());
+    number.map_or(Value::Null, Value::Number)
+}
+
+/// Quotes a field that has a comma, quote or line break in it, as RFC 4180
+/// asks
+fn csv_field(text: &str) -> String {
+    match text.contains([',', '"', '\n', '\r']) {
+        true => format!("\"{}\"", text.replace('"', "\"\"")),
+        false => text.to_string(),
+    }
+}
+
+/// TSV cannot quote, so tabs and line breaks become spaces
+fn tsv_field(text: &str) -> String {
+    text.replace(['\t', '\n', '\r'], " ")
+}
+
+/// Cuts text down to `width` characters, ending it with … if anything was cut
+fn truncate(text: &str, width: usize) -> String {
+    if text.width() <= width {
+        return text.to_string();
+    }
+    let mut truncated = String::new();
+    let mut used = 1;
+    for c in text.chars() {
+        used += c.width().unwrap_or_default();
+        if used > width {
+            break;
+        }
+        truncated.push(c);
+    }
+    if width > 0 {
+        truncated.push('…');
+    }
+    truncated
+}
+
+/// A size in bytes for people, e.g. 340 B, 12 kB or 1.4 MB
+pub fn file_size(bytes: usize) -> String {
+    match bytes {
+        0..1_000 => format!("{bytes} B"),
+        1_000..1_000_000 => format!("{:.0} kB", bytes as f64 / 1e3),
+        _ => format!("{:.1} MB", bytes as f64 / 1e6),
+    }
+}
+
+impl TableRow for InboxEntry {
+    fn columns() -> Vec<Column<Self>> {
+        inbox_columns()
+    }
+}
+
+impl AsRef<InboxEntry> for InboxEntry {
+    fn as_ref(&self) -> &InboxEntry {
+        self
+    }
+}
+
+/// The columns of inbox entries, for rows that list entries along with
+/// something more
+pub fn inbox_columns<R: AsRef<InboxEntry>>() -> Vec<Column<R>> {
+    vec![
+        Column {
+            name: "new",
+            header: "New",
+            width: Width::Fit,
+            cell: |row| match row.as_ref().item.status {
+                Status::Unread => TableCell::from("NEW").bold(),
+                _ => TableCell::from("   "),
+            },
+            value: None,
+        },
+        Column {
+            name: "profile",
+            header: "Profile",
+            width: Width::Fit,
+            cell: |row| {
+                let entry = row.as_ref();
+                entry.profile.as_deref().unwrap_or_default().into()
+            },
+            value: None,
+        },
+        Column {
+            name: "id",
+            header: "Id",
+            width: Width::Fit,
+            cell: |row| row.as_ref().id.to_string().into(),
+            value: Some(|row| row.as_ref().id.into()),
+        },
+        Column {
+            name: "sender",
+            header: "Sender",
+            width: Width::Fill(20),
+            cell: |row| row.as_ref().item.sender_name.as_str().into(),
+            value: None,
+        },
+        Column {
+            name: "subject",
+            header: "Subject",
+            width: Width::Fill(20),
+            cell: |row| row.as_ref().item.subject.as_str().into(),
+            value: None,
+        },
+        Column {
+            name: "amount",
+            header: "Amount",
+            width: Width::Fit,
+            cell: |row| {
+                let entry = row.as_ref();
+                let item = &entry.item;
+                let amount = item.amount.map(|amount| {
+                    let currency = item.currency.as_deref();
+                    format!("{amount} {}", currency.unwrap_or_default())
+                });
+                amount.unwrap_or_default().trim_end().into()
+            },
+            value: Some(|row| number(row.as_ref().item.amount)),
+        },
+        Column {
+            name: "currency",
+            header: "Currency",
+            width: Width::Fit,
+            cell: |row| {
+                let entry = row.as_ref();
+                entry.item.currency.as_deref().unwrap_or_default().into()
+            },
+            value: Some(|row| row.as_ref().item.currency.clone().into()),
+        },
+        Column {
+            name: "due",
+            header: "Due Date",
+            width: Width::Fit,
+            cell: |row| {
+                let entry = row.as_ref();
+                let due_date = entry.item.due_date.as_ref();
+                due_date
+                    .map(|date| date.0.to_string())
+                    .unwrap_or_default()
+                    .into()
+            },
+            value: None,
+        },
+        Column {
+            name: "agreement",
+            header: "Agreement",
+            width: Width::Fit,
+            cell: |row| {
+                let entry = row.as_ref();
+                let item = &entry.item;
+                let status = item.agreement_status.as_ref();
+                status
+                    .or(item.agreement_key.as_ref())
+                    .map(String::as_str)
+                    .unwrap_or_default()
+                    .into()
+            },
+            value: None,
+        },
+        Column {
+            name: "attachments",
+            header: "Attachments",
+            width: Width::Fit,
+            cell: |row| {
+                let entry = row.as_ref();
+                let attachments = entry.attachments.as_ref();
+                (attachments.map(|attachments| attachments.count.to_string()))
+                    .unwrap_or_default()
+                    .into()
+            },
+            value: Some(|row| {
+                let entry = row.as_ref();
+                number(entry.attachments.map(|summary| summary.count))
+            }),
+        },
+        Column {
+            name: "size",
+            header: "Size",
+            width: Width::Fit,
+            cell: |row| {
+                let entry = row.as_ref();
+                let attachments = entry.attachments.as_ref();
+                (attachments.map(|attachments| file_size(attachments.size)))
+                    .unwrap_or_default()
+                    .into()
+            },
+            value: None,
+        },
+        Column {
+            name: "created",
+            header: "Created At",
+            width: Width::Fit,
+            cell: |row| {
+                let entry = row.as_ref();
+                Local
+                    .from_utc_datetime(&entry.item.created_at.naive_utc())
+                    .format("%Y-%m-%d %H:%M")
+                    .to_string()
+                    .into()
+            },
+            value: None,
+        },
+        Column {
+            name: "age",
+            header: "Created",
+            width: Width::Fit,
+            cell: |row| {
+                let entry = row.as_ref();
+                let created_at = entry.item.created_at.with_timezone(&Local);
+                i18n::time_ago(created_at, Local::now()).into()
+            },
+            value: None,
+        },
+    ]
+}
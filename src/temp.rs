@@ -0,0 +1,118 @@
+//! Attachments written out for `open` are kept in a directory of their own,
+//! readable only by the user, rather than in the shared temp dir, and are
+//! removed again on exit or once they are old enough
+
+use std::{
+    fs::{self, DirBuilder, File},
+    io::Write,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{Duration, SystemTime},
+};
+use thiserror::Error;
+use tracing::{debug, warn};
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("IO error: {0}")]
+    IOError(#[from] std::io::Error),
+}
+
+/// Files written by this process, removed by `clean_opened`
+static OPENED: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+/// The per-user runtime dir where there is one, otherwise a dir named after
+/// the user in the temp dir
+pub fn dir() -> PathBuf {
+    match dirs::runtime_dir() {
+        Some(dir) => dir.join("kivinge"),
+        None => {
+            let user = std::env::var("USER").unwrap_or_default();
+            std::env::temp_dir().join(format!("kivinge-{user}"))
+        }
+    }
+}
+
+fn create_dir() -> Result<PathBuf, Error> {
+    let dir = dir();
+    let mut builder = DirBuilder::new();
+    builder.recursive(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+        builder.mode(0o700);
+        builder.create(&dir)?;
+        // The dir may be left from before, or made by someone else
+        fs::set_permissions(&dir, fs::Permissions::from_mode(0o700))?;
+    }
+    #[cfg(not(unix))]
+    builder.create(&dir)?;
+    Ok(dir)
+}
+
+/// Writes a file to be opened, and remembers it for `clean_opened`
+pub fn write(filename: &str, contents: &[u8]) -> Result<PathBuf, Error> {
+    let path = create_dir()?.join(filename);
+    let mut options = File::options();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(&path)?.write_all(contents)?;
+    if let Ok(mut opened) = OPENED.lock() {
+        opened.push(path.clone());
+    }
+    Ok(path)
+}
+
+/// Removes the files written by this process. Viewers that have them open
+/// keep showing them.
+pub fn clean_opened() {
+    let Ok(mut opened) = OPENED.lock() else { return };
+    for path in opened.drain(..) {
+        remove(&path);
+    }
+}
+
+/// Removes the files last modified longer than `age` ago, or all of them
+/// without an age. Returns how many were removed.
+pub fn clean(age: Option<Duration>) -> Result<usize, Error> {
+    let dir = dir();
+    if !dir.exists() {
+        return Ok(0);
+    }
+    let now = SystemTime::now();
+    let mut removed = 0;
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        if !entry.file_type()?.is_file() {
+            continue;
+        }
+        if let Some(age) = age {
+            let modified = entry.metadata()?.modified()?;
+            if now.duration_since(modified).unwrap_or_default() < age {
+                continue;
+            }
+        }
+        if remove(&entry.path()) {
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
+
+fn remove(path: &Path) -> bool {
+    match fs::remove_file(path) {
+        Ok(()) => {
+            debug!("removed {}", path.display());
+            true
+        }
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => false,
+        Err(error) => {
+            warn!("failed to remove {}: {error}", path.display());
+            false
+        }
+    }
+}
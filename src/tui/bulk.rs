@@ -0,0 +1,243 @@
+use std::{fmt::Display, time::Instant};
+
+use ratatui::{
+    layout::{Constraint, Layout, Rect},
+    style::{Color, Style, Stylize},
+    widgets::{Gauge, List, ListItem, Paragraph},
+    Frame,
+};
+
+use super::{
+    keymap::{KeyEvent, KeyHint},
+    popup::Popup,
+    Command, Error, Event, Message, TuiView,
+};
+use crate::{
+    client::Client,
+    model::content::{InboxEntry, Status},
+    tr,
+    util::{download_all_attachments, Destination},
+};
+
+#[derive(Clone, Debug)]
+pub enum BulkAction {
+    Download(Destination),
+    MarkRead,
+}
+
+impl Display for BulkAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            BulkAction::Download(destination) => {
+                let dir = destination.dir.to_string_lossy();
+                write!(f, "{}", tr!("Downloading to {dir}", dir = dir))
+            }
+            BulkAction::MarkRead => write!(f, "{}", tr!("Marking as read")),
+        }
+    }
+}
+
+enum ItemStatus {
+    Pending,
+    Done(String),
+    Failed(String),
+    Cancelled,
+}
+
+pub struct BulkSummary {
+    pub done: usize,
+    pub failed: usize,
+    pub cancelled: usize,
+}
+
+impl BulkSummary {
+    /// What was done, for the status bar of the view that asked for it
+    pub fn message(&self, action: &BulkAction) -> Message {
+        let mut text = match action {
+            BulkAction::Download(destination) => tr!(
+                "Downloaded {done} item(s) to {dir}",
+                done = self.done,
+                dir = destination.dir.to_string_lossy()
+            ),
+            BulkAction::MarkRead => {
+                tr!("Marked {done} item(s) as read", done = self.done)
+            }
+        };
+        if self.failed > 0 {
+            text += &tr!(", {failed} failed", failed = self.failed);
+        }
+        if self.cancelled > 0 {
+            text += &tr!(", {cancelled} cancelled", cancelled = self.cancelled);
+        }
+        match self.failed {
+            0 => Message::info(text),
+            _ => Message::error(text),
+        }
+    }
+}
+
+pub struct BulkView<'a, C: Client> {
+    client: &'a mut C,
+    action: BulkAction,
+    items: Vec<(InboxEntry, ItemStatus)>,
+    next: usize,
+    finished: bool,
+}
+
+impl<'a, C: Client> BulkView<'a, C> {
+    pub fn make(
+        client: &'a mut C,
+        action: BulkAction,
+        entries: Vec<InboxEntry>,
+    ) -> BulkView<'a, C> {
+        let items = entries.into_iter().map(|e| (e, ItemStatus::Pending));
+        BulkView {
+            client,
+            action,
+            items: items.collect(),
+            next: 0,
+            finished: false,
+        }
+    }
+
+    fn process_next(&mut self) {
+        let Some((entry, status)) = self.items.get_mut(self.next) else {
+            self.finished = true;
+            return;
+        };
+        let result = match &self.action {
+            BulkAction::Download(destination) => {
+                download_all_attachments(self.client, &entry.item, destination)
+                    .map(|paths| format!("{} file(s)", paths.len()))
+                    .map_err(|err| err.to_string())
+            }
+            BulkAction::MarkRead => {
+                match self.client.mark_as_read(&entry.item.key) {
+                    Ok(()) => {
+                        entry.item.status = Status::Read;
+                        Ok("read".to_string())
+                    }
+                    Err(err) => Err(err.to_string()),
+                }
+            }
+        };
+        *status = match result {
+            Ok(message) => ItemStatus::Done(message),
+            Err(message) => ItemStatus::Failed(message),
+        };
+        self.next += 1;
+        self.finished = self.next >= self.items.len();
+    }
+
+    fn cancel(&mut self) {
+        for (_, status) in self.items.iter_mut().skip(self.next) {
+            *status = ItemStatus::Cancelled;
+        }
+        self.next = self.items.len();
+        self.finished = true;
+    }
+
+    fn summary(&self) -> BulkSummary {
+        let count = |pred: fn(&ItemStatus) -> bool| {
+            self.items.iter().filter(|(_, status)| pred(status)).count()
+        };
+        BulkSummary {
+            done: count(|s| matches!(s, ItemStatus::Done(_))),
+            failed: count(|s| matches!(s, ItemStatus::Failed(_))),
+            cancelled: count(|s| matches!(s, ItemStatus::Cancelled)),
+        }
+    }
+}
+
+impl<'a, C: Client> TuiView for BulkView<'a, C> {
+    type ReturnType = BulkSummary;
+
+    fn update(
+        &mut self,
+        event: Event,
+    ) -> Result<Command<Self::ReturnType>, Error> {
+        match event {
+            _ if self.finished => match event {
+                Event::Key(_) => Ok(Command::Return(self.summary())),
+                _ => Ok(Command::Await),
+            },
+
+            Event::Key(KeyEvent::Quit) => {
+                self.cancel();
+                Ok(Command::Await)
+            }
+
+            Event::Tick => {
+                self.process_next();
+                match self.finished {
+                    true => Ok(Command::Await),
+                    false => Ok(Command::AwaitUntil(Instant::now())),
+                }
+            }
+
+            _ => Ok(Command::AwaitUntil(Instant::now())),
+        }
+    }
+
+    fn key_hints(&self) -> Vec<KeyHint> {
+        match self.finished {
+            true => vec![KeyHint::new("any key", "close")],
+            false => vec![KeyHint::new("q", "cancel")],
+        }
+    }
+
+    fn render(&mut self, frame: &mut Frame, rect: Rect) {
+        let title = self.action.to_string();
+        let inner = Popup::new(&title).size(80, 60).render(frame, rect);
+
+        let layout = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Fill(1),
+            Constraint::Length(1),
+        ])
+        .split(inner);
+
+        let total = self.items.len().max(1);
+        let gauge = Gauge::default()
+            .gauge_style(Style::new().fg(Color::Green))
+            .ratio(self.next as f64 / total as f64)
+            .label(format!("{}/{}", self.next, self.items.len()));
+        frame.render_widget(gauge, layout[0]);
+
+        // Keep the item being processed in view
+        let visible = layout[1].height as usize;
+        let skip = self.next.saturating_sub(visible.saturating_sub(1));
+        let rows: Vec<ListItem> =
+            self.items.iter().skip(skip).map(status_row).collect();
+        frame.render_widget(List::new(rows), layout[1]);
+
+        let footer = match self.finished {
+            false => tr!("Press 'q' to cancel").to_string(),
+            true => {
+                let summary = self.summary();
+                tr!(
+                    "{done} done, {failed} failed, {cancelled} cancelled - \
+                     press any key",
+                    done = summary.done,
+                    failed = summary.failed,
+                    cancelled = summary.cancelled
+                )
+            }
+        };
+        frame.render_widget(Paragraph::new(footer).centered(), layout[2]);
+    }
+}
+
+fn status_row((entry, status): &(InboxEntry, ItemStatus)) -> ListItem<'static> {
+    let (marker, message, color) = match status {
+        ItemStatus::Pending => ("…", String::new(), Color::Gray),
+        ItemStatus::Done(message) => ("✓", message.clone(), Color::Green),
+        ItemStatus::Failed(message) => ("✗", message.clone(), Color::Red),
+        ItemStatus::Cancelled => ("-", "cancelled".into(), Color::DarkGray),
+    };
+    let text = format!(
+        "{marker} {:>4} {} - {} {message}",
+        entry.id, entry.item.sender_name, entry.item.subject
+    );
+    ListItem::new(text).fg(color)
+}
@@ -0,0 +1,65 @@
+use crossterm::event::KeyCode;
+use ratatui::{layout::Rect, Frame};
+
+use super::{
+    keymap::{KeyEvent, KeyHint},
+    popup::render_confirm,
+    Command, Error, Event, TuiView,
+};
+
+/// A yes/no question. Views that need one keep it while it is open, pass it
+/// their keys and draw it on top of themselves.
+pub struct ConfirmView {
+    prompt: String,
+    default: bool,
+}
+
+impl ConfirmView {
+    /// Asks `prompt`, with no as the answer to enter
+    pub fn new(prompt: impl Into<String>) -> ConfirmView {
+        ConfirmView { prompt: prompt.into(), default: false }
+    }
+
+    /// The answer to enter
+    pub fn with_default(self, default: bool) -> ConfirmView {
+        ConfirmView { default, ..self }
+    }
+}
+
+impl TuiView for ConfirmView {
+    type ReturnType = bool;
+
+    fn update(
+        &mut self,
+        event: Event,
+    ) -> Result<Command<Self::ReturnType>, Error> {
+        match event {
+            Event::Key(KeyEvent::Key(KeyCode::Char('y'))) => {
+                Ok(Command::Return(true))
+            }
+
+            Event::Key(KeyEvent::Select) => Ok(Command::Return(self.default)),
+
+            // Including n, which the keymap takes for down
+            Event::Key(_) => Ok(Command::Return(false)),
+
+            _ => Ok(Command::Await),
+        }
+    }
+
+    fn key_hints(&self) -> Vec<KeyHint> {
+        let default = match self.default {
+            true => "yes",
+            false => "no",
+        };
+        vec![
+            KeyHint::new("y", "yes"),
+            KeyHint::new("n", "no"),
+            KeyHint::new("enter", default),
+        ]
+    }
+
+    fn render(&mut self, frame: &mut Frame, rect: Rect) {
+        render_confirm(frame, rect, &self.prompt, self.default);
+    }
+}
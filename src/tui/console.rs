@@ -0,0 +1,37 @@
+use ratatui::{
+    layout::{Constraint, Layout, Rect},
+    style::{Color, Style, Stylize},
+    widgets::{Block, Paragraph},
+    Frame,
+};
+
+use crate::logging;
+
+/// Splits off the bottom third of `rect` for the console, returning what is
+/// left for the view and where the console goes
+pub fn split(rect: Rect) -> (Rect, Rect) {
+    let height = (rect.height / 3).max(6).min(rect.height);
+    let [view, console] =
+        Layout::vertical([Constraint::Fill(1), Constraint::Length(height)])
+            .areas(rect);
+    (view, console)
+}
+
+/// The last lines of the log, newest at the bottom
+pub fn render(frame: &mut Frame, rect: Rect) {
+    let title = match logging::log_path() {
+        Some(path) => format!("Log - {}", path.display()),
+        None => "Log".to_string(),
+    };
+    let block = Block::bordered()
+        .title(title)
+        .title_style(Style::new().bold())
+        .fg(Color::DarkGray);
+    let inner = block.inner(rect);
+    frame.render_widget(block, rect);
+
+    let lines = logging::console_lines();
+    let shown = lines.len().saturating_sub(inner.height as usize);
+    let text = lines[shown..].join("\n");
+    frame.render_widget(Paragraph::new(text).fg(Color::Gray), inner);
+}
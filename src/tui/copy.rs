@@ -0,0 +1,64 @@
+use std::fmt::Display;
+
+use crossterm::event::KeyCode;
+
+use super::keymap::{KeyEvent, KeyHint};
+
+/// What of an item is put on the clipboard
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CopyField {
+    /// The item filled into the copy template of the config
+    Summary,
+    Subject,
+    Sender,
+    /// The content key, which the API and `kivinge view --copy-key` use
+    Key,
+    /// Where the attachment with this number is downloaded to
+    AttachmentPath(u32),
+}
+
+impl Display for CopyField {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            CopyField::Summary => write!(f, "summary"),
+            CopyField::Subject => write!(f, "subject"),
+            CopyField::Sender => write!(f, "sender"),
+            CopyField::Key => write!(f, "content key"),
+            CopyField::AttachmentPath(_) => write!(f, "attachment path"),
+        }
+    }
+}
+
+/// The key that asks which field to copy
+pub const PREFIX: KeyHint = KeyHint::new("c", "copy…");
+
+/// The field picked by the key pressed after the prefix. The letters avoid
+/// those the keymap takes for moving. `attachment` is the selected one, if
+/// the view has any.
+pub fn field(key: &KeyEvent, attachment: Option<u32>) -> Option<CopyField> {
+    match key {
+        KeyEvent::Key(KeyCode::Char('s')) => Some(CopyField::Subject),
+        KeyEvent::Key(KeyCode::Char('e')) => Some(CopyField::Sender),
+        KeyEvent::Key(KeyCode::Char('c')) => Some(CopyField::Key),
+        KeyEvent::Key(KeyCode::Char('y')) => Some(CopyField::Summary),
+        KeyEvent::Key(KeyCode::Char('a')) => {
+            attachment.map(CopyField::AttachmentPath)
+        }
+        _ => None,
+    }
+}
+
+/// The keys `field` takes, for the status bar while it waits for one
+pub fn hints(attachment: bool) -> Vec<KeyHint> {
+    let mut hints = vec![
+        KeyHint::new("s", "subject"),
+        KeyHint::new("e", "sender"),
+        KeyHint::new("c", "content key"),
+        KeyHint::new("y", "summary"),
+    ];
+    if attachment {
+        hints.push(KeyHint::new("a", "attachment path"));
+    }
+    hints.push(KeyHint::new("any other", "cancel"));
+    hints
+}
@@ -0,0 +1,419 @@
+use chrono::{Datelike, Local, Months, NaiveDate};
+use crossterm::event::KeyCode;
+use ratatui::{
+    layout::{Constraint, Layout, Rect},
+    style::{Color, Modifier, Style, Stylize},
+    text::Line,
+    widgets::{Bar, BarChart, BarGroup, Block, Cell, Paragraph, Row, Table},
+    Frame,
+};
+
+use super::{
+    keymap::{self, KeyEvent, KeyHint},
+    render_loading,
+    tabs::Tab,
+    Command, Data, Error, Event, Message, TuiView,
+};
+use crate::{
+    client::ContentClient,
+    filter::Filter,
+    i18n,
+    model::content::{InboxEntry, InboxFilter, InboxListing, Status},
+    tr,
+};
+
+/// Width of a bar in the chart of items per month, and the gap after it
+const BAR_WIDTH: u16 = 7;
+const BAR_GAP: u16 = 1;
+
+/// An overview of the inbox: how much is unread, the bills that are due and
+/// how many items came each month
+pub struct DashboardView {
+    inbox: InboxListing,
+    // False until the listing from `load` arrives
+    loaded: bool,
+    /// Payable items not yet paid, the first due first
+    bills: Vec<InboxEntry>,
+    /// The first day of each month from the oldest item on, and its items
+    months: Vec<(NaiveDate, u64)>,
+    focus: Focus,
+    selected_bill: usize,
+    selected_month: usize,
+    message: Option<Message>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Focus {
+    Bills,
+    Months,
+}
+
+pub enum DashboardViewResult {
+    Open(Box<InboxEntry>),
+    /// The inbox, showing the tab with what the search matches
+    ShowInbox(InboxFilter, Option<Filter>),
+    ShowTab(Tab),
+    Back,
+    Quit,
+}
+
+impl DashboardView {
+    /// An empty view that waits for what `load` fetches
+    pub fn new() -> DashboardView {
+        DashboardView {
+            inbox: InboxListing::default(),
+            loaded: false,
+            bills: Vec::new(),
+            months: Vec::new(),
+            focus: Focus::Bills,
+            selected_bill: 0,
+            selected_month: 0,
+            message: None,
+        }
+    }
+
+    /// The dashboard counts every item, so it fetches all of the inbox
+    pub fn load(client: &mut impl ContentClient) -> Data {
+        Data::Inbox(client.get_inbox_listing().map(|inbox| (inbox, true)))
+    }
+
+    pub fn is_loaded(&self) -> bool {
+        self.loaded
+    }
+
+    fn set_inbox(&mut self, inbox: InboxListing) {
+        let in_inbox =
+            |entry: &&InboxEntry| InboxFilter::All.matches(&entry.item);
+        let mut bills: Vec<InboxEntry> = inbox
+            .iter()
+            .filter(|entry| InboxFilter::Payments.matches(&entry.item))
+            .filter(|entry| !paid(entry))
+            .cloned()
+            .collect();
+        // Bills without a due date go last
+        bills.sort_by_key(|entry| {
+            let due = entry.item.due_date.as_ref().map(|date| date.0);
+            (due.is_none(), due)
+        });
+
+        let month = |entry: &InboxEntry| {
+            first_of_month(
+                entry.item.created_at.with_timezone(&Local).date_naive(),
+            )
+        };
+        let this_month = first_of_month(Local::now().date_naive());
+        let first = (inbox.iter().filter(in_inbox).map(month).min())
+            .unwrap_or(this_month);
+        let last = this_month.max(first);
+        let months = std::iter::successors(Some(first), |start| {
+            Some(*start + Months::new(1)).filter(|next| *next <= last)
+        });
+        self.months = months
+            .map(|start| {
+                let count = inbox
+                    .iter()
+                    .filter(in_inbox)
+                    .filter(|entry| month(entry) == start)
+                    .count();
+                (start, count as u64)
+            })
+            .collect();
+
+        self.inbox = inbox;
+        self.bills = bills;
+        self.selected_bill = 0;
+        self.selected_month = self.months.len().saturating_sub(1);
+    }
+
+    fn inbox_loaded(
+        &mut self,
+        result: Result<(InboxListing, bool), crate::client::Error>,
+    ) {
+        self.loaded = true;
+        match result {
+            Ok((inbox, _)) => self.set_inbox(inbox),
+            Err(error) => {
+                self.message = Some(Message::error(tr!(
+                    "Failed to load the inbox: {error}",
+                    error = error
+                )))
+            }
+        }
+    }
+
+    fn unread(&self) -> usize {
+        self.inbox
+            .iter()
+            .filter(|entry| InboxFilter::Unread.matches(&entry.item))
+            .count()
+    }
+
+    fn move_selection(&mut self, down: bool) {
+        let (selected, count) = match self.focus {
+            Focus::Bills => (&mut self.selected_bill, self.bills.len()),
+            Focus::Months => (&mut self.selected_month, self.months.len()),
+        };
+        *selected = match down {
+            true => (*selected + 1).min(count.saturating_sub(1)),
+            false => selected.saturating_sub(1),
+        };
+    }
+
+    /// The inbox with the items of the selected month, or the selected bill
+    fn select(&self) -> Result<Command<DashboardViewResult>, Error> {
+        match self.focus {
+            Focus::Bills => match self.bills.get(self.selected_bill) {
+                Some(entry) => Ok(Command::Return(DashboardViewResult::Open(
+                    Box::new(entry.clone()),
+                ))),
+                None => Ok(Command::Await),
+            },
+            Focus::Months => {
+                let Some((start, _)) = self.months.get(self.selected_month)
+                else {
+                    return Ok(Command::Await);
+                };
+                let end = *start + Months::new(1) - chrono::Days::new(1);
+                let search = Filter::parse(&format!("date={start}..{end}"))
+                    .map_err(|_| Error::AppError("Invalid month search"))?;
+                Ok(Command::Return(DashboardViewResult::ShowInbox(
+                    InboxFilter::All,
+                    Some(search),
+                )))
+            }
+        }
+    }
+}
+
+impl Default for DashboardView {
+    fn default() -> DashboardView {
+        DashboardView::new()
+    }
+}
+
+impl TuiView for DashboardView {
+    type ReturnType = DashboardViewResult;
+
+    fn update(
+        &mut self,
+        event: Event,
+    ) -> Result<Command<Self::ReturnType>, Error> {
+        match event {
+            Event::Data(Data::Inbox(result)) => {
+                self.inbox_loaded(result);
+                Ok(Command::Await)
+            }
+
+            Event::Key(KeyEvent::Quit) => {
+                Ok(Command::Return(DashboardViewResult::Quit))
+            }
+
+            Event::Key(KeyEvent::Back) => {
+                Ok(Command::Return(DashboardViewResult::Back))
+            }
+
+            Event::Key(KeyEvent::Up) => {
+                self.move_selection(false);
+                Ok(Command::Await)
+            }
+
+            Event::Key(KeyEvent::Down) => {
+                self.move_selection(true);
+                Ok(Command::Await)
+            }
+
+            Event::Key(KeyEvent::Key(KeyCode::Tab)) => {
+                self.focus = match self.focus {
+                    Focus::Bills => Focus::Months,
+                    Focus::Months => Focus::Bills,
+                };
+                Ok(Command::Await)
+            }
+
+            Event::Key(KeyEvent::Select) => self.select(),
+
+            Event::Key(KeyEvent::Key(KeyCode::Char('u'))) => {
+                Ok(Command::Return(DashboardViewResult::ShowInbox(
+                    InboxFilter::Unread,
+                    None,
+                )))
+            }
+
+            Event::Key(KeyEvent::Key(KeyCode::Char(c @ '1'..='9'))) => {
+                match Tab::from_key(c) {
+                    Some(tab @ Tab::Inbox(_)) => {
+                        Ok(Command::Return(DashboardViewResult::ShowTab(tab)))
+                    }
+                    _ => Ok(Command::Await),
+                }
+            }
+
+            _ => Ok(Command::Await),
+        }
+    }
+
+    fn key_hints(&self) -> Vec<KeyHint> {
+        let select = match self.focus {
+            Focus::Bills => KeyHint::new("enter", "open bill"),
+            Focus::Months => KeyHint::new("enter", "show month"),
+        };
+        vec![
+            keymap::MOVE,
+            select,
+            KeyHint::new("tab", "bills/months"),
+            KeyHint::new("u", "unread"),
+            KeyHint::new("1-5", "tabs"),
+            keymap::BACK,
+            keymap::QUIT,
+        ]
+    }
+
+    fn help_hints(&self) -> Vec<KeyHint> {
+        vec![
+            KeyHint::new("tab", "move between the bills and the chart"),
+            KeyHint::new("j / k", "select a bill, or a later or earlier month"),
+            KeyHint::new("enter", "open the bill, or show the month's items"),
+            KeyHint::new("u", "show the unread items"),
+            KeyHint::new("1 / 2 / 3", "show all, unread or payable items"),
+            KeyHint::new("4 / 5", "show archived or trashed items"),
+            KeyHint::new("h", "go back to the inbox"),
+        ]
+    }
+
+    fn take_message(&mut self) -> Option<Message> {
+        self.message.take()
+    }
+
+    fn badge(&self) -> Option<String> {
+        match self.unread() {
+            0 => None,
+            unread => Some(tr!("{unread} unread", unread = unread)),
+        }
+    }
+
+    fn render(&mut self, frame: &mut Frame, rect: Rect) {
+        let [title, summary, bills, chart] = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Length(1),
+            Constraint::Fill(1),
+            Constraint::Length(12),
+        ])
+        .areas(rect);
+        let title_widget = Paragraph::new(tr!("Dashboard"))
+            .style(Style::new().add_modifier(Modifier::REVERSED))
+            .fg(Color::Green);
+        frame.render_widget(title_widget, title);
+        if !self.loaded {
+            render_loading(frame, bills, "the inbox");
+            return;
+        }
+
+        let items = self
+            .inbox
+            .iter()
+            .filter(|entry| InboxFilter::All.matches(&entry.item))
+            .count();
+        let text = tr!(
+            "{items} item(s) in the inbox, {unread} unread, {bills} bill(s) to \
+             pay",
+            items = items,
+            unread = self.unread(),
+            bills = self.bills.len()
+        );
+        frame.render_widget(Paragraph::new(text).bold(), summary);
+
+        let border = |focus: Focus| match self.focus == focus {
+            true => Style::new().fg(Color::Yellow),
+            false => Style::new().fg(Color::Green),
+        };
+        let bills_widget = bills_widget(&self.bills, self.selected_bill).block(
+            Block::bordered().title(tr!("Due")).style(border(Focus::Bills)),
+        );
+        frame.render_widget(bills_widget, bills);
+
+        let block = Block::bordered()
+            .title(tr!("Items per month"))
+            .style(border(Focus::Months));
+        let width = block.inner(chart).width;
+        let chart_widget =
+            months_widget(&self.months, self.selected_month, width)
+                .block(block);
+        frame.render_widget(chart_widget, chart);
+    }
+}
+
+fn paid(entry: &InboxEntry) -> bool {
+    entry.item.labels.get("paid").copied().unwrap_or(false)
+}
+
+fn first_of_month(date: NaiveDate) -> NaiveDate {
+    date.with_day(1).expect("every month has a first day")
+}
+
+fn bills_widget(bills: &[InboxEntry], selected: usize) -> Table<'static> {
+    let today = Local::now().date_naive();
+    let rows = bills.iter().enumerate().map(|(index, entry)| {
+        let item = &entry.item;
+        let due = item.due_date.as_ref().map(|date| date.0);
+        let amount = match (&item.amount, &item.currency) {
+            (Some(amount), Some(currency)) => format!("{amount} {currency}"),
+            (Some(amount), None) => amount.to_string(),
+            _ => String::new(),
+        };
+        let mut style = match due.is_some_and(|due| due < today) {
+            true => Style::new().fg(Color::Red),
+            false => Style::new(),
+        };
+        if item.status == Status::Unread {
+            style = style.bold();
+        }
+        if index == selected {
+            style = style.add_modifier(Modifier::REVERSED);
+        }
+        Row::new([
+            Cell::new(due.map(|due| due.to_string()).unwrap_or_default()),
+            Cell::new(entry.id.to_string()),
+            Cell::new(item.sender_name.clone()),
+            Cell::new(item.subject.clone()),
+            Cell::new(amount),
+        ])
+        .style(style)
+    });
+    let widths = [
+        Constraint::Length(10),
+        Constraint::Length(4),
+        Constraint::Fill(1),
+        Constraint::Fill(2),
+        Constraint::Max(14),
+    ];
+    Table::new(rows, widths).column_spacing(1)
+}
+
+/// As many months as fit in `width`, ending with the selected one or later
+fn months_widget(
+    months: &[(NaiveDate, u64)],
+    selected: usize,
+    width: u16,
+) -> BarChart<'static> {
+    let fit = (width / (BAR_WIDTH + BAR_GAP)).max(1) as usize;
+    let end = (selected + 1).max(fit).min(months.len());
+    let start = end.saturating_sub(fit);
+    let bars: Vec<Bar> = (start..end)
+        .map(|index| {
+            let (start, count) = months[index];
+            let style = match index == selected {
+                true => Style::new().fg(Color::Yellow),
+                false => Style::new().fg(Color::Green),
+            };
+            Bar::default()
+                .value(count)
+                .label(Line::from(i18n::short_month(start)))
+                .style(style)
+                .value_style(style.add_modifier(Modifier::REVERSED))
+        })
+        .collect();
+    BarChart::default()
+        .data(BarGroup::default().bars(&bars))
+        .bar_width(BAR_WIDTH)
+        .bar_gap(BAR_GAP)
+}
@@ -0,0 +1,89 @@
+use crossterm::event::KeyCode;
+use ratatui::{
+    layout::Rect,
+    style::Stylize,
+    text::Line,
+    widgets::{Paragraph, Wrap},
+    Frame,
+};
+
+use super::{
+    keymap::{KeyEvent, KeyHint},
+    popup::Popup,
+    Command, Error, Event, TuiView,
+};
+use crate::tr;
+
+pub enum FailureChoice {
+    /// Hand the view what it failed on again
+    Retry,
+    /// Carry on with the view as it was before it failed
+    Back,
+    Quit,
+}
+
+/// An error a view ran into, with what caused it. The event loop keeps it
+/// open over the view instead of leaving the TUI.
+pub struct FailureView {
+    message: String,
+    causes: Vec<String>,
+    can_retry: bool,
+}
+
+impl FailureView {
+    pub fn new(error: &Error, can_retry: bool) -> FailureView {
+        let mut causes = Vec::new();
+        let mut source = std::error::Error::source(error);
+        while let Some(cause) = source {
+            causes.push(cause.to_string());
+            source = cause.source();
+        }
+        FailureView { message: error.to_string(), causes, can_retry }
+    }
+}
+
+impl TuiView for FailureView {
+    type ReturnType = FailureChoice;
+
+    fn update(
+        &mut self,
+        event: Event,
+    ) -> Result<Command<Self::ReturnType>, Error> {
+        match event {
+            Event::Key(KeyEvent::Key(KeyCode::Char('r'))) if self.can_retry => {
+                Ok(Command::Return(FailureChoice::Retry))
+            }
+
+            Event::Key(KeyEvent::Back) | Event::Key(KeyEvent::Select) => {
+                Ok(Command::Return(FailureChoice::Back))
+            }
+
+            Event::Key(KeyEvent::Quit) => {
+                Ok(Command::Return(FailureChoice::Quit))
+            }
+
+            _ => Ok(Command::Await),
+        }
+    }
+
+    fn key_hints(&self) -> Vec<KeyHint> {
+        let retry = self.can_retry.then_some(KeyHint::new("r", "retry"));
+        retry
+            .into_iter()
+            .chain([KeyHint::new("h", "go back"), KeyHint::new("q", "quit")])
+            .collect()
+    }
+
+    fn render(&mut self, frame: &mut Frame, rect: Rect) {
+        let inner = Popup::new("Error").size(70, 50).render(frame, rect);
+        let mut lines = vec![Line::default(), Line::from(self.message.clone())];
+        if !self.causes.is_empty() {
+            lines.push(Line::default());
+            lines.push(Line::from(tr!("Caused by:")).bold());
+            let causes = self.causes.iter().map(|cause| format!("  {cause}"));
+            lines.extend(causes.map(Line::from));
+        }
+        let widget = Paragraph::new(lines).wrap(Wrap { trim: false });
+        frame.render_widget(widget, inner);
+    }
+}
@@ -0,0 +1,224 @@
+//! Images drawn with the graphics protocols of kitty and iTerm2, over cells
+//! that the views leave blank for them. Terminals with neither get no
+//! images. Sixel is not supported, as it would take decoding the images.
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use crossterm::{cursor::MoveTo, queue, style::Print, terminal};
+use ratatui::{
+    backend::{Backend, CrosstermBackend},
+    buffer::{Buffer, Cell},
+    layout::Rect,
+};
+use std::{
+    env,
+    io::{self, Write},
+};
+
+use crate::config::Images;
+
+/// Longest piece of an image sent to kitty in one escape sequence
+const KITTY_CHUNK: usize = 4096;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Protocol {
+    Kitty,
+    Iterm,
+}
+
+impl Protocol {
+    /// The protocol to draw images with, `None` to draw none
+    pub fn from_config(images: Images) -> Option<Protocol> {
+        match images {
+            Images::Auto => detect(),
+            Images::Kitty => Some(Protocol::Kitty),
+            Images::Iterm => Some(Protocol::Iterm),
+            Images::Off => None,
+        }
+    }
+
+    /// Whether images with the file extension can be drawn. kitty only
+    /// decodes PNG by itself.
+    pub fn draws(self, extension: &str) -> bool {
+        match self {
+            Protocol::Kitty => extension == "png",
+            Protocol::Iterm => matches!(extension, "png" | "jpg" | "gif"),
+        }
+    }
+}
+
+fn detect() -> Option<Protocol> {
+    let var = |name| env::var(name).unwrap_or_default();
+    // The escape sequences would have to be wrapped to get through tmux
+    if env::var_os("TMUX").is_some() {
+        return None;
+    }
+    let term = var("TERM");
+    let program = var("TERM_PROGRAM");
+    if env::var_os("KITTY_WINDOW_ID").is_some()
+        || term == "xterm-kitty"
+        || term == "xterm-ghostty"
+    {
+        Some(Protocol::Kitty)
+    } else if program == "iTerm.app"
+        || program == "WezTerm"
+        || var("LC_TERMINAL") == "iTerm2"
+    {
+        Some(Protocol::Iterm)
+    } else {
+        None
+    }
+}
+
+/// An image for the event loop to draw over `rect`, scaled to fit
+pub struct Picture<'a> {
+    pub protocol: Protocol,
+    pub rect: Rect,
+    pub data: &'a [u8],
+}
+
+/// A picture as drawn, told apart from others by where its data is, as
+/// views keep the data of what they show in one place
+#[derive(PartialEq)]
+struct Placement {
+    protocol: Protocol,
+    rect: Rect,
+    data: (usize, usize),
+}
+
+impl Placement {
+    fn of(picture: &Picture) -> Placement {
+        let data = (picture.data.as_ptr() as usize, picture.data.len());
+        Placement { protocol: picture.protocol, rect: picture.rect, data }
+    }
+}
+
+/// The pictures on the screen. They are only drawn again when they change,
+/// as the terminal keeps them while the cells under them are left alone.
+#[derive(Default)]
+pub struct Pictures {
+    shown: Vec<Placement>,
+    area: Rect,
+    /// The cells under the pictures, to draw back when they are removed
+    covered: Vec<(u16, u16, Cell)>,
+}
+
+impl Pictures {
+    /// Draws the pictures over the frame just drawn, `buffer` of `area`,
+    /// unless they are already there
+    pub fn update(
+        &mut self,
+        area: Rect,
+        buffer: &Buffer,
+        pictures: &[Picture],
+    ) -> io::Result<()> {
+        let placements: Vec<_> = pictures.iter().map(Placement::of).collect();
+        if placements == self.shown && area == self.area {
+            return Ok(());
+        }
+        // A resize draws every cell anew, and some elsewhere
+        if area != self.area {
+            self.covered.clear();
+        }
+        self.clear()?;
+
+        let mut stdout = io::stdout();
+        for picture in pictures {
+            let Some(sequence) = escape(picture) else {
+                continue;
+            };
+            let Rect { x, y, .. } = picture.rect;
+            queue!(stdout, MoveTo(x, y), Print(sequence))?;
+            let cells = picture.rect.positions().map(|position| {
+                (position.x, position.y, buffer.get(position.x, position.y))
+            });
+            (self.covered)
+                .extend(cells.map(|(x, y, cell)| (x, y, cell.clone())));
+        }
+        stdout.flush()?;
+        self.shown = placements;
+        self.area = area;
+        Ok(())
+    }
+
+    /// Removes the pictures and draws back what they covered
+    pub fn clear(&mut self) -> io::Result<()> {
+        let mut backend = CrosstermBackend::new(io::stdout());
+        let kitty =
+            |placement: &Placement| placement.protocol == Protocol::Kitty;
+        if self.shown.iter().any(kitty) {
+            backend.write_all(b"\x1b_Ga=d,d=A,q=2\x1b\\")?;
+        }
+        let covered = self.covered.iter().map(|(x, y, cell)| (*x, *y, cell));
+        backend.draw(covered)?;
+        Backend::flush(&mut backend)?;
+        self.shown.clear();
+        self.covered.clear();
+        Ok(())
+    }
+}
+
+/// The escape sequence that draws the picture at the cursor, if its data
+/// is an image the terminal can draw
+fn escape(picture: &Picture) -> Option<String> {
+    let Rect { width, height, .. } = picture.rect;
+    if width == 0 || height == 0 {
+        return None;
+    }
+    let data = STANDARD.encode(picture.data);
+    match picture.protocol {
+        Protocol::Kitty => {
+            let (columns, rows) = fit(picture.rect, png_size(picture.data)?);
+            let chunks: Vec<_> = data.as_bytes().chunks(KITTY_CHUNK).collect();
+            let mut sequence = String::new();
+            for (index, chunk) in chunks.iter().enumerate() {
+                let more = (index + 1 < chunks.len()) as u8;
+                let chunk = String::from_utf8_lossy(chunk);
+                sequence += &match index {
+                    0 => format!(
+                        "\x1b_Ga=T,f=100,c={columns},r={rows},C=1,q=2,\
+                         m={more};{chunk}\x1b\\"
+                    ),
+                    _ => format!("\x1b_Gm={more};{chunk}\x1b\\"),
+                };
+            }
+            Some(sequence)
+        }
+        Protocol::Iterm => Some(format!(
+            "\x1b]1337;File=inline=1;size={};width={width};height={height};\
+             preserveAspectRatio=1:{data}\x07",
+            picture.data.len()
+        )),
+    }
+}
+
+/// The width and height of a PNG image, in pixels
+fn png_size(data: &[u8]) -> Option<(u32, u32)> {
+    if !data.starts_with(b"\x89PNG\r\n\x1a\n") || data.get(12..16)? != b"IHDR" {
+        return None;
+    }
+    let number = |at: usize| {
+        let bytes = data.get(at..at + 4)?.try_into().ok()?;
+        Some(u32::from_be_bytes(bytes))
+    };
+    Some((number(16)?, number(20)?))
+}
+
+/// The columns and rows of `rect` an image of the size takes up when scaled
+/// to fit without being stretched
+fn fit(rect: Rect, (width, height): (u32, u32)) -> (u16, u16) {
+    // Cells are usually twice as high as they are wide
+    let (cell_width, cell_height) = match terminal::window_size() {
+        Ok(size) if size.width > 0 && size.columns > 0 && size.rows > 0 => (
+            f64::from(size.width) / f64::from(size.columns),
+            f64::from(size.height) / f64::from(size.rows),
+        ),
+        _ => (1.0, 2.0),
+    };
+    let columns = f64::from(width.max(1)) / cell_width;
+    let rows = f64::from(height.max(1)) / cell_height;
+    let scale =
+        (f64::from(rect.width) / columns).min(f64::from(rect.height) / rows);
+    let columns = (columns * scale).round().clamp(1.0, rect.width.into());
+    let rows = (rows * scale).round().clamp(1.0, rect.height.into());
+    (columns as u16, rows as u16)
+}
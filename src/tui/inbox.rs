@@ -1,110 +1,1326 @@
-use chrono::{Local, TimeZone};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    ops::Range,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use crossterm::event::KeyCode;
 use ratatui::{
-    layout::{Constraint, Rect},
+    layout::{Constraint, Layout, Rect},
     style::{Color, Modifier, Style, Stylize},
-    widgets::{Block, Cell, Row, Table, TableState},
+    text::{Line, Span},
+    widgets::{
+        Block, Borders, Clear, List, ListItem, ListState, Padding, Paragraph,
+        Table, TableState,
+    },
     Frame,
 };
 
-use super::{keymap::KeyEvent, Command, Error, Event, TuiView};
+use super::{
+    bulk::BulkAction,
+    confirm::ConfirmView,
+    copy::{self, CopyField},
+    keymap::{self, KeyEvent, KeyHint},
+    popup::Popup,
+    render_loading,
+    tabs::{self, Tab},
+    Command, Data, Error, Event, Message, TuiView,
+};
+use tracing::warn;
+
 use crate::{
-    client::Client,
-    model::content::{InboxEntry, InboxListing, Status},
+    cli::inbox::DETAILS_COLUMNS,
+    client::{self, ContentClient, Page},
+    filter::Filter,
+    index::Contents,
+    model::{
+        content::{
+            AttachmentSummary, ContentKey, InboxEntry, InboxFilter, InboxItem,
+            InboxListing, ItemDetails, SenderKey, SenderSummary, Status,
+        },
+        user::Actor,
+    },
+    table::{self, Timestamps},
+    tr,
+    util::Destination,
 };
 
+const COLUMNS: [&str; 5] = ["new", "id", "sender", "subject", "created"];
+/// For inboxes merged from several profiles
+const PROFILE_COLUMNS: [&str; 6] =
+    ["new", "profile", "id", "sender", "subject", "created"];
+
 pub struct InboxView {
     inbox: InboxListing,
+    // False until the listing from `load` arrives
+    loaded: bool,
+    filter: InboxFilter,
+    sender: Option<SenderKey>,
+    senders: Vec<SenderSummary>,
+    // Some while the sender picker is open. Index 0 means all senders.
+    sender_picker: Option<ListState>,
+    visible: Vec<InboxEntry>,
     table_state: TableState,
+    // Keys rather than ids, which shift as pages load when not from the index
+    marked: BTreeSet<ContentKey>,
+    destination: Destination,
+    timestamps: Timestamps,
+    // Whether the attachment count and size columns are shown
+    show_attachments: bool,
+    // What is known of the attachments of items, None for those whose
+    // details could not be fetched
+    attachments: BTreeMap<ContentKey, Option<AttachmentSummary>>,
+    // The selected row while it is expanded with Tab
+    expanded: Option<Expanded>,
+    // None when the whole inbox is loaded at once
+    page_size: Option<usize>,
+    complete: bool,
+    // Set while the next page is being fetched
+    loading: bool,
+    // Rows that fit on screen, as of the last render
+    page_height: usize,
+    sync: Option<SyncTimer>,
+    // Set from when a sync is due until its listing is in
+    syncing: bool,
+    // Listing fetched in the background, shown once the user asks for it
+    pending: Option<InboxListing>,
+    // An open question and what to return if the answer is yes
+    confirm: Option<(ConfirmView, InboxViewResult)>,
+    // Set after the copy key, until the field to copy is picked
+    copying: bool,
+    // Set after 'g', until the view to go to is picked
+    going: bool,
+    // The expression being typed after '/', and the one searched for
+    search_input: Option<String>,
+    search: Option<Filter>,
+    // The text of attachments, for searches with a content term
+    contents: Arc<Contents>,
+    // Whose inboxes there are to switch between, the user's own first, and
+    // whose is shown
+    actors: Vec<Actor>,
+    actor: Actor,
+    // Some while the actor picker is open
+    actor_picker: Option<ListState>,
+    message: Option<Message>,
+}
+
+/// A row opened to show more of its item under it
+struct Expanded {
+    key: ContentKey,
+    // Names of the attachments, None until the details are in
+    attachments: Option<Vec<String>>,
+}
+
+// Start loading the next page this many rows before the end of the list
+const LOAD_AHEAD: usize = 5;
+
+// Never poll Kivra more often than this, whatever the config says
+pub const MIN_SYNC_INTERVAL: Duration = Duration::from_secs(30);
+const MAX_SYNC_BACKOFF: u32 = 4;
+
+struct SyncTimer {
+    interval: Duration,
+    failures: u32,
+    next: Instant,
+}
+
+impl SyncTimer {
+    fn new(interval: Duration) -> SyncTimer {
+        let interval = interval.max(MIN_SYNC_INTERVAL);
+        SyncTimer { interval, failures: 0, next: Instant::now() + interval }
+    }
+
+    /// Failed syncs back off exponentially, e.g. when rate limited
+    fn schedule(&mut self, succeeded: bool) {
+        self.failures = match succeeded {
+            true => 0,
+            false => (self.failures + 1).min(MAX_SYNC_BACKOFF),
+        };
+        self.next = Instant::now() + self.interval * 2u32.pow(self.failures);
+    }
+}
+
+pub enum InboxViewResult {
+    Open(Box<InboxEntry>),
+    Copy(Box<InboxEntry>, CopyField),
+    Bulk(BulkAction, Vec<InboxEntry>),
+    ShowReceipts,
+    ShowDashboard,
+    ShowSettings,
+    SwitchActor(Actor),
+    Trash(Box<InboxEntry>),
+    /// Kivra could not be reached to load the inbox
+    Unreachable(String),
+    Logout,
+    /// A sync is due, which is fetched while the view is shown again
+    Sync,
+    /// The listing of the sync is in
+    Synced(Result<InboxListing, client::Error>),
+    LoadMore,
+    /// The attachments of these items are to be shown
+    LoadAttachments(Vec<ContentKey>),
+    /// The row of this item was expanded, which shows its attachments
+    LoadDetails(ContentKey),
+    Quit,
 }
 
 impl InboxView {
-    pub fn make(client: &mut impl Client) -> Result<InboxView, Error> {
-        let inbox = client.get_inbox_listing()?;
-        let table_state = TableState::new().with_selected(Some(0));
-        Ok(InboxView { inbox, table_state })
+    /// An empty view that waits for what `load` fetches
+    pub fn new(filter: InboxFilter, page_size: Option<usize>) -> InboxView {
+        let mut view = InboxView {
+            inbox: InboxListing::default(),
+            loaded: false,
+            filter,
+            sender: None,
+            senders: Vec::new(),
+            sender_picker: None,
+            visible: Vec::new(),
+            table_state: TableState::new(),
+            marked: BTreeSet::new(),
+            destination: Destination::default(),
+            timestamps: Timestamps::default(),
+            show_attachments: false,
+            attachments: BTreeMap::new(),
+            expanded: None,
+            page_size,
+            complete: false,
+            loading: false,
+            page_height: 0,
+            sync: None,
+            syncing: false,
+            pending: None,
+            confirm: None,
+            copying: false,
+            going: false,
+            search_input: None,
+            search: None,
+            contents: Arc::default(),
+            actors: Vec::new(),
+            actor: Actor::User,
+            actor_picker: None,
+            message: None,
+        };
+        view.set_filter(filter);
+        view
+    }
+
+    /// Fetches the listing for the view. With a page size only the newest
+    /// page is fetched up front, the rest is loaded as the user scrolls
+    /// towards the end.
+    pub fn load(
+        client: &mut impl ContentClient,
+        page_size: Option<usize>,
+    ) -> Data {
+        Data::Inbox(match page_size {
+            None => client.get_inbox_listing().map(|inbox| (inbox, true)),
+            Some(page_size) => {
+                let page = client.inbox_pager(page_size).next().transpose();
+                page.map(|items| {
+                    let items = items.unwrap_or_default();
+                    let complete = items.len() < page_size;
+                    (client.number_items(items), complete)
+                })
+            }
+        })
+    }
+
+    pub fn is_loaded(&self) -> bool {
+        self.loaded
+    }
+
+    /// Whether a sync is due, for `show_loading` to fetch with `Data::Sync`
+    pub fn is_syncing(&self) -> bool {
+        self.syncing
+    }
+
+    fn inbox_loaded(
+        &mut self,
+        result: Result<(InboxListing, bool), client::Error>,
+    ) {
+        self.loaded = true;
+        match result {
+            Ok((inbox, complete)) => {
+                self.complete = complete;
+                self.set_inbox(inbox);
+            }
+            Err(error) => {
+                warn!("failed to load the inbox: {error}");
+                self.message = Some(Message::error(tr!(
+                    "Failed to load the inbox: {error}",
+                    error = error
+                )));
+            }
+        }
+    }
+
+    /// Refresh the listing in the background while the view is open
+    pub fn with_sync_interval(mut self, interval: Option<Duration>) -> Self {
+        self.sync = interval.map(SyncTimer::new);
+        self
+    }
+
+    pub fn with_timestamps(mut self, timestamps: Timestamps) -> Self {
+        self.timestamps = timestamps;
+        self
+    }
+
+    /// Where marked items are downloaded to
+    pub fn with_destination(mut self, destination: Destination) -> Self {
+        self.destination = destination;
+        self
+    }
+
+    pub fn with_contents(mut self, contents: Arc<Contents>) -> Self {
+        self.contents = contents;
+        self
+    }
+
+    pub fn with_actors(mut self, actors: Vec<Actor>, actor: Actor) -> Self {
+        self.actors = actors;
+        self.actor = actor;
+        self
+    }
+
+    /// New contents for the search, which items loaded from now on are
+    /// searched with
+    pub fn set_contents(&mut self, contents: Arc<Contents>) {
+        self.search = (self.search.take())
+            .map(|search| search.with_contents(contents.clone()));
+        self.contents = contents;
+    }
+
+    /// Tells how something done outside of the view went
+    pub fn set_message(&mut self, message: Message) {
+        self.message = Some(message);
+    }
+
+    pub fn sync_finished(
+        &mut self,
+        result: Result<InboxListing, client::Error>,
+    ) {
+        self.syncing = false;
+        let succeeded = match result {
+            Ok(listing) => {
+                self.pending = Some(listing);
+                true
+            }
+            Err(error) => {
+                warn!("background sync failed: {error}");
+                let text = tr!("Sync failed: {error}", error = error);
+                self.message = Some(Message::error(text));
+                false
+            }
+        };
+        if let Some(sync) = &mut self.sync {
+            sync.schedule(succeeded);
+        }
+    }
+
+    /// The page following the items loaded so far
+    pub fn next_page(&self) -> Option<Page> {
+        match (self.complete, self.page_size) {
+            (false, Some(limit)) => {
+                Some(Page { offset: self.inbox.len(), limit })
+            }
+            _ => None,
+        }
+    }
+
+    /// Adds a page to the loaded items, which are numbered again by
+    /// `number` together with it
+    pub fn add_page(
+        &mut self,
+        result: Result<Vec<InboxItem>, client::Error>,
+        number: impl FnOnce(Vec<InboxItem>) -> InboxListing,
+    ) {
+        self.loading = false;
+        let items = match result {
+            Ok(items) => items,
+            Err(error) => {
+                // Stop paging, a sync or reload fetches the whole inbox
+                warn!("failed to load more of the inbox: {error}");
+                self.message = Some(Message::error(tr!(
+                    "Failed to load more of the inbox: {error}",
+                    error = error
+                )));
+                self.complete = true;
+                return;
+            }
+        };
+        self.complete = self.page_size.is_none_or(|size| items.len() < size);
+        // The inbox may have changed between pages, which shifts the offsets
+        let mut loaded: Vec<InboxItem> =
+            self.inbox.iter().map(|entry| entry.item.clone()).collect();
+        for item in items {
+            if !loaded.iter().any(|old| old.key == item.key) {
+                loaded.push(item);
+            }
+        }
+        self.set_inbox(number(loaded));
+    }
+
+    fn new_item_count(&self) -> usize {
+        let Some(pending) = &self.pending else {
+            return 0;
+        };
+        // Older items than the loaded ones are not new, just not loaded yet
+        let oldest = self.inbox.first().map(|entry| entry.item.created_at);
+        pending
+            .iter()
+            .filter(|new| {
+                self.complete || oldest.is_none_or(|t| new.item.created_at >= t)
+            })
+            .filter(|new| {
+                !self.inbox.iter().any(|old| old.item.key == new.item.key)
+            })
+            .count()
+    }
+
+    fn load_pending(&mut self) {
+        let Some(pending) = self.pending.take() else {
+            return;
+        };
+        self.complete = true;
+        self.set_inbox(pending);
+    }
+
+    /// Replaces the listing, keeping the selection and marks on the same items
+    fn set_inbox(&mut self, inbox: InboxListing) {
+        let selected =
+            self.selected_entry().map(|entry| entry.item.key.clone());
+        self.inbox = inbox;
+        self.senders = self.inbox.senders();
+        let inbox = &self.inbox;
+        self.marked.retain(|key| inbox.iter().any(|e| e.item.key == *key));
+        self.set_filter(self.filter);
+        let position = self.visible.iter().position(|entry| {
+            selected.as_ref().is_some_and(|key| entry.item.key == *key)
+        });
+        if position.is_some() {
+            self.table_state.select(position);
+        }
+    }
+
+    fn await_input(&self) -> Command<InboxViewResult> {
+        match &self.sync {
+            // There is nothing to refresh before the first listing is in,
+            // and no second sync to start while one is on its way
+            Some(_) if !self.loaded || self.syncing => Command::Await,
+            None => Command::Await,
+            Some(sync) => Command::AwaitUntil(sync.next),
+        }
+    }
+
+    pub fn filter(&self) -> InboxFilter {
+        self.filter
+    }
+
+    pub fn sender(&self) -> Option<SenderKey> {
+        self.sender.clone()
+    }
+
+    pub fn set_sender(&mut self, sender: Option<SenderKey>) {
+        self.sender = sender;
+        self.set_filter(self.filter);
+    }
+
+    pub fn search(&self) -> Option<Filter> {
+        self.search.clone()
+    }
+
+    pub fn set_search(&mut self, search: Option<Filter>) {
+        self.search = search;
+        self.set_filter(self.filter);
+    }
+
+    fn set_filter(&mut self, filter: InboxFilter) {
+        self.filter = filter;
+        let sender = self.sender.as_ref();
+        let search = self.search.as_ref();
+        let attachments = &self.attachments;
+        self.visible = self
+            .inbox
+            .iter()
+            .rev()
+            .filter(|entry| filter.matches(&entry.item))
+            .filter(|entry| sender.is_none_or(|s| *s == entry.item.sender))
+            .filter(|entry| search.is_none_or(|f| f.matches(&entry.item)))
+            .map(|entry| InboxEntry {
+                attachments: attachments
+                    .get(&entry.item.key)
+                    .copied()
+                    .flatten(),
+                ..entry.clone()
+            })
+            .collect();
+        let selected = if self.visible.is_empty() { None } else { Some(0) };
+        self.table_state.select(selected);
+        let visible = &self.visible;
+        self.expanded = self.expanded.take().filter(|expanded| {
+            visible.iter().any(|entry| entry.item.key == expanded.key)
+        });
+    }
+
+    /// Selects a row, or the nearest one there is, and asks for more of the
+    /// inbox when the selection gets close to the end of what is loaded
+    fn select(&mut self, index: usize) -> Command<InboxViewResult> {
+        if self.visible.is_empty() {
+            return self.await_input();
+        }
+        let index = index.min(self.visible.len() - 1);
+        self.table_state.select(Some(index));
+        // Moving away collapses the expanded row
+        let key = &self.visible[index].item.key;
+        self.expanded = self.expanded.take().filter(|e| e.key == *key);
+        let near_end = index + LOAD_AHEAD >= self.visible.len();
+        if near_end && self.next_page().is_some() {
+            self.loading = true;
+            return Command::Return(InboxViewResult::LoadMore);
+        }
+        match self.missing_attachments() {
+            keys if keys.is_empty() => self.await_input(),
+            keys => Command::Return(InboxViewResult::LoadAttachments(keys)),
+        }
+    }
+
+    /// Items on or near the screen whose attachments are to be shown but
+    /// have not been asked for
+    fn missing_attachments(&self) -> Vec<ContentKey> {
+        if !self.show_attachments {
+            return Vec::new();
+        }
+        // Any window with the selected row in it is within this range
+        let selected = self.table_state.selected().unwrap_or(0);
+        let height = self.page_height.max(1);
+        let start = selected.saturating_sub(height);
+        let end = (selected + height).min(self.visible.len());
+        (self.visible[start..end].iter())
+            .map(|entry| &entry.item.key)
+            .filter(|key| !self.attachments.contains_key(*key))
+            .cloned()
+            .collect()
+    }
+
+    /// What `LoadDetails` fetched for the expanded row
+    pub fn set_details(
+        &mut self,
+        key: &ContentKey,
+        result: Result<ItemDetails, client::Error>,
+    ) {
+        let expanded = self.expanded.as_mut();
+        let Some(expanded) = expanded.filter(|e| e.key == *key) else {
+            return;
+        };
+        let details = match result {
+            Ok(details) => details,
+            Err(error) => {
+                expanded.attachments = Some(Vec::new());
+                self.message = Some(Message::error(tr!(
+                    "Failed to load the item: {error}",
+                    error = error
+                )));
+                return;
+            }
+        };
+        let names = (0..details.parts.len())
+            .filter_map(|index| details.attachment_name(index).ok());
+        expanded.attachments = Some(names.collect());
+    }
+
+    /// What was fetched for `LoadAttachments`
+    pub fn set_attachments(
+        &mut self,
+        attachments: Vec<(ContentKey, Option<AttachmentSummary>)>,
+    ) {
+        self.attachments.extend(attachments);
+        for entry in &mut self.visible {
+            let summary = self.attachments.get(&entry.item.key);
+            entry.attachments = summary.copied().flatten();
+        }
+    }
+
+    /// The rows that fit in `height` lines, scrolled so that the selected row
+    /// is among them
+    fn window(&mut self, height: usize) -> Range<usize> {
+        let height = height.max(1);
+        let selected = self.table_state.selected().unwrap_or(0);
+        let last_offset = self.visible.len().saturating_sub(height);
+        let offset = self.table_state.offset_mut();
+        if selected < *offset {
+            *offset = selected;
+        } else if selected >= *offset + height {
+            *offset = selected + 1 - height;
+        }
+        *offset = (*offset).min(last_offset);
+        *offset..(*offset + height).min(self.visible.len())
+    }
+
+    /// The first unread row after the selected one, starting over from the
+    /// top if there is none below it
+    fn next_unread(&self) -> Option<usize> {
+        let start = self.table_state.selected().map_or(0, |index| index + 1);
+        let count = self.visible.len();
+        (0..count)
+            .map(|offset| (start + offset) % count)
+            .find(|&index| self.visible[index].item.status == Status::Unread)
+    }
+
+    fn selected_entry(&self) -> Option<&InboxEntry> {
+        self.visible.get(self.table_state.selected()?)
+    }
+
+    /// Marked entries, or the selected entry if nothing is marked.
+    fn bulk_entries(&self) -> Vec<InboxEntry> {
+        match self.marked.is_empty() {
+            true => self.selected_entry().cloned().into_iter().collect(),
+            false => self
+                .inbox
+                .iter()
+                .filter(|entry| self.marked.contains(&entry.item.key))
+                .cloned()
+                .collect(),
+        }
+    }
+
+    fn copy(&self, field: CopyField) -> Command<InboxViewResult> {
+        match self.selected_entry() {
+            None => self.await_input(),
+            Some(entry) => {
+                let entry = Box::new(entry.clone());
+                Command::Return(InboxViewResult::Copy(entry, field))
+            }
+        }
+    }
+
+    fn update_confirm(
+        &mut self,
+        key: KeyEvent,
+    ) -> Result<Command<InboxViewResult>, Error> {
+        let Some((confirm, _)) = &mut self.confirm else {
+            return Ok(self.await_input());
+        };
+        let answer = match confirm.update(Event::Key(key))? {
+            Command::Return(answer) => answer,
+            _ => return Ok(self.await_input()),
+        };
+        match (answer, self.confirm.take()) {
+            (true, Some((_, result))) => Ok(Command::Return(result)),
+            _ => Ok(self.await_input()),
+        }
+    }
+
+    /// Edits the search expression, and searches for it on enter. An empty
+    /// one shows everything again.
+    fn update_search(&mut self, key: KeyEvent) -> Command<InboxViewResult> {
+        let Some(input) = &mut self.search_input else {
+            return self.await_input();
+        };
+        match key {
+            KeyEvent::Key(KeyCode::Char(c)) => input.push(c),
+            KeyEvent::Key(KeyCode::Backspace) => _ = input.pop(),
+            KeyEvent::Key(KeyCode::Esc) => self.search_input = None,
+            KeyEvent::Key(KeyCode::Enter) if input.trim().is_empty() => {
+                self.search_input = None;
+                self.search = None;
+                self.set_filter(self.filter);
+            }
+            KeyEvent::Key(KeyCode::Enter) => match Filter::parse(input) {
+                Ok(search) => {
+                    self.search_input = None;
+                    self.search =
+                        Some(search.with_contents(self.contents.clone()));
+                    self.set_filter(self.filter);
+                }
+                Err(error) => {
+                    self.message = Some(Message::error(format!(
+                        "{} at column {}",
+                        error.message,
+                        error.column + 1
+                    )));
+                }
+            },
+            _ => {}
+        }
+        self.await_input()
+    }
+
+    fn update_sender_picker(
+        &mut self,
+        key: KeyEvent,
+    ) -> Result<Command<InboxViewResult>, Error> {
+        let Some(picker) = &mut self.sender_picker else {
+            return Ok(self.await_input());
+        };
+        let selected = picker.selected().unwrap_or(0);
+        match key {
+            KeyEvent::Up => picker.select(Some(selected.saturating_sub(1))),
+            KeyEvent::Down => {
+                picker.select(Some((selected + 1).min(self.senders.len())))
+            }
+            KeyEvent::Select => {
+                let sender = match selected {
+                    0 => None,
+                    n => self.senders.get(n - 1).map(|s| s.key.clone()),
+                };
+                self.sender_picker = None;
+                self.set_sender(sender);
+            }
+            KeyEvent::Quit | KeyEvent::Back => self.sender_picker = None,
+            _ => {}
+        }
+        Ok(self.await_input())
+    }
+
+    fn update_actor_picker(
+        &mut self,
+        key: KeyEvent,
+    ) -> Result<Command<InboxViewResult>, Error> {
+        let Some(picker) = &mut self.actor_picker else {
+            return Ok(self.await_input());
+        };
+        let selected = picker.selected().unwrap_or(0);
+        match key {
+            KeyEvent::Up => picker.select(Some(selected.saturating_sub(1))),
+            KeyEvent::Down => {
+                picker.select(Some((selected + 1).min(self.actors.len() - 1)))
+            }
+            KeyEvent::Select => {
+                self.actor_picker = None;
+                match self.actors.get(selected) {
+                    Some(actor) if *actor != self.actor => {
+                        let actor = actor.clone();
+                        return Ok(Command::Return(
+                            InboxViewResult::SwitchActor(actor),
+                        ));
+                    }
+                    _ => {}
+                }
+            }
+            KeyEvent::Quit | KeyEvent::Back => self.actor_picker = None,
+            _ => {}
+        }
+        Ok(self.await_input())
     }
 }
 
 impl TuiView for InboxView {
-    type ReturnType = Option<InboxEntry>;
+    type ReturnType = InboxViewResult;
 
     fn update(
         &mut self,
         event: Event,
     ) -> Result<Command<Self::ReturnType>, Error> {
         match event {
-            Event::Key(KeyEvent::Quit) => Ok(Command::Return(None)),
+            Event::Key(key) if self.search_input.is_some() => {
+                Ok(self.update_search(key))
+            }
+
+            Event::Key(key) if self.sender_picker.is_some() => {
+                self.update_sender_picker(key)
+            }
+
+            Event::Key(key) if self.actor_picker.is_some() => {
+                self.update_actor_picker(key)
+            }
+
+            Event::Key(key) if self.confirm.is_some() => {
+                self.update_confirm(key)
+            }
+
+            Event::Key(key) if self.copying => {
+                self.copying = false;
+                match copy::field(&key, None) {
+                    Some(field) => Ok(self.copy(field)),
+                    None => Ok(self.await_input()),
+                }
+            }
+
+            Event::Key(key) if self.going => {
+                self.going = false;
+                match key {
+                    KeyEvent::Key(KeyCode::Char('d')) => {
+                        Ok(Command::Return(InboxViewResult::ShowDashboard))
+                    }
+                    KeyEvent::Key(KeyCode::Char('s')) => {
+                        Ok(Command::Return(InboxViewResult::ShowSettings))
+                    }
+                    _ => Ok(self.await_input()),
+                }
+            }
+
+            Event::Key(KeyEvent::Key(KeyCode::Char('g'))) => {
+                self.going = true;
+                Ok(self.await_input())
+            }
+
+            Event::Key(KeyEvent::Key(KeyCode::Char('c'))) => {
+                self.copying = self.selected_entry().is_some();
+                Ok(self.await_input())
+            }
+
+            Event::Key(KeyEvent::Key(KeyCode::Char('x'))) => {
+                if let Some(entry) = self.selected_entry().cloned() {
+                    let prompt = tr!(
+                        "Move '{subject}' to trash?",
+                        subject = entry.item.subject
+                    );
+                    let result = InboxViewResult::Trash(Box::new(entry));
+                    self.confirm = Some((ConfirmView::new(prompt), result));
+                }
+                Ok(self.await_input())
+            }
+
+            Event::Key(KeyEvent::Key(KeyCode::Char('/'))) => {
+                let current = self.search.as_ref().map(Filter::to_string);
+                self.search_input = Some(current.unwrap_or_default());
+                Ok(self.await_input())
+            }
+
+            Event::Key(KeyEvent::Key(KeyCode::Char('A'))) => {
+                match self.actors.len() > 1 {
+                    true => {
+                        let current =
+                            self.actors.iter().position(|a| *a == self.actor);
+                        self.actor_picker =
+                            Some(ListState::default().with_selected(current));
+                    }
+                    false => {
+                        let text = tr!("No company inboxes to switch to");
+                        self.message = Some(Message::info(text));
+                    }
+                }
+                Ok(self.await_input())
+            }
+
+            Event::Key(KeyEvent::Key(KeyCode::Char('L'))) => {
+                let prompt = tr!("Log out and forget the saved session?");
+                let result = InboxViewResult::Logout;
+                self.confirm = Some((ConfirmView::new(prompt), result));
+                Ok(self.await_input())
+            }
+
+            Event::Key(KeyEvent::Quit) => {
+                Ok(Command::Return(InboxViewResult::Quit))
+            }
+
+            Event::Tick => {
+                self.syncing = true;
+                Ok(Command::Return(InboxViewResult::Sync))
+            }
+
+            Event::Data(Data::Sync(result)) => {
+                Ok(Command::Return(InboxViewResult::Synced(result)))
+            }
+
+            Event::Data(Data::Inbox(Err(error))) if error.is_unreachable() => {
+                self.loaded = true;
+                let error = error.to_string();
+                Ok(Command::Return(InboxViewResult::Unreachable(error)))
+            }
+
+            Event::Data(Data::Inbox(result)) => {
+                self.inbox_loaded(result);
+                Ok(self.await_input())
+            }
+
+            Event::Key(KeyEvent::Key(KeyCode::Char('r'))) => {
+                self.load_pending();
+                Ok(self.await_input())
+            }
+
+            Event::Key(KeyEvent::Key(KeyCode::Char('s'))) => {
+                let current = self.sender.as_ref().and_then(|sender| {
+                    self.senders.iter().position(|s| s.key == *sender)
+                });
+                let selected = current.map_or(0, |index| index + 1);
+                self.sender_picker =
+                    Some(ListState::default().with_selected(Some(selected)));
+                Ok(self.await_input())
+            }
 
             Event::Key(KeyEvent::Up) => {
-                let select = match self.table_state.selected().unwrap_or(0) {
-                    0 => 0,
-                    n => n - 1,
-                };
-                self.table_state.select(Some(select));
-                Ok(Command::AwaitKey)
+                let selected = self.table_state.selected().unwrap_or(0);
+                Ok(self.select(selected.saturating_sub(1)))
             }
 
             Event::Key(KeyEvent::Down) => {
-                let select = match self.table_state.selected().unwrap_or(0) {
-                    n if n >= self.inbox.len() - 1 => n,
-                    n => n + 1,
+                let selected = self.table_state.selected().unwrap_or(0);
+                Ok(self.select(selected + 1))
+            }
+
+            Event::Key(KeyEvent::Key(KeyCode::PageUp)) => {
+                let selected = self.table_state.selected().unwrap_or(0);
+                let page = self.page_height.max(1);
+                Ok(self.select(selected.saturating_sub(page)))
+            }
+
+            Event::Key(KeyEvent::Key(KeyCode::PageDown)) => {
+                let selected = self.table_state.selected().unwrap_or(0);
+                Ok(self.select(selected + self.page_height.max(1)))
+            }
+
+            Event::Key(KeyEvent::Key(KeyCode::Home)) => Ok(self.select(0)),
+
+            Event::Key(KeyEvent::Key(KeyCode::Tab)) => {
+                let Some(entry) = self.selected_entry() else {
+                    return Ok(self.await_input());
                 };
-                self.table_state.select(Some(select));
-                Ok(Command::AwaitKey)
+                let key = entry.item.key.clone();
+                if self.expanded.as_ref().is_some_and(|e| e.key == key) {
+                    self.expanded = None;
+                    return Ok(self.await_input());
+                }
+                self.expanded =
+                    Some(Expanded { key: key.clone(), attachments: None });
+                Ok(Command::Return(InboxViewResult::LoadDetails(key)))
+            }
+
+            Event::Key(KeyEvent::Key(KeyCode::Char('m'))) => {
+                self.show_attachments = !self.show_attachments;
+                let selected = self.table_state.selected().unwrap_or(0);
+                Ok(self.select(selected))
+            }
+
+            Event::Key(KeyEvent::Key(KeyCode::Char('u'))) => {
+                match self.next_unread() {
+                    Some(index) => Ok(self.select(index)),
+                    None => {
+                        let text = tr!("No unread items");
+                        self.message = Some(Message::info(text));
+                        Ok(self.await_input())
+                    }
+                }
+            }
+
+            Event::Key(KeyEvent::Key(KeyCode::End)) => {
+                Ok(self.select(usize::MAX))
             }
 
             Event::Key(KeyEvent::Select) => match self.table_state.selected() {
-                None => Ok(Command::AwaitKey),
-                Some(selected) => {
-                    let index = self.inbox.len() - 1 - selected;
+                None => Ok(self.await_input()),
+                Some(_) => {
                     let entry = self
-                        .inbox
-                        .get(index)
+                        .selected_entry()
                         .ok_or(Error::AppError("Selected item out of bounds"))?
                         .clone();
-                    Ok(Command::Return(Some(entry)))
+                    Ok(Command::Return(InboxViewResult::Open(Box::new(entry))))
                 }
             },
 
-            _ => Ok(Command::AwaitKey),
+            Event::Key(KeyEvent::Key(KeyCode::Char(' '))) => {
+                if let Some(entry) = self.selected_entry() {
+                    let key = entry.item.key.clone();
+                    if !self.marked.remove(&key) {
+                        self.marked.insert(key);
+                    }
+                }
+                Ok(self.await_input())
+            }
+
+            Event::Key(KeyEvent::Key(KeyCode::Char('Y'))) => {
+                Ok(self.copy(CopyField::Summary))
+            }
+
+            Event::Key(KeyEvent::Key(KeyCode::Char('d'))) => {
+                let action = BulkAction::Download(self.destination.clone());
+                let entries = self.bulk_entries();
+                Ok(Command::Return(InboxViewResult::Bulk(action, entries)))
+            }
+
+            Event::Key(KeyEvent::Key(KeyCode::Char('R'))) => {
+                let all = self.marked.is_empty();
+                let entries: Vec<InboxEntry> = match all {
+                    false => self.bulk_entries(),
+                    true => self
+                        .inbox
+                        .iter()
+                        .filter(|entry| entry.item.status == Status::Unread)
+                        .cloned()
+                        .collect(),
+                };
+                let prompt = tr!(
+                    "Mark all {count} unread items as read?",
+                    count = entries.len()
+                );
+                let action = BulkAction::MarkRead;
+                let result = InboxViewResult::Bulk(action, entries);
+                match all {
+                    true => {
+                        let confirm =
+                            ConfirmView::new(prompt).with_default(true);
+                        self.confirm = Some((confirm, result));
+                        Ok(self.await_input())
+                    }
+                    false => Ok(Command::Return(result)),
+                }
+            }
+
+            Event::Key(KeyEvent::Key(KeyCode::Char(c @ '1'..='9'))) => {
+                match Tab::from_key(c) {
+                    Some(Tab::Inbox(filter)) => self.set_filter(filter),
+                    Some(Tab::Receipts) => {
+                        return Ok(Command::Return(
+                            InboxViewResult::ShowReceipts,
+                        ));
+                    }
+                    None => {}
+                }
+                Ok(self.await_input())
+            }
+
+            _ => Ok(self.await_input()),
+        }
+    }
+
+    fn key_hints(&self) -> Vec<KeyHint> {
+        if let Some((confirm, _)) = &self.confirm {
+            return confirm.key_hints();
+        }
+        if self.copying {
+            return copy::hints(false);
+        }
+        if self.going {
+            return vec![
+                KeyHint::new("d", "dashboard"),
+                KeyHint::new("s", "settings"),
+            ];
+        }
+        if self.search_input.is_some() {
+            return vec![
+                KeyHint::new("enter", "search"),
+                KeyHint::new("esc", "cancel"),
+            ];
+        }
+        if self.sender_picker.is_some() || self.actor_picker.is_some() {
+            return vec![
+                keymap::MOVE,
+                KeyHint::new("enter", "pick"),
+                KeyHint::new("q", "close"),
+            ];
+        }
+        vec![
+            keymap::MOVE,
+            keymap::SELECT,
+            KeyHint::new("space", "mark"),
+            KeyHint::new("d", "download"),
+            KeyHint::new("R", "mark read"),
+            KeyHint::new("x", "trash"),
+            copy::PREFIX,
+            KeyHint::new("s", "sender"),
+            KeyHint::new("/", "search"),
+            KeyHint::new("1-6", "tabs"),
+            keymap::QUIT,
+        ]
+    }
+
+    fn help_hints(&self) -> Vec<KeyHint> {
+        if self.confirm.is_some()
+            || self.sender_picker.is_some()
+            || self.actor_picker.is_some()
+            || self.search_input.is_some()
+            || self.copying
+            || self.going
+        {
+            return self.key_hints();
+        }
+        vec![
+            KeyHint::new("PageDown / PageUp", "move a screen down or up"),
+            KeyHint::new("Home / End", "go to the first or last item"),
+            KeyHint::new("u", "go to the next unread item"),
+            KeyHint::new("space", "mark or unmark for a bulk action"),
+            KeyHint::new("d", "download attachments of marked items"),
+            KeyHint::new("R", "mark marked (or all unread) items as read"),
+            KeyHint::new("Y", "copy a summary of the item"),
+            KeyHint::new("c", "copy the subject, sender or content key"),
+            KeyHint::new("x", "move the item to trash"),
+            KeyHint::new("tab", "show or hide more of the item"),
+            KeyHint::new("m", "show or hide attachment counts and sizes"),
+            KeyHint::new("s", "show items from one sender"),
+            KeyHint::new("/", "search, e.g. sender=skatteverket && year=2024"),
+            KeyHint::new("r", "load new items"),
+            KeyHint::new("1 / 2 / 3", "show all, unread or payable items"),
+            KeyHint::new("4 / 5", "show archived or trashed items"),
+            KeyHint::new("6", "show receipts"),
+            KeyHint::new("g d", "show the dashboard"),
+            KeyHint::new("g s", "change the settings"),
+            KeyHint::new("A", "switch to a company inbox and back"),
+            KeyHint::new("L", "log out"),
+        ]
+    }
+
+    fn take_message(&mut self) -> Option<Message> {
+        self.message.take()
+    }
+
+    fn takes_text(&self) -> bool {
+        self.search_input.is_some()
+    }
+
+    fn badge(&self) -> Option<String> {
+        let unread = self
+            .inbox
+            .iter()
+            .filter(|entry| InboxFilter::Unread.matches(&entry.item))
+            .count();
+        match unread {
+            0 => None,
+            unread => Some(tr!("{unread} unread", unread = unread)),
         }
     }
 
     fn render(&mut self, frame: &mut Frame, rect: Rect) {
-        let widget = inbox_widget(&self.inbox);
-        frame.render_stateful_widget(widget, rect, &mut self.table_state);
+        let new_items = self.new_item_count();
+        let searching = self.search_input.is_some();
+        let footer_height = if new_items > 0 || searching { 1 } else { 0 };
+        let layout = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Fill(1),
+            Constraint::Length(footer_height),
+        ])
+        .split(rect);
+        frame.render_widget(tabs::widget(Tab::Inbox(self.filter)), layout[0]);
+        if let Some(input) = &self.search_input {
+            let widget = Paragraph::new(format!("/{input}█"));
+            frame.render_widget(widget, layout[2]);
+        } else if new_items > 0 {
+            let footer = format!("{new_items} new item(s) - press r to load");
+            let widget = Paragraph::new(footer).bold().fg(Color::Green);
+            frame.render_widget(widget, layout[2]);
+        }
+        let sender_name = self.sender.as_ref().and_then(|sender| {
+            let summary = self.senders.iter().find(|s| s.key == *sender)?;
+            Some(summary.name.clone())
+        });
+        let search = self.search.as_ref().map(|search| format!("/{search}"));
+        let title = [sender_name, search].into_iter().flatten();
+        let block = Block::bordered()
+            .title(title.collect::<Vec<_>>().join(" "))
+            .fg(Color::Green);
+        let inner = block.inner(layout[1]);
+        frame.render_widget(block, layout[1]);
+        if !self.loaded {
+            render_loading(frame, inner, "the inbox");
+            return;
+        }
+        let loading_height = if self.loading { 1 } else { 0 };
+        let [rows_area, loading_area] = Layout::vertical([
+            Constraint::Fill(1),
+            Constraint::Length(loading_height),
+        ])
+        .areas(inner);
+
+        // Only the rows on screen are built, however long the inbox is
+        self.page_height = rows_area.height as usize;
+        let window = self.window(self.page_height);
+        let selected = self.table_state.selected();
+        let mut state = TableState::new()
+            .with_selected(selected.and_then(|i| i.checked_sub(window.start)));
+        let expanded = (self.expanded.as_ref())
+            .zip(self.selected_entry())
+            .filter(|(expanded, entry)| expanded.key == entry.item.key)
+            .map(|(expanded, entry)| {
+                let width = rows_area.width.saturating_sub(4) as usize;
+                let attachments = expanded.attachments.as_deref();
+                let mut lines = expanded_lines(entry, attachments, width);
+                lines.truncate(rows_area.height.saturating_sub(1) as usize);
+                lines
+            });
+        let expanded_key = self.expanded.as_ref().map(|e| &e.key);
+        let expanded_height = expanded.as_ref().map_or(0, Vec::len) as u16;
+        let widget = inbox_widget(
+            &self.visible[window],
+            &self.marked,
+            self.timestamps,
+            self.show_attachments,
+            rows_area.width,
+            |entry| match Some(&entry.item.key) == expanded_key {
+                true => 1 + expanded_height,
+                false => 1,
+            },
+        );
+        frame.render_stateful_widget(widget, rows_area, &mut state);
+        // Drawn in the room the expanded row leaves under its cells, where
+        // the rows above it are one line each
+        if let (Some(lines), Some(selected)) = (expanded, state.selected()) {
+            let row = selected.saturating_sub(state.offset()) as u16;
+            let area = Rect {
+                y: rows_area.y + row + 1,
+                height: expanded_height,
+                ..rows_area
+            }
+            .intersection(rows_area);
+            let block = Block::new()
+                .borders(Borders::LEFT)
+                .padding(Padding::left(1))
+                .fg(Color::Green);
+            let inner = Rect {
+                x: area.x + 2,
+                width: area.width.saturating_sub(2),
+                ..area
+            };
+            frame.render_widget(Clear, area);
+            frame.render_widget(Paragraph::new(lines).block(block), inner);
+        }
+        if self.loading {
+            let widget = Paragraph::new(tr!("Loading more…")).italic();
+            frame.render_widget(widget, loading_area);
+        }
+
+        if let Some((confirm, _)) = &mut self.confirm {
+            confirm.render(frame, rect);
+        }
+
+        if let Some(picker) = &mut self.sender_picker {
+            let inner = Popup::new("Sender").size(60, 60).render(frame, rect);
+            let widget = sender_picker_widget(&self.senders);
+            frame.render_stateful_widget(widget, inner, picker);
+        }
+
+        if let Some(picker) = &mut self.actor_picker {
+            let inner = Popup::new("Inbox of").size(60, 40).render(frame, rect);
+            let widget = actor_picker_widget(&self.actors, &self.actor);
+            frame.render_stateful_widget(widget, inner, picker);
+        }
     }
 }
 
-fn inbox_widget(inbox: &InboxListing) -> Table<'static> {
-    let rows = inbox.iter().rev().map(inbox_row);
-    let max_id_len =
-        inbox.iter().map(|i| i.id.to_string().len()).max().unwrap_or_default();
-    let widths = [
-        Constraint::Max(3),
-        Constraint::Length(max_id_len as u16),
-        Constraint::Max(20),
-        Constraint::Fill(1),
-        Constraint::Length(16),
-    ];
-
-    Table::new(rows, widths)
-        .column_spacing(1)
+fn sender_picker_widget(senders: &[SenderSummary]) -> List<'static> {
+    let all = ListItem::new(tr!("All senders").to_string());
+    let items = senders.iter().map(|sender| {
+        ListItem::new(tr!(
+            "{name} ({items} items, {unread} unread)",
+            name = sender.name,
+            items = sender.items,
+            unread = sender.unread
+        ))
+    });
+    List::new([all].into_iter().chain(items))
+        .highlight_style(Style::new().add_modifier(Modifier::REVERSED))
+}
+
+fn actor_picker_widget(actors: &[Actor], current: &Actor) -> List<'static> {
+    let items = actors.iter().map(|actor| {
+        let name = match actor {
+            Actor::User => tr!("Your own inbox").to_string(),
+            Actor::Company(company) => {
+                format!("{} ({})", company.name, company.org_number)
+            }
+        };
+        match actor == current {
+            true => ListItem::new(format!("{name} *")),
+            false => ListItem::new(name),
+        }
+    });
+    List::new(items)
         .highlight_style(Style::new().add_modifier(Modifier::REVERSED))
-        .block(Block::bordered().fg(Color::Green))
 }
 
-fn inbox_row(entry: &InboxEntry) -> Row<'static> {
-    let local_datetime =
-        Local.from_utc_datetime(&entry.item.created_at.naive_utc());
-    let unread_marker =
-        if entry.item.status == Status::Unread { "NEW" } else { "   " };
-    let cells = [
-        Cell::new(unread_marker).bold(),
-        Cell::new(entry.id.to_string()),
-        Cell::new(entry.item.sender_name.clone()),
-        Cell::new(entry.item.subject.clone()),
-        Cell::new(local_datetime.format("%Y-%m-%d %H:%M").to_string()),
-    ];
-    Row::new(cells)
+/// What an expanded row shows under it, fitted to `width`
+fn expanded_lines(
+    entry: &InboxEntry,
+    attachments: Option<&[String]>,
+    width: usize,
+) -> Vec<Line<'static>> {
+    let item = &entry.item;
+    let mut fields = vec![(tr!("Subject"), item.subject.clone())];
+    if let Some(amount) = item.amount {
+        let currency = item.currency.as_deref().unwrap_or_default();
+        let amount = format!("{amount} {currency}").trim_end().to_string();
+        fields.push((tr!("Amount"), amount));
+    }
+    if let Some(due_date) = &item.due_date {
+        fields.push((tr!("Due date"), due_date.0.to_string()));
+    }
+    let labels = item.active_labels();
+    if !labels.is_empty() {
+        fields.push((tr!("Labels"), labels.join(", ")));
+    }
+    match attachments {
+        None => fields.push((tr!("Attachments"), tr!("Loading…").to_string())),
+        // Attachments after the first go without the label
+        Some(names) => {
+            fields.extend(names.iter().zip(1..).map(|(name, n)| match n {
+                1 => (tr!("Attachments"), name.clone()),
+                _ => ("", name.clone()),
+            }))
+        }
+    }
+
+    let label_width = (fields.iter())
+        .map(|(label, _)| label.chars().count() + 1)
+        .max()
+        .unwrap_or_default();
+    let text_width = width.saturating_sub(label_width + 1);
+    fields
+        .into_iter()
+        .flat_map(|(label, text)| {
+            let label = match label {
+                "" => String::new(),
+                label => format!("{label}:"),
+            };
+            wrap(&text, text_width).into_iter().zip(0..).map(
+                move |(text, n)| {
+                    let label = if n == 0 { label.as_str() } else { "" };
+                    let label = format!("{label:label_width$} ");
+                    Line::from(vec![Span::raw(label).bold(), Span::raw(text)])
+                },
+            )
+        })
+        .collect()
+}
+
+/// Breaks text into lines of at most `width` characters, between words
+/// where they fit
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let width = width.max(1);
+    let mut lines = vec![String::new()];
+    for word in text.split_whitespace() {
+        let used = lines.last().map_or(0, |line| line.chars().count());
+        match used {
+            0 => {}
+            used if used + 1 + word.chars().count() <= width => {
+                lines.last_mut().expect("there is a line").push(' ');
+            }
+            _ => lines.push(String::new()),
+        }
+        for c in word.chars() {
+            if lines.last().is_some_and(|line| line.chars().count() >= width) {
+                lines.push(String::new());
+            }
+            lines.last_mut().expect("there is a line").push(c);
+        }
+    }
+    lines
+}
+
+fn inbox_widget(
+    entries: &[InboxEntry],
+    marked: &BTreeSet<ContentKey>,
+    timestamps: Timestamps,
+    show_attachments: bool,
+    width: u16,
+    row_height: impl Fn(&InboxEntry) -> u16,
+) -> Table<'static> {
+    let columns = match entries.iter().any(|entry| entry.profile.is_some()) {
+        true => &PROFILE_COLUMNS[..],
+        false => &COLUMNS[..],
+    };
+    let mut columns = timestamps.columns(columns);
+    if show_attachments {
+        columns.extend(DETAILS_COLUMNS.map(String::from));
+    }
+    table::Table::new(entries)
+        .with_columns(&columns)
+        .expect("inbox rows have these columns")
+        .widget(
+            width,
+            |entry| marked.contains(&entry.item.key),
+            |entry| match entry.item.status {
+                Status::Unread => Style::new().bold().fg(Color::Yellow),
+                _ => Style::new(),
+            },
+            row_height,
+        )
+        .highlight_style(Style::new().add_modifier(Modifier::REVERSED))
 }
@@ -1,68 +1,212 @@
+use bytes::Bytes;
+use chrono::Local;
 use crossterm::event::KeyCode;
 use ratatui::layout::{Constraint, Layout, Rect};
 use ratatui::style::{Color, Modifier, Style, Stylize};
+use ratatui::text::{Line, Span};
 use ratatui::widgets::{
-    Block, Borders, List, ListDirection, ListState, Paragraph,
+    Block, Borders, Clear, List, ListDirection, ListState, Paragraph,
 };
 use ratatui::{symbols, Frame};
+use std::collections::BTreeMap;
 use std::fmt::Display;
 
-use super::keymap::KeyEvent;
-use super::{Command, Error, Event, TuiView};
-use crate::client::Client;
+use super::confirm::ConfirmView;
+use super::copy::{self, CopyField};
+use super::image::{Picture, Protocol};
+use super::keymap::{self, KeyEvent, KeyHint};
+use super::{render_loading, Command, Data, Error, Event, TuiView};
+use crate::client::{self, ContentClient};
 use crate::model::content::Status;
 use crate::model::content::{InboxItem, ItemDetails};
+use crate::{filename, i18n, tr};
+
+/// Columns the sender icon takes up, next to the sender name
+const ICON_WIDTH: u16 = 6;
 
 pub struct ItemView {
     item: InboxItem,
-    details: ItemDetails,
+    // None until the details from `load` arrive
+    details: Option<ItemDetails>,
     list_state: ListState,
+    confirm_trash: Option<ConfirmView>,
+    show_details: bool,
+    // Set after the copy key, until the field to copy is picked
+    copying: bool,
+    // How images are drawn, None if they are not
+    protocol: Option<Protocol>,
+    // Images loaded so far, None for those that could not be drawn
+    images: BTreeMap<Preview, Option<Bytes>>,
+    // Where the last render left room for the images
+    image_rects: Vec<(Preview, Rect)>,
+}
+
+/// An image of the item the view can show
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Preview {
+    SenderIcon,
+    Attachment(usize),
 }
 
 pub enum ItemViewResult {
     Open(u32),
     MarkRead,
+    Trash,
+    Copy(CopyField),
     Close,
+    LoadFailed(client::Error),
+    /// Fetch the image for `set_image`
+    LoadImage(Preview),
 }
 
 impl ItemView {
-    pub fn make(
-        client: &mut impl Client,
-        item: InboxItem,
-    ) -> Result<ItemView, Error> {
-        let details = client.get_item_details(&item.key)?;
-        let list_state = match details.parts.len() {
-            0 => ListState::default(),
-            _ => ListState::default().with_selected(Some(0)),
+    /// A view of the item that waits for what `load` fetches. Images are
+    /// shown if there is a protocol to draw them with.
+    pub fn new(item: InboxItem, protocol: Option<Protocol>) -> ItemView {
+        let list_state = ListState::default();
+        ItemView {
+            item,
+            details: None,
+            list_state,
+            confirm_trash: None,
+            show_details: true,
+            copying: false,
+            protocol,
+            images: BTreeMap::new(),
+            image_rects: Vec::new(),
+        }
+    }
+
+    /// Shows the image that was asked for with `LoadImage`, `None` if it
+    /// could not be fetched
+    pub fn set_image(&mut self, preview: Preview, data: Option<Bytes>) {
+        let drawable = |data: &Bytes| {
+            let extension = filename::sniff_extension(data);
+            self.protocol
+                .zip(extension)
+                .is_some_and(|(protocol, extension)| protocol.draws(extension))
         };
-        Ok(ItemView { item, details, list_state })
+        let data = data.filter(drawable);
+        self.images.insert(preview, data);
     }
-}
 
-impl TuiView for ItemView {
-    type ReturnType = ItemViewResult;
+    /// The images to show as the view is, loaded or not
+    fn previews(&self) -> Vec<Preview> {
+        let Some(protocol) = self.protocol else {
+            return Vec::new();
+        };
+        let mut previews = Vec::new();
+        if !self.item.sender_icon_url.is_empty() {
+            previews.push(Preview::SenderIcon);
+        }
+        let selected = self.list_state.selected();
+        let attachment = (self.details.as_ref().zip(selected)).and_then(
+            |(details, index)| Some((index, details.parts.get(index)?)),
+        );
+        if let Some((index, attachment)) = attachment {
+            let extension = filename::extension(&attachment.content_type);
+            if attachment.key.is_some()
+                && extension.is_some_and(|extension| protocol.draws(extension))
+            {
+                previews.push(Preview::Attachment(index));
+            }
+        }
+        previews
+    }
 
-    fn update(
+    /// An image to show that has not been fetched yet
+    fn missing_image(&self) -> Option<Preview> {
+        (self.previews().into_iter())
+            .find(|preview| !self.images.contains_key(preview))
+    }
+
+    pub fn load(client: &mut impl ContentClient, item: &InboxItem) -> Data {
+        Data::Details(client.get_item_details(&item.key))
+    }
+
+    pub fn is_loaded(&self) -> bool {
+        self.details.is_some()
+    }
+
+    fn update_item(
         &mut self,
         event: Event,
-    ) -> Result<Command<Self::ReturnType>, Error> {
+    ) -> Result<Command<ItemViewResult>, Error> {
+        let Some(details) = &self.details else {
+            return match event {
+                Event::Data(Data::Details(Ok(details))) => {
+                    if !details.parts.is_empty() {
+                        self.list_state.select(Some(0));
+                    }
+                    self.details = Some(details);
+                    Ok(Command::Await)
+                }
+                Event::Data(Data::Details(Err(error))) => {
+                    Ok(Command::Return(ItemViewResult::LoadFailed(error)))
+                }
+                Event::Key(KeyEvent::Quit) | Event::Key(KeyEvent::Back) => {
+                    Ok(Command::Return(ItemViewResult::Close))
+                }
+                _ => Ok(Command::Await),
+            };
+        };
         match event {
+            Event::Key(key) if self.confirm_trash.is_some() => {
+                let confirm =
+                    self.confirm_trash.as_mut().expect("checked above");
+                match confirm.update(Event::Key(key))? {
+                    Command::Return(true) => {
+                        Ok(Command::Return(ItemViewResult::Trash))
+                    }
+                    Command::Return(false) => {
+                        self.confirm_trash = None;
+                        Ok(Command::Await)
+                    }
+                    _ => Ok(Command::Await),
+                }
+            }
+
+            Event::Key(key) if self.copying => {
+                self.copying = false;
+                let attachment = self.list_state.selected().map(|n| n as u32);
+                match copy::field(&key, attachment) {
+                    Some(field) => {
+                        Ok(Command::Return(ItemViewResult::Copy(field)))
+                    }
+                    None => Ok(Command::Await),
+                }
+            }
+
+            Event::Key(KeyEvent::Key(KeyCode::Char('c'))) => {
+                self.copying = true;
+                Ok(Command::Await)
+            }
+
+            Event::Key(KeyEvent::Key(KeyCode::Char('x'))) => {
+                let prompt = tr!(
+                    "Move '{subject}' to trash?",
+                    subject = self.item.subject
+                );
+                self.confirm_trash = Some(ConfirmView::new(prompt));
+                Ok(Command::Await)
+            }
+
             Event::Key(KeyEvent::Up) => {
                 let select = match self.list_state.selected().unwrap_or(0) {
                     0 => 0,
                     n => n - 1,
                 };
                 self.list_state.select(Some(select));
-                Ok(Command::AwaitKey)
+                Ok(Command::Await)
             }
 
             Event::Key(KeyEvent::Down) => {
                 let select = match self.list_state.selected().unwrap_or(0) {
-                    n if n >= self.details.parts.len() - 1 => n,
+                    n if n + 1 >= details.parts.len() => n,
                     n => n + 1,
                 };
                 self.list_state.select(Some(select));
-                Ok(Command::AwaitKey)
+                Ok(Command::Await)
             }
 
             Event::Key(KeyEvent::Select) => {
@@ -83,18 +227,94 @@ impl TuiView for ItemView {
                 Ok(Command::Return(ItemViewResult::MarkRead))
             }
 
-            _ => Ok(Command::AwaitKey),
+            Event::Key(KeyEvent::Key(KeyCode::Char('Y'))) => {
+                Ok(Command::Return(ItemViewResult::Copy(CopyField::Summary)))
+            }
+
+            Event::Key(KeyEvent::Key(KeyCode::Char('m'))) => {
+                self.show_details = !self.show_details;
+                Ok(Command::Await)
+            }
+
+            _ => Ok(Command::Await),
+        }
+    }
+}
+
+impl TuiView for ItemView {
+    type ReturnType = ItemViewResult;
+
+    fn update(
+        &mut self,
+        event: Event,
+    ) -> Result<Command<Self::ReturnType>, Error> {
+        let command = self.update_item(event)?;
+        if self.details.is_none() || !matches!(command, Command::Await) {
+            return Ok(command);
+        }
+        match self.missing_image() {
+            Some(preview) => {
+                Ok(Command::Return(ItemViewResult::LoadImage(preview)))
+            }
+            None => Ok(command),
+        }
+    }
+
+    fn key_hints(&self) -> Vec<KeyHint> {
+        if let Some(confirm) = &self.confirm_trash {
+            return confirm.key_hints();
+        }
+        if self.details.is_none() {
+            return vec![keymap::BACK];
+        }
+        if self.copying {
+            let attachment = self.list_state.selected().is_some();
+            return copy::hints(attachment);
         }
+        vec![
+            keymap::MOVE,
+            KeyHint::new("enter", "open attachment"),
+            KeyHint::new("r", "mark read"),
+            KeyHint::new("Y", "copy"),
+            copy::PREFIX,
+            KeyHint::new("x", "trash"),
+            KeyHint::new("m", "details"),
+            keymap::BACK,
+        ]
     }
 
     fn render(&mut self, frame: &mut Frame, rect: Rect) {
-        render_widget(
+        let previews: Vec<_> = (self.previews().into_iter())
+            .filter(|preview| matches!(self.images.get(preview), Some(Some(_))))
+            .collect();
+        self.image_rects = render_widget(
             &self.item,
-            &self.details,
+            self.details.as_ref(),
+            self.show_details,
+            &previews,
             &mut self.list_state,
             frame,
             rect,
         );
+        if let Some(confirm) = &mut self.confirm_trash {
+            confirm.render(frame, rect);
+        }
+    }
+
+    fn pictures(&self) -> Vec<Picture<'_>> {
+        let Some(protocol) = self.protocol else {
+            return Vec::new();
+        };
+        // The prompt is drawn over where the images are
+        if self.confirm_trash.is_some() {
+            return Vec::new();
+        }
+        (self.image_rects.iter())
+            .filter_map(|(preview, rect)| {
+                let data = self.images.get(preview)?.as_ref()?;
+                Some(Picture { protocol, rect: *rect, data })
+            })
+            .collect()
     }
 }
 
@@ -102,18 +322,71 @@ fn indent(n: usize, s: impl Display) -> String {
     format!("\n{:indent$}{}", "", s, indent = n)
 }
 
+/// What there is to know about the item beyond its sender and subject, as
+/// pairs of label and value. Only what the item has is included.
+fn metadata(item: &InboxItem) -> Vec<(&'static str, String)> {
+    let mut fields = Vec::new();
+    if let Some(amount) = item.amount {
+        let currency = item.currency.as_deref().unwrap_or_default();
+        let amount = format!("{amount} {currency}").trim_end().to_string();
+        fields.push(("Amount", amount));
+    }
+    if item.variable_amount == Some(true) {
+        fields.push(("Variable amount", "Yes".to_string()));
+    }
+    if let Some(date) = &item.due_date {
+        fields.push(("Due date", date.0.to_string()));
+    }
+    if let Some(date) = &item.pay_date {
+        fields.push(("Pay date", date.0.to_string()));
+    }
+    if let Some(status) = &item.payment_status {
+        fields.push(("Payment status", status.clone()));
+    }
+    if let Some(agreement) = &item.agreement_key {
+        let agreement = match &item.agreement_status {
+            Some(status) => format!("{agreement} ({status})"),
+            None => agreement.clone(),
+        };
+        fields.push(("Agreement", agreement));
+    }
+    match item.active_labels().as_slice() {
+        [] => {}
+        labels => fields.push(("Labels", labels.join(", "))),
+    }
+    fields.push(("Type", item.content_type.clone()));
+    // Answers cannot be sent from here, only read
+    if let Some(form) = &item.form {
+        fields.push(("Form", "answer it in the Kivra app".to_string()));
+        fields.extend(form.lines().into_iter().map(|line| ("", line)));
+    }
+    fields
+}
+
+/// Renders the item, leaving room for the loaded images in `previews`, and
+/// returns where
 fn render_widget(
     item: &InboxItem,
-    details: &ItemDetails,
+    details: Option<&ItemDetails>,
+    show_details: bool,
+    previews: &[Preview],
     list_state: &mut ListState,
     frame: &mut Frame,
     rect: Rect,
-) {
+) -> Vec<(Preview, Rect)> {
+    let mut image_rects = Vec::new();
+    let metadata = metadata(item);
+    // A collapsed panel is only its title line
+    let metadata_height = match show_details {
+        true => metadata.len() as u16 + 1,
+        false => 1,
+    };
     let main_layout = Layout::default()
         .direction(ratatui::layout::Direction::Vertical)
         .constraints(vec![
             Constraint::Length(4),
             Constraint::Length(5),
+            Constraint::Length(metadata_height),
             Constraint::Min(5),
         ])
         .split(rect);
@@ -134,30 +407,48 @@ fn render_widget(
             ..symbols::border::PLAIN
         })
         .borders(Borders::TOP | Borders::LEFT | Borders::RIGHT)
-        .title("Sender:")
+        .title(tr!("Sender:"))
         .title_style(Style::new().bold())
         .fg(Color::Green);
+    let sender_inner = sender_block.inner(top_layout[0]);
     let sender_text = indent(2, &item.sender_name);
     let sender_widget = Paragraph::new(sender_text).block(sender_block);
     frame.render_widget(sender_widget, top_layout[0]);
+    if previews.contains(&Preview::SenderIcon) {
+        let width = ICON_WIDTH.min(sender_inner.width);
+        let icon = Rect {
+            x: sender_inner.right().saturating_sub(width + 1),
+            width,
+            ..sender_inner
+        };
+        frame.render_widget(Clear, icon);
+        image_rects.push((Preview::SenderIcon, icon));
+    }
 
     let status_block = Block::new()
         .borders(Borders::TOP | Borders::RIGHT)
-        .title("Status:")
+        .title(tr!("Status:"))
         .title_style(Style::new().bold())
         .fg(Color::Green);
-    let status_text =
-        if item.status == Status::Read { "Read" } else { "Unread" };
+    let status = match item.status {
+        Status::Read => tr!("status|Read"),
+        Status::Unread => tr!("status|Unread"),
+    };
+    let status_text = match item.active_labels().as_slice() {
+        [] => status.to_string(),
+        labels => format!("{status} ({})", labels.join(", ")),
+    };
     let status_widget =
         Paragraph::new(indent(2, status_text)).block(status_block);
     frame.render_widget(status_widget, top_layout[1]);
 
     let created_block = Block::new()
         .borders(Borders::TOP | Borders::RIGHT)
-        .title("Created at:")
+        .title(tr!("Created at:"))
         .title_style(Style::new().bold())
         .fg(Color::Green);
-    let created_text = indent(2, item.created_at.format("%Y-%m-%d %H:%M"));
+    let created_at = item.created_at.with_timezone(&Local).naive_local();
+    let created_text = indent(2, i18n::long_date_time(created_at));
     let created_widget = Paragraph::new(created_text).block(created_block);
     frame.render_widget(created_widget, top_layout[2]);
 
@@ -168,23 +459,103 @@ fn render_widget(
             ..symbols::border::PLAIN
         })
         .borders(Borders::TOP | Borders::LEFT | Borders::RIGHT)
-        .title("Subject:")
+        .title(tr!("Subject:"))
         .title_style(Style::new().bold())
         .fg(Color::Green);
     let subject_text = indent(2, &item.subject);
     let subject_widget = Paragraph::new(subject_text).block(subject_block);
     frame.render_widget(subject_widget, main_layout[1]);
 
-    let attachments_block = Block::new()
+    let details_title = match show_details {
+        true => tr!("Details:"),
+        false => tr!("Details: (m to show)"),
+    };
+    let details_block = Block::new()
         .border_set(symbols::border::Set {
             top_left: symbols::line::VERTICAL_RIGHT,
             top_right: symbols::line::VERTICAL_LEFT,
             ..symbols::border::PLAIN
         })
+        .borders(Borders::TOP | Borders::LEFT | Borders::RIGHT)
+        .title(details_title)
+        .title_style(Style::new().bold())
+        .fg(Color::Green);
+    // Labels and their colons, lined up
+    let metadata: Vec<_> = (metadata.into_iter())
+        .map(|(label, value)| (tr!(label), value))
+        .collect();
+    let width =
+        metadata.iter().map(|(label, _)| label.chars().count() + 1).max();
+    let width = width.unwrap_or_default();
+    let lines: Vec<Line> = match show_details {
+        true => metadata
+            .into_iter()
+            .map(|(label, value)| {
+                // Lines without a label continue the one above
+                let label = match label {
+                    "" => String::new(),
+                    label => format!("{label}:"),
+                };
+                let label = format!("  {label:width$} ");
+                Line::from(vec![Span::raw(label).bold(), Span::raw(value)])
+            })
+            .collect(),
+        false => Vec::new(),
+    };
+    let details_widget = Paragraph::new(lines).block(details_block);
+    frame.render_widget(details_widget, main_layout[2]);
+
+    // The selected attachment is shown next to the list, if it is an image
+    let preview = previews.iter().find_map(|preview| match preview {
+        Preview::Attachment(index) => Some(*index),
+        Preview::SenderIcon => None,
+    });
+    let (attachments_area, preview_area) = match preview {
+        Some(_) => {
+            let [list, preview] =
+                Layout::horizontal([Constraint::Fill(1), Constraint::Fill(1)])
+                    .areas(main_layout[3]);
+            (list, Some(preview))
+        }
+        None => (main_layout[3], None),
+    };
+    let attachments_block = Block::new()
+        .border_set(symbols::border::Set {
+            top_left: symbols::line::VERTICAL_RIGHT,
+            top_right: match preview {
+                Some(_) => symbols::line::HORIZONTAL_DOWN,
+                None => symbols::line::VERTICAL_LEFT,
+            },
+            bottom_right: match preview {
+                Some(_) => symbols::line::HORIZONTAL_UP,
+                None => symbols::line::BOTTOM_RIGHT,
+            },
+            ..symbols::border::PLAIN
+        })
         .borders(Borders::ALL)
-        .title("Attachments:")
+        .title(tr!("Attachments:"))
         .title_style(Style::new().bold())
         .fg(Color::Green);
+    if let Some((index, area)) = preview.zip(preview_area) {
+        let preview_block = Block::new()
+            .border_set(symbols::border::Set {
+                top_right: symbols::line::VERTICAL_LEFT,
+                ..symbols::border::PLAIN
+            })
+            .borders(Borders::TOP | Borders::RIGHT | Borders::BOTTOM)
+            .title(tr!("Preview:"))
+            .title_style(Style::new().bold())
+            .fg(Color::Green);
+        image_rects
+            .push((Preview::Attachment(index), preview_block.inner(area)));
+        frame.render_widget(preview_block, area);
+    }
+    let Some(details) = details else {
+        let inner = attachments_block.inner(attachments_area);
+        frame.render_widget(attachments_block, attachments_area);
+        render_loading(frame, inner, "attachments");
+        return image_rects;
+    };
     let attachments: Vec<String> = (0..(details.parts.len()))
         .map(|i| details.attachment_name(i).unwrap())
         .collect();
@@ -194,7 +565,8 @@ fn render_widget(
         .highlight_style(Style::new().add_modifier(Modifier::REVERSED));
     frame.render_stateful_widget(
         attachments_widget,
-        main_layout[2],
+        attachments_area,
         list_state,
     );
+    image_rects
 }
@@ -1,41 +1,71 @@
-use crossterm::event::{Event, KeyCode};
-
-use super::Error;
+use crossterm::event::KeyCode;
 
+#[derive(Clone, Copy)]
 pub enum KeyEvent {
     Up,
     Down,
     Select,
     Back,
     Quit,
+    Help,
+    Console,
     Key(KeyCode),
-    Unknown,
 }
 
-pub fn read_key() -> Result<KeyEvent, Error> {
-    match crossterm::event::read()? {
-        Event::Key(key) => match key.code {
-            KeyCode::Up | KeyCode::Char('k') | KeyCode::Char('p') => {
-                Ok(KeyEvent::Up)
-            }
+/// A key and what it does, for the status bar and the help
+#[derive(Clone, Copy, PartialEq)]
+pub struct KeyHint {
+    pub keys: &'static str,
+    pub action: &'static str,
+}
+
+impl KeyHint {
+    pub const fn new(keys: &'static str, action: &'static str) -> KeyHint {
+        KeyHint { keys, action }
+    }
+}
+
+// Hints for the keys mapped below, which every view uses the same way
+pub const MOVE: KeyHint = KeyHint::new("j/k", "move");
+pub const SELECT: KeyHint = KeyHint::new("enter", "open");
+pub const BACK: KeyHint = KeyHint::new("h", "back");
+pub const QUIT: KeyHint = KeyHint::new("q", "quit");
+pub const HELP: KeyHint = KeyHint::new("?", "help");
+
+/// Every key mapped below, for the help
+pub const BINDINGS: [KeyHint; 7] = [
+    KeyHint::new("k / p / Up", "move up"),
+    KeyHint::new("j / n / Down", "move down"),
+    KeyHint::new("l / f / Enter / Right", "select or open"),
+    KeyHint::new("h / b / Left", "go back"),
+    KeyHint::new("q / Esc", "quit or close"),
+    KeyHint::new("?", "show this help"),
+    KeyHint::new("F12 / ~", "show or hide the log"),
+];
+
+pub fn map_key(key: crossterm::event::KeyEvent) -> KeyEvent {
+    match key.code {
+        KeyCode::Up | KeyCode::Char('k') | KeyCode::Char('p') => KeyEvent::Up,
+
+        KeyCode::Down | KeyCode::Char('j') | KeyCode::Char('n') => {
+            KeyEvent::Down
+        }
+
+        KeyCode::Enter
+        | KeyCode::Right
+        | KeyCode::Char('l')
+        | KeyCode::Char('f') => KeyEvent::Select,
 
-            KeyCode::Down | KeyCode::Char('j') | KeyCode::Char('n') => {
-                Ok(KeyEvent::Down)
-            }
+        KeyCode::Left | KeyCode::Char('h') | KeyCode::Char('b') => {
+            KeyEvent::Back
+        }
 
-            KeyCode::Enter
-            | KeyCode::Right
-            | KeyCode::Char('l')
-            | KeyCode::Char('f') => Ok(KeyEvent::Select),
+        KeyCode::Esc | KeyCode::Char('q') => KeyEvent::Quit,
 
-            KeyCode::Left | KeyCode::Char('h') | KeyCode::Char('b') => {
-                Ok(KeyEvent::Back)
-            }
+        KeyCode::Char('?') => KeyEvent::Help,
 
-            KeyCode::Esc | KeyCode::Char('q') => Ok(KeyEvent::Quit),
+        KeyCode::F(12) | KeyCode::Char('~') => KeyEvent::Console,
 
-            _ => Ok(KeyEvent::Key(key.code)),
-        },
-        _ => Ok(KeyEvent::Unknown),
+        code => KeyEvent::Key(code),
     }
 }
@@ -1,10 +1,12 @@
+use std::time::Instant;
+
+use crossterm::event::KeyCode;
 use ratatui::{
     layout::{Alignment, Constraint, Direction, Layout, Rect},
     prelude,
     style::{Color, Style},
     widgets::Paragraph,
 };
-use std::time::Duration;
 
 #[rustfmt::skip]
 const QR_BRANDING: &str = concat!(
@@ -14,67 +16,59 @@ const QR_BRANDING: &str = concat!(
     " ▀▀  ▀▀ \n",
 );
 
-use super::{keymap::KeyEvent, qr, Command, Error, Event, TuiView};
+use super::{
+    keymap::{KeyEvent, KeyHint},
+    qr::{self, QrRenderer},
+    Command, Error, Event, TuiView,
+};
 use crate::{
-    client::{self, Client},
-    model::{
-        auth::{AuthCode, AuthTokenResponse},
-        Config,
+    client::{
+        login::{bankid_url, LoginFlow},
+        AuthClient, LoginOptions,
     },
+    model::auth::AuthTokenResponse,
+    tr,
 };
 
-pub struct LoginView<'a, C: Client> {
-    client: &'a C,
-    config: Config,
-    auth_code: AuthCode,
-    code_verifier: Vec<u8>,
-    qr_code: String,
-    next_poll_url: String,
-    retry_after: u32,
+pub struct LoginView<'a, C: AuthClient> {
+    flow: LoginFlow<'a, C>,
+    same_device: bool,
+    qr_renderer: QrRenderer,
+    next_poll: Instant,
 }
 
-impl<'a, C: Client> LoginView<'a, C> {
-    pub fn make(client: &'a C) -> Result<LoginView<'a, C>, Error> {
-        let config = client.get_config()?;
-        let (verifier, auth_resp) = client.start_auth(&config)?;
-
+impl<'a, C: AuthClient> LoginView<'a, C> {
+    pub fn make(
+        client: &'a C,
+        options: LoginOptions,
+    ) -> Result<LoginView<'a, C>, Error> {
+        let flow = LoginFlow::start(client)?;
         Ok(LoginView {
-            client,
-            config,
-            auth_code: auth_resp.code,
-            code_verifier: verifier,
-            qr_code: auth_resp.qr_code,
-            next_poll_url: auth_resp.next_poll_url,
-            retry_after: 1,
+            flow,
+            same_device: options.same_device,
+            qr_renderer: options.qr_renderer,
+            next_poll: Instant::now(),
         })
     }
 
-    fn check_auth(
-        &mut self,
-    ) -> Result<Option<AuthTokenResponse>, client::Error> {
-        let check = self.client.check_auth(&self.next_poll_url)?;
-        match check.ssn {
-            None => {
-                self.qr_code = check.qr_code;
-                self.next_poll_url =
-                    check.next_poll_url.unwrap_or(self.next_poll_url.clone());
-                self.retry_after =
-                    check.retry_after.unwrap_or(self.retry_after);
-                Ok(None)
-            }
-            Some(_) => {
-                let auth_token = self.client.get_auth_token(
-                    &self.config,
-                    self.auth_code.clone(),
-                    self.code_verifier.clone(),
-                )?;
-                Ok(Some(auth_token))
-            }
+    /// What BankID asks for, and how long the order has left. One that is
+    /// not started in time is replaced, while one that is has to be signed
+    /// before it runs out.
+    fn status(&self) -> String {
+        let secs = self.flow.expires_in().as_secs();
+        match self.flow.is_user_started() {
+            true => format!("{} ({secs}s left)", self.flow.status()),
+            false => format!("{} (new code in {secs}s)", self.flow.status()),
         }
     }
+
+    fn launch_bankid(&mut self) -> Result<(), Error> {
+        self.same_device = true;
+        Ok(opener::open(bankid_url(&self.flow.auto_start_token))?)
+    }
 }
 
-impl<'a, C: Client> TuiView for LoginView<'a, C> {
+impl<'a, C: AuthClient> TuiView for LoginView<'a, C> {
     type ReturnType = Option<AuthTokenResponse>;
     fn update(
         &mut self,
@@ -82,38 +76,67 @@ impl<'a, C: Client> TuiView for LoginView<'a, C> {
     ) -> Result<Command<Self::ReturnType>, Error> {
         match event {
             Event::Init => {
-                let duration = Duration::from_secs(self.retry_after.into());
-                Ok(Command::AwaitTimeout(duration))
+                if self.same_device {
+                    self.launch_bankid()?;
+                }
+                self.next_poll = Instant::now() + self.flow.retry_after();
+                Ok(Command::AwaitUntil(self.next_poll))
+            }
+
+            Event::Key(KeyEvent::Key(KeyCode::Char('o'))) => {
+                self.launch_bankid()?;
+                Ok(Command::AwaitUntil(self.next_poll))
             }
 
             Event::Key(KeyEvent::Quit) => {
-                self.client.abort_auth(&self.next_poll_url)?;
+                self.flow.abort()?;
                 Ok(Command::Return(None))
             }
 
-            Event::Timeout => match self.check_auth()? {
+            Event::Tick => match self.flow.poll()? {
                 None => {
-                    let timeout = Duration::from_secs(self.retry_after.into());
-                    Ok(Command::AwaitTimeout(timeout))
+                    self.next_poll = Instant::now() + self.flow.retry_after();
+                    Ok(Command::AwaitUntil(self.next_poll))
                 }
                 Some(auth_token) => Ok(Command::Return(Some(auth_token))),
             },
 
-            _ => {
-                let timeout = Duration::from_secs(self.retry_after.into());
-                Ok(Command::AwaitTimeout(timeout))
-            }
+            _ => Ok(Command::AwaitUntil(self.next_poll)),
         }
     }
 
+    fn key_hints(&self) -> Vec<KeyHint> {
+        vec![KeyHint::new("o", "open BankID"), KeyHint::new("q", "abort")]
+    }
+
     fn render(&mut self, frame: &mut prelude::Frame, rect: Rect) {
-        let qr = qr::encode(&self.qr_code).unwrap();
+        if self.same_device {
+            let msg = tr!(
+                "Waiting for BankID on this device\n\n{status}\n\n\
+                 Press 'o' to open BankID again or 'q' to abort",
+                status = self.status()
+            );
+            let layout = Layout::vertical([
+                Constraint::Fill(1),
+                Constraint::Length(5),
+                Constraint::Fill(1),
+            ])
+            .split(rect);
+            frame.render_widget(
+                Paragraph::new(msg).alignment(Alignment::Center),
+                layout[1],
+            );
+            return;
+        }
+
+        let qr = qr::encode(&self.flow.qr_code, self.qr_renderer).unwrap();
         let qr_height = qr.lines().count() as u16;
         let qr_width =
             qr.lines().next().unwrap_or_default().chars().count() as u16;
 
-        // Need space for: title (2) + QR + quit message (1) + margins (4)
-        let min_height = qr_height + 7;
+        // Need space for: title (2) + QR + status (1) + quit message (1) +
+        // margins (4)
+        let min_height = qr_height + 8;
         let min_width = qr_width;
 
         if rect.height < min_height || rect.width < min_width {
@@ -140,12 +163,13 @@ impl<'a, C: Client> TuiView for LoginView<'a, C> {
                 Constraint::Length(2),
                 Constraint::Length(qr_height),
                 Constraint::Length(1),
+                Constraint::Length(1),
                 Constraint::Fill(1),
             ])
             .split(rect);
 
         frame.render_widget(
-            Paragraph::new("Authenticate with BankID")
+            Paragraph::new(tr!("Authenticate with BankID"))
                 .alignment(Alignment::Center),
             layout[1],
         );
@@ -176,10 +200,23 @@ impl<'a, C: Client> TuiView for LoginView<'a, C> {
             branding_rect,
         );
 
+        let status_style = match self.flow.failure {
+            Some(_) => Style::default().fg(Color::Red),
+            None => Style::default(),
+        };
         frame.render_widget(
-            Paragraph::new("Press 'q' to abort login")
-                .alignment(Alignment::Center),
+            Paragraph::new(self.status())
+                .alignment(Alignment::Center)
+                .style(status_style),
             layout[3],
         );
+
+        frame.render_widget(
+            Paragraph::new(tr!(
+                "Press 'o' to use BankID on this device or 'q' to abort login"
+            ))
+            .alignment(Alignment::Center),
+            layout[4],
+        );
     }
 }
@@ -1,24 +1,52 @@
-use std::time::Duration;
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        mpsc::{self, Receiver, RecvTimeoutError, Sender},
+        Arc, Mutex, PoisonError,
+    },
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
 
-use crossterm::event::poll;
-use keymap::{read_key, KeyEvent};
+use crossterm::event::{poll, read, Event as CrosstermEvent};
+use failure::{FailureChoice, FailureView};
+use keymap::{KeyEvent, KeyHint};
 use ratatui::{
     layout::{Constraint, Layout, Rect},
-    style::Stylize,
+    style::{Color, Stylize},
+    text::{Line, Span},
     widgets::Paragraph,
     Frame,
 };
 use terminal::LoadedTerminal;
 use thiserror::Error;
+use tracing::warn;
 
-use crate::client::session::UserInfo;
+use crate::{
+    client::{self, worker::Cancel},
+    model::content::{InboxListing, ItemDetails},
+    tr,
+};
 
+pub mod bulk;
+pub mod confirm;
+mod console;
+pub mod copy;
+pub mod dashboard;
+mod failure;
+pub mod image;
 pub mod inbox;
 pub mod inbox_item;
 mod keymap;
 pub mod login;
+mod popup;
 pub mod qr;
+pub mod receipts;
+pub mod settings;
+pub mod stack;
+pub mod tabs;
 pub mod terminal;
+pub mod unreachable;
 
 #[derive(Debug, Error)]
 pub enum Error {
@@ -31,8 +59,20 @@ pub enum Error {
     #[error("HTTP client error: {0}")]
     ClientError(#[from] crate::client::Error),
 
+    #[error("failed to launch BankID: {0}")]
+    OpenError(#[from] opener::OpenError),
+
     #[error("app error: {0}")]
     AppError(&'static str),
+
+    #[error("stopped reading the terminal")]
+    InputClosed,
+
+    #[error("the terminal UI is not shown with --non-interactive")]
+    NonInteractive,
+
+    #[error("a login is needed to load the view")]
+    LoginRequired,
 }
 
 pub trait TuiView {
@@ -43,60 +83,454 @@ pub trait TuiView {
         event: Event,
     ) -> Result<Command<Self::ReturnType>, Error>;
     fn render(&mut self, frame: &mut Frame, rect: Rect);
+
+    /// Keys to remind the user of in the status bar
+    fn key_hints(&self) -> Vec<KeyHint> {
+        Vec::new()
+    }
+
+    /// Keys to list in the help, for views with more than fit in the
+    /// status bar
+    fn help_hints(&self) -> Vec<KeyHint> {
+        self.key_hints()
+    }
+
+    /// Something for the status bar that the view has to tell, e.g. that a
+    /// background refresh failed
+    fn take_message(&mut self) -> Option<Message> {
+        None
+    }
+
+    /// A short note for the header bar, next to the user name
+    fn badge(&self) -> Option<String> {
+        None
+    }
+
+    /// Whether the view is having text typed into it, and so gets every
+    /// key as `KeyEvent::Key` rather than as what the keymap makes of it
+    fn takes_text(&self) -> bool {
+        false
+    }
+
+    /// Images to draw over what the view rendered, where it left room for
+    /// them
+    fn pictures(&self) -> Vec<image::Picture<'_>> {
+        Vec::new()
+    }
 }
 
 pub enum Command<Ret> {
-    AwaitKey,
-    AwaitTimeout(Duration),
+    /// Wait for the next event
+    Await,
+    /// Wait for the next event, but send a tick at the given time if nothing
+    /// else happened before it. Views with a timer return the same time
+    /// until it has passed, so that events do not push it back.
+    AwaitUntil(Instant),
     Return(Ret),
 }
 
 pub enum Event {
     Init,
     Key(KeyEvent),
-    Timeout,
+    /// A key as read, which views get as a `Key`
+    Input(crossterm::event::KeyEvent),
+    /// The time asked for with `Command::AwaitUntil` has come
+    Tick,
+    /// The terminal changed size, and the view is drawn again for it
+    Resize,
+    Data(Data),
+}
+
+/// What a worker thread loaded for the view, see `show_loading`
+pub enum Data {
+    /// The listing, and whether it is all of the inbox or just a first page
+    Inbox(Result<(InboxListing, bool), client::Error>),
+    Details(Result<ItemDetails, client::Error>),
+    /// A new listing for a view that already has one
+    Sync(Result<InboxListing, client::Error>),
+}
+
+impl Data {
+    /// Whether the worker needed a login, which it leaves to the caller
+    fn login_required(&self) -> bool {
+        let error = match self {
+            Data::Inbox(result) => result.as_ref().err(),
+            Data::Details(result) => result.as_ref().err(),
+            Data::Sync(result) => result.as_ref().err(),
+        };
+        matches!(error, Some(client::Error::LoginRequired))
+    }
 }
 
+const SPINNER: [&str; 10] = ["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
+const SPINNER_TICK: Duration = Duration::from_millis(100);
+// How often the input thread checks whether the TUI has closed
+const INPUT_POLL: Duration = Duration::from_millis(50);
+// How often the open console is drawn again for what was logged meanwhile
+const CONSOLE_TICK: Duration = Duration::from_millis(500);
+
+/// Whether the log console is open. It stays open from view to view.
+static CONSOLE_OPEN: AtomicBool = AtomicBool::new(false);
+
+/// The input of the TUI while a terminal is loaded
+static INPUT: Mutex<Option<Input>> = Mutex::new(None);
+/// Numbers the runs of views, so that a view is not handed what was loaded
+/// for an earlier one
+static RUNS: AtomicU64 = AtomicU64::new(0);
+
+/// Keys and resizes, read by one thread into one channel for as long as the
+/// TUI is loaded, so that none read as one view returns are lost to the next
+struct Input {
+    sender: Sender<Received>,
+    events: Receiver<Received>,
+    stop: Arc<AtomicBool>,
+}
+
+impl Input {
+    fn start() -> Input {
+        let (sender, events) = mpsc::channel();
+        let input = Input { sender, events, stop: Arc::default() };
+        let (sender, stop) = (input.sender.clone(), input.stop.clone());
+        thread::spawn(move || forward_input(sender, &stop));
+        input
+    }
+}
+
+/// Stops reading the terminal, as it is given back when the TUI closes
+fn stop_input() {
+    // Also when the TUI closes on a panic, which poisons the lock
+    let input = INPUT.lock().unwrap_or_else(PoisonError::into_inner).take();
+    if let Some(input) = input {
+        input.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+enum Received {
+    Input(Result<Event, Error>),
+    /// What the worker of the numbered run loaded
+    Loaded(u64, Result<Event, Error>),
+}
+
+/// Contents of the header bar shown above every view
+#[derive(Clone, Debug)]
+pub struct Header {
+    pub user_name: Option<String>,
+    pub profile: Option<String>,
+    /// The company whose inbox is shown, if not the user's own
+    pub company: Option<String>,
+    /// Whether what is shown was kept locally rather than fetched
+    pub offline: bool,
+    pub accent: Color,
+}
+
+impl Default for Header {
+    fn default() -> Header {
+        Header {
+            user_name: None,
+            profile: None,
+            company: None,
+            offline: false,
+            accent: Color::Green,
+        }
+    }
+}
+
+/// A note in the status bar about how something went, shown instead of the
+/// key hints until the next key press
+#[derive(Clone, Debug)]
+pub struct Message {
+    pub text: String,
+    pub is_error: bool,
+}
+
+impl Message {
+    pub fn info(text: impl Into<String>) -> Message {
+        Message { text: text.into(), is_error: false }
+    }
+
+    pub fn error(text: impl Into<String>) -> Message {
+        Message { text: text.into(), is_error: true }
+    }
+}
+
+/// Runs a view until it returns. `message` is shown in the status bar when
+/// the view opens, e.g. the outcome of what the previous view asked for.
 pub fn show<Ret>(
     view: &mut impl TuiView<ReturnType = Ret>,
     terminal: &mut LoadedTerminal,
-    user_info: Option<UserInfo>,
+    header: &Header,
+    message: Option<Message>,
+) -> Result<Ret, Error> {
+    run(view, terminal, header, message, None)
+}
+
+/// Like `show`, but runs `load` on a worker thread and hands the view what
+/// it returns as `Event::Data`, so that the view is drawn and answers keys
+/// while it waits. Returning before the data arrives cancels the worker.
+/// The worker does not log in, but fails with `Error::LoginRequired` for
+/// the caller to log in and show the view again.
+pub fn show_loading<Ret>(
+    view: &mut impl TuiView<ReturnType = Ret>,
+    terminal: &mut LoadedTerminal,
+    header: &Header,
+    message: Option<Message>,
+    load: impl FnOnce() -> Data + Send,
 ) -> Result<Ret, Error> {
+    run(view, terminal, header, message, Some(Box::new(load)))
+}
+
+type Load<'a> = Box<dyn FnOnce() -> Data + Send + 'a>;
+
+/// Everything the view gets goes through one channel: keys and resizes from
+/// the input thread, data from the worker, and ticks when nothing arrives in
+/// time.
+fn run<Ret>(
+    view: &mut impl TuiView<ReturnType = Ret>,
+    terminal: &mut LoadedTerminal,
+    header: &Header,
+    message: Option<Message>,
+    load: Option<Load>,
+) -> Result<Ret, Error> {
+    terminal.reclaim()?;
+    let mut input = INPUT.lock().expect("input lock");
+    // The thread stops when reading the terminal fails
+    if input.as_ref().is_some_and(|input| input.stop.load(Ordering::Relaxed)) {
+        *input = None;
+    }
+    let input = input.get_or_insert_with(Input::start);
+    let run = RUNS.fetch_add(1, Ordering::Relaxed);
+    let cancel = Cancel::default();
+    thread::scope(|scope| {
+        let loading = load.is_some();
+        if let Some(load) = load {
+            let sender = input.sender.clone();
+            let cancel = cancel.clone();
+            scope.spawn(move || {
+                let data = cancel.run(load);
+                let event = match data.login_required() {
+                    true => Err(Error::LoginRequired),
+                    false => Ok(Event::Data(data)),
+                };
+                // The view may have returned already, and the next one
+                // leaves it be
+                _ = sender.send(Received::Loaded(run, event));
+            });
+        }
+
+        let events = &input.events;
+        let result =
+            event_loop(view, terminal, header, message, events, run, loading);
+        cancel.cancel();
+        result
+    })
+}
+
+fn event_loop<Ret>(
+    view: &mut impl TuiView<ReturnType = Ret>,
+    terminal: &mut LoadedTerminal,
+    header: &Header,
+    message: Option<Message>,
+    events: &Receiver<Received>,
+    run: u64,
+    loading: bool,
+) -> Result<Ret, Error> {
+    let mut message = message;
+    let mut loading = loading;
+    let mut help = false;
+    // An error the view ran into, shown over it, and what to hand it again
+    // on a retry
+    let mut failure: Option<(FailureView, Error, Option<Event>)> = None;
     let mut command = view.update(Event::Init)?;
+    let mut pictures = image::Pictures::default();
 
     loop {
+        let deadline = match command {
+            Command::Await => None,
+            Command::AwaitUntil(deadline) => Some(deadline),
+            Command::Return(ret) => {
+                pictures.clear()?;
+                return Ok(ret);
+            }
+        };
+        // A view that failed on a tick would fail again right away
+        let deadline = deadline.filter(|_| failure.is_none());
+
+        message = view.take_message().or(message);
+        let console = CONSOLE_OPEN.load(Ordering::Relaxed);
         let draw = |frame: &mut Frame| {
-            let subview_rect = render_main(frame, user_info.as_ref());
+            let badge = view.badge();
+            let (subview_rect, status_rect) =
+                render_main(frame, header, badge.as_deref());
+            let subview_rect = match console {
+                true => {
+                    let (subview_rect, console_rect) =
+                        console::split(subview_rect);
+                    console::render(frame, console_rect);
+                    subview_rect
+                }
+                false => subview_rect,
+            };
             view.render(frame, subview_rect);
+            if let Some((failure_view, _, _)) = &mut failure {
+                failure_view.render(frame, subview_rect);
+            }
+            let hints = match &failure {
+                Some((failure_view, _, _)) => failure_view.key_hints(),
+                None => view.key_hints(),
+            };
+            if help {
+                let hints = match &failure {
+                    Some(_) => hints.clone(),
+                    None => view.help_hints(),
+                };
+                popup::render_help(frame, subview_rect, &hints);
+            }
+            let status = status_widget(message.as_ref(), &hints);
+            frame.render_widget(status, status_rect);
+        };
+        let frame = terminal.draw(draw)?;
+        // Images would cover the popups
+        let shown = match (&failure, help) {
+            (None, false) => view.pictures(),
+            _ => Vec::new(),
         };
-        terminal.draw(draw)?;
+        pictures.update(frame.area, frame.buffer, &shown)?;
 
-        match command {
-            Command::AwaitKey => {
-                let key = read_key()?;
-                command = view.update(Event::Key(key))?;
+        // Wake up to turn the spinner while data is on its way, and to show
+        // what was logged
+        let now = Instant::now();
+        let wait = [
+            deadline.map(|deadline| deadline.saturating_duration_since(now)),
+            loading.then_some(SPINNER_TICK),
+            console.then_some(CONSOLE_TICK),
+        ];
+        let received = match wait.into_iter().flatten().min() {
+            None => Some(events.recv().map_err(|_| Error::InputClosed)?),
+            Some(wait) => match events.recv_timeout(wait) {
+                Ok(received) => Some(received),
+                Err(RecvTimeoutError::Timeout) => None,
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err(Error::InputClosed)
+                }
+            },
+        };
+        let event = match received {
+            Some(Received::Input(event)) => Some(event?),
+            Some(Received::Loaded(loaded, event)) if loaded == run => {
+                Some(event?)
             }
+            // Loaded for a view that has returned
+            Some(Received::Loaded(_, _)) | None => None,
+        };
 
-            Command::AwaitTimeout(duration) => {
-                if poll(duration)? {
-                    let key = read_key()?;
-                    command = view.update(Event::Key(key))?;
-                } else {
-                    command = view.update(Event::Timeout)?;
+        let event = event.map(|event| match event {
+            Event::Input(key) if view.takes_text() && !help => {
+                Event::Key(KeyEvent::Key(key.code))
+            }
+            Event::Input(key) => Event::Key(keymap::map_key(key)),
+            event => event,
+        });
+        let event = match event {
+            // Keys close the help rather than reach the view
+            Some(Event::Key(_)) if help => {
+                message = None;
+                help = false;
+                continue;
+            }
+            Some(Event::Key(KeyEvent::Help)) => {
+                message = None;
+                help = true;
+                continue;
+            }
+            // The view is drawn smaller, as if the terminal had shrunk
+            Some(Event::Key(KeyEvent::Console)) => {
+                CONSOLE_OPEN.store(!console, Ordering::Relaxed);
+                Event::Resize
+            }
+            Some(Event::Key(key)) if failure.is_some() => {
+                let (mut failure_view, error, retry) =
+                    failure.take().expect("checked above");
+                match failure_view.update(Event::Key(key))? {
+                    Command::Return(FailureChoice::Retry) => {
+                        retry.expect("only offered with an event to retry")
+                    }
+                    Command::Return(FailureChoice::Back) => continue,
+                    Command::Return(FailureChoice::Quit) => return Err(error),
+                    Command::Await | Command::AwaitUntil(_) => {
+                        failure = Some((failure_view, error, retry));
+                        continue;
+                    }
                 }
             }
+            Some(Event::Key(key)) => {
+                message = None;
+                Event::Key(key)
+            }
+            Some(Event::Data(data)) => {
+                loading = false;
+                Event::Data(data)
+            }
+            Some(event) => event,
+            None if deadline.is_some_and(|d| Instant::now() >= d) => {
+                Event::Tick
+            }
+            // Only the spinner or the console needs drawing
+            None => continue,
+        };
+        let retry = match &event {
+            Event::Key(key) => Some(Event::Key(*key)),
+            Event::Tick => Some(Event::Tick),
+            _ => None,
+        };
+        // The view is left as it was, and keeps what it waited for
+        match view.update(event) {
+            Ok(next) => command = next,
+            Err(error) => {
+                warn!("view failed: {error}");
+                let failure_view = FailureView::new(&error, retry.is_some());
+                failure = Some((failure_view, error, retry));
+            }
+        }
+    }
+}
 
-            Command::Return(ret) => {
-                return Ok(ret);
+/// Sends keys and resizes until the TUI closes
+fn forward_input(sender: Sender<Received>, stop: &AtomicBool) {
+    let forward = || -> Result<(), Error> {
+        while !stop.load(Ordering::Relaxed) {
+            if !poll(INPUT_POLL)? {
+                continue;
+            }
+            let event = match read()? {
+                CrosstermEvent::Key(key) => Event::Input(key),
+                CrosstermEvent::Resize(_, _) => Event::Resize,
+                _ => continue,
+            };
+            if sender.send(Received::Input(Ok(event))).is_err() {
+                break;
             }
         }
+        Ok(())
+    };
+    if let Err(error) = forward() {
+        stop.store(true, Ordering::Relaxed);
+        _ = sender.send(Received::Input(Err(error)));
     }
 }
 
-fn render_main(frame: &mut Frame, user_info: Option<&UserInfo>) -> Rect {
+/// Draws the header and returns where the view and the status bar go
+fn render_main(
+    frame: &mut Frame,
+    header_info: &Header,
+    badge: Option<&str>,
+) -> (Rect, Rect) {
     let layout = Layout::default()
         .direction(ratatui::layout::Direction::Vertical)
-        .constraints(vec![Constraint::Length(1), Constraint::Fill(1)])
+        .constraints(vec![
+            Constraint::Length(1),
+            Constraint::Fill(1),
+            Constraint::Length(1),
+        ])
         .split(frame.size());
 
     let header = Layout::default()
@@ -106,15 +540,80 @@ fn render_main(frame: &mut Frame, user_info: Option<&UserInfo>) -> Rect {
 
     let title = Paragraph::new("Kivinge")
         .bold()
-        .fg(ratatui::style::Color::Black)
-        .bg(ratatui::style::Color::Green);
+        .fg(Color::Black)
+        .bg(header_info.accent);
     frame.render_widget(title, header[0]);
 
-    let user_name = user_info.map(|i| i.name.clone()).unwrap_or_default();
-    let session_header = Paragraph::new(user_name)
-        .fg(ratatui::style::Color::Black)
-        .bg(ratatui::style::Color::Green)
+    let user_name = match (&header_info.user_name, &header_info.company) {
+        (Some(user_name), Some(company)) => {
+            format!("{user_name} for {company}")
+        }
+        (user_name, company) => {
+            user_name.clone().or(company.clone()).unwrap_or_default()
+        }
+    };
+    let session_text = match &header_info.profile {
+        Some(profile) => format!("{user_name} [{profile}]"),
+        None => user_name,
+    };
+    let badge = badge.map(|badge| Span::raw(format!("{badge}  ")).bold());
+    let offline = header_info.offline.then(|| {
+        Span::raw(format!("{}  ", tr!("OFFLINE"))).bold().fg(Color::Red)
+    });
+    let session_line = Line::from_iter(
+        (offline.into_iter().chain(badge)).chain([Span::raw(session_text)]),
+    );
+    let session_header = Paragraph::new(session_line)
+        .fg(Color::Black)
+        .bg(header_info.accent)
         .right_aligned();
     frame.render_widget(session_header, header[1]);
-    layout[1]
+    (layout[1], layout[2])
+}
+
+fn status_widget(
+    message: Option<&Message>,
+    hints: &[KeyHint],
+) -> Paragraph<'static> {
+    match message {
+        Some(Message { text, is_error: true }) => {
+            Paragraph::new(text.clone()).bold().fg(Color::White).bg(Color::Red)
+        }
+        Some(Message { text, is_error: false }) => {
+            Paragraph::new(text.clone()).bold().fg(Color::Green)
+        }
+        None => {
+            let hints = hints.iter().chain(match hints.is_empty() {
+                true => None,
+                false => Some(&keymap::HELP),
+            });
+            let spans = hints.flat_map(|hint| {
+                [
+                    Span::raw(hint.keys).bold(),
+                    Span::raw(format!(" {}  ", tr!(hint.action))),
+                ]
+            });
+            Paragraph::new(Line::from_iter(spans)).fg(Color::DarkGray)
+        }
+    }
+}
+
+/// A frame of a spinner that turns every tick, for views that wait for data
+fn spinner() -> &'static str {
+    let now = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH);
+    let ticks = now.unwrap_or_default().as_millis() / SPINNER_TICK.as_millis();
+    SPINNER[ticks as usize % SPINNER.len()]
+}
+
+/// A spinner and what is loading, in the middle of `rect`
+fn render_loading(frame: &mut Frame, rect: Rect, what: &str) {
+    let [_, line, _] = Layout::vertical([
+        Constraint::Fill(1),
+        Constraint::Length(1),
+        Constraint::Fill(1),
+    ])
+    .areas(rect);
+    let text =
+        format!("{} {}", spinner(), tr!("Loading {what}…", what = tr!(what)));
+    frame.render_widget(Paragraph::new(text).centered(), line);
 }
@@ -0,0 +1,99 @@
+use ratatui::{
+    layout::Rect,
+    style::{Color, Style, Stylize},
+    text::{Line, Span},
+    widgets::{Block, Clear, Paragraph},
+    Frame,
+};
+use unicode_width::UnicodeWidthStr;
+
+use super::keymap::{self, KeyHint};
+use crate::tr;
+
+/// A bordered box over the middle of a view, hiding what is under it
+pub struct Popup<'a> {
+    title: &'a str,
+    percent_x: u16,
+    percent_y: u16,
+}
+
+impl<'a> Popup<'a> {
+    pub fn new(title: &'a str) -> Self {
+        Popup { title, percent_x: 50, percent_y: 20 }
+    }
+
+    /// How much of the view the popup covers, in percent
+    pub fn size(self, percent_x: u16, percent_y: u16) -> Self {
+        Popup { percent_x, percent_y, ..self }
+    }
+
+    /// Draws the popup and returns the area inside its border
+    pub fn render(&self, frame: &mut Frame, rect: Rect) -> Rect {
+        let popup = centered(rect, self.percent_x, self.percent_y);
+        frame.render_widget(Clear, popup);
+        let block = Block::bordered()
+            .title(tr!(self.title))
+            .title_style(Style::new().bold())
+            .fg(Color::Green);
+        let inner = block.inner(popup);
+        frame.render_widget(block, popup);
+        inner
+    }
+}
+
+/// A popup area in the middle of `rect`
+fn centered(rect: Rect, percent_x: u16, percent_y: u16) -> Rect {
+    let width = rect.width * percent_x / 100;
+    let height = (rect.height * percent_y / 100).max(8).min(rect.height);
+    Rect {
+        x: rect.x + (rect.width - width) / 2,
+        y: rect.y + (rect.height - height) / 2,
+        width,
+        height,
+    }
+}
+
+/// A yes/no question on top of the current view, with the answer to enter
+/// in upper case
+pub fn render_confirm(
+    frame: &mut Frame,
+    rect: Rect,
+    question: &str,
+    default: bool,
+) {
+    let inner = Popup::new("Confirm").render(frame, rect);
+    let choices = match default {
+        true => "Y/n",
+        false => "y/N",
+    };
+    let text = format!("\n{question}\n\n({choices})");
+    frame.render_widget(Paragraph::new(text).centered(), inner);
+}
+
+/// The keys every view shares and then those of the view itself
+pub fn render_help(frame: &mut Frame, rect: Rect, hints: &[KeyHint]) {
+    let inner = Popup::new("Help").size(60, 80).render(frame, rect);
+    let shared = keymap::BINDINGS.iter();
+    // The short hints for shared keys are already among the bindings
+    let short = [keymap::MOVE, keymap::SELECT, keymap::BACK, keymap::QUIT];
+    let own = hints.iter().filter(|hint| !short.contains(hint));
+    let width = shared.clone().chain(own.clone()).map(|hint| hint.keys.width());
+    let width = width.max().unwrap_or_default();
+
+    let line = |hint: &KeyHint| {
+        Line::from(vec![
+            Span::raw(format!(" {:width$}  ", hint.keys)).bold(),
+            Span::raw(tr!(hint.action)),
+        ])
+    };
+    let lines: Vec<Line> = shared
+        .map(line)
+        .chain([Line::default()])
+        .chain(own.map(line))
+        .chain([
+            Line::default(),
+            Line::from(tr!(" Press any key to close")).italic(),
+        ])
+        .collect();
+    frame.render_widget(Paragraph::new(lines), inner);
+}
@@ -1,13 +1,70 @@
 use super::Error;
-use qrcode2::{EcLevel, QrCode, Version};
+use qrcode2::{render::unicode::Dense1x2, EcLevel, QrCode, Version};
 use qrcode_unicode_ext::BraillePixel;
+use serde::{Deserialize, Serialize};
 
-pub fn encode(code_data: &String) -> Result<String, Error> {
-    let code =
-        QrCode::with_version(code_data, Version::Normal(11), EcLevel::H)?;
-    Ok(code
-        .render::<BraillePixel>()
-        .dark_color(BraillePixel::Light)
-        .light_color(BraillePixel::Dark)
-        .build())
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QrRenderer {
+    #[default]
+    Auto,
+    Braille,
+    Blocks,
+    Ascii,
+}
+
+impl QrRenderer {
+    /// Resolves `Auto` to a renderer the terminal is likely to display.
+    /// Braille needs a UTF-8 locale and a font with Braille glyphs, which
+    /// the Linux console lacks, while half blocks are widely available.
+    pub fn detect(self) -> QrRenderer {
+        if self != QrRenderer::Auto {
+            return self;
+        }
+        let locale = ["LC_ALL", "LC_CTYPE", "LANG"]
+            .iter()
+            .find_map(|var| std::env::var(var).ok().filter(|v| !v.is_empty()))
+            .unwrap_or_default()
+            .to_lowercase();
+        let term = std::env::var("TERM").unwrap_or_default();
+        if !locale.contains("utf-8") && !locale.contains("utf8") {
+            QrRenderer::Ascii
+        } else if term == "linux" {
+            QrRenderer::Blocks
+        } else {
+            QrRenderer::Braille
+        }
+    }
+}
+
+pub fn encode(code_data: &str, renderer: QrRenderer) -> Result<String, Error> {
+    // The colors are inverted since the code is drawn light-on-dark
+    match renderer.detect() {
+        QrRenderer::Auto | QrRenderer::Braille => {
+            let code = QrCode::with_version(
+                code_data,
+                Version::Normal(11),
+                EcLevel::H,
+            )?;
+            Ok(code
+                .render::<BraillePixel>()
+                .dark_color(BraillePixel::Light)
+                .light_color(BraillePixel::Dark)
+                .build())
+        }
+        QrRenderer::Blocks => {
+            let code =
+                QrCode::with_error_correction_level(code_data, EcLevel::L)?;
+            Ok(code
+                .render::<Dense1x2>()
+                .dark_color(Dense1x2::Light)
+                .light_color(Dense1x2::Dark)
+                .build())
+        }
+        QrRenderer::Ascii => {
+            let code =
+                QrCode::with_error_correction_level(code_data, EcLevel::L)?;
+            Ok(code.render::<&str>().dark_color("  ").light_color("##").build())
+        }
+    }
 }
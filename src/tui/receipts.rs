@@ -0,0 +1,206 @@
+use chrono::{Local, TimeZone};
+use crossterm::event::KeyCode;
+use ratatui::{
+    layout::{Constraint, Layout, Rect},
+    style::{Color, Modifier, Style, Stylize},
+    widgets::{Block, Cell, Paragraph, Row, Table, TableState},
+    Frame,
+};
+
+use super::{
+    keymap::{self, KeyEvent, KeyHint},
+    tabs::{self, Tab},
+    Command, Error, Event, Message, TuiView,
+};
+use crate::{
+    cache::{Cache, RECEIPT_POLICY},
+    cli::receipts::format_details,
+    client::ContentClient,
+    model::receipt::{ReceiptDetails, ReceiptEntry, ReceiptKey},
+    tr,
+};
+
+pub struct ReceiptsView<'a, C: ContentClient> {
+    client: &'a mut C,
+    receipts: Vec<ReceiptEntry>,
+    table_state: TableState,
+    details: Option<ReceiptDetails>,
+    // Receipts never change, so opening one again should not refetch it
+    details_cache: Cache<ReceiptKey, ReceiptDetails>,
+    message: Option<Message>,
+}
+
+pub enum ReceiptsViewResult {
+    ShowTab(Tab),
+    Quit,
+}
+
+impl<'a, C: ContentClient> ReceiptsView<'a, C> {
+    pub fn make(client: &'a mut C) -> Result<ReceiptsView<'a, C>, Error> {
+        let receipts: Vec<ReceiptEntry> =
+            client.get_receipts()?.into_iter().rev().collect();
+        let selected = if receipts.is_empty() { None } else { Some(0) };
+        Ok(ReceiptsView {
+            client,
+            receipts,
+            table_state: TableState::new().with_selected(selected),
+            details: None,
+            details_cache: Cache::new("receipt", RECEIPT_POLICY),
+            message: None,
+        })
+    }
+
+    fn selected_entry(&self) -> Option<&ReceiptEntry> {
+        self.receipts.get(self.table_state.selected()?)
+    }
+}
+
+impl<'a, C: ContentClient> TuiView for ReceiptsView<'a, C> {
+    type ReturnType = ReceiptsViewResult;
+
+    fn update(
+        &mut self,
+        event: Event,
+    ) -> Result<Command<Self::ReturnType>, Error> {
+        match event {
+            Event::Key(KeyEvent::Quit) if self.details.is_some() => {
+                self.details = None;
+                Ok(Command::Await)
+            }
+
+            Event::Key(KeyEvent::Quit) => {
+                Ok(Command::Return(ReceiptsViewResult::Quit))
+            }
+
+            Event::Key(KeyEvent::Back) => {
+                self.details = None;
+                Ok(Command::Await)
+            }
+
+            Event::Key(KeyEvent::Up) => {
+                let select = match self.table_state.selected().unwrap_or(0) {
+                    0 => 0,
+                    n => n - 1,
+                };
+                self.table_state.select(Some(select));
+                self.details = None;
+                Ok(Command::Await)
+            }
+
+            Event::Key(KeyEvent::Down) => {
+                let select = match self.table_state.selected().unwrap_or(0) {
+                    n if n + 1 >= self.receipts.len() => n,
+                    n => n + 1,
+                };
+                self.table_state.select(Some(select));
+                self.details = None;
+                Ok(Command::Await)
+            }
+
+            Event::Key(KeyEvent::Select) => {
+                if let Some(key) =
+                    self.selected_entry().map(|e| e.receipt.key.clone())
+                {
+                    let client = &mut self.client;
+                    let details = self
+                        .details_cache
+                        .try_get_or_insert_with(key.clone(), || {
+                            client.get_receipt_details(&key)
+                        });
+                    match details {
+                        Ok(details) => self.details = Some(details.clone()),
+                        Err(error) => {
+                            self.message = Some(Message::error(tr!(
+                                "Failed to load the receipt: {error}",
+                                error = error
+                            )))
+                        }
+                    }
+                }
+                Ok(Command::Await)
+            }
+
+            Event::Key(KeyEvent::Key(KeyCode::Char(c @ '1'..='9'))) => {
+                match Tab::from_key(c) {
+                    Some(tab @ Tab::Inbox(_)) => {
+                        Ok(Command::Return(ReceiptsViewResult::ShowTab(tab)))
+                    }
+                    _ => Ok(Command::Await),
+                }
+            }
+
+            _ => Ok(Command::Await),
+        }
+    }
+
+    fn key_hints(&self) -> Vec<KeyHint> {
+        let close = match self.details {
+            Some(_) => keymap::BACK,
+            None => keymap::QUIT,
+        };
+        vec![keymap::MOVE, keymap::SELECT, KeyHint::new("1-6", "tabs"), close]
+    }
+
+    fn take_message(&mut self) -> Option<Message> {
+        self.message.take()
+    }
+
+    fn render(&mut self, frame: &mut Frame, rect: Rect) {
+        let layout =
+            Layout::vertical([Constraint::Length(1), Constraint::Fill(1)])
+                .split(rect);
+        frame.render_widget(tabs::widget(Tab::Receipts), layout[0]);
+
+        let Some(details) = &self.details else {
+            let widget = receipts_widget(&self.receipts);
+            frame.render_stateful_widget(
+                widget,
+                layout[1],
+                &mut self.table_state,
+            );
+            return;
+        };
+
+        let columns =
+            Layout::horizontal([Constraint::Fill(1), Constraint::Fill(1)])
+                .split(layout[1]);
+        let widget = receipts_widget(&self.receipts);
+        frame.render_stateful_widget(widget, columns[0], &mut self.table_state);
+        let details_widget = Paragraph::new(format_details(details))
+            .block(Block::bordered().fg(Color::Green));
+        frame.render_widget(details_widget, columns[1]);
+    }
+}
+
+fn receipts_widget(entries: &[ReceiptEntry]) -> Table<'static> {
+    let rows = entries.iter().map(receipt_row);
+    let max_id_len = entries
+        .iter()
+        .map(|e| e.id.to_string().len())
+        .max()
+        .unwrap_or_default();
+    let widths = [
+        Constraint::Length(max_id_len as u16),
+        Constraint::Fill(1),
+        Constraint::Max(14),
+        Constraint::Length(16),
+    ];
+
+    Table::new(rows, widths)
+        .column_spacing(1)
+        .highlight_style(Style::new().add_modifier(Modifier::REVERSED))
+        .block(Block::bordered().fg(Color::Green))
+}
+
+fn receipt_row(entry: &ReceiptEntry) -> Row<'static> {
+    let receipt = &entry.receipt;
+    let local_datetime =
+        Local.from_utc_datetime(&receipt.purchase_date.naive_utc());
+    let cells = [
+        Cell::new(entry.id.to_string()),
+        Cell::new(receipt.store_name.clone()),
+        Cell::new(format!("{} {}", receipt.total_amount, receipt.currency)),
+        Cell::new(local_datetime.format("%Y-%m-%d %H:%M").to_string()),
+    ];
+    Row::new(cells)
+}
@@ -0,0 +1,204 @@
+use crossterm::event::KeyCode;
+use ratatui::{
+    layout::{Constraint, Layout, Rect},
+    style::{Color, Modifier, Style, Stylize},
+    text::Span,
+    widgets::{Block, Paragraph, Row, Table, TableState},
+    Frame,
+};
+use std::path::Path;
+
+use super::{
+    keymap::{self, KeyEvent, KeyHint},
+    Command, Error, Event, TuiView,
+};
+use crate::{
+    config::Config,
+    settings::{Kind, SETTINGS},
+    tr,
+};
+
+/// The settings of `settings::SETTINGS` with their values, to change them
+/// without editing the config file
+pub struct SettingsView {
+    path: String,
+    values: Vec<String>,
+    table_state: TableState,
+    /// What is typed for the selected setting, while it is
+    input: Option<String>,
+}
+
+pub enum SettingsViewResult {
+    /// Save what was given for the setting at the index, empty to leave it
+    /// to the default
+    Save(usize, String),
+    Close,
+}
+
+impl SettingsView {
+    pub fn new(config: &Config, path: &Path) -> SettingsView {
+        let mut view = SettingsView {
+            path: path.display().to_string(),
+            values: Vec::new(),
+            table_state: TableState::new().with_selected(Some(0)),
+            input: None,
+        };
+        view.set_config(config);
+        view
+    }
+
+    /// Shows the values of the config as it is now, e.g. after a save
+    pub fn set_config(&mut self, config: &Config) {
+        self.values =
+            SETTINGS.iter().map(|setting| setting.value(config)).collect();
+    }
+
+    fn selected(&self) -> usize {
+        self.table_state.selected().unwrap_or(0)
+    }
+
+    fn update_input(&mut self, key: KeyEvent) -> Command<SettingsViewResult> {
+        let Some(input) = &mut self.input else {
+            return Command::Await;
+        };
+        match key {
+            KeyEvent::Key(KeyCode::Char(c)) => input.push(c),
+            KeyEvent::Key(KeyCode::Backspace) => _ = input.pop(),
+            KeyEvent::Key(KeyCode::Esc) => self.input = None,
+            KeyEvent::Key(KeyCode::Enter) => {
+                let input = self.input.take().unwrap_or_default();
+                let index = self.selected();
+                return Command::Return(SettingsViewResult::Save(index, input));
+            }
+            _ => {}
+        }
+        Command::Await
+    }
+}
+
+impl TuiView for SettingsView {
+    type ReturnType = SettingsViewResult;
+
+    fn update(
+        &mut self,
+        event: Event,
+    ) -> Result<Command<Self::ReturnType>, Error> {
+        match event {
+            Event::Key(key) if self.input.is_some() => {
+                Ok(self.update_input(key))
+            }
+
+            Event::Key(KeyEvent::Quit) | Event::Key(KeyEvent::Back) => {
+                Ok(Command::Return(SettingsViewResult::Close))
+            }
+
+            Event::Key(KeyEvent::Up) => {
+                self.table_state
+                    .select(Some(self.selected().saturating_sub(1)));
+                Ok(Command::Await)
+            }
+
+            Event::Key(KeyEvent::Down) => {
+                let last = SETTINGS.len() - 1;
+                self.table_state.select(Some((self.selected() + 1).min(last)));
+                Ok(Command::Await)
+            }
+
+            // Flags and choices step to the next value, the rest is typed
+            Event::Key(KeyEvent::Select) => {
+                let index = self.selected();
+                let value = &self.values[index];
+                match SETTINGS[index].next(value) {
+                    Some(next) => {
+                        let save = SettingsViewResult::Save(index, next);
+                        Ok(Command::Return(save))
+                    }
+                    None => {
+                        self.input = Some(value.clone());
+                        Ok(Command::Await)
+                    }
+                }
+            }
+
+            Event::Key(KeyEvent::Key(KeyCode::Char('x'))) => {
+                let save = SettingsViewResult::Save(self.selected(), "".into());
+                Ok(Command::Return(save))
+            }
+
+            _ => Ok(Command::Await),
+        }
+    }
+
+    fn key_hints(&self) -> Vec<KeyHint> {
+        match self.input {
+            Some(_) => vec![
+                KeyHint::new("enter", "save"),
+                KeyHint::new("esc", "cancel"),
+            ],
+            None => vec![
+                keymap::MOVE,
+                KeyHint::new("enter", "change"),
+                KeyHint::new("x", "reset to default"),
+                keymap::BACK,
+            ],
+        }
+    }
+
+    fn takes_text(&self) -> bool {
+        self.input.is_some()
+    }
+
+    fn render(&mut self, frame: &mut Frame, rect: Rect) {
+        let input_height = if self.input.is_some() { 1 } else { 0 };
+        let [table_area, input_area] = Layout::vertical([
+            Constraint::Fill(1),
+            Constraint::Length(input_height),
+        ])
+        .areas(rect);
+
+        let rows = SETTINGS.iter().zip(&self.values).map(|(setting, value)| {
+            let value = match (value.is_empty(), &setting.kind) {
+                (true, _) => Span::raw(tr!("not set")).fg(Color::DarkGray),
+                (false, Kind::Flag) => match value.as_str() {
+                    "true" => Span::raw(tr!("on")),
+                    _ => Span::raw(tr!("off")),
+                },
+                (false, _) => Span::raw(value.clone()),
+            };
+            let restart = match setting.restart {
+                true => tr!("on restart"),
+                false => "",
+            };
+            Row::new([
+                Span::raw(tr!(setting.label)),
+                value,
+                Span::raw(restart).fg(Color::DarkGray),
+            ])
+        });
+        // Translated labels are of any length
+        let label_width = (SETTINGS.iter())
+            .map(|setting| tr!(setting.label).chars().count())
+            .max()
+            .unwrap_or_default();
+        let widths = [
+            Constraint::Length(label_width as u16 + 2),
+            Constraint::Fill(1),
+            Constraint::Length(tr!("on restart").chars().count() as u16),
+        ];
+        let table = Table::new(rows, widths)
+            .column_spacing(1)
+            .highlight_style(Style::new().add_modifier(Modifier::REVERSED))
+            .block(
+                Block::bordered()
+                    .title(tr!("Settings in {path}", path = self.path))
+                    .fg(Color::Green),
+            );
+        frame.render_stateful_widget(table, table_area, &mut self.table_state);
+
+        if let Some(input) = &self.input {
+            let label = tr!(SETTINGS[self.selected()].label);
+            let widget = Paragraph::new(format!("{label}: {input}█"));
+            frame.render_widget(widget, input_area);
+        }
+    }
+}
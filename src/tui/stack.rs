@@ -0,0 +1,90 @@
+//! Navigation between the screens of the TUI. Screens are pushed on a stack
+//! as they are opened and popped as they are closed, handing what they
+//! returned to the screen under them, so that a screen opening another does
+//! not have to run it itself.
+
+use super::{terminal::LoadedTerminal, Message};
+
+/// One screen of the TUI, which shows its views and acts on what they
+/// return. `Ctx` is what screens share, e.g. the client, and `Out` is what
+/// they hand the screen under them when they are closed.
+pub trait Screen<Ctx, Out, E> {
+    /// Shows the screen until it has somewhere else to go. `message` is for
+    /// its status bar, e.g. how what it asked for went.
+    fn show(
+        &mut self,
+        ctx: &mut Ctx,
+        terminal: &mut LoadedTerminal,
+        message: Option<Message>,
+    ) -> Result<Nav<Ctx, Out, E>, E>;
+
+    /// The screen above this one was closed with `out`
+    fn resume(
+        &mut self,
+        _ctx: &mut Ctx,
+        _out: Out,
+    ) -> Result<Nav<Ctx, Out, E>, E> {
+        Ok(Nav::Stay(None))
+    }
+}
+
+pub type BoxedScreen<Ctx, Out, E> = Box<dyn Screen<Ctx, Out, E>>;
+
+/// Where to go after a screen has been shown or resumed
+pub enum Nav<Ctx, Out, E> {
+    /// Show the screen again, with a message for its status bar
+    Stay(Option<Message>),
+    /// Open a screen over this one
+    Push(BoxedScreen<Ctx, Out, E>),
+    /// Open a screen in place of this one, which is closed without handing
+    /// anything on
+    Replace(BoxedScreen<Ctx, Out, E>),
+    /// Close the screen, handing `Out` to the one under it
+    Pop(Out),
+}
+
+pub struct ViewStack<Ctx, Out, E> {
+    screens: Vec<BoxedScreen<Ctx, Out, E>>,
+}
+
+impl<Ctx, Out, E> ViewStack<Ctx, Out, E> {
+    pub fn new(root: BoxedScreen<Ctx, Out, E>) -> Self {
+        ViewStack { screens: vec![root] }
+    }
+
+    /// Shows the screen on top until the last one is closed, and returns
+    /// what that one was closed with
+    pub fn run(
+        mut self,
+        ctx: &mut Ctx,
+        terminal: &mut LoadedTerminal,
+    ) -> Result<Out, E> {
+        let mut message = None;
+        loop {
+            let top = self.screens.last_mut().expect("the root is kept");
+            let mut nav = top.show(ctx, terminal, message.take())?;
+            // What a popped screen returned may send the one under it on
+            loop {
+                match nav {
+                    Nav::Stay(next) => message = next,
+                    Nav::Push(screen) => self.screens.push(screen),
+                    Nav::Replace(screen) => {
+                        self.screens.pop();
+                        self.screens.push(screen);
+                    }
+                    Nav::Pop(out) => {
+                        self.screens.pop();
+                        match self.screens.last_mut() {
+                            Some(below) => {
+                                nav = below.resume(ctx, out)?;
+                                continue;
+                            }
+                            None => return Ok(out),
+                        }
+                    }
+                }
+                break;
+            }
+        }
+    }
+}
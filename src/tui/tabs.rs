@@ -0,0 +1,45 @@
+use std::fmt::Display;
+
+use ratatui::{
+    style::{Color, Modifier, Style, Stylize},
+    widgets::Tabs,
+};
+
+use crate::{model::content::InboxFilter, tr};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Tab {
+    Inbox(InboxFilter),
+    Receipts,
+}
+
+impl Tab {
+    pub fn all() -> impl Iterator<Item = Tab> {
+        InboxFilter::ALL.into_iter().map(Tab::Inbox).chain([Tab::Receipts])
+    }
+
+    /// Tabs are selected with the number keys, starting from '1'
+    pub fn from_key(key: char) -> Option<Tab> {
+        let index = key.to_digit(10)?.checked_sub(1)?;
+        Tab::all().nth(index as usize)
+    }
+}
+
+impl Display for Tab {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Tab::Inbox(filter) => filter.fmt(formatter),
+            Tab::Receipts => "Receipts".fmt(formatter),
+        }
+    }
+}
+
+pub fn widget(selected: Tab) -> Tabs<'static> {
+    let titles = (Tab::all().zip(1..))
+        .map(|(tab, num)| format!("{num}:{}", tr!(&tab.to_string())));
+    let selected = Tab::all().position(|tab| tab == selected);
+    Tabs::new(titles)
+        .select(selected.unwrap_or_default())
+        .fg(Color::Green)
+        .highlight_style(Style::new().add_modifier(Modifier::REVERSED))
+}
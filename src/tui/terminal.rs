@@ -5,13 +5,32 @@ use std::{
     io::stdout,
     ops::{Deref, DerefMut},
     panic,
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
 };
 
 use super::Error;
+use crate::cli::prompt;
+
+/// Terminals loaded and not yet dropped. A login loads one of its own while
+/// the TUI has one, and only the last one dropped restores the terminal.
+static LOADED: AtomicUsize = AtomicUsize::new(0);
+/// Whether a terminal loaded on top of another drew over the screen
+static OVERDRAWN: AtomicBool = AtomicBool::new(false);
 
 #[derive(Debug)]
 pub struct LoadedTerminal(Terminal<CrosstermBackend<io::Stdout>>);
 
+impl LoadedTerminal {
+    /// Draws everything anew at the next draw, if another terminal drew
+    /// over this one since
+    pub fn reclaim(&mut self) -> io::Result<()> {
+        match OVERDRAWN.swap(false, Ordering::Relaxed) {
+            true => self.0.clear(),
+            false => Ok(()),
+        }
+    }
+}
+
 impl Deref for LoadedTerminal {
     type Target = Terminal<CrosstermBackend<io::Stdout>>;
     fn deref(&self) -> &Self::Target {
@@ -27,6 +46,11 @@ impl DerefMut for LoadedTerminal {
 
 impl Drop for LoadedTerminal {
     fn drop(&mut self) {
+        if LOADED.fetch_sub(1, Ordering::Relaxed) > 1 {
+            OVERDRAWN.store(true, Ordering::Relaxed);
+            return;
+        }
+        super::stop_input();
         terminal::disable_raw_mode()
             .expect("IO Error disabling terminal raw mode");
         io::stdout()
@@ -35,10 +59,19 @@ impl Drop for LoadedTerminal {
     }
 }
 
+/// Whether the TUI is on the screen, so that nothing else is to be printed
+pub fn is_loaded() -> bool {
+    LOADED.load(Ordering::Relaxed) > 0
+}
+
 pub fn load() -> Result<LoadedTerminal, Error> {
+    if prompt::non_interactive() {
+        return Err(Error::NonInteractive);
+    }
     terminal::enable_raw_mode()?;
     io::stdout().execute(terminal::EnterAlternateScreen)?;
     let terminal = Terminal::new(CrosstermBackend::new(io::stdout()))?;
+    LOADED.fetch_add(1, Ordering::Relaxed);
     let original_hook = panic::take_hook();
     panic::set_hook(Box::new(move |panic_info| {
         let _ = terminal::disable_raw_mode();
@@ -0,0 +1,76 @@
+use crossterm::event::KeyCode;
+use ratatui::{
+    layout::Rect,
+    widgets::{Paragraph, Wrap},
+    Frame,
+};
+
+use super::{
+    keymap::{KeyEvent, KeyHint},
+    popup::Popup,
+    Command, Error, Event, TuiView,
+};
+use crate::tr;
+
+pub enum UnreachableChoice {
+    Retry,
+    WorkOffline,
+    Quit,
+}
+
+/// Asks what to do when Kivra cannot be reached, instead of giving up
+pub struct UnreachableView {
+    error: String,
+}
+
+impl UnreachableView {
+    pub fn new(error: impl ToString) -> UnreachableView {
+        UnreachableView { error: error.to_string() }
+    }
+}
+
+impl TuiView for UnreachableView {
+    type ReturnType = UnreachableChoice;
+
+    fn update(
+        &mut self,
+        event: Event,
+    ) -> Result<Command<Self::ReturnType>, Error> {
+        match event {
+            Event::Key(KeyEvent::Key(KeyCode::Char('r')))
+            | Event::Key(KeyEvent::Select) => {
+                Ok(Command::Return(UnreachableChoice::Retry))
+            }
+
+            Event::Key(KeyEvent::Key(KeyCode::Char('o'))) => {
+                Ok(Command::Return(UnreachableChoice::WorkOffline))
+            }
+
+            Event::Key(KeyEvent::Quit) => {
+                Ok(Command::Return(UnreachableChoice::Quit))
+            }
+
+            _ => Ok(Command::Await),
+        }
+    }
+
+    fn key_hints(&self) -> Vec<KeyHint> {
+        vec![
+            KeyHint::new("r", "retry"),
+            KeyHint::new("o", "work offline"),
+            KeyHint::new("q", "quit"),
+        ]
+    }
+
+    fn render(&mut self, frame: &mut Frame, rect: Rect) {
+        let inner =
+            Popup::new("Network unreachable").size(60, 40).render(frame, rect);
+        let text = tr!(
+            "\nKivra could not be reached:\n{error}\n\n(r)etry, work (o)ffline \
+             from what is kept locally, or (q)uit",
+            error = self.error
+        );
+        let widget = Paragraph::new(text).centered().wrap(Wrap { trim: true });
+        frame.render_widget(widget, inner);
+    }
+}
@@ -1,29 +1,72 @@
 use std::{
-    fs::File,
+    fs::{self, File},
     io::Write,
     path::{Path, PathBuf},
+    process,
+    sync::Arc,
 };
 
+use arboard::Clipboard;
 use bytes::Bytes;
+use chrono::TimeDelta;
+use tracing::warn;
 
 use crate::{
-    client::Client,
+    cli,
+    client::{Client, ContentClient},
+    config::{Config, Existing},
+    crypto,
     error::Error,
-    model::content::{InboxEntry, InboxItem, InboxListing, ItemDetails},
+    filename,
+    filter::Filter,
+    fulltext, index,
+    model::{
+        content::{InboxEntry, InboxItem, InboxListing, ItemDetails, ItemRef},
+        receipt::{ReceiptEntry, ReceiptListing},
+    },
+    temp,
 };
 
-pub fn get_entry_by_id(
+/// The entry referred to by id or content key, or else the one whose sender
+/// and subject fit the words of the reference. When several fit the user is
+/// asked which, if there is a terminal to ask on.
+pub fn get_entry(
     inbox: InboxListing,
-    item_id: u32,
+    item: &ItemRef,
 ) -> Result<InboxEntry, Error> {
-    inbox
+    let mut candidates = find_entries(inbox, item);
+    match candidates.len() {
+        0 => Err(Error::UserError("Inbox item does not exist")),
+        1 => Ok(candidates.remove(0)),
+        _ if cli::prompt::interactive() => cli::inbox_item::choose(candidates),
+        _ => {
+            eprint!("{}", cli::inbox_item::format_candidates(&candidates));
+            Err(Error::UserError("Several inbox items match, give an id"))
+        }
+    }
+}
+
+/// The entry referred to by id or content key, or else every entry whose
+/// sender and subject fit the words of the reference
+pub fn find_entries(inbox: InboxListing, item: &ItemRef) -> Vec<InboxEntry> {
+    if let Some(entry) = inbox.iter().find(|entry| item.is(entry)) {
+        return vec![entry.clone()];
+    }
+    inbox.into_iter().filter(|entry| item.resembles(entry)).collect()
+}
+
+pub fn get_receipt_by_id(
+    receipts: ReceiptListing,
+    receipt_id: u32,
+) -> Result<ReceiptEntry, Error> {
+    receipts
         .into_iter()
-        .find(|i| i.id == item_id)
-        .ok_or(Error::UserError("Inbox item does not exist"))
+        .find(|r| r.id == receipt_id)
+        .ok_or(Error::UserError("Receipt does not exist"))
 }
 
-fn get_attachment_body(
-    client: &mut impl Client,
+pub fn get_attachment_body(
+    client: &mut impl ContentClient,
     item: &InboxItem,
     details: &ItemDetails,
     attachment_num: u32,
@@ -42,27 +85,247 @@ fn get_attachment_body(
     }
 }
 
+/// Where attachments are downloaded to
+#[derive(Clone, Debug)]
+pub struct Destination {
+    pub dir: PathBuf,
+    /// The path of each attachment in `dir`, see `filename::fill_template`
+    pub template: String,
+    pub existing: Existing,
+}
+
+/// The user's download directory, with attachments named as they always
+/// have been
+impl Default for Destination {
+    fn default() -> Destination {
+        Destination {
+            dir: dirs::download_dir().unwrap_or(".".into()),
+            template: filename::DEFAULT_TEMPLATE.to_string(),
+            existing: Existing::default(),
+        }
+    }
+}
+
+impl Destination {
+    /// The directory given, or else the configured one, with the configured
+    /// template and policy
+    pub fn new(config: &Config, dir: Option<PathBuf>) -> Destination {
+        Destination {
+            dir: dir.unwrap_or_else(|| config.download_dir()),
+            template: config.download.template.clone(),
+            existing: config.download.existing,
+        }
+    }
+}
+
+/// Where a file for `path` is to be written by the policy, `None` if it is
+/// to be skipped
+pub fn free_path(
+    path: &Path,
+    existing: Existing,
+) -> Result<Option<PathBuf>, Error> {
+    if !path.try_exists()? {
+        return Ok(Some(path.to_path_buf()));
+    }
+    match existing {
+        Existing::Fail => Err(Error::FileExists(path.to_path_buf())),
+        Existing::Overwrite => Ok(Some(path.to_path_buf())),
+        Existing::Skip => Ok(None),
+        Existing::Rename => {
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            for count in 2.. {
+                let name = filename::with_suffix(&name, &format!("-{count}"));
+                let renamed = path.with_file_name(name);
+                if !renamed.try_exists()? {
+                    return Ok(Some(renamed));
+                }
+            }
+            unreachable!("one of the names is free")
+        }
+    }
+}
+
+/// A hidden file next to `path`, to write it to before renaming it into
+/// place, so that an interrupted download leaves nothing at `path`
+pub fn partial_path(path: &Path) -> PathBuf {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!(".{name}.{}.part", process::id()))
+}
+
+/// Writes the file at `partial_path` and renames it to `path`
+fn write_atomically(path: &Path, contents: &[u8]) -> Result<(), Error> {
+    let partial = partial_path(path);
+    let written = File::create(&partial)
+        .and_then(|mut file| {
+            file.write_all(contents)?;
+            file.sync_all()
+        })
+        .and_then(|()| fs::rename(&partial, path));
+    if written.is_err() {
+        _ = fs::remove_file(&partial);
+    }
+    Ok(written?)
+}
+
+/// Whether the file at `path` holds exactly `contents`, and if so whether it
+/// is encrypted as it would be written now
+fn holds(path: &Path, contents: &[u8]) -> Option<bool> {
+    let existing = fs::read(path).ok()?;
+    let current = !crypto::needs_migration(&existing);
+    let existing = crypto::unseal(existing).ok()?;
+    (existing == contents).then_some(current)
+}
+
+fn write_attachment(
+    client: &mut impl Client,
+    item: &InboxItem,
+    details: &ItemDetails,
+    attachment_num: u32,
+    destination: &Destination,
+) -> Result<PathBuf, Error> {
+    let file = get_attachment_body(client, item, details, attachment_num)?;
+    let path = details.attachment_path(
+        &destination.template,
+        attachment_num as usize,
+        &file,
+    )?;
+    let mut full_path = destination.dir.join(path);
+    if let Some(dir) = full_path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    // Downloading the same file again changes nothing, but for encrypting
+    // it as it would be now
+    let stored = crypto::seal(file.to_vec())?;
+    match holds(&full_path, &file) {
+        Some(true) => {}
+        Some(false) => write_atomically(&full_path, &stored)?,
+        None => {
+            match free_path(&full_path, destination.existing)? {
+                Some(path) => full_path = path,
+                None => return Ok(full_path),
+            }
+            write_atomically(&full_path, &stored)?;
+        }
+    }
+    let content_type = &details.parts[attachment_num as usize].content_type;
+    record_download(
+        client.profile(),
+        item,
+        attachment_num,
+        content_type,
+        &full_path,
+        &file,
+    );
+    Ok(full_path)
+}
+
+/// Records where the attachment went, and its text for content search.
+/// Failing to update the index should not fail the download itself.
+fn record_download(
+    profile: &str,
+    item: &InboxItem,
+    attachment_num: u32,
+    content_type: &str,
+    path: &Path,
+    contents: &[u8],
+) {
+    let sha256 = index::sha256(contents);
+    let text = fulltext::extract(content_type, contents);
+    let result = index::update(profile, |index| {
+        index.record_download(&item.key, attachment_num, path, sha256);
+        if let Some(text) = text {
+            index.record_text(&item.key, attachment_num, text);
+        }
+        ((), true)
+    });
+    if let Err(error) = result {
+        warn!("failed to record download in the index: {error}");
+    }
+}
+
 pub fn download_attachment(
     client: &mut impl Client,
     item: &InboxItem,
     attachment_num: u32,
-    download_dir: PathBuf,
+    destination: &Destination,
 ) -> Result<PathBuf, Error> {
     let details = client.get_item_details(&item.key)?;
-    let file = get_attachment_body(client, item, &details, attachment_num)?;
-    let filename = details.attachment_name(attachment_num as usize)?;
-    let full_path = Path::new(&download_dir).join(filename);
-    File::create(&full_path)?.write_all(&file)?;
-    Ok(full_path)
+    write_attachment(client, item, &details, attachment_num, destination)
 }
 
-pub fn open_attachment(
+pub fn download_all_attachments(
     client: &mut impl Client,
     item: &InboxItem,
+    destination: &Destination,
+) -> Result<Vec<PathBuf>, Error> {
+    let details = client.get_item_details(&item.key)?;
+    (0..details.parts.len() as u32)
+        .map(|num| write_attachment(client, item, &details, num, destination))
+        .collect()
+}
+
+/// The filter, with the text of attachments from the index of the profile if
+/// it searches them
+pub fn with_contents(profile: &str, filter: Filter) -> Result<Filter, Error> {
+    if !filter.searches_contents() {
+        return Ok(filter);
+    }
+    let contents = index::load(profile)?.contents();
+    Ok(filter.with_contents(Arc::new(contents)))
+}
+
+pub fn open_attachment(
+    client: &mut impl ContentClient,
+    item: &InboxItem,
     attachment_num: u32,
 ) -> Result<(), Error> {
-    let tmp_dir = std::env::temp_dir();
-    let path = download_attachment(client, item, attachment_num, tmp_dir)?;
+    // Temporary copies are not recorded in the index
+    let details = client.get_item_details(&item.key)?;
+    let file = get_attachment_body(client, item, &details, attachment_num)?;
+    let filename =
+        details.attachment_name_for(attachment_num as usize, &file)?;
+    let path = temp::write(&filename, &file)?;
     opener::open(path)?;
     Ok(())
 }
+
+/// On Linux the clipboard contents belong to the process that set them, so a
+/// background process keeps serving them until something else is copied.
+pub fn copy_to_clipboard(text: &str) -> Result<(), Error> {
+    #[cfg(target_os = "linux")]
+    {
+        use arboard::SetExtLinux;
+        use fork::Fork;
+        use std::process;
+
+        // Fail here rather than in the background if there is no clipboard
+        drop(Clipboard::new()?);
+        if let Fork::Child = fork::fork()? {
+            _ = fork::setsid();
+            let result = Clipboard::new()
+                .and_then(|mut clipboard| clipboard.set().wait().text(text));
+            process::exit(result.is_err() as i32);
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    Clipboard::new()?.set_text(text)?;
+
+    Ok(())
+}
+
+/// Parses durations like "90m", "12h", "30d" or "2w"
+pub fn parse_age(age: &str) -> Result<TimeDelta, String> {
+    let invalid = || format!("invalid age '{age}', expected e.g. 12h or 30d");
+    let split = age.len().checked_sub(1).ok_or_else(invalid)?;
+    let (amount, unit) = age.split_at_checked(split).ok_or_else(invalid)?;
+    let amount: i64 = amount.parse().map_err(|_| invalid())?;
+    match unit {
+        "m" => TimeDelta::try_minutes(amount),
+        "h" => TimeDelta::try_hours(amount),
+        "d" => TimeDelta::try_days(amount),
+        "w" => TimeDelta::try_weeks(amount),
+        _ => None,
+    }
+    .ok_or_else(invalid)
+}
@@ -0,0 +1,220 @@
+use chrono::{DateTime, Utc};
+use std::{
+    sync::mpsc::{self, Receiver, RecvTimeoutError},
+    thread,
+    time::{Duration, Instant},
+};
+use tracing::warn;
+
+use crate::{
+    client::Client,
+    dbus,
+    error::Error,
+    fulltext,
+    model::content::{InboxEntry, InboxFilter, InboxListing, ItemDetails},
+    notify::{Forward, Notifier, Summary},
+    util::{get_attachment_body, open_attachment},
+};
+
+// Never poll Kivra more often than this, whatever the command line says
+pub const MIN_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Asks the watch loop to do something between polls
+#[derive(Debug)]
+pub enum Request {
+    Refresh,
+    OpenItem(u32),
+}
+
+/// What the watcher last saw of the inbox
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Status {
+    pub profile: String,
+    pub user_name: String,
+    pub unread: u32,
+    pub items: Vec<Item>,
+}
+
+/// An item in the inbox, as listed on D-Bus
+#[derive(Clone, Debug, PartialEq)]
+pub struct Item {
+    pub id: u32,
+    pub key: String,
+    pub sender: String,
+    pub subject: String,
+    pub created_at: DateTime<Utc>,
+    pub unread: bool,
+}
+
+impl Item {
+    fn new(entry: &InboxEntry) -> Item {
+        let item = &entry.item;
+        Item {
+            id: entry.id,
+            key: item.key.clone(),
+            sender: item.sender_name.clone(),
+            subject: item.subject.clone(),
+            created_at: item.created_at,
+            unread: InboxFilter::Unread.matches(item),
+        }
+    }
+}
+
+impl Status {
+    fn new(client: &impl Client, listing: &InboxListing) -> Status {
+        let items: Vec<Item> = listing.iter().map(Item::new).collect();
+        let unread = items.iter().filter(|item| item.unread).count();
+        Status {
+            profile: client.profile().to_string(),
+            user_name: client
+                .get_session()
+                .map(|session| session.user_info.name)
+                .unwrap_or_default(),
+            unread: unread as u32,
+            items,
+        }
+    }
+}
+
+/// Polls the inbox until the process is stopped, printing every item that
+/// arrives and sending it to the notifiers and `forward`. With `dbus` the
+/// status is also published on the session bus.
+pub fn run(
+    client: &mut impl Client,
+    interval: Duration,
+    dbus: bool,
+    notifiers: &[Box<dyn Notifier>],
+    forward: Option<&Forward>,
+) -> Result<(), Error> {
+    // Log in while there is still a terminal to show the QR code in
+    client.get_session_or_login()?;
+    let (sender, requests) = mpsc::channel();
+    let service = match dbus {
+        true => Some(dbus::Service::register(client.profile(), sender)?),
+        false => None,
+    };
+
+    let interval = interval.max(MIN_INTERVAL);
+    let mut listing: Option<InboxListing> = None;
+    loop {
+        match client.refresh_inbox() {
+            Ok(new) => {
+                if let Some(old) = &listing {
+                    let service = service.as_ref();
+                    announce(client, old, &new, service, notifiers, forward);
+                }
+                if let Some(service) = &service {
+                    if let Err(error) =
+                        service.publish(Status::new(client, &new))
+                    {
+                        warn!("failed to publish status on D-Bus: {error}");
+                    }
+                }
+                // A few at a time, so that the first poll is not held up
+                if let Err(error) =
+                    fulltext::index_new(client, &new, fulltext::SYNC_BATCH)
+                {
+                    warn!("failed to index the contents of the inbox: {error}");
+                }
+                listing = Some(new);
+            }
+            Err(error) => warn!("failed to poll the inbox: {error}"),
+        }
+        wait(client, interval, &requests, listing.as_ref());
+    }
+}
+
+fn announce(
+    client: &mut impl Client,
+    old: &InboxListing,
+    new: &InboxListing,
+    service: Option<&dbus::Service>,
+    notifiers: &[Box<dyn Notifier>],
+    forward: Option<&Forward>,
+) {
+    let unread = new
+        .iter()
+        .filter(|entry| InboxFilter::Unread.matches(&entry.item))
+        .count();
+    for entry in new.iter() {
+        if old.iter().any(|old| old.item.key == entry.item.key) {
+            continue;
+        }
+        let item = &entry.item;
+        println!("{}: {} - {}", entry.id, item.sender_name, item.subject);
+        if let Some(service) = service {
+            if let Err(error) = service.announce(entry) {
+                warn!("failed to signal item {} on D-Bus: {error}", entry.id);
+            }
+        }
+        if notifiers.is_empty() && forward.is_none() {
+            continue;
+        }
+        let details = match client.get_item_details(&item.key) {
+            Ok(details) => Some(details),
+            Err(error) => {
+                warn!("failed to get details of item {}: {error}", entry.id);
+                None
+            }
+        };
+        if let (Some(forward), Some(details)) = (forward, &details) {
+            if let Err(error) = forward_item(client, forward, entry, details) {
+                let name = forward.name();
+                warn!("failed to forward item {} to {name}: {error}", entry.id);
+            }
+        }
+        let attachments = details.map(|details| details.parts.len());
+        let summary = Summary { attachments, unread };
+        for notifier in notifiers {
+            if let Err(error) = notifier.send(entry, &summary) {
+                let name = notifier.name();
+                warn!("failed to notify {name} of item {}: {error}", entry.id);
+            }
+        }
+    }
+}
+
+fn forward_item(
+    client: &mut impl Client,
+    forward: &Forward,
+    entry: &InboxEntry,
+    details: &ItemDetails,
+) -> Result<(), Error> {
+    let contents = (0..details.parts.len() as u32)
+        .map(|num| get_attachment_body(client, &entry.item, details, num))
+        .collect::<Result<Vec<_>, _>>()?;
+    forward.send(entry, details, &contents)?;
+    Ok(())
+}
+
+/// Sleeps until the next poll is due, serving requests in the meantime
+fn wait(
+    client: &mut impl Client,
+    interval: Duration,
+    requests: &Receiver<Request>,
+    listing: Option<&InboxListing>,
+) {
+    let deadline = Instant::now() + interval;
+    loop {
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        match requests.recv_timeout(timeout) {
+            Ok(Request::Refresh) | Err(RecvTimeoutError::Timeout) => return,
+            Ok(Request::OpenItem(id)) => {
+                let entry = listing
+                    .and_then(|listing| listing.iter().find(|e| e.id == id));
+                let result = match entry {
+                    Some(entry) => open_attachment(client, &entry.item, 0),
+                    None => Err(Error::UserError("Inbox item does not exist")),
+                };
+                if let Err(error) = result {
+                    warn!("failed to open item {id}: {error}");
+                }
+            }
+            // Nobody can send requests, e.g. without D-Bus
+            Err(RecvTimeoutError::Disconnected) => {
+                thread::sleep(timeout);
+                return;
+            }
+        }
+    }
+}